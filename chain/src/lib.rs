@@ -1,6 +1,7 @@
 mod epoch;
 mod known_assets;
 mod note_source;
+mod state_key;
 mod view;
 
 pub mod genesis;
@@ -10,5 +11,6 @@ pub mod sync;
 pub use epoch::Epoch;
 pub use known_assets::KnownAssets;
 pub use note_source::NoteSource;
+pub use state_key::existing_state_keys;
 pub use sync::CompactBlock;
 pub use view::View;
@@ -0,0 +1,31 @@
+//! The keys under which the chain state is stored.
+
+use penumbra_storage::StateKey;
+
+use crate::params::ChainParams;
+
+// These keys predate domain-separated state keys, and keep their original names so that existing
+// chain state can still be read.
+
+pub fn chain_params() -> StateKey<ChainParams> {
+    StateKey::new("chain_params")
+}
+
+pub fn block_height() -> StateKey<u64> {
+    StateKey::new("block_height")
+}
+
+/// The timestamp is stored as an RFC 3339 string.
+pub fn block_timestamp() -> StateKey<String> {
+    StateKey::new("block_timestamp")
+}
+
+/// Returns the keys which may have been written before the key index existed, for
+/// [`reindex_keys`](penumbra_storage::reindex_keys).
+pub fn existing_state_keys() -> Vec<String> {
+    vec![
+        chain_params().to_string(),
+        block_height().to_string(),
+        block_timestamp().to_string(),
+    ]
+}
@@ -2,10 +2,10 @@ use std::str::FromStr;
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use penumbra_storage::StateExt;
+use penumbra_storage::{StateExt, StateRead};
 use tendermint::Time;
 
-use crate::{params::ChainParams, Epoch};
+use crate::{params::ChainParams, state_key, Epoch};
 
 /// This trait provides read and write access to common parts of the Penumbra
 /// state store.
@@ -13,17 +13,20 @@ use crate::{params::ChainParams, Epoch};
 /// Note: the `get_` methods in this trait assume that the state store has been
 /// initialized, so they will error on an empty state.
 #[async_trait]
-pub trait View: StateExt {
+pub trait View: StateRead {
     /// Gets the chain parameters from the JMT.
     async fn get_chain_params(&self) -> Result<ChainParams> {
-        self.get_domain(b"chain_params".into())
+        self.get_typed(&state_key::chain_params())
             .await?
             .ok_or_else(|| anyhow!("Missing ChainParams"))
     }
 
     /// Writes the provided chain parameters to the JMT.
-    async fn put_chain_params(&self, params: ChainParams) {
-        self.put_domain(b"chain_params".into(), params).await
+    async fn put_chain_params(&self, params: ChainParams)
+    where
+        Self: StateExt,
+    {
+        self.put_typed(&state_key::chain_params(), params).await
     }
 
     /// Gets the current epoch for the chain.
@@ -56,7 +59,7 @@ pub trait View: StateExt {
     /// Gets the current block height from the JMT
     async fn get_block_height(&self) -> Result<u64> {
         let height_bytes: u64 = self
-            .get_proto(b"block_height".into())
+            .get_typed_proto(&state_key::block_height())
             .await?
             .ok_or_else(|| anyhow!("Missing block_height"))?;
 
@@ -64,14 +67,18 @@ pub trait View: StateExt {
     }
 
     /// Writes the block height to the JMT
-    async fn put_block_height(&self, height: u64) {
-        self.put_proto(b"block_height".into(), height).await
+    async fn put_block_height(&self, height: u64)
+    where
+        Self: StateExt,
+    {
+        self.put_typed_proto(&state_key::block_height(), height)
+            .await
     }
 
     /// Gets the current block timestamp from the JMT
     async fn get_block_timestamp(&self) -> Result<Time> {
         let timestamp_string: String = self
-            .get_proto(b"block_timestamp".into())
+            .get_typed_proto(&state_key::block_timestamp())
             .await?
             .ok_or_else(|| anyhow!("Missing block_timestamp"))?;
 
@@ -79,8 +86,11 @@ pub trait View: StateExt {
     }
 
     /// Writes the block timestamp to the JMT
-    async fn put_block_timestamp(&self, timestamp: Time) {
-        self.put_proto(b"block_timestamp".into(), timestamp.to_rfc3339())
+    async fn put_block_timestamp(&self, timestamp: Time)
+    where
+        Self: StateExt,
+    {
+        self.put_typed_proto(&state_key::block_timestamp(), timestamp.to_rfc3339())
             .await
     }
 
@@ -104,4 +114,4 @@ pub trait View: StateExt {
     }
 }
 
-impl<T: StateExt> View for T {}
+impl<T: StateRead> View for T {}
@@ -1,6 +1,7 @@
 use std::io::{Cursor, Read, Write};
 
 use ark_serialize::CanonicalDeserialize;
+use bech32::FromBase32;
 use f4jumble::{f4jumble, f4jumble_inv};
 use penumbra_proto::{crypto as pb, serializers::bech32str};
 use serde::{Deserialize, Serialize};
@@ -11,6 +12,34 @@ use crate::{fmd, ka, keys::Diversifier, Fq};
 // using this 5 byte padding.
 const ADDR_PADDING: &[u8] = "pen00".as_bytes();
 
+/// The length of an address (before jumbling and Bech32m encoding).
+const ADDR_LEN_BYTES: usize = 80;
+
+/// The reason an address string was rejected by [`Address::parse_with_diagnostics`].
+#[derive(thiserror::Error, Debug)]
+pub enum AddressError {
+    #[error("not a valid Bech32m string: {0}")]
+    Encoding(bech32::Error),
+    #[error("checksum failed, so the address was probably mistyped or truncated")]
+    Checksum,
+    #[error("wrong prefix {found}, expected {expected}; this is not a Penumbra address")]
+    Hrp {
+        found: String,
+        expected: &'static str,
+    },
+    #[error("address is for a different network (prefix {found}, expected {expected})")]
+    Network {
+        found: String,
+        expected: &'static str,
+    },
+    #[error("address uses Bech32 rather than Bech32m encoding")]
+    Variant,
+    #[error("address encodes {found} bytes, expected {expected}")]
+    Length { found: usize, expected: usize },
+    #[error("address is well-formed but does not contain valid key material")]
+    Invalid,
+}
+
 /// A valid payment address.
 #[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(try_from = "pb::Address", into = "pb::Address")]
@@ -148,14 +177,64 @@ impl std::fmt::Debug for Address {
     }
 }
 
+impl Address {
+    /// Parses an address, explaining exactly why it was rejected if it is invalid.
+    ///
+    /// This accepts the same addresses as [`FromStr`](std::str::FromStr), but distinguishes a
+    /// mistyped address from one for another network or another kind of key entirely, so that
+    /// user-facing tools can tell users how to fix what they pasted.
+    pub fn parse_with_diagnostics(s: &str) -> Result<Self, AddressError> {
+        let expected = bech32str::address::BECH32_PREFIX;
+
+        let (hrp, data, variant) = bech32::decode(s).map_err(|e| match e {
+            bech32::Error::InvalidChecksum => AddressError::Checksum,
+            e => AddressError::Encoding(e),
+        })?;
+
+        if hrp != expected {
+            // Addresses for other networks have prefixes like `penumbrav0t`.
+            let is_address_prefix = hrp
+                .strip_prefix("penumbrav")
+                .map(|version| {
+                    let version = version.strip_suffix('t').unwrap_or(version);
+                    !version.is_empty() && version.bytes().all(|b| b.is_ascii_digit())
+                })
+                .unwrap_or(false);
+            return Err(if is_address_prefix {
+                AddressError::Network {
+                    found: hrp,
+                    expected,
+                }
+            } else {
+                AddressError::Hrp {
+                    found: hrp,
+                    expected,
+                }
+            });
+        }
+        if variant != bech32str::Bech32m {
+            return Err(AddressError::Variant);
+        }
+
+        let bytes = Vec::<u8>::from_base32(&data).map_err(AddressError::Encoding)?;
+        if bytes.len() != ADDR_LEN_BYTES {
+            return Err(AddressError::Length {
+                found: bytes.len(),
+                expected: ADDR_LEN_BYTES,
+            });
+        }
+
+        pb::Address { inner: bytes }
+            .try_into()
+            .map_err(|_| AddressError::Invalid)
+    }
+}
+
 impl std::str::FromStr for Address {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        pb::Address {
-            inner: bech32str::decode(s, bech32str::address::BECH32_PREFIX, bech32str::Bech32m)?,
-        }
-        .try_into()
+        Ok(Self::parse_with_diagnostics(s)?)
     }
 }
 
@@ -208,4 +287,68 @@ mod tests {
 
         assert_eq!(addr, dest);
     }
+
+    #[test]
+    fn test_address_diagnostics() {
+        let mut rng = OsRng;
+        let seed_phrase = SeedPhrase::generate(&mut rng);
+        let spend_seed = SpendSeed::from_seed_phrase(seed_phrase, 0);
+        let sk = SpendKey::new(spend_seed);
+        let (dest, _dtk_d) = sk.incoming_viewing_key().payment_address(0u64.into());
+        let inner = pb::Address::from(dest).inner;
+        let prefix = bech32str::address::BECH32_PREFIX;
+
+        let encoded_addr = dest.to_string();
+        assert_eq!(
+            Address::parse_with_diagnostics(&encoded_addr).unwrap(),
+            dest
+        );
+
+        // Change the last character, which is part of the checksum.
+        let mut mistyped = encoded_addr.clone();
+        let last = mistyped.pop().unwrap();
+        mistyped.push(if last == 'q' { 'p' } else { 'q' });
+        assert!(matches!(
+            Address::parse_with_diagnostics(&mistyped),
+            Err(AddressError::Checksum)
+        ));
+
+        assert!(matches!(
+            Address::parse_with_diagnostics("not an address"),
+            Err(AddressError::Encoding(_))
+        ));
+
+        let other_key = bech32str::encode(&inner, "passet", bech32str::Bech32m);
+        assert!(matches!(
+            Address::parse_with_diagnostics(&other_key),
+            Err(AddressError::Hrp { .. })
+        ));
+
+        let other_network = bech32str::encode(&inner, "penumbrav0t", bech32str::Bech32m);
+        assert!(matches!(
+            Address::parse_with_diagnostics(&other_network),
+            Err(AddressError::Network { .. })
+        ));
+
+        let bech32 = bech32str::encode(&inner, prefix, bech32str::Bech32);
+        assert!(matches!(
+            Address::parse_with_diagnostics(&bech32),
+            Err(AddressError::Variant)
+        ));
+
+        let truncated = bech32str::encode(&inner[..ADDR_LEN_BYTES - 1], prefix, bech32str::Bech32m);
+        assert!(matches!(
+            Address::parse_with_diagnostics(&truncated),
+            Err(AddressError::Length {
+                found: 79,
+                expected: ADDR_LEN_BYTES,
+            })
+        ));
+
+        let garbage = bech32str::encode(&[0u8; ADDR_LEN_BYTES], prefix, bech32str::Bech32m);
+        assert!(matches!(
+            Address::parse_with_diagnostics(&garbage),
+            Err(AddressError::Invalid)
+        ));
+    }
 }
@@ -13,7 +13,8 @@ pub use spend::{SpendKey, SpendSeed, SPENDSEED_LEN_BYTES};
 mod fvk;
 mod ivk;
 mod ovk;
+pub mod threshold;
 
-pub use fvk::FullViewingKey;
+pub use fvk::{FullViewingKey, FullViewingKeyHash};
 pub use ivk::{IncomingViewingKey, IVK_LEN_BYTES};
 pub use ovk::{OutgoingViewingKey, OVK_LEN_BYTES};
@@ -6,11 +6,13 @@ use once_cell::sync::Lazy;
 use penumbra_proto::{crypto as pb, serializers::bech32str, Protobuf};
 use serde::{Deserialize, Serialize};
 
-use super::{DiversifierKey, IncomingViewingKey, NullifierKey, OutgoingViewingKey};
+use super::{
+    DiversifierIndex, DiversifierKey, IncomingViewingKey, NullifierKey, OutgoingViewingKey,
+};
 use crate::{
     ka, merkle, note, prf,
     rdsa::{SpendAuth, VerificationKey},
-    Fq, Fr, Nullifier,
+    Address, Fq, Fr, Note, Nullifier,
 };
 
 static IVK_DOMAIN_SEP: Lazy<Fq> = Lazy::new(|| Fq::from_le_bytes_mod_order(b"penumbra.derive.ivk"));
@@ -72,6 +74,39 @@ impl FullViewingKey {
         &self.nk
     }
 
+    /// Derive the shielded payment address with the given [`DiversifierIndex`].
+    ///
+    /// Every index gives a distinct address, and the addresses can't be linked to each other
+    /// without the viewing key, so a fresh index can be used for each counterparty.
+    pub fn address_at(&self, index: DiversifierIndex) -> Address {
+        let (address, _dtk) = self.ivk.payment_address(index);
+        address
+    }
+
+    /// Returns the [`DiversifierIndex`] of the given address, or `None` if the address
+    /// doesn't belong to this full viewing key.
+    ///
+    /// This is the inverse of [`FullViewingKey::address_at`].
+    pub fn index_for_address(&self, address: &Address) -> Option<DiversifierIndex> {
+        if self.ivk.diversified_public(address.diversified_generator())
+            == *address.transmission_key()
+        {
+            Some(self.ivk.index_for_diversifier(address.diversifier()))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the [`DiversifierIndex`] of the address the given note was sent to, or `None`
+    /// if the note wasn't sent to this full viewing key.
+    pub fn index_for_note(&self, note: &Note) -> Option<DiversifierIndex> {
+        if self.ivk.diversified_public(&note.diversified_generator()) == note.transmission_key() {
+            Some(self.ivk.index_for_diversifier(&note.diversifier()))
+        } else {
+            None
+        }
+    }
+
     /// Derive the [`Nullifier`] for a positioned note given its [`merkle::Position`] and
     /// [`note::Commitment`].
     pub fn derive_nullifier(
@@ -191,3 +226,46 @@ impl std::fmt::Debug for FullViewingKeyHash {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::{
+        asset,
+        keys::{SeedPhrase, SpendKey, SpendSeed},
+        Value,
+    };
+
+    #[test]
+    fn address_index_round_trip() {
+        let sk = SpendKey::new(SpendSeed::from_seed_phrase(
+            SeedPhrase::generate(&mut OsRng),
+            0,
+        ));
+        let fvk = sk.full_viewing_key();
+        let other_sk = SpendKey::new(SpendSeed::from_seed_phrase(
+            SeedPhrase::generate(&mut OsRng),
+            0,
+        ));
+        let value = Value {
+            amount: 10,
+            asset_id: asset::REGISTRY.parse_denom("upenumbra").unwrap().id(),
+        };
+
+        for index in [0u64, 1, 7, 1 << 40] {
+            let index = DiversifierIndex::from(index);
+            let address = fvk.address_at(index);
+            assert_eq!(fvk.index_for_address(&address), Some(index));
+            assert_eq!(
+                other_sk.full_viewing_key().index_for_address(&address),
+                None
+            );
+
+            let note = Note::generate(&mut OsRng, &address, value);
+            assert_eq!(fvk.index_for_note(&note), Some(index));
+            assert_eq!(other_sk.full_viewing_key().index_for_note(&note), None);
+        }
+    }
+}
@@ -2,6 +2,7 @@ use std::fmt;
 
 use rand_core::{CryptoRng, RngCore};
 use sha2::Digest;
+use zeroize::Zeroize;
 
 mod words;
 use words::BIP39_WORDS;
@@ -15,14 +16,26 @@ pub const NUM_BITS_PER_WORD: usize = 11;
 pub const NUM_BITS_PER_BYTE: usize = 8;
 
 /// A mnemonic seed phrase. Used to generate [`SpendSeed`]s.
+///
+/// The words are zeroized when the seed phrase is dropped.
 pub struct SeedPhrase(pub [String; NUM_WORDS]);
 
+impl Drop for SeedPhrase {
+    fn drop(&mut self) {
+        for word in self.0.iter_mut() {
+            word.zeroize();
+        }
+    }
+}
+
 impl SeedPhrase {
     /// Randomly generates a BIP39 [`SeedPhrase`].
     pub fn generate<R: RngCore + CryptoRng>(mut rng: R) -> Self {
         let mut randomness = [0u8; NUM_ENTROPY_BITS / NUM_BITS_PER_BYTE];
         rng.fill_bytes(&mut randomness);
-        Self::from_randomness(randomness)
+        let seed_phrase = Self::from_randomness(randomness);
+        randomness.zeroize();
+        seed_phrase
     }
 
     /// Given 32 bytes of randomness, generate a [`SeedPhrase`].
@@ -49,6 +62,7 @@ impl SeedPhrase {
             let word_index = convert_bits_to_usize(bits_this_word);
             *word = BIP39_WORDS[word_index].to_string();
         }
+        bits.zeroize();
         SeedPhrase(words)
     }
 
@@ -79,6 +93,8 @@ impl SeedPhrase {
 
         let mut hasher = sha2::Sha256::new();
         hasher.update(randomness);
+        randomness.zeroize();
+        bits.zeroize();
         if hasher.finalize()[0] != checksum {
             return Err(anyhow::anyhow!("seed phrase checksum did not validate"));
         } else {
@@ -1,8 +1,9 @@
-use std::convert::TryFrom;
+use std::{convert::TryFrom, fmt, ptr};
 
 use hmac::Hmac;
 use pbkdf2::pbkdf2;
 use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
 
 use super::{
     seed_phrase::{SeedPhrase, NUM_PBKDF2_ROUNDS},
@@ -11,12 +12,16 @@ use super::{
 use crate::{
     prf,
     rdsa::{SigningKey, SpendAuth},
+    Fr, Zero,
 };
 
 pub const SPENDSEED_LEN_BYTES: usize = 32;
 
 /// The root key material for a [`SpendKey`].
-#[derive(Clone, Debug, Deserialize, Serialize)]
+///
+/// The seed is zeroized when it is dropped, and is not shown by its `Debug` implementation.
+#[derive(Clone, Deserialize, Serialize, Zeroize)]
+#[zeroize(drop)]
 pub struct SpendSeed(pub [u8; SPENDSEED_LEN_BYTES]);
 
 impl SpendSeed {
@@ -29,21 +34,30 @@ impl SpendSeed {
     ///
     /// [`BIP39`]: https://github.com/bitcoin/bips/blob/master/bip-0039.mediawiki
     pub fn from_seed_phrase(seed_phrase: SeedPhrase, index: u64) -> Self {
-        let password = format!("{}", seed_phrase);
+        let password = Zeroizing::new(format!("{}", seed_phrase));
         let salt = format!("mnemonic{}", index);
-        let mut spend_seed_bytes = [0u8; 32];
+        let mut spend_seed = SpendSeed([0u8; SPENDSEED_LEN_BYTES]);
         pbkdf2::<Hmac<sha2::Sha512>>(
             password.as_bytes(),
             salt.as_bytes(),
             NUM_PBKDF2_ROUNDS,
-            &mut spend_seed_bytes,
+            &mut spend_seed.0,
         );
-        SpendSeed(spend_seed_bytes)
+        spend_seed
+    }
+}
+
+impl fmt::Debug for SpendSeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SpendSeed(<redacted>)")
     }
 }
 
 /// A key representing a single spending authority.
-#[derive(Debug, Clone)]
+///
+/// The key material is zeroized when the key is dropped. Its `Debug` implementation shows only
+/// the full viewing key.
+#[derive(Clone)]
 pub struct SpendKey {
     seed: SpendSeed,
     ask: SigningKey<SpendAuth>,
@@ -97,6 +111,28 @@ impl SpendKey {
     }
 }
 
+impl Drop for SpendKey {
+    fn drop(&mut self) {
+        // The seed zeroizes itself, but the signing key doesn't implement `Zeroize`, so overwrite
+        // the expanded spend authorization key in place with a dummy key instead. The write is
+        // volatile so that it isn't optimized away as a dead store.
+        //
+        // SAFETY: `self.ask` is a valid, aligned place, and `SigningKey` has no `Drop`
+        // implementation, so nothing is skipped by not dropping the old value.
+        unsafe {
+            ptr::write_volatile(&mut self.ask, SigningKey::new_from_field(Fr::zero()));
+        }
+    }
+}
+
+impl fmt::Debug for SpendKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpendKey")
+            .field("fvk", &self.fvk)
+            .finish_non_exhaustive()
+    }
+}
+
 impl TryFrom<&[u8]> for SpendSeed {
     type Error = anyhow::Error;
     fn try_from(slice: &[u8]) -> Result<Self, Self::Error> {
@@ -107,8 +143,27 @@ impl TryFrom<&[u8]> for SpendSeed {
             ));
         }
 
-        let mut bytes = [0u8; SPENDSEED_LEN_BYTES];
-        bytes.copy_from_slice(&slice[0..32]);
-        Ok(SpendSeed(bytes))
+        let mut seed = SpendSeed([0u8; SPENDSEED_LEN_BYTES]);
+        seed.0.copy_from_slice(&slice[0..32]);
+        Ok(seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_does_not_show_seed() {
+        let seed = SpendSeed([0xab; SPENDSEED_LEN_BYTES]);
+        let seed_hex = hex::encode(&seed.0);
+
+        let seed_debug = format!("{:?}", seed);
+        assert!(!seed_debug.contains("171"));
+        assert!(!seed_debug.contains(&seed_hex));
+
+        let key_debug = format!("{:?}", SpendKey::from(seed));
+        assert!(!key_debug.contains(&seed_hex));
+        assert!(key_debug.starts_with("SpendKey { fvk: "));
     }
 }
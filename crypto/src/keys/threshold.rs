@@ -0,0 +1,573 @@
+//! Threshold spend authorization, using FROST over the spend authorization key.
+//!
+//! A group of `n` participants runs the distributed key generation protocol in [`dkg`], after
+//! which each holds a [`KeyShare`] of a spend authorization key which no single participant
+//! knows.  Any `t` of them can then cooperate to produce an ordinary [`Signature<SpendAuth>`]
+//! under the (randomized) group key, in two rounds:
+//!
+//! 1. each signer calls [`commit`], keeping the [`SigningNonces`] secret and sending the
+//!    [`SigningCommitments`] to whoever is coordinating the signature;
+//! 2. the coordinator collects the commitments into a [`SigningPackage`] along with the message
+//!    and spend authorization randomizer, and each signer calls [`sign`] to produce a
+//!    [`SignatureShare`], which the coordinator combines with [`aggregate`].
+//!
+//! The resulting signatures are indistinguishable from those made with a single
+//! [`SigningKey<SpendAuth>`](crate::rdsa::SigningKey), so the chain doesn't need to know that a
+//! spend was threshold-authorized.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use anyhow::anyhow;
+use ark_ff::{Field, PrimeField, UniformRand, Zero};
+use decaf377::{FieldExt, Fr};
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::rdsa::{Signature, SpendAuth, VerificationKey};
+
+pub mod dkg;
+
+/// The personalization used by decaf377-rdsa to derive signature challenges.
+const CHALLENGE_PERSONALIZATION: &[u8; 16] = b"decaf377-rdsa---";
+/// The personalization used to derive the binding factor for each signer.
+const BINDING_FACTOR_PERSONALIZATION: &[u8; 16] = b"Penumbra_FROSTbf";
+
+/// The index of a participant in a threshold key, from 1 to the number of participants.
+pub type ParticipantIndex = u16;
+
+/// The public information about a threshold key, shared by all participants.
+#[serde_as]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicKeyPackage {
+    /// The number of participants needed to sign.
+    pub threshold: u16,
+    /// The group's spend verification key.
+    #[serde_as(as = "serde_with::hex::Hex")]
+    group_key: [u8; 32],
+    /// The verification key for each participant's share, used to check signature shares.
+    #[serde_as(as = "BTreeMap<_, serde_with::hex::Hex>")]
+    verifying_shares: BTreeMap<ParticipantIndex, [u8; 32]>,
+}
+
+impl PublicKeyPackage {
+    /// The group's spend verification key, which signatures made by the group verify under
+    /// (once randomized).
+    pub fn group_key(&self) -> anyhow::Result<VerificationKey<SpendAuth>> {
+        Ok(VerificationKey::try_from(self.group_key)?)
+    }
+
+    /// The indices of the participants holding shares of the key.
+    pub fn participants(&self) -> impl Iterator<Item = ParticipantIndex> + '_ {
+        self.verifying_shares.keys().copied()
+    }
+
+    fn verifying_share(&self, index: ParticipantIndex) -> anyhow::Result<decaf377::Element> {
+        let bytes = self
+            .verifying_shares
+            .get(&index)
+            .ok_or_else(|| anyhow!("unknown participant {}", index))?;
+        decode_element(bytes)
+    }
+}
+
+/// One participant's share of a threshold spend authorization key.
+///
+/// The signing share is secret: anyone holding `threshold` of them can spend the group's notes.
+/// It is zeroized when the share is dropped, and is not shown by its `Debug` implementation.
+#[serde_as]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct KeyShare {
+    /// The participant holding this share.
+    pub index: ParticipantIndex,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    signing_share: [u8; 32],
+    /// The public information about the key.
+    pub public: PublicKeyPackage,
+}
+
+impl Drop for KeyShare {
+    fn drop(&mut self) {
+        self.signing_share.zeroize();
+    }
+}
+
+impl fmt::Debug for KeyShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyShare")
+            .field("index", &self.index)
+            .field("public", &self.public)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyShare {
+    fn signing_share(&self) -> anyhow::Result<Fr> {
+        Fr::from_bytes(self.signing_share).map_err(|_| anyhow!("invalid signing share"))
+    }
+}
+
+/// The secret nonces generated by a signer in the first round of signing.
+///
+/// These must be used for at most one signature, and never revealed, so they can't be cloned,
+/// are zeroized when dropped, and are not shown by their `Debug` implementation.
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+pub struct SigningNonces {
+    /// The signer who generated the nonces.
+    pub index: ParticipantIndex,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    hiding: [u8; 32],
+    #[serde_as(as = "serde_with::hex::Hex")]
+    binding: [u8; 32],
+}
+
+impl Drop for SigningNonces {
+    fn drop(&mut self) {
+        self.hiding.zeroize();
+        self.binding.zeroize();
+    }
+}
+
+impl fmt::Debug for SigningNonces {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigningNonces")
+            .field("index", &self.index)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The commitments to a signer's [`SigningNonces`], sent to the signing coordinator.
+#[serde_as]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningCommitments {
+    /// The signer who generated the commitments.
+    pub index: ParticipantIndex,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    hiding: [u8; 32],
+    #[serde_as(as = "serde_with::hex::Hex")]
+    binding: [u8; 32],
+}
+
+/// Everything the signers need for the second round of signing: the message, the spend
+/// authorization randomizer, and the commitments of every participating signer.
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SigningPackage {
+    #[serde_as(as = "serde_with::hex::Hex")]
+    message: Vec<u8>,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    randomizer: [u8; 32],
+    commitments: Vec<SigningCommitments>,
+}
+
+impl SigningPackage {
+    /// Collects the signers' commitments to sign `message` under the group key randomized by
+    /// `randomizer`, as for a spend with that spend authorization randomizer.
+    pub fn new(
+        message: Vec<u8>,
+        randomizer: Fr,
+        mut commitments: Vec<SigningCommitments>,
+    ) -> anyhow::Result<Self> {
+        commitments.sort_by_key(|commitments| commitments.index);
+        let signers = commitments
+            .iter()
+            .map(|commitments| commitments.index)
+            .collect::<BTreeSet<_>>();
+        if signers.len() != commitments.len() {
+            return Err(anyhow!("duplicate commitments from a signer"));
+        }
+
+        Ok(Self {
+            message,
+            randomizer: randomizer.to_bytes(),
+            commitments,
+        })
+    }
+
+    /// The message being signed.
+    pub fn message(&self) -> &[u8] {
+        &self.message
+    }
+
+    /// The indices of the participating signers.
+    pub fn signers(&self) -> impl Iterator<Item = ParticipantIndex> + '_ {
+        self.commitments.iter().map(|commitments| commitments.index)
+    }
+
+    fn randomizer(&self) -> anyhow::Result<Fr> {
+        Fr::from_bytes(self.randomizer).map_err(|_| anyhow!("invalid randomizer"))
+    }
+}
+
+/// A signer's share of a signature, sent to the signing coordinator.
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignatureShare {
+    /// The signer who produced the share.
+    pub index: ParticipantIndex,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    share: [u8; 32],
+}
+
+/// Generates nonces for one signature, returning the secret nonces to keep and the commitments
+/// to send to the signing coordinator.
+pub fn commit<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    key_share: &KeyShare,
+) -> (SigningNonces, SigningCommitments) {
+    let hiding = Fr::rand(rng);
+    let binding = Fr::rand(rng);
+    let basepoint = decaf377::basepoint();
+
+    (
+        SigningNonces {
+            index: key_share.index,
+            hiding: hiding.to_bytes(),
+            binding: binding.to_bytes(),
+        },
+        SigningCommitments {
+            index: key_share.index,
+            hiding: (hiding * basepoint).compress().0,
+            binding: (binding * basepoint).compress().0,
+        },
+    )
+}
+
+/// Produces this signer's share of the signature described by `package`, consuming the nonces
+/// generated for it by [`commit`].
+pub fn sign(
+    package: &SigningPackage,
+    nonces: SigningNonces,
+    key_share: &KeyShare,
+) -> anyhow::Result<SignatureShare> {
+    if nonces.index != key_share.index {
+        return Err(anyhow!(
+            "nonces were generated by participant {}, not {}",
+            nonces.index,
+            key_share.index
+        ));
+    }
+    let own_commitments = package
+        .commitments
+        .iter()
+        .find(|commitments| commitments.index == key_share.index)
+        .ok_or_else(|| anyhow!("signing package has no commitments from this signer"))?;
+    let basepoint = decaf377::basepoint();
+    // The decoded nonces are as secret as the encoded ones, so they're zeroized when dropped too.
+    let hiding =
+        Zeroizing::new(Fr::from_bytes(nonces.hiding).map_err(|_| anyhow!("invalid nonce"))?);
+    let binding =
+        Zeroizing::new(Fr::from_bytes(nonces.binding).map_err(|_| anyhow!("invalid nonce"))?);
+    if (*hiding * basepoint).compress().0 != own_commitments.hiding
+        || (*binding * basepoint).compress().0 != own_commitments.binding
+    {
+        return Err(anyhow!(
+            "signing package commitments don't match this signer's nonces"
+        ));
+    }
+
+    let context = SigningContext::new(package, &key_share.public)?;
+    let binding_factor = context.binding_factors[&key_share.index];
+    let lambda = lagrange_coefficient(key_share.index, package.signers())?;
+
+    let signing_share = Zeroizing::new(key_share.signing_share()?);
+    let share = *hiding + *binding * binding_factor + lambda * *signing_share * context.challenge;
+
+    Ok(SignatureShare {
+        index: key_share.index,
+        share: share.to_bytes(),
+    })
+}
+
+/// Combines the signers' shares into a signature under the randomized group key, checking each
+/// share so that a misbehaving signer can be identified.
+pub fn aggregate(
+    package: &SigningPackage,
+    shares: &[SignatureShare],
+    public: &PublicKeyPackage,
+) -> anyhow::Result<Signature<SpendAuth>> {
+    if package.commitments.len() < public.threshold as usize {
+        return Err(anyhow!(
+            "{} signers participated, but {} are required",
+            package.commitments.len(),
+            public.threshold
+        ));
+    }
+
+    let context = SigningContext::new(package, public)?;
+    let basepoint = decaf377::basepoint();
+
+    let mut z = Fr::zero();
+    for commitments in &package.commitments {
+        let share = shares
+            .iter()
+            .find(|share| share.index == commitments.index)
+            .ok_or_else(|| anyhow!("missing signature share from signer {}", commitments.index))?;
+        let share_value = Fr::from_bytes(share.share)
+            .map_err(|_| anyhow!("invalid signature share from signer {}", share.index))?;
+
+        // Check that z_i B = D_i + ρ_i E_i + c λ_i Y_i.
+        let lambda = lagrange_coefficient(share.index, package.signers())?;
+        let expected = decode_element(&commitments.hiding)?
+            + context.binding_factors[&share.index] * decode_element(&commitments.binding)?
+            + (context.challenge * lambda) * public.verifying_share(share.index)?;
+        if share_value * basepoint != expected {
+            return Err(anyhow!(
+                "invalid signature share from signer {}",
+                share.index
+            ));
+        }
+
+        z += share_value;
+    }
+    // The randomized key is the group key plus randomizer times the basepoint, so the signature
+    // needs the matching multiple of the challenge.
+    z += context.challenge * package.randomizer()?;
+
+    let mut signature_bytes = [0u8; 64];
+    signature_bytes[..32].copy_from_slice(&context.group_commitment.compress().0);
+    signature_bytes[32..].copy_from_slice(&z.to_bytes());
+    let signature = Signature::from(signature_bytes);
+
+    context
+        .randomized_key
+        .verify(&package.message, &signature)
+        .map_err(|_| anyhow!("aggregated signature does not verify"))?;
+
+    Ok(signature)
+}
+
+/// The values derived from a signing package which are shared by all signers.
+struct SigningContext {
+    binding_factors: BTreeMap<ParticipantIndex, Fr>,
+    group_commitment: decaf377::Element,
+    randomized_key: VerificationKey<SpendAuth>,
+    challenge: Fr,
+}
+
+impl SigningContext {
+    fn new(package: &SigningPackage, public: &PublicKeyPackage) -> anyhow::Result<Self> {
+        let randomized_key = public.group_key()?.randomize(&package.randomizer()?);
+
+        let mut encoded_commitments = Vec::new();
+        for commitments in &package.commitments {
+            encoded_commitments.extend_from_slice(&commitments.index.to_le_bytes());
+            encoded_commitments.extend_from_slice(&commitments.hiding);
+            encoded_commitments.extend_from_slice(&commitments.binding);
+        }
+
+        let mut binding_factors = BTreeMap::new();
+        let mut group_commitment = decaf377::Element::default();
+        for commitments in &package.commitments {
+            let binding_factor = hash_to_fr(
+                BINDING_FACTOR_PERSONALIZATION,
+                &[
+                    &commitments.index.to_le_bytes(),
+                    randomized_key.as_ref(),
+                    &package.message,
+                    &encoded_commitments,
+                ],
+            );
+            group_commitment += decode_element(&commitments.hiding)?
+                + binding_factor * decode_element(&commitments.binding)?;
+            binding_factors.insert(commitments.index, binding_factor);
+        }
+
+        let challenge = hash_to_fr(
+            CHALLENGE_PERSONALIZATION,
+            &[
+                &group_commitment.compress().0,
+                randomized_key.as_ref(),
+                &package.message,
+            ],
+        );
+
+        Ok(Self {
+            binding_factors,
+            group_commitment,
+            randomized_key,
+            challenge,
+        })
+    }
+}
+
+/// Hashes `parts` to a scalar using BLAKE2b-512 with the given personalization.
+fn hash_to_fr(personalization: &[u8; 16], parts: &[&[u8]]) -> Fr {
+    let mut state = blake2b_simd::Params::new()
+        .hash_length(64)
+        .personal(personalization)
+        .to_state();
+    for part in parts {
+        state.update(part);
+    }
+    Fr::from_le_bytes_mod_order(state.finalize().as_bytes())
+}
+
+fn decode_element(bytes: &[u8; 32]) -> anyhow::Result<decaf377::Element> {
+    decaf377::Encoding(*bytes)
+        .decompress()
+        .map_err(|_| anyhow!("invalid group element encoding"))
+}
+
+/// The Lagrange coefficient for interpolating the value at zero from the participant `index`,
+/// among the participants `signers`.
+fn lagrange_coefficient(
+    index: ParticipantIndex,
+    signers: impl Iterator<Item = ParticipantIndex>,
+) -> anyhow::Result<Fr> {
+    let x_i = Fr::from(index as u64);
+    let mut numerator = Fr::from(1u64);
+    let mut denominator = Fr::from(1u64);
+    for j in signers.filter(|&j| j != index) {
+        let x_j = Fr::from(j as u64);
+        numerator *= x_j;
+        denominator *= x_j - x_i;
+    }
+    Ok(numerator
+        * denominator
+            .inverse()
+            .ok_or_else(|| anyhow!("duplicate signer {}", index))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_core::OsRng;
+
+    use super::*;
+
+    /// Runs the first two rounds of distributed key generation for `max_signers` participants.
+    fn round2(
+        threshold: u16,
+        max_signers: u16,
+    ) -> (Vec<dkg::Round2Secret>, Vec<dkg::Round2Package>) {
+        let (secrets, packages): (Vec<_>, Vec<_>) = (1..=max_signers)
+            .map(|index| dkg::round1(&mut OsRng, index, threshold, max_signers).unwrap())
+            .unzip();
+
+        let (secrets, round2_packages): (Vec<_>, Vec<_>) = secrets
+            .into_iter()
+            .map(|secret| dkg::round2(secret, &packages).unwrap())
+            .unzip();
+        (secrets, round2_packages.into_iter().flatten().collect())
+    }
+
+    /// Runs the distributed key generation protocol for `max_signers` participants.
+    fn generate(threshold: u16, max_signers: u16) -> Vec<KeyShare> {
+        let (secrets, round2_packages) = round2(threshold, max_signers);
+
+        secrets
+            .into_iter()
+            .map(|secret| {
+                let received = round2_packages
+                    .iter()
+                    .filter(|package| package.receiver == secret.index())
+                    .cloned()
+                    .collect::<Vec<_>>();
+                dkg::finish(secret, &received).unwrap()
+            })
+            .collect()
+    }
+
+    fn sign_with(
+        key_shares: &[&KeyShare],
+        message: &[u8],
+        randomizer: Fr,
+    ) -> (SigningPackage, Vec<SignatureShare>) {
+        let (nonces, commitments): (Vec<_>, Vec<_>) = key_shares
+            .iter()
+            .map(|key_share| commit(&mut OsRng, key_share))
+            .unzip();
+        let package = SigningPackage::new(message.to_vec(), randomizer, commitments).unwrap();
+        let shares = nonces
+            .into_iter()
+            .zip(key_shares)
+            .map(|(nonces, key_share)| sign(&package, nonces, key_share).unwrap())
+            .collect();
+        (package, shares)
+    }
+
+    #[test]
+    fn threshold_signature_verifies_under_randomized_group_key() {
+        let key_shares = generate(2, 3);
+        let public = &key_shares[0].public;
+        assert!(key_shares.iter().all(|share| &share.public == public));
+
+        let message = b"a transaction sighash";
+        let randomizer = Fr::rand(&mut OsRng);
+        for signers in [[0, 1], [0, 2], [1, 2]] {
+            let signers = signers.map(|i| &key_shares[i]);
+            let (package, shares) = sign_with(&signers, message, randomizer);
+
+            let signature = aggregate(&package, &shares, public).unwrap();
+            let rk = public.group_key().unwrap().randomize(&randomizer);
+            rk.verify(message, &signature).unwrap();
+        }
+    }
+
+    #[test]
+    fn too_few_signers_are_rejected() {
+        let key_shares = generate(3, 4);
+        let (package, shares) = sign_with(
+            &[&key_shares[0], &key_shares[1]],
+            b"message",
+            Fr::rand(&mut OsRng),
+        );
+        assert!(aggregate(&package, &shares, &key_shares[0].public).is_err());
+    }
+
+    #[test]
+    fn invalid_signature_share_is_detected() {
+        let key_shares = generate(2, 2);
+        let (package, mut shares) = sign_with(
+            &[&key_shares[0], &key_shares[1]],
+            b"message",
+            Fr::rand(&mut OsRng),
+        );
+        shares[1].share = Fr::rand(&mut OsRng).to_bytes();
+
+        let error = aggregate(&package, &shares, &key_shares[0].public).unwrap_err();
+        assert!(error.to_string().contains("signer 2"));
+    }
+
+    #[test]
+    fn dkg_requires_one_share_from_every_other_participant() {
+        let share_for_1 = |packages: &[dkg::Round2Package], sender| {
+            packages
+                .iter()
+                .find(|package| package.sender == sender && package.receiver == 1)
+                .unwrap()
+                .clone()
+        };
+
+        // A repeated share from participant 2 must not stand in for participant 3's.
+        let (mut secrets, packages) = round2(2, 3);
+        let shares = [share_for_1(&packages, 2), share_for_1(&packages, 2)];
+        let error = dkg::finish(secrets.remove(0), &shares).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("duplicate share from participant 2"));
+
+        let (mut secrets, packages) = round2(2, 3);
+        let shares = [share_for_1(&packages, 2)];
+        let error = dkg::finish(secrets.remove(0), &shares).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("missing shares from participants 3"));
+    }
+
+    #[test]
+    fn secrets_are_not_shown_by_debug() {
+        let key_share = generate(1, 1).remove(0);
+        let (nonces, _) = commit(&mut OsRng, &key_share);
+
+        assert!(!format!("{:?}", key_share).contains(&hex::encode(key_share.signing_share)));
+        let debug = format!("{:?}", nonces);
+        assert!(!debug.contains(&hex::encode(nonces.hiding)));
+        assert!(!debug.contains(&hex::encode(nonces.binding)));
+    }
+}
@@ -0,0 +1,346 @@
+//! Distributed generation of a threshold spend authorization key, following the FROST key
+//! generation protocol, so that no participant ever learns the whole key.
+//!
+//! 1. Each participant calls [`round1`] and broadcasts the resulting [`Round1Package`] to every
+//!    other participant.
+//! 2. Once it has every other participant's [`Round1Package`], each participant calls [`round2`]
+//!    and sends each of the resulting [`Round2Package`]s, *privately*, to its receiver.
+//! 3. Once it has a [`Round2Package`] from every other participant, each participant calls
+//!    [`finish`] to obtain its [`KeyShare`].
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::anyhow;
+use ark_ff::{UniformRand, Zero};
+use decaf377::{FieldExt, Fr};
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use zeroize::{Zeroize, Zeroizing};
+
+use super::{decode_element, hash_to_fr, KeyShare, ParticipantIndex, PublicKeyPackage};
+
+/// The personalization used for the proofs of knowledge of each participant's secret.
+const PROOF_PERSONALIZATION: &[u8; 16] = b"Penumbra_FROSTpk";
+
+/// A participant's secret state between the first and second rounds.
+///
+/// The coefficients of the secret polynomial are zeroized when it is dropped.
+pub struct Round1Secret {
+    index: ParticipantIndex,
+    threshold: u16,
+    max_signers: u16,
+    coefficients: Vec<Fr>,
+    commitments: Vec<decaf377::Element>,
+}
+
+impl Drop for Round1Secret {
+    fn drop(&mut self) {
+        self.coefficients.zeroize();
+    }
+}
+
+/// The message broadcast by each participant in the first round: commitments to the
+/// coefficients of its secret polynomial, and a proof that it knows the constant term.
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Round1Package {
+    /// The participant who sent the package.
+    pub sender: ParticipantIndex,
+    #[serde_as(as = "Vec<serde_with::hex::Hex>")]
+    commitments: Vec<[u8; 32]>,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    proof_commitment: [u8; 32],
+    #[serde_as(as = "serde_with::hex::Hex")]
+    proof_response: [u8; 32],
+}
+
+/// A participant's secret state between the second round and the end of the protocol.
+///
+/// The participant's share of its own polynomial is zeroized when it is dropped.
+pub struct Round2Secret {
+    index: ParticipantIndex,
+    threshold: u16,
+    own_share: Fr,
+    commitments: BTreeMap<ParticipantIndex, Vec<decaf377::Element>>,
+}
+
+impl Drop for Round2Secret {
+    fn drop(&mut self) {
+        self.own_share.zeroize();
+    }
+}
+
+impl Round2Secret {
+    /// The index of the participant holding this state.
+    pub fn index(&self) -> ParticipantIndex {
+        self.index
+    }
+}
+
+/// The message sent privately from one participant to another in the second round: the
+/// sender's secret polynomial evaluated at the receiver's index.
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Round2Package {
+    /// The participant who sent the package.
+    pub sender: ParticipantIndex,
+    /// The participant the package must be sent to.
+    pub receiver: ParticipantIndex,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    share: [u8; 32],
+}
+
+/// Starts key generation as participant `index` of `max_signers`, of whom `threshold` will be
+/// needed to sign.
+pub fn round1<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    index: ParticipantIndex,
+    threshold: u16,
+    max_signers: u16,
+) -> anyhow::Result<(Round1Secret, Round1Package)> {
+    if threshold == 0 || threshold > max_signers {
+        return Err(anyhow!(
+            "threshold must be between 1 and the number of participants {}, got {}",
+            max_signers,
+            threshold
+        ));
+    }
+    if index == 0 || index > max_signers {
+        return Err(anyhow!(
+            "participant index must be between 1 and {}, got {}",
+            max_signers,
+            index
+        ));
+    }
+
+    let basepoint = decaf377::basepoint();
+    let coefficients = (0..threshold).map(|_| Fr::rand(rng)).collect::<Vec<_>>();
+    let commitments = coefficients
+        .iter()
+        .map(|coefficient| *coefficient * basepoint)
+        .collect::<Vec<_>>();
+
+    // Prove knowledge of the constant term, so that no participant can choose its commitment as
+    // a function of the others' to bias the group key.
+    let nonce = Fr::rand(rng);
+    let proof_commitment = (nonce * basepoint).compress().0;
+    let challenge = proof_challenge(index, &commitments[0].compress().0, &proof_commitment);
+    let proof_response = nonce + coefficients[0] * challenge;
+
+    let package = Round1Package {
+        sender: index,
+        commitments: commitments
+            .iter()
+            .map(|commitment| commitment.compress().0)
+            .collect(),
+        proof_commitment,
+        proof_response: proof_response.to_bytes(),
+    };
+
+    Ok((
+        Round1Secret {
+            index,
+            threshold,
+            max_signers,
+            coefficients,
+            commitments,
+        },
+        package,
+    ))
+}
+
+/// Checks every other participant's [`Round1Package`] and computes the shares to send to each
+/// of them.
+///
+/// `packages` may include this participant's own package, which is ignored.
+pub fn round2(
+    secret: Round1Secret,
+    packages: &[Round1Package],
+) -> anyhow::Result<(Round2Secret, Vec<Round2Package>)> {
+    let basepoint = decaf377::basepoint();
+
+    let mut commitments = BTreeMap::new();
+    commitments.insert(secret.index, secret.commitments.clone());
+    for package in packages.iter().filter(|p| p.sender != secret.index) {
+        if package.sender == 0 || package.sender > secret.max_signers {
+            return Err(anyhow!("unexpected participant {}", package.sender));
+        }
+        if package.commitments.len() != secret.threshold as usize {
+            return Err(anyhow!(
+                "participant {} committed to {} coefficients, expected {}",
+                package.sender,
+                package.commitments.len(),
+                secret.threshold
+            ));
+        }
+
+        let challenge = proof_challenge(
+            package.sender,
+            &package.commitments[0],
+            &package.proof_commitment,
+        );
+        let response = Fr::from_bytes(package.proof_response)
+            .map_err(|_| anyhow!("invalid proof from participant {}", package.sender))?;
+        if response * basepoint
+            != decode_element(&package.proof_commitment)?
+                + challenge * decode_element(&package.commitments[0])?
+        {
+            return Err(anyhow!("invalid proof from participant {}", package.sender));
+        }
+
+        let decoded = package
+            .commitments
+            .iter()
+            .map(decode_element)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if commitments.insert(package.sender, decoded).is_some() {
+            return Err(anyhow!(
+                "duplicate package from participant {}",
+                package.sender
+            ));
+        }
+    }
+    if commitments.len() != secret.max_signers as usize {
+        return Err(anyhow!(
+            "expected packages from {} participants, got {}",
+            secret.max_signers,
+            commitments.len()
+        ));
+    }
+
+    let outgoing = (1..=secret.max_signers)
+        .filter(|&receiver| receiver != secret.index)
+        .map(|receiver| Round2Package {
+            sender: secret.index,
+            receiver,
+            share: evaluate(&secret.coefficients, receiver).to_bytes(),
+        })
+        .collect();
+
+    Ok((
+        Round2Secret {
+            index: secret.index,
+            threshold: secret.threshold,
+            own_share: evaluate(&secret.coefficients, secret.index),
+            commitments,
+        },
+        outgoing,
+    ))
+}
+
+/// Checks the shares received from every other participant and combines them into this
+/// participant's [`KeyShare`].
+pub fn finish(secret: Round2Secret, packages: &[Round2Package]) -> anyhow::Result<KeyShare> {
+    let basepoint = decaf377::basepoint();
+
+    // The sum is zeroized when dropped, on every path out of this function.
+    let mut signing_share = Zeroizing::new(secret.own_share);
+    let mut senders = BTreeSet::new();
+    for package in packages {
+        if package.receiver != secret.index {
+            return Err(anyhow!(
+                "package from participant {} is for participant {}",
+                package.sender,
+                package.receiver
+            ));
+        }
+        let commitments = secret
+            .commitments
+            .get(&package.sender)
+            .filter(|_| package.sender != secret.index)
+            .ok_or_else(|| anyhow!("unexpected participant {}", package.sender))?;
+        if !senders.insert(package.sender) {
+            return Err(anyhow!(
+                "duplicate share from participant {}",
+                package.sender
+            ));
+        }
+
+        let share = Fr::from_bytes(package.share)
+            .map_err(|_| anyhow!("invalid share from participant {}", package.sender))?;
+        if share * basepoint != evaluate_commitments(commitments, secret.index) {
+            return Err(anyhow!("invalid share from participant {}", package.sender));
+        }
+
+        *signing_share += share;
+    }
+    let missing = secret
+        .commitments
+        .keys()
+        .filter(|&&index| index != secret.index && !senders.contains(&index))
+        .map(|index| index.to_string())
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "missing shares from participants {}",
+            missing.join(", ")
+        ));
+    }
+
+    let group_key = secret
+        .commitments
+        .values()
+        .fold(decaf377::Element::default(), |sum, commitments| {
+            sum + commitments[0]
+        });
+    let verifying_shares = secret
+        .commitments
+        .keys()
+        .map(|&index| {
+            let verifying_share = secret
+                .commitments
+                .values()
+                .fold(decaf377::Element::default(), |sum, commitments| {
+                    sum + evaluate_commitments(commitments, index)
+                });
+            (index, verifying_share.compress().0)
+        })
+        .collect();
+
+    let key_share = KeyShare {
+        index: secret.index,
+        signing_share: signing_share.to_bytes(),
+        public: PublicKeyPackage {
+            threshold: secret.threshold,
+            group_key: group_key.compress().0,
+            verifying_shares,
+        },
+    };
+    Ok(key_share)
+}
+
+fn proof_challenge(
+    index: ParticipantIndex,
+    commitment: &[u8; 32],
+    proof_commitment: &[u8; 32],
+) -> Fr {
+    hash_to_fr(
+        PROOF_PERSONALIZATION,
+        &[&index.to_le_bytes(), commitment, proof_commitment],
+    )
+}
+
+/// Evaluates the polynomial with the given coefficients at a participant's index.
+fn evaluate(coefficients: &[Fr], index: ParticipantIndex) -> Fr {
+    let x = Fr::from(index as u64);
+    coefficients
+        .iter()
+        .rev()
+        .fold(Fr::zero(), |value, coefficient| value * x + coefficient)
+}
+
+/// Evaluates the polynomial committed to by `commitments` at a participant's index, in the
+/// exponent.
+fn evaluate_commitments(
+    commitments: &[decaf377::Element],
+    index: ParticipantIndex,
+) -> decaf377::Element {
+    let x = Fr::from(index as u64);
+    commitments
+        .iter()
+        .rev()
+        .fold(decaf377::Element::default(), |value, commitment| {
+            x * value + *commitment
+        })
+}
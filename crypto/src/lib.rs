@@ -18,7 +18,7 @@ mod prf;
 pub mod proofs;
 pub mod value;
 
-pub use address::Address;
+pub use address::{Address, AddressError};
 pub use asset::Asset;
 pub use delegation_token::DelegationToken;
 pub use identity_key::IdentityKey;
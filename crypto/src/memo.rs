@@ -6,6 +6,8 @@ use chacha20poly1305::{
     ChaCha20Poly1305, Key, Nonce,
 };
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
 
 use crate::{ka, keys::IncomingViewingKey, note::derive_symmetric_key, Address};
 
@@ -21,8 +23,9 @@ pub static MEMO_ENCRYPTION_NONCE: Lazy<[u8; 12]> = Lazy::new(|| {
 });
 
 // The memo is stored separately from the `Note`.
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct MemoPlaintext(pub [u8; MEMO_LEN_BYTES]);
+#[serde_as]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoPlaintext(#[serde_as(as = "serde_with::hex::Hex")] pub [u8; MEMO_LEN_BYTES]);
 
 impl Default for MemoPlaintext {
     fn default() -> MemoPlaintext {
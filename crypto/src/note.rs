@@ -12,6 +12,7 @@ use penumbra_proto::{crypto as pb, Protobuf};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use thiserror;
+use zeroize::Zeroizing;
 
 use crate::{
     asset, ka,
@@ -173,15 +174,17 @@ impl Note {
         let kdf_output = kdf.finalize();
         let ock = Key::from_slice(kdf_output.as_bytes());
 
-        let mut op = Vec::new();
+        // The plaintext contains the ephemeral secret key, so zeroize it once it's encrypted.
+        let esk_bytes = Zeroizing::new(esk.to_bytes());
+        let mut op = Zeroizing::new(Vec::new());
         op.extend_from_slice(&self.transmission_key().0);
-        op.extend_from_slice(&esk.to_bytes());
+        op.extend_from_slice(&*esk_bytes);
 
         let cipher = ChaCha20Poly1305::new(ock);
         let nonce = Nonce::from_slice(&*NOTE_ENCRYPTION_NONCE);
 
         let encryption_result = cipher
-            .encrypt(nonce, op.as_ref())
+            .encrypt(nonce, op.as_slice())
             .expect("OVK encryption succeeded");
 
         let wrapped_ovk: [u8; OVK_WRAPPED_LEN_BYTES] = encryption_result
@@ -234,6 +237,34 @@ impl Note {
     }
 }
 
+/// Trial-decrypt a batch of note ciphertexts with the same incoming viewing key.
+///
+/// Each entry of `encrypted_notes` is the ephemeral public key of an output together with its
+/// note ciphertext.  The result holds, in the same order, the decrypted note for each output
+/// addressed to `ivk`, and `None` for every other output.
+///
+/// The key agreement for each output dominates the cost of trial decryption, so with the
+/// `parallel` feature the batch is split across the current [`rayon`] thread pool; this should
+/// then be called from a blocking context rather than directly from an async task.
+pub fn decrypt_batch(
+    ivk: &IncomingViewingKey,
+    encrypted_notes: &[(ka::Public, [u8; NOTE_CIPHERTEXT_BYTES])],
+) -> Vec<Option<Note>> {
+    let decrypt = |(epk, ciphertext): &(ka::Public, [u8; NOTE_CIPHERTEXT_BYTES])| {
+        Note::decrypt(ciphertext, ivk, epk).ok()
+    };
+
+    #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+    {
+        use rayon::prelude::*;
+        encrypted_notes.par_iter().map(decrypt).collect()
+    }
+    #[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+    {
+        encrypted_notes.iter().map(decrypt).collect()
+    }
+}
+
 /// Use Blake2b-256 to derive the symmetric key material for note and memo encryption.
 pub(crate) fn derive_symmetric_key(
     shared_secret: &ka::SharedSecret,
@@ -512,4 +543,48 @@ mod tests {
 
         assert!(Note::decrypt(&ciphertext, ivk2, &epk).is_err());
     }
+
+    #[test]
+    fn test_note_batch_decryption() {
+        let mut rng = OsRng;
+
+        let ivk = SpendKey::new(SpendSeed::from_seed_phrase(
+            SeedPhrase::generate(&mut rng),
+            0,
+        ))
+        .full_viewing_key()
+        .incoming()
+        .clone();
+        let other_ivk = SpendKey::new(SpendSeed::from_seed_phrase(
+            SeedPhrase::generate(&mut rng),
+            0,
+        ))
+        .full_viewing_key()
+        .incoming()
+        .clone();
+
+        let value = Value {
+            amount: 10,
+            asset_id: asset::REGISTRY.parse_denom("upenumbra").unwrap().id(),
+        };
+
+        // Interleave notes sent to us with notes sent to someone else.
+        let mut notes = Vec::new();
+        let mut encrypted_notes = Vec::new();
+        for i in 0..8u64 {
+            let (dest, _dtk_d) = if i % 2 == 0 {
+                ivk.payment_address(i.into())
+            } else {
+                other_ivk.payment_address(i.into())
+            };
+            let note = Note::generate(&mut rng, &dest, value);
+            let esk = ka::Secret::new(&mut rng);
+            let epk = esk.diversified_public(dest.diversified_generator());
+            encrypted_notes.push((epk, note.encrypt(&esk)));
+            notes.push(if i % 2 == 0 { Some(note) } else { None });
+        }
+
+        assert_eq!(decrypt_batch(&ivk, &encrypted_notes), notes);
+        assert!(decrypt_batch(&ivk, &[]).is_empty());
+    }
 }
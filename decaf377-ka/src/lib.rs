@@ -14,11 +14,15 @@ use zeroize::Zeroize;
 pub struct Public(pub [u8; 32]);
 
 /// A secret key used to perform key agreement using the counterparty's public key.
+///
+/// The secret is zeroized when it is dropped.
 #[derive(Clone, Zeroize)]
 #[zeroize(drop)]
 pub struct Secret(decaf377::Fr);
 
 /// The shared secret derived at the end of the key agreement protocol.
+///
+/// The shared secret is zeroized when it is dropped.
 #[derive(PartialEq, Eq, Clone, Zeroize)]
 #[zeroize(drop)]
 pub struct SharedSecret(pub [u8; 32]);
@@ -0,0 +1,22 @@
+use decaf377_ka as ka;
+use zeroize::Zeroize;
+
+#[test]
+fn secret_is_zeroized() {
+    let mut secret = ka::Secret::new_from_field(decaf377::Fr::from(7u64));
+    assert_ne!(<[u8; 32]>::from(&secret), [0u8; 32]);
+
+    secret.zeroize();
+    assert_eq!(<[u8; 32]>::from(&secret), [0u8; 32]);
+}
+
+#[test]
+fn shared_secret_is_zeroized() {
+    let alice = ka::Secret::new_from_field(decaf377::Fr::from(7u64));
+    let bob = ka::Secret::new_from_field(decaf377::Fr::from(11u64));
+    let mut shared = alice.key_agreement_with(&bob.public()).unwrap();
+    assert_ne!(shared.0, [0u8; 32]);
+
+    shared.zeroize();
+    assert_eq!(shared.0, [0u8; 32]);
+}
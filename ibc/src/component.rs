@@ -5,6 +5,7 @@
 
 mod client;
 mod connection;
+mod state_key;
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -16,6 +17,8 @@ use penumbra_transaction::Transaction;
 use tendermint::abci;
 use tracing::instrument;
 
+pub use state_key::existing_state_keys;
+
 pub struct IBCComponent {
     client: client::Ics2Client,
     connection: connection::ConnectionComponent,
@@ -27,7 +27,7 @@ use penumbra_proto::ibc::{
     ibc_action::Action::{CreateClient, UpdateClient},
     IbcAction,
 };
-use penumbra_storage::{State, StateExt};
+use penumbra_storage::{State, StateExt, StateRead};
 use penumbra_transaction::Transaction;
 use tendermint::{abci, validator};
 use tendermint_light_client_verifier::{
@@ -36,6 +36,7 @@ use tendermint_light_client_verifier::{
 };
 use tracing::instrument;
 
+use super::state_key;
 use crate::{ClientConnections, ClientCounter, ClientData, ConsensusState, VerifiedHeights};
 
 mod stateful;
@@ -349,48 +350,26 @@ impl Ics2Client {
 #[async_trait]
 pub trait View: StateExt {
     async fn put_client_counter(&mut self, counter: ClientCounter) {
-        self.put_domain("ibc/ics02-client/client_counter".into(), counter)
-            .await;
+        self.put_typed(&state_key::client_counter(), counter).await;
     }
     async fn client_counter(&self) -> Result<ClientCounter> {
-        self.get_domain("ibc/ics02-client/client_counter".into())
+        self.get_typed(&state_key::client_counter())
             .await
             .map(|counter| counter.unwrap_or(ClientCounter(0)))
     }
     async fn put_client_data(&mut self, data: ClientData) {
-        self.put_domain(
-            format!(
-                "ibc/ics02-client/clients/{}",
-                hex::encode(data.client_id.as_bytes())
-            )
-            .into(),
-            data,
-        )
-        .await;
+        self.put_typed(&state_key::client_data(&data.client_id), data)
+            .await;
     }
     async fn get_client_data(&self, client_id: &ClientId) -> Result<ClientData> {
-        let client_data = self
-            .get_domain(
-                format!(
-                    "ibc/ics02-client/clients/{}",
-                    hex::encode(client_id.as_bytes())
-                )
-                .into(),
-            )
-            .await?;
+        let client_data = self.get_typed(&state_key::client_data(client_id)).await?;
 
         client_data.ok_or(anyhow::anyhow!("client not found"))
     }
 
     async fn get_verified_heights(&self, client_id: &ClientId) -> Result<Option<VerifiedHeights>> {
-        self.get_domain(
-            format!(
-                "ibc/ics02-client/clients/{}/verified_heights",
-                hex::encode(client_id.as_bytes())
-            )
-            .into(),
-        )
-        .await
+        self.get_typed(&state_key::verified_heights(client_id))
+            .await
     }
 
     async fn put_verified_heights(
@@ -398,28 +377,21 @@ pub trait View: StateExt {
         client_id: &ClientId,
         verified_heights: VerifiedHeights,
     ) {
-        self.put_domain(
-            format!(
-                "ibc/ics02-client/clients/{}/verified_heights",
-                hex::encode(client_id.as_bytes())
-            )
-            .into(),
-            verified_heights,
-        )
-        .await;
+        self.put_typed(&state_key::verified_heights(client_id), verified_heights)
+            .await;
     }
 
     // returns the ConsensusState for the penumbra chain (this chain) at the given height
     async fn get_penumbra_consensus_state(&self, height: Height) -> Result<ConsensusState> {
-        self.get_domain(format!("ibc/ics02-client/penumbra_consensus_states/{}", height).into())
+        self.get_typed(&state_key::penumbra_consensus_state(height))
             .await?
             .ok_or(anyhow::anyhow!("consensus state not found"))
     }
 
     // returns the ConsensusState for the penumbra chain (this chain) at the given height
     async fn put_penumbra_consensus_state(&self, height: Height, consensus_state: ConsensusState) {
-        self.put_domain(
-            format!("ibc/ics02-client/penumbra_consensus_states/{}", height).into(),
+        self.put_typed(
+            &state_key::penumbra_consensus_state(height),
             consensus_state,
         )
         .await;
@@ -430,16 +402,9 @@ pub trait View: StateExt {
         height: Height,
         client_id: ClientId,
     ) -> Result<ConsensusState> {
-        self.get_domain(
-            format!(
-                "ibc/ics02-client/clients/{}/consensus_state/{}",
-                hex::encode(client_id.as_bytes()),
-                height
-            )
-            .into(),
-        )
-        .await?
-        .ok_or(anyhow::anyhow!("consensus state not found"))
+        self.get_typed(&state_key::verified_consensus_state(&client_id, height))
+            .await?
+            .ok_or(anyhow::anyhow!("consensus state not found"))
     }
 
     async fn put_verified_consensus_state(
@@ -448,13 +413,8 @@ pub trait View: StateExt {
         client_id: ClientId,
         consensus_state: ConsensusState,
     ) -> Result<()> {
-        self.put_domain(
-            format!(
-                "ibc/ics02-client/clients/{}/consensus_state/{}",
-                hex::encode(client_id.as_bytes()),
-                height
-            )
-            .into(),
+        self.put_typed(
+            &state_key::verified_consensus_state(&client_id, height),
             consensus_state,
         )
         .await;
@@ -547,27 +507,14 @@ pub trait View: StateExt {
         self.get_client_data(client_id).await?;
 
         let mut connections = self
-            .get_domain(
-                format!(
-                    "ibc/ics02-client/clients/{}/connections",
-                    hex::encode(client_id.as_bytes())
-                )
-                .into(),
-            )
+            .get_typed(&state_key::client_connections(client_id))
             .await?
             .unwrap_or(ClientConnections::default());
 
         connections.connection_ids.push(connection_id.clone());
 
-        self.put_domain(
-            format!(
-                "ibc/ics02-client/clients/{}/connections",
-                hex::encode(client_id.as_bytes())
-            )
-            .into(),
-            connections,
-        )
-        .await;
+        self.put_typed(&state_key::client_connections(client_id), connections)
+            .await;
 
         Ok(())
     }
@@ -584,7 +531,7 @@ mod tests {
     use penumbra_crypto::{Fq, Zero};
     use penumbra_proto::ibc::ibc_action::Action as IbcActionInner;
     use penumbra_proto::Message;
-    use penumbra_storage::Storage;
+    use penumbra_storage::{Migrations, Storage};
     use penumbra_transaction::{Action, Fee, Transaction, TransactionBody};
     use std::fs;
     use tempfile::tempdir;
@@ -597,7 +544,7 @@ mod tests {
         let dir = tempdir().unwrap();
         let file_path = dir.path().join("ibc-testing.db");
 
-        let storage = Storage::load(file_path).await.unwrap();
+        let storage = Storage::load(file_path, &Migrations::new()).await.unwrap();
         let state = storage.state().await.unwrap();
 
         let mut client_component = Ics2Client::new(state).await;
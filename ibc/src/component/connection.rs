@@ -24,11 +24,12 @@ use penumbra_proto::ibc::{
     },
     IbcAction,
 };
-use penumbra_storage::{State, StateExt};
+use penumbra_storage::{State, StateExt, StateRead};
 use penumbra_transaction::Transaction;
 use tendermint::abci;
 use tracing::instrument;
 
+use super::state_key;
 use crate::component::client::View as _;
 use crate::{
     validate_penumbra_client_state, Connection, ConnectionCounter, COMMITMENT_PREFIX,
@@ -585,13 +586,13 @@ impl ConnectionComponent {
 #[async_trait]
 pub trait View: StateExt + Send + Sync {
     async fn get_connection_counter(&self) -> Result<ConnectionCounter> {
-        self.get_domain("ibc/ics03-connection/connection_counter".into())
+        self.get_typed(&state_key::connection_counter())
             .await
             .map(|counter| counter.unwrap_or(ConnectionCounter(0)))
     }
 
     async fn put_connection_counter(&self, counter: ConnectionCounter) {
-        self.put_domain("ibc/ics03-connection/connection_counter".into(), counter)
+        self.put_typed(&state_key::connection_counter(), counter)
             .await;
     }
 
@@ -602,15 +603,8 @@ pub trait View: StateExt + Send + Sync {
         connection_id: &ConnectionId,
         connection: Connection,
     ) -> Result<()> {
-        self.put_domain(
-            format!(
-                "ibc/ics03-connection/connections/{}",
-                connection_id.as_str()
-            )
-            .into(),
-            connection.clone(),
-        )
-        .await;
+        self.put_typed(&state_key::connection(connection_id), connection.clone())
+            .await;
         let counter = self
             .get_connection_counter()
             .await
@@ -625,26 +619,12 @@ pub trait View: StateExt + Send + Sync {
     }
 
     async fn get_connection(&self, connection_id: &ConnectionId) -> Result<Option<Connection>> {
-        self.get_domain(
-            format!(
-                "ibc/ics03-connection/connections/{}",
-                connection_id.as_str()
-            )
-            .into(),
-        )
-        .await
+        self.get_typed(&state_key::connection(connection_id)).await
     }
 
     async fn update_connection(&self, connection_id: &ConnectionId, connection: Connection) {
-        self.put_domain(
-            format!(
-                "ibc/ics03-connection/connections/{}",
-                connection_id.as_str()
-            )
-            .into(),
-            connection,
-        )
-        .await;
+        self.put_typed(&state_key::connection(connection_id), connection)
+            .await;
     }
 }
 
@@ -0,0 +1,103 @@
+//! The keys under which the IBC state is stored.
+
+use anyhow::Result;
+use ibc::core::{
+    ics02_client::{client_type::ClientType, height::Height},
+    ics24_host::identifier::{ClientId, ConnectionId},
+};
+use penumbra_chain::View as _;
+use penumbra_storage::{State, StateKey};
+
+use super::{client::View as _, connection::View as _};
+use crate::{
+    ClientConnections, ClientCounter, ClientData, Connection, ConnectionCounter, ConsensusState,
+    VerifiedHeights,
+};
+
+const DOMAIN: &str = "ibc";
+
+fn ics02_client<T>() -> StateKey<T> {
+    StateKey::new(DOMAIN).join("ics02-client")
+}
+
+fn ics03_connection<T>() -> StateKey<T> {
+    StateKey::new(DOMAIN).join("ics03-connection")
+}
+
+// Client ids are hex-encoded, as they may contain `/`.
+fn client<T>(client_id: &ClientId) -> StateKey<T> {
+    ics02_client()
+        .join("clients")
+        .join(hex::encode(client_id.as_bytes()))
+}
+
+pub fn client_counter() -> StateKey<ClientCounter> {
+    ics02_client().join("client_counter")
+}
+
+pub fn client_data(client_id: &ClientId) -> StateKey<ClientData> {
+    client(client_id)
+}
+
+pub fn verified_heights(client_id: &ClientId) -> StateKey<VerifiedHeights> {
+    client(client_id).join("verified_heights")
+}
+
+pub fn verified_consensus_state(client_id: &ClientId, height: Height) -> StateKey<ConsensusState> {
+    client(client_id).join("consensus_state").join(height)
+}
+
+pub fn client_connections(client_id: &ClientId) -> StateKey<ClientConnections> {
+    client(client_id).join("connections")
+}
+
+pub fn penumbra_consensus_state(height: Height) -> StateKey<ConsensusState> {
+    ics02_client()
+        .join("penumbra_consensus_states")
+        .join(height)
+}
+
+pub fn connection_counter() -> StateKey<ConnectionCounter> {
+    ics03_connection().join("connection_counter")
+}
+
+pub fn connection(connection_id: &ConnectionId) -> StateKey<Connection> {
+    ics03_connection()
+        .join("connections")
+        .join(connection_id.as_str())
+}
+
+/// Returns the keys which may have been written before the key index existed, for
+/// [`reindex_keys`](penumbra_storage::reindex_keys).
+///
+/// Clients and connections are found from their counters, since their ids are assigned in order,
+/// and our own consensus states are listed for every height up to the current one.
+pub async fn existing_state_keys(state: &State) -> Result<Vec<String>> {
+    let mut keys = vec![
+        client_counter().to_string(),
+        connection_counter().to_string(),
+    ];
+
+    for counter in 0..state.client_counter().await?.0 {
+        // Only Tendermint clients can be created.
+        let client_id = ClientId::new(ClientType::Tendermint, counter)?;
+        keys.push(client_data(&client_id).to_string());
+        keys.push(verified_heights(&client_id).to_string());
+        keys.push(client_connections(&client_id).to_string());
+        if let Some(verified) = state.get_verified_heights(&client_id).await? {
+            for height in verified.heights {
+                keys.push(verified_consensus_state(&client_id, height).to_string());
+            }
+        }
+    }
+
+    for counter in 0..state.get_connection_counter().await?.0 {
+        keys.push(connection(&ConnectionId::new(counter)).to_string());
+    }
+
+    for height in 0..=state.get_block_height().await? {
+        keys.push(penumbra_consensus_state(Height::new(0, height)).to_string());
+    }
+
+    Ok(keys)
+}
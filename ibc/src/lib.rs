@@ -11,7 +11,7 @@ pub use client::{
     validate_penumbra_client_state, ClientConnections, ClientCounter, ClientData, ConsensusState,
     VerifiedHeights,
 };
-pub use component::IBCComponent;
+pub use component::{existing_state_keys, IBCComponent};
 pub use connection::{Connection, ConnectionCounter, SUPPORTED_VERSIONS};
 
 pub static COMMITMENT_PREFIX: &str = "penumbra-ibc-commitment";
@@ -1,7 +1,14 @@
 use structopt::StructOpt;
 
 mod addr;
+mod assets;
 mod balance;
+mod bench;
+mod chain;
+mod completions;
+mod config;
+mod dash;
+mod notes;
 mod stake;
 mod temp;
 mod tx;
@@ -9,7 +16,14 @@ mod validator;
 mod wallet;
 
 pub use addr::AddrCmd;
+pub use assets::AssetsCmd;
 pub use balance::BalanceCmd;
+pub use bench::BenchCmd;
+pub use chain::ChainCmd;
+pub use completions::{CompleteCmd, CompletionsCmd};
+pub use config::ConfigCmd;
+pub use dash::DashCmd;
+pub use notes::NotesCmd;
 pub use stake::StakeCmd;
 pub use temp::TmpCmd;
 pub use tx::TxCmd;
@@ -22,19 +36,54 @@ pub enum Command {
     Tx(TxCmd),
     /// Manages the wallet state.
     Wallet(WalletCmd),
+    /// Manages the pcli config file.
+    Config(ConfigCmd),
+    /// Generates shell completion scripts.
+    ///
+    /// For example, `pcli completions bash > /etc/bash_completion.d/pcli`.
+    Completions(CompletionsCmd),
+    /// Prints candidate values for dynamic shell completion.
+    #[structopt(setting = structopt::clap::AppSettings::Hidden)]
+    Complete(CompleteCmd),
     /// Manages addresses.
     Addr(AddrCmd),
     /// Synchronizes the client, privately scanning the chain state.
     ///
     /// `pcli` syncs automatically prior to any action requiring chain state,
     /// but this command can be used to "pre-sync" before interactive use.
-    Sync,
+    Sync {
+        /// Optional. Rediscover notes by rescanning the chain from the given height.
+        ///
+        /// Every block is fetched and replayed from genesis whatever the height, since the note
+        /// commitment tree has to be rebuilt from the start; the height only limits trial
+        /// decryption to the blocks from that height onwards. Notes already in the wallet are
+        /// re-witnessed below the height, and notes from the height onwards are rediscovered, so
+        /// this can be used to recover from client state corruption, or to find notes sent to
+        /// an imported seed before it was imported.
+        #[structopt(long)]
+        rescan_from: Option<u64>,
+    },
     /// Displays the current wallet balance.
     Balance(BalanceCmd),
+    /// Lists and labels the wallet's notes.
+    Notes(NotesCmd),
+    /// Opens an interactive dashboard showing the wallet's balance, sync progress, pending
+    /// transactions, and delegations, from which funds can be sent.
+    ///
+    /// While the dashboard is open, log messages are written to `dash.log` in the data directory,
+    /// unless `--log-file` is given.
+    Dash(DashCmd),
+    /// Queries information about the chain.
+    Chain(ChainCmd),
+    /// Queries the chain's asset registry.
+    Assets(AssetsCmd),
     /// Manages a validator.
     Validator(ValidatorCmd),
     /// Manages delegations and undelegations.
     Stake(StakeCmd),
+    /// Measures the performance of syncing, trial decryption, proving, and note commitment tree
+    /// insertion on this machine.
+    Bench(BenchCmd),
     /// Temporary commands for migrating address formats.
     Tmp(TmpCmd),
 }
@@ -45,11 +94,19 @@ impl Command {
         match self {
             Command::Tx(cmd) => cmd.needs_sync(),
             Command::Wallet(cmd) => cmd.needs_sync(),
+            Command::Config(cmd) => cmd.needs_sync(),
+            Command::Completions(cmd) => cmd.needs_sync(),
+            Command::Complete(cmd) => cmd.needs_sync(),
             Command::Addr(cmd) => cmd.needs_sync(),
-            Command::Sync => true,
+            Command::Sync { .. } => true,
             Command::Balance(cmd) => cmd.needs_sync(),
+            Command::Notes(cmd) => cmd.needs_sync(),
+            Command::Dash(cmd) => cmd.needs_sync(),
+            Command::Chain(cmd) => cmd.needs_sync(),
+            Command::Assets(cmd) => cmd.needs_sync(),
             Command::Validator(cmd) => cmd.needs_sync(),
             Command::Stake(cmd) => cmd.needs_sync(),
+            Command::Bench(cmd) => cmd.needs_sync(),
             Command::Tmp(cmd) => cmd.needs_sync(),
         }
     }
@@ -1,8 +1,10 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use comfy_table::{presets, Table};
+use penumbra_crypto::Address;
+use serde_json::json;
 use structopt::StructOpt;
 
-use crate::ClientStateFile;
+use crate::{ClientStateFile, Opt};
 
 #[derive(Debug, StructOpt)]
 pub enum AddrCmd {
@@ -18,10 +20,21 @@ pub enum AddrCmd {
         addr_only: bool,
     },
     /// Create a new address.
+    ///
+    /// Each address has its own index, and addresses can't be linked to each other by anyone
+    /// but the wallet owner, so a new address can be handed out to each counterparty.
     New {
         /// A freeform label for the address, stored only locally.
         label: String,
     },
+    /// Show the index and label of one of this wallet's addresses.
+    ///
+    /// This can be used to recognize which counterparty a payment came from, if each one was
+    /// given its own address.
+    Lookup {
+        /// The address to look up.
+        address: String,
+    },
 }
 
 impl AddrCmd {
@@ -31,36 +44,63 @@ impl AddrCmd {
             AddrCmd::List => false,
             AddrCmd::Show { .. } => false,
             AddrCmd::New { .. } => false,
+            AddrCmd::Lookup { .. } => false,
         }
     }
 
-    pub fn exec(&self, state: &mut ClientStateFile) -> Result<()> {
-        // Set up table (this won't be used with `show --addr-only`)
-        let mut table = Table::new();
-        table.load_preset(presets::NOTHING);
-        table.set_header(vec!["Index", "Label", "Address"]);
-
-        match self {
-            AddrCmd::List => {
-                for (index, label, address) in state.wallet().addresses() {
-                    table.add_row(vec![index.to_string(), label, address.to_string()]);
-                }
-            }
+    pub fn exec(&self, opt: &Opt, state: &mut ClientStateFile) -> Result<()> {
+        // Collect the (index, label, address) rows to display
+        let rows = match self {
+            AddrCmd::List => state
+                .wallet()
+                .addresses()
+                .map(|(index, label, address)| (index, label, address.to_string()))
+                .collect::<Vec<_>>(),
             AddrCmd::Show { index, addr_only } => {
                 let (label, address) = state.wallet().address_by_index(*index as usize)?;
 
-                if *addr_only {
+                if *addr_only && !opt.output.is_json() {
                     println!("{}", address);
                     return Ok(()); // don't print the label
-                } else {
-                    table.add_row(vec![index.to_string(), label, address.to_string()]);
                 }
+                vec![(*index as usize, label, address.to_string())]
             }
             AddrCmd::New { label } => {
                 let (index, address, _dtk) = state.wallet_mut().new_address(label.clone());
                 state.commit()?;
-                table.add_row(vec![index.to_string(), label.clone(), address.to_string()]);
+                vec![(index, label.clone(), address.to_string())]
             }
+            AddrCmd::Lookup { address } => {
+                let parsed = Address::parse_with_diagnostics(address)
+                    .map_err(|e| anyhow!("address is invalid: {}", e))?;
+                let (index, label) = state
+                    .wallet()
+                    .index_for_address(&parsed)
+                    .ok_or_else(|| anyhow!("address does not belong to this wallet"))?;
+                vec![(index as usize, label, address.clone())]
+            }
+        };
+
+        if opt.output.is_json() {
+            let rows = rows
+                .into_iter()
+                .map(|(index, label, address)| {
+                    json!({
+                        "index": index,
+                        "label": label,
+                        "address": address,
+                    })
+                })
+                .collect::<Vec<_>>();
+            return opt.output.print_json(&rows);
+        }
+
+        // Set up table (this won't be used with `show --addr-only`)
+        let mut table = Table::new();
+        table.load_preset(presets::NOTHING);
+        table.set_header(vec!["Index", "Label", "Address"]);
+        for (index, label, address) in rows {
+            table.add_row(vec![index.to_string(), label, address]);
         }
 
         // Print the table (we don't get here if `show --addr-only`)
@@ -0,0 +1,113 @@
+use anyhow::{anyhow, Result};
+use comfy_table::{presets, Table};
+use penumbra_crypto::asset::{self, REGISTRY};
+use serde_json::json;
+use structopt::StructOpt;
+
+use crate::{fetch, ClientStateFile, Opt};
+
+#[derive(Debug, StructOpt)]
+pub enum AssetsCmd {
+    /// List all assets registered on the chain.
+    List,
+    /// Look up an asset by its denomination or asset ID.
+    Lookup {
+        /// The denomination (e.g. `upenumbra`) or asset ID (e.g. `passet1...`) to look up.
+        asset: String,
+    },
+}
+
+impl AssetsCmd {
+    /// Determine if this command requires a network sync before it executes.
+    pub fn needs_sync(&self) -> bool {
+        match self {
+            AssetsCmd::List => false,
+            AssetsCmd::Lookup { .. } => false,
+        }
+    }
+
+    pub async fn exec(&self, opt: &Opt, state: &mut ClientStateFile) -> Result<()> {
+        match self {
+            AssetsCmd::List => {
+                fetch::assets(opt, state).await?;
+
+                if opt.output.is_json() {
+                    let assets = state
+                        .asset_cache()
+                        .iter()
+                        .map(|(id, denom)| {
+                            json!({
+                                "asset_id": id.to_string(),
+                                "denom": denom.to_string(),
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    return opt.output.print_json(&assets);
+                }
+
+                let mut table = Table::new();
+                table.load_preset(presets::NOTHING);
+                table.set_header(vec!["Denomination", "Asset ID"]);
+                for (id, denom) in state.asset_cache().iter() {
+                    table.add_row(vec![denom.to_string(), id.to_string()]);
+                }
+                println!("{}", table);
+            }
+            AssetsCmd::Lookup { asset } => {
+                let denom = lookup(opt, state, asset).await?;
+
+                if opt.output.is_json() {
+                    return opt.output.print_json(&json!({
+                        "asset_id": denom.id().to_string(),
+                        "denom": denom.to_string(),
+                        "units": denom.units().iter().map(ToString::to_string).collect::<Vec<_>>(),
+                    }));
+                }
+
+                let mut table = Table::new();
+                table.load_preset(presets::NOTHING);
+                table.add_row(vec!["Denomination".to_string(), denom.to_string()]);
+                table.add_row(vec!["Asset ID".to_string(), denom.id().to_string()]);
+                table.add_row(vec![
+                    "Units".to_string(),
+                    denom
+                        .units()
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ]);
+                println!("{}", table);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Resolves a denomination or asset ID to a denomination, consulting the node only if the asset
+/// is not already in the local asset cache.
+async fn lookup(opt: &Opt, state: &mut ClientStateFile, asset: &str) -> Result<asset::Denom> {
+    // Asset IDs are bech32-encoded, so they can't be confused with denominations.
+    let id = match asset.parse::<asset::Id>() {
+        Ok(id) => id,
+        Err(_) => REGISTRY
+            .parse_denom(asset)
+            .ok_or_else(|| anyhow!("{} is neither an asset ID nor a denomination", asset))?
+            .id(),
+    };
+
+    if let Some(denom) = state.asset_cache().get(&id) {
+        return Ok(denom.clone());
+    }
+
+    let denom = fetch::asset_denom(opt, state, &id)
+        .await?
+        .ok_or_else(|| anyhow!("asset {} is not registered on the chain", asset))?;
+    state
+        .asset_cache_mut()
+        .extend(std::iter::once(denom.clone()));
+    state.commit()?;
+
+    Ok(denom)
+}
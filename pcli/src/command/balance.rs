@@ -1,9 +1,16 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use comfy_table::{presets, Table};
-use penumbra_crypto::asset::{self, Denom};
+use penumbra_crypto::{
+    asset::{self, Denom},
+    Value,
+};
+use penumbra_proto::wallet::{BalanceRequest, StatusRequest};
 use penumbra_wallet::{ClientState, UnspentNote};
+use serde::Serialize;
 use structopt::StructOpt;
 
+use crate::Opt;
+
 #[derive(Debug, StructOpt)]
 pub struct BalanceCmd {
     /// If set, breaks down balances by address.
@@ -17,72 +24,86 @@ pub struct BalanceCmd {
     pub by_note: bool,
 }
 
+/// The amounts of a single asset in a group of notes.
+#[derive(Debug, Default, Serialize)]
+pub(super) struct Tally {
+    /// The total amount once every pending transaction is confirmed.
+    pub(super) total: u64,
+    /// The amount which can be spent right now.
+    pub(super) spendable: u64,
+    /// The amount of change from pending transactions, which can't be spent until it's
+    /// confirmed.
+    pub(super) pending_in: u64,
+    /// The amount of the notes spent by pending transactions, which can't be spent again.
+    pub(super) pending_out: u64,
+}
+
+/// A single row of balance output, in machine-readable form.
+#[derive(Debug, Serialize)]
+struct BalanceEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address_index: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address_label: Option<String>,
+    denom: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note_label: Option<String>,
+    #[serde(flatten)]
+    tally: Tally,
+}
+
 /// Result of formatting the tally for a particular asset.
-struct FormattedTally {
-    total: String,
-    available: String,
-    submitted_spend: String,
-    submitted_change: String,
+pub(super) struct FormattedTally {
+    pub(super) total: String,
+    pub(super) spendable: String,
+    pub(super) pending_in: String,
+    pub(super) pending_out: String,
 }
 
-// Format a tally of notes as a set of strings.
+// Tally a group of notes.
 ///
 /// This assumes that the notes are all of the same denomination, and it is called below only
 // in the places where they are.
-fn tally_format_notes<'a>(
-    denom: &'a Denom,
-    cache: &'a asset::Cache,
-    notes_groups: impl IntoIterator<Item = impl IntoIterator<Item = UnspentNote<'a>> + 'a> + 'a,
-) -> impl IntoIterator<Item = FormattedTally> + 'a {
-    notes_groups.into_iter().map(|notes| {
-        // Tally each of the kinds of note:
-        let mut unspent = 0;
-        let mut submitted_spend = 0;
-        let mut submitted_change = 0;
-
-        for note in notes {
-            *match note {
-                UnspentNote::Ready(_) => &mut unspent,
-                UnspentNote::SubmittedSpend(_) => &mut submitted_spend,
-                UnspentNote::SubmittedChange(_) => &mut submitted_change,
-            } += note.as_ref().amount();
-        }
-
-        // The amount spent is the difference between submitted spend and submitted change:
-        let net_submitted_spend = submitted_spend - submitted_change;
+pub(super) fn tally_notes<'a>(notes: impl IntoIterator<Item = UnspentNote<'a>>) -> Tally {
+    // Tally each of the kinds of note:
+    let mut spendable = 0;
+    let mut pending_out = 0;
+    let mut pending_in = 0;
 
-        // Convert the results to denominations:
-        let submitted_change = denom.value(submitted_change);
-        let net_submitted_spend = denom.value(net_submitted_spend);
+    for note in notes {
+        *match note {
+            UnspentNote::Ready(_) => &mut spendable,
+            UnspentNote::SubmittedSpend(_) => &mut pending_out,
+            UnspentNote::SubmittedChange(_) => &mut pending_in,
+        } += note.as_ref().amount();
+    }
 
-        let submitted_change_string = if submitted_change.amount > 0 {
-            format!("+{} (change)", submitted_change.try_format(cache).unwrap())
-        } else {
-            "".to_string()
-        };
+    Tally {
+        // Notes spent by pending transactions are gone, and their change will arrive:
+        total: spendable + pending_in,
+        spendable,
+        pending_in,
+        pending_out,
+    }
+}
 
-        let submitted_spend_string = if net_submitted_spend.amount > 0 {
-            format!(
-                "-{} (spend)",
-                net_submitted_spend.try_format(cache).unwrap()
-            )
+// Format a tally of notes as a set of strings.
+pub(super) fn format_tally(denom: &Denom, cache: &asset::Cache, tally: &Tally) -> FormattedTally {
+    // Pending amounts are left blank when there are none, so that they stand out when there are.
+    let format_pending = |sign: &str, amount: u64| {
+        if amount > 0 {
+            format!("{}{}", sign, denom.value(amount).try_format(cache).unwrap())
         } else {
             "".to_string()
-        };
-
-        // The total amount, disregarding submitted transactions:
-        let total = denom.value(submitted_change.amount + unspent);
-
-        // The amount available to spend:
-        let available = denom.value(unspent);
-
-        FormattedTally {
-            total: total.try_format(cache).unwrap(),
-            available: available.try_format(cache).unwrap(),
-            submitted_change: submitted_change_string,
-            submitted_spend: submitted_spend_string,
         }
-    })
+    };
+
+    FormattedTally {
+        total: denom.value(tally.total).try_format(cache).unwrap(),
+        spendable: denom.value(tally.spendable).try_format(cache).unwrap(),
+        pending_in: format_pending("+", tally.pending_in),
+        pending_out: format_pending("-", tally.pending_out),
+    }
 }
 
 impl BalanceCmd {
@@ -90,42 +111,43 @@ impl BalanceCmd {
         !self.offline
     }
 
-    pub fn exec(&self, state: &ClientState) -> Result<()> {
-        // Initialize the table
-        let mut table = Table::new();
-        table.load_preset(presets::NOTHING);
-        let mut print_submitted_column = false; // This will become true if there are any submitted transactions
-        let mut headers;
+    pub fn exec(&self, opt: &Opt, state: &ClientState) -> Result<()> {
+        // Collect the tallies for each row of output
+        let mut entries = Vec::new();
+
+        // When printing notes individually, show any labels the user has attached to them.
+        let note_label = |notes: &[UnspentNote]| -> Option<String> {
+            match notes {
+                [note] if self.by_note => state
+                    .note_label(&note.as_ref().commit())
+                    .map(ToString::to_string),
+                _ => None,
+            }
+        };
 
         if self.by_address {
             for (address_id, by_denom) in state.unspent_notes_by_address_and_denom().into_iter() {
-                let (mut label, _) = state.wallet().address_by_index(address_id as usize)?;
+                let (label, _) = state.wallet().address_by_index(address_id as usize)?;
                 for (denom, notes) in by_denom.into_iter() {
                     let notes_groups = if self.by_note {
                         notes.into_iter().map(|n| vec![n]).collect()
                     } else {
                         vec![notes]
                     };
-                    let tallies = tally_format_notes(&denom, state.asset_cache(), notes_groups);
-                    for tally in tallies {
-                        let mut row = vec![label.clone(), tally.total];
-                        if !tally.submitted_change.is_empty() || !tally.submitted_spend.is_empty() {
-                            print_submitted_column = true;
-                            row.push(tally.available);
-                            row.push(tally.submitted_change);
-                            row.push(tally.submitted_spend);
-                        }
-                        table.add_row(row);
-
-                        // Only display the label on the first row
-                        label = String::default();
+                    for notes in notes_groups {
+                        entries.push((
+                            denom.clone(),
+                            BalanceEntry {
+                                address_index: Some(address_id),
+                                address_label: Some(label.clone()),
+                                denom: denom.to_string(),
+                                note_label: note_label(&notes),
+                                tally: tally_notes(notes),
+                            },
+                        ));
                     }
                 }
             }
-
-            // Set up headers for the table (a "Submitted" column will be added if there are any
-            // submitted transactions)
-            headers = vec!["Address", "Total"];
         } else {
             for (denom, by_address) in state.unspent_notes_by_denom_and_address().into_iter() {
                 let notes = by_address.into_values().flatten();
@@ -136,33 +158,146 @@ impl BalanceCmd {
                     vec![notes.collect()]
                 };
 
-                let tallies = tally_format_notes(&denom, state.asset_cache(), notes_groups);
-
-                for tally in tallies {
-                    let mut row = vec![tally.total];
-                    if !tally.submitted_change.is_empty() || !tally.submitted_spend.is_empty() {
-                        print_submitted_column = true;
-                        row.push(tally.available);
-                        row.push(tally.submitted_change);
-                        row.push(tally.submitted_spend);
-                    }
-                    table.add_row(row);
+                for notes in notes_groups {
+                    entries.push((
+                        denom.clone(),
+                        BalanceEntry {
+                            address_index: None,
+                            address_label: None,
+                            denom: denom.to_string(),
+                            note_label: note_label(&notes),
+                            tally: tally_notes(notes),
+                        },
+                    ));
                 }
             }
+        }
 
-            // Set up headers for the table (a "Submitted" column will be added if there are any
-            // submitted transactions)
-            headers = vec!["Total"];
+        if opt.output.is_json() {
+            let entries = entries
+                .into_iter()
+                .map(|(_, entry)| entry)
+                .collect::<Vec<_>>();
+            return opt.output.print_json(&entries);
         }
 
-        // Add an "Available" and "Submitted" column if there are any submitted transactions
-        if print_submitted_column {
-            headers.push("Available");
-            headers.push("Submitted");
+        // Initialize the table
+        let mut table = Table::new();
+        table.load_preset(presets::NOTHING);
+        let mut last_label = None;
+
+        for (denom, entry) in entries {
+            let tally = format_tally(&denom, state.asset_cache(), &entry.tally);
+
+            let mut row = Vec::new();
+            if self.by_address {
+                // Only display the label on the first row for each address
+                if last_label != entry.address_index {
+                    row.push(entry.address_label.unwrap_or_default());
+                    last_label = entry.address_index;
+                } else {
+                    row.push(String::default());
+                }
+            }
+            if self.by_note {
+                row.push(entry.note_label.unwrap_or_default());
+            }
+            row.push(tally.total);
+            row.push(tally.spendable);
+            row.push(tally.pending_in);
+            row.push(tally.pending_out);
+            table.add_row(row);
         }
+
+        // Set up headers for the table
+        let mut headers = if self.by_address {
+            vec!["Address"]
+        } else {
+            vec![]
+        };
+        if self.by_note {
+            headers.push("Label");
+        }
+        headers.extend(["Total", "Spendable", "Pending in", "Pending out"]);
         table.set_header(headers);
         println!("{}", table);
 
         Ok(())
     }
+
+    /// Prints the balance reported by the view service, rather than the local client state.
+    ///
+    /// The view service doesn't know about transactions submitted from this wallet, so every
+    /// note it reports is counted as spendable.
+    pub async fn exec_view_service(&self, opt: &Opt, state: &ClientState) -> Result<()> {
+        if self.by_address || self.by_note {
+            return Err(anyhow!(
+                "--by-address and --by-note are not supported with a view service"
+            ));
+        }
+
+        let fvk_hash = state.wallet().full_viewing_key().hash();
+        let mut client = opt.view_client().await?;
+
+        let status = client
+            .status(StatusRequest {
+                fvk_hash: Some(fvk_hash.clone().into()),
+            })
+            .await?
+            .into_inner();
+        if !status.synchronized {
+            tracing::warn!(
+                sync_height = status.sync_height,
+                chain_height = status.chain_height,
+                "view service has not finished syncing, balance may be out of date"
+            );
+        }
+
+        let balances = client
+            .balance(BalanceRequest {
+                fvk_hash: Some(fvk_hash.into()),
+                diversifier_index: None,
+            })
+            .await?
+            .into_inner()
+            .balances
+            .into_iter()
+            .map(Value::try_from)
+            .collect::<Result<Vec<_>>>()?;
+
+        let cache = state.asset_cache();
+        let entries = balances
+            .iter()
+            .map(|value| BalanceEntry {
+                address_index: None,
+                address_label: None,
+                denom: cache
+                    .get(&value.asset_id)
+                    .map(ToString::to_string)
+                    .unwrap_or_else(|| value.asset_id.to_string()),
+                note_label: None,
+                tally: Tally {
+                    total: value.amount,
+                    spendable: value.amount,
+                    ..Default::default()
+                },
+            })
+            .collect::<Vec<_>>();
+
+        if opt.output.is_json() {
+            return opt.output.print_json(&entries);
+        }
+
+        let mut table = Table::new();
+        table.load_preset(presets::NOTHING);
+        table.set_header(vec!["Total"]);
+        for value in balances {
+            table.add_row(vec![value
+                .try_format(cache)
+                .unwrap_or_else(|| format!("{} {}", value.amount, value.asset_id))]);
+        }
+        println!("{}", table);
+
+        Ok(())
+    }
 }
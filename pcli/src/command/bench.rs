@@ -0,0 +1,205 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use ark_ff::UniformRand;
+use comfy_table::{presets, Table};
+use futures::TryStreamExt;
+use penumbra_chain::CompactBlock;
+use penumbra_crypto::{
+    keys::{SeedPhrase, SpendKey},
+    memo::MemoPlaintext,
+    merkle::{Frontier, NoteCommitmentTree},
+    Fr, Note, Value, STAKING_TOKEN_ASSET_ID,
+};
+use penumbra_proto::client::oblivious::CompactBlockRangeRequest;
+use penumbra_transaction::action::{output, Output};
+use penumbra_wallet::{ClientState, DecryptionPool};
+use rand_core::OsRng;
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{sync, ClientStateFile, Opt};
+
+#[derive(Debug, StructOpt)]
+pub struct BenchCmd {
+    /// The number of outputs to generate for the proof, trial decryption, and note commitment
+    /// tree benchmarks.
+    #[structopt(long, default_value = "1000")]
+    pub outputs: usize,
+    /// The number of blocks to fetch from the node for the sync benchmark (0 to skip it).
+    ///
+    /// Blocks are scanned into a scratch copy of the client state, so the wallet is not modified.
+    #[structopt(long, default_value = "1000")]
+    pub sync_blocks: u64,
+}
+
+/// The result of a single benchmark.
+#[derive(Debug, Serialize)]
+struct Measurement {
+    name: &'static str,
+    /// The number of items processed.
+    count: u64,
+    /// What the items are.
+    unit: &'static str,
+    /// The total time taken, in seconds.
+    seconds: f64,
+    /// The number of items processed per second.
+    rate: f64,
+}
+
+impl Measurement {
+    fn new(name: &'static str, count: u64, unit: &'static str, elapsed: Duration) -> Self {
+        let seconds = elapsed.as_secs_f64();
+        Self {
+            name,
+            count,
+            unit,
+            seconds,
+            rate: count as f64 / seconds,
+        }
+    }
+}
+
+impl BenchCmd {
+    /// Determine if this command requires a network sync before it executes.
+    pub fn needs_sync(&self) -> bool {
+        false
+    }
+
+    pub async fn exec(&self, opt: &Opt, state: &ClientStateFile) -> Result<()> {
+        let mut results = Vec::new();
+
+        // Generate outputs to someone else, as most outputs seen during sync will be.
+        let other = SpendKey::from_seed_phrase(SeedPhrase::generate(&mut OsRng), 0);
+        let (dest, _) = other.incoming_viewing_key().payment_address(0u64.into());
+        let value = Value {
+            amount: 1,
+            asset_id: *STAKING_TOKEN_ASSET_ID,
+        };
+
+        tracing::info!(outputs = self.outputs, "benchmarking output proofs");
+        let start = Instant::now();
+        let bodies = (0..self.outputs)
+            .map(|_| {
+                let note = Note::generate(&mut OsRng, &dest, value);
+                let output = Output::new(
+                    &mut OsRng,
+                    note,
+                    MemoPlaintext::default(),
+                    &dest,
+                    other.outgoing_viewing_key(),
+                    Fr::rand(&mut OsRng),
+                );
+                output.body
+            })
+            .collect::<Vec<output::Body>>();
+        results.push(Measurement::new(
+            "Output proof generation",
+            self.outputs as u64,
+            "outputs",
+            start.elapsed(),
+        ));
+
+        tracing::info!(outputs = self.outputs, "benchmarking trial decryption");
+        let ivk = state.wallet().incoming_viewing_key().clone();
+        let start = Instant::now();
+        for body in &bodies {
+            let _ = Note::decrypt(body.encrypted_note.as_ref(), &ivk, &body.ephemeral_key);
+        }
+        results.push(Measurement::new(
+            "Trial decryption (1 thread)",
+            self.outputs as u64,
+            "outputs",
+            start.elapsed(),
+        ));
+
+        let block = CompactBlock {
+            height: 0,
+            outputs: bodies,
+            nullifiers: Vec::new(),
+        };
+        let pool = DecryptionPool::new(opt.decryption_threads)?;
+        let start = Instant::now();
+        let scanned = tokio::task::spawn_blocking(move || pool.trial_decrypt(&ivk, block)).await?;
+        results.push(Measurement::new(
+            "Trial decryption (decryption pool)",
+            self.outputs as u64,
+            "outputs",
+            start.elapsed(),
+        ));
+
+        tracing::info!(outputs = self.outputs, "benchmarking note commitment tree");
+        // The tree only needs to hold a single checkpoint, since we never rewind it.
+        let mut tree = NoteCommitmentTree::new(1);
+        let start = Instant::now();
+        for (commitment, _) in &scanned.outputs {
+            tree.append(commitment);
+        }
+        results.push(Measurement::new(
+            "Note commitment tree insertion",
+            self.outputs as u64,
+            "commitments",
+            start.elapsed(),
+        ));
+
+        if self.sync_blocks > 0 {
+            tracing::info!(blocks = self.sync_blocks, "benchmarking sync");
+            results.push(self.sync(opt, state).await?);
+        }
+
+        if opt.output.is_json() {
+            return opt.output.print_json(&results);
+        }
+
+        let mut table = Table::new();
+        table.load_preset(presets::NOTHING);
+        table.set_header(vec!["Benchmark", "Count", "Time", "Rate"]);
+        for m in results {
+            table.add_row(vec![
+                m.name.to_string(),
+                format!("{} {}", m.count, m.unit),
+                format!("{:.3}s", m.seconds),
+                format!("{:.1} {}/s", m.rate, m.unit),
+            ]);
+        }
+        println!("{}", table);
+
+        Ok(())
+    }
+
+    /// Measures the throughput of fetching and scanning blocks from the start of the chain, using
+    /// the same pipeline as `pcli sync`.
+    async fn sync(&self, opt: &Opt, state: &ClientStateFile) -> Result<Measurement> {
+        let mut scratch = ClientState::new(state.wallet().clone());
+        let mut client = opt.oblivious_client().await?;
+
+        let start = Instant::now();
+        let stream = client
+            .compact_block_range(tonic::Request::new(CompactBlockRangeRequest {
+                start_height: 0,
+                end_height: self.sync_blocks,
+                chain_id: state.chain_id().unwrap_or_default(),
+            }))
+            .await?
+            .into_inner();
+
+        let mut blocks = sync::scan(
+            stream,
+            state.wallet().incoming_viewing_key().clone(),
+            DecryptionPool::new(opt.decryption_threads)?,
+            0,
+        );
+        let mut count = 0;
+        while let Some(block) = blocks.try_next().await? {
+            scratch.apply_scanned_block(block)?;
+            count += 1;
+        }
+
+        Ok(Measurement::new(
+            "Sync (fetch, decrypt, and apply)",
+            count,
+            "blocks",
+            start.elapsed(),
+        ))
+    }
+}
@@ -0,0 +1,123 @@
+use anyhow::Result;
+use comfy_table::{presets, Table};
+use penumbra_chain::{params::ChainParams, Epoch};
+use penumbra_proto::client::oblivious::ChainParamsRequest;
+use serde_json::json;
+use structopt::StructOpt;
+
+use crate::{ClientStateFile, Opt};
+
+#[derive(Debug, StructOpt)]
+pub enum ChainCmd {
+    /// Display the chain parameters of the network the node is serving, along with the current
+    /// height and epoch.
+    Params,
+}
+
+impl ChainCmd {
+    /// Determine if this command requires a network sync before it executes.
+    pub fn needs_sync(&self) -> bool {
+        match self {
+            ChainCmd::Params => false,
+        }
+    }
+
+    pub async fn exec(&self, opt: &Opt, state: &ClientStateFile) -> Result<()> {
+        match self {
+            ChainCmd::Params => {
+                // Fetch the parameters afresh rather than using the cached copy in the client
+                // state, so that we display what the node is actually serving.
+                let mut client = opt.oblivious_client().await?;
+                let params: ChainParams = client
+                    .chain_params(tonic::Request::new(ChainParamsRequest {
+                        chain_id: state.chain_id().unwrap_or_default(),
+                        ..Default::default()
+                    }))
+                    .await?
+                    .into_inner()
+                    .into();
+
+                let height = opt.latest_block_height().await?;
+                let epoch = Epoch::from_height(height, params.epoch_duration);
+
+                if opt.output.is_json() {
+                    return opt.output.print_json(&json!({
+                        "chain_params": params,
+                        "current_height": height,
+                        "current_epoch": epoch.index,
+                        "fee": opt.fee(None),
+                    }));
+                }
+
+                let mut table = Table::new();
+                table.load_preset(presets::NOTHING);
+                table.set_header(vec!["Parameter", "Value"]);
+                let mut row = |name: &str, value: String| {
+                    table.add_row(vec![name.to_string(), value]);
+                };
+
+                row("Chain ID", params.chain_id.clone());
+                row("Current Height", height.to_string());
+                row(
+                    "Current Epoch",
+                    format!(
+                        "{} (blocks {} to {})",
+                        epoch.index,
+                        epoch.start_height(),
+                        epoch.end_height()
+                    ),
+                );
+                row(
+                    "Epoch Duration",
+                    format!("{} blocks", params.epoch_duration),
+                );
+                row(
+                    "Unbonding Time",
+                    format!(
+                        "{} epochs ({} blocks)",
+                        params.unbonding_epochs,
+                        params.unbonding_epochs * params.epoch_duration
+                    ),
+                );
+                row(
+                    "Active Validator Limit",
+                    params.active_validator_limit.to_string(),
+                );
+                row(
+                    "Base Reward Rate",
+                    format!("{} bps²", params.base_reward_rate),
+                );
+                row(
+                    "Slashing Penalty (Misbehavior)",
+                    format!("{} bps", params.slashing_penalty_misbehavior_bps),
+                );
+                row(
+                    "Slashing Penalty (Downtime)",
+                    format!("{} bps", params.slashing_penalty_downtime_bps),
+                );
+                row(
+                    "Downtime Window",
+                    format!(
+                        "{} of {} blocks may be missed",
+                        params.missed_blocks_maximum, params.signed_blocks_window_len
+                    ),
+                );
+                row("IBC Enabled", params.ibc_enabled.to_string());
+                row(
+                    "Inbound ICS-20 Transfers",
+                    params.inbound_ics20_transfers_enabled.to_string(),
+                );
+                row(
+                    "Outbound ICS-20 Transfers",
+                    params.outbound_ics20_transfers_enabled.to_string(),
+                );
+                // The chain does not (yet) set a fee schedule, so show the fee pcli will pay.
+                row("Fee (configured)", format!("{} upenumbra", opt.fee(None)));
+
+                println!("{}", table);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use structopt::{clap::Shell, StructOpt};
+
+use crate::{ClientStateFile, Opt};
+
+#[derive(Debug, StructOpt)]
+pub struct CompletionsCmd {
+    /// The shell to generate completions for.
+    #[structopt(possible_values = &Shell::variants(), case_insensitive = true)]
+    pub shell: Shell,
+}
+
+/// Prints candidate values for dynamic shell completion, one per line.
+///
+/// This is used by the scripts generated by `pcli completions`, and can be used to write custom
+/// completions.  It only reads local wallet state, and never contacts the network.
+#[derive(Debug, StructOpt)]
+pub enum CompleteCmd {
+    /// The addresses in the wallet.
+    Addresses,
+    /// The indices of the addresses in the wallet, each followed by a tab and its label.
+    Labels,
+    /// The denominations of the assets known to the wallet.
+    Denoms,
+}
+
+/// Bash completion for `--to`, `--source` and the values sent by `tx send`, deferring to the
+/// generated completion function otherwise.
+const BASH_DYNAMIC: &str = r#"
+_pcli_dynamic() {
+    local cur prev
+    cur="${COMP_WORDS[COMP_CWORD]}"
+    prev="${COMP_WORDS[COMP_CWORD-1]}"
+    case "${prev}" in
+        --to)
+            COMPREPLY=( $(compgen -W "$(PCLI_UNLEASH_DANGER=1 pcli complete addresses 2>/dev/null)" -- "${cur}") )
+            return 0
+            ;;
+        --source)
+            COMPREPLY=( $(compgen -W "$(PCLI_UNLEASH_DANGER=1 pcli complete labels 2>/dev/null | cut -f1)" -- "${cur}") )
+            return 0
+            ;;
+    esac
+    # Values are sent as an amount followed by a denomination, such as 10penumbra, so complete
+    # the denomination once an amount has been typed.
+    if [[ " ${COMP_WORDS[*]:1:COMP_CWORD-1} " == *" send "* && "${prev}" != --* && "${cur}" =~ ^[0-9.]+ ]]; then
+        local amount="${BASH_REMATCH[0]}"
+        COMPREPLY=( $(compgen -P "${amount}" -W "$(PCLI_UNLEASH_DANGER=1 pcli complete denoms 2>/dev/null)" -- "${cur#"${amount}"}") )
+        return 0
+    fi
+    _pcli "$@"
+}
+complete -F _pcli_dynamic -o bashdefault -o default pcli
+"#;
+
+/// Fish completion for `--to`, `--source` and the values sent by `tx send`.
+const FISH_DYNAMIC: &str = r#"
+complete -c pcli -n "__fish_seen_subcommand_from send" -l to -xa "(PCLI_UNLEASH_DANGER=1 pcli complete addresses 2>/dev/null)"
+complete -c pcli -n "__fish_seen_subcommand_from send delegate undelegate redelegate upload-definition" -l source -xa "(PCLI_UNLEASH_DANGER=1 pcli complete labels 2>/dev/null)"
+complete -c pcli -n "__fish_seen_subcommand_from send; and string match -qr '^[0-9.]+' -- (commandline -ct)" -fa "(string match -r '^[0-9.]+' -- (commandline -ct))(PCLI_UNLEASH_DANGER=1 pcli complete denoms 2>/dev/null)"
+"#;
+
+/// The call to the generated completion function which ends the zsh script.
+const ZSH_ENTRY: &str = r#"_pcli "$@""#;
+
+/// Zsh completion for `--to`, `--source` and the values sent by `tx send`, deferring to the
+/// generated completion function otherwise.
+///
+/// This replaces [`ZSH_ENTRY`], so that both the first completion (which runs the script) and
+/// later ones (which call the function registered with `compdef`) go through it.
+const ZSH_DYNAMIC: &str = r#"_pcli_dynamic() {
+    case "${words[CURRENT-1]}" in
+        --to)
+            compadd -- ${(f)"$(PCLI_UNLEASH_DANGER=1 pcli complete addresses 2>/dev/null)"}
+            return
+            ;;
+        --source)
+            local line
+            local -a sources
+            for line in ${(f)"$(PCLI_UNLEASH_DANGER=1 pcli complete labels 2>/dev/null)"}; do
+                sources+=("${line%%$'\t'*}:${line#*$'\t'}")
+            done
+            _describe 'address index' sources
+            return
+            ;;
+    esac
+    # Values are sent as an amount followed by a denomination, such as 10penumbra, so complete
+    # the denomination once an amount has been typed.
+    if (( ${words[(I)send]} )) && [[ "${words[CURRENT-1]}" != --* ]] && compset -P '[0-9.]##'; then
+        compadd -- ${(f)"$(PCLI_UNLEASH_DANGER=1 pcli complete denoms 2>/dev/null)"}
+        return
+    fi
+    _pcli "$@"
+}
+
+compdef _pcli_dynamic pcli
+_pcli_dynamic "$@"
+"#;
+
+impl CompletionsCmd {
+    /// Determine if this command requires a network sync before it executes.
+    pub fn needs_sync(&self) -> bool {
+        false
+    }
+
+    pub fn exec(&self) -> Result<()> {
+        let mut script = Vec::new();
+        Opt::clap().gen_completions_to("pcli", self.shell, &mut script);
+        let mut script = String::from_utf8(script)?;
+
+        // Where the shell makes it feasible, complete values from the local wallet state.
+        match self.shell {
+            Shell::Bash => script.push_str(BASH_DYNAMIC),
+            Shell::Fish => script.push_str(FISH_DYNAMIC),
+            Shell::Zsh => {
+                let entry = script
+                    .rfind(ZSH_ENTRY)
+                    .ok_or_else(|| anyhow!("unexpected zsh completion script"))?;
+                script.replace_range(entry.., ZSH_DYNAMIC);
+            }
+            _ => {}
+        }
+
+        print!("{}", script);
+        Ok(())
+    }
+}
+
+impl CompleteCmd {
+    /// Determine if this command requires a network sync before it executes.
+    pub fn needs_sync(&self) -> bool {
+        false
+    }
+
+    pub fn exec(&self, wallet_path: PathBuf) -> Result<()> {
+        // Completion runs on every keypress, so silently complete nothing if there's no wallet.
+        let state = match ClientStateFile::load(wallet_path) {
+            Ok(state) => state,
+            Err(_) => return Ok(()),
+        };
+
+        match self {
+            CompleteCmd::Addresses => {
+                for (_, _, address) in state.wallet().addresses() {
+                    println!("{}", address);
+                }
+            }
+            CompleteCmd::Labels => {
+                for (index, label, _) in state.wallet().addresses() {
+                    println!("{}\t{}", index, label);
+                }
+            }
+            CompleteCmd::Denoms => {
+                for (_, denom) in state.asset_cache().iter() {
+                    println!("{}", denom);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
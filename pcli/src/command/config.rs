@@ -0,0 +1,74 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use structopt::StructOpt;
+
+use crate::{config::PcliConfig, Opt};
+
+#[derive(Debug, StructOpt)]
+pub enum ConfigCmd {
+    /// Write a config file populated with the default settings.
+    Init {
+        /// Overwrite an existing config file.
+        #[structopt(long)]
+        force: bool,
+    },
+    /// Display the current config file.
+    Show,
+    /// Change a setting in the config file.
+    Set {
+        /// The setting to change: one of node, tendermint_port, pd_port, fee, wallet_location,
+        /// chain_id, view_service, or decryption_threads.
+        key: String,
+        /// The new value for the setting; an empty string clears the setting.
+        value: String,
+    },
+}
+
+impl ConfigCmd {
+    /// Determine if this command requires a network sync before it executes.
+    pub fn needs_sync(&self) -> bool {
+        match self {
+            ConfigCmd::Init { .. } => false,
+            ConfigCmd::Show => false,
+            ConfigCmd::Set { .. } => false,
+        }
+    }
+
+    pub fn exec(&self, opt: &Opt, config_path: &Path) -> Result<()> {
+        match self {
+            ConfigCmd::Init { force } => {
+                if config_path.exists() && !force {
+                    return Err(anyhow!(
+                        "Config file {} already exists, refusing to overwrite it (use --force to override)",
+                        config_path.display()
+                    ));
+                }
+                PcliConfig::with_defaults().save(config_path)?;
+                if opt.output.is_json() {
+                    opt.output.print_json(&serde_json::json!({
+                        "config_file": config_path.display().to_string(),
+                    }))?;
+                } else {
+                    println!("Wrote default config to {}", config_path.display());
+                }
+            }
+            ConfigCmd::Show => {
+                let config = PcliConfig::load(config_path)?;
+                if opt.output.is_json() {
+                    opt.output.print_json(&config)?;
+                } else {
+                    println!("# {}", config_path.display());
+                    print!("{}", toml::to_string_pretty(&config)?);
+                }
+            }
+            ConfigCmd::Set { key, value } => {
+                let mut config = PcliConfig::load(config_path)?;
+                config.set(key, value)?;
+                config.save(config_path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,672 @@
+use std::{
+    collections::VecDeque,
+    io::Stdout,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use futures::stream::TryStreamExt;
+use penumbra_crypto::{Address, DelegationToken, Value, STAKING_TOKEN_ASSET_ID};
+use penumbra_proto::client::oblivious::ValidatorInfoRequest;
+use penumbra_stake::validator;
+use penumbra_wallet::{ClientState, SelectionStrategy, UnspentNote};
+use rand_core::OsRng;
+use structopt::StructOpt;
+use tokio::sync::mpsc;
+use tui::{
+    backend::{Backend, CrosstermBackend},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, Gauge, List, ListItem, Paragraph, Row, Table, Wrap},
+    Frame, Terminal,
+};
+
+use super::balance::{format_tally, tally_notes};
+use crate::{fetch, sync::sync, ClientStateFile, Opt};
+
+/// How long to wait for a keypress before redrawing the dashboard.
+const TICK: Duration = Duration::from_millis(250);
+
+/// The number of entries kept in the activity log.
+const ACTIVITY_LEN: usize = 100;
+
+#[derive(Debug, StructOpt)]
+pub struct DashCmd {
+    /// How often to sync with the chain, in seconds.
+    #[structopt(long, default_value = "10")]
+    pub refresh: u64,
+}
+
+impl DashCmd {
+    /// Determine if this command requires a network sync before it executes.
+    pub fn needs_sync(&self) -> bool {
+        true
+    }
+
+    pub async fn exec(&self, opt: &Opt, state: &mut ClientStateFile) -> Result<()> {
+        enable_raw_mode()?;
+        let mut stdout = std::io::stdout();
+        execute!(stdout, EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+        // Restore the terminal regardless of whether the dashboard exited cleanly, so that an
+        // error doesn't leave the user's shell in raw mode.
+        let result = self.run(opt, state, &mut terminal).await;
+
+        disable_raw_mode()?;
+        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+        terminal.show_cursor()?;
+
+        result
+    }
+
+    async fn run(
+        &self,
+        opt: &Opt,
+        state: &mut ClientStateFile,
+        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    ) -> Result<()> {
+        let interval = Duration::from_secs(self.refresh);
+        let mut dash = Dash::default();
+        // The wallet was synced before the command started, so only fetch what the sync doesn't.
+        dash.fetch_chain_info(opt, state).await;
+        let mut last_refresh = Instant::now();
+        let mut refresh_requested = false;
+        let mut events = read_events();
+
+        loop {
+            terminal.draw(|f| dash.draw(f, state))?;
+
+            let event = match tokio::time::timeout(TICK, events.recv()).await {
+                Ok(Some(event)) => Some(event?),
+                Ok(None) => return Err(anyhow!("stopped reading terminal events")),
+                Err(_) => None,
+            };
+            if let Some(Event::Key(key)) = event {
+                match dash.handle_key(key) {
+                    Action::None => {}
+                    Action::Quit => return Ok(()),
+                    Action::Refresh => refresh_requested = true,
+                    Action::Review { to, amount } => dash.review(opt, state, to, amount),
+                    Action::Send { to, amount } => {
+                        dash.status = "Sending…".to_string();
+                        terminal.draw(|f| dash.draw(f, state))?;
+                        dash.send(opt, state, &to, &amount).await;
+                    }
+                }
+            }
+
+            if refresh_requested || last_refresh.elapsed() >= interval {
+                dash.status = "Syncing…".to_string();
+                terminal.draw(|f| dash.draw(f, state))?;
+                dash.refresh(opt, state).await;
+                last_refresh = Instant::now();
+                refresh_requested = false;
+            }
+        }
+    }
+}
+
+/// Reads terminal events on a blocking thread, since crossterm only reads them synchronously,
+/// and sends them to the returned channel until it is closed.
+fn read_events() -> mpsc::Receiver<Result<Event>> {
+    let (tx, rx) = mpsc::channel(16);
+    tokio::task::spawn_blocking(move || {
+        // Poll with a timeout, so that the thread notices when the dashboard has closed.
+        while !tx.is_closed() {
+            let event = match event::poll(TICK) {
+                Ok(false) => continue,
+                Ok(true) => event::read(),
+                Err(e) => Err(e),
+            };
+            let failed = event.is_err();
+            if tx.blocking_send(event.map_err(Into::into)).is_err() || failed {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// What the dashboard should do in response to a keypress.
+enum Action {
+    None,
+    Quit,
+    Refresh,
+    /// Ask the user to confirm a send.
+    Review {
+        to: String,
+        amount: String,
+    },
+    Send {
+        to: String,
+        amount: String,
+    },
+}
+
+/// The field of the send form which is receiving input.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Field {
+    To,
+    Amount,
+}
+
+/// A send which is waiting for the user to confirm it.
+struct Confirm {
+    to: String,
+    amount: String,
+    /// The amount and fee, formatted for display.
+    value: String,
+    fee: String,
+}
+
+#[derive(Default)]
+struct SendForm {
+    to: String,
+    amount: String,
+    focus: Option<Field>,
+}
+
+impl SendForm {
+    fn focused(&mut self) -> &mut String {
+        match self.focus.unwrap_or(Field::To) {
+            Field::To => &mut self.to,
+            Field::Amount => &mut self.amount,
+        }
+    }
+}
+
+/// The state of the dashboard which is not part of the client state.
+#[derive(Default)]
+struct Dash {
+    /// The height of the latest block known to the node, if it could be fetched.
+    chain_height: Option<u64>,
+    /// The validators, used to value the wallet's delegations.
+    validators: Vec<validator::Info>,
+    /// A description of what the dashboard is currently doing.
+    status: String,
+    /// Events which happened while the dashboard was open, most recent first.
+    activity: VecDeque<String>,
+    /// The send form, if it is open.
+    send_form: Option<SendForm>,
+    /// The send awaiting confirmation, if any.
+    confirm: Option<Confirm>,
+}
+
+impl Dash {
+    fn log(&mut self, message: String) {
+        self.activity.push_front(message);
+        self.activity.truncate(ACTIVITY_LEN);
+    }
+
+    /// Syncs the client state and refreshes everything displayed on the dashboard.
+    ///
+    /// Errors are displayed on the dashboard rather than returned, so that a transient network
+    /// failure doesn't close it.
+    async fn refresh(&mut self, opt: &Opt, state: &mut ClientStateFile) {
+        let start_height = state.last_block_height();
+        if let Err(e) = sync(opt, state, None).await {
+            self.status = "Sync failed".to_string();
+            self.log(format!("sync failed: {:#}", e));
+            return;
+        }
+        if let Err(e) = fetch::assets(opt, state).await {
+            self.log(format!("could not fetch assets: {:#}", e));
+        }
+        if state.last_block_height() != start_height {
+            self.log(format!(
+                "synced to height {}",
+                state.last_block_height().unwrap_or_default()
+            ));
+        }
+        self.fetch_chain_info(opt, state).await;
+    }
+
+    async fn fetch_chain_info(&mut self, opt: &Opt, state: &ClientStateFile) {
+        match opt.latest_block_height().await {
+            Ok(height) => self.chain_height = Some(height),
+            Err(e) => self.log(format!("could not fetch chain height: {:#}", e)),
+        }
+        match validators(opt, state).await {
+            Ok(validators) => self.validators = validators,
+            Err(e) => self.log(format!("could not fetch validators: {:#}", e)),
+        }
+        self.status = "Idle".to_string();
+    }
+
+    /// Checks the send form's contents, and asks the user to confirm the send if they are valid.
+    fn review(&mut self, opt: &Opt, state: &ClientState, to: String, amount: String) {
+        let value = match amount.parse::<Value>() {
+            Ok(value) => value,
+            Err(e) => {
+                self.status = "Send failed".to_string();
+                self.log(format!("could not send {}: {:#}", amount, e));
+                return;
+            }
+        };
+        if to.parse::<Address>().is_err() {
+            self.status = "Send failed".to_string();
+            self.log(format!(
+                "could not send {} to {}: address is invalid",
+                amount, to
+            ));
+            return;
+        }
+
+        let fee = Value {
+            amount: opt.fee(None),
+            asset_id: *STAKING_TOKEN_ASSET_ID,
+        };
+        self.confirm = Some(Confirm {
+            value: value
+                .try_format(state.asset_cache())
+                .unwrap_or_else(|| amount.clone()),
+            fee: fee.try_format(state.asset_cache()).unwrap_or_default(),
+            to,
+            amount,
+        });
+    }
+
+    async fn send(&mut self, opt: &Opt, state: &mut ClientStateFile, to: &str, amount: &str) {
+        match send(opt, state, to, amount).await {
+            Ok(id) => {
+                self.status = "Sent".to_string();
+                self.log(format!("sent {} to {} in {}", amount, to, hex::encode(id)));
+            }
+            Err(e) => {
+                self.status = "Send failed".to_string();
+                self.log(format!("could not send {} to {}: {:#}", amount, to, e));
+            }
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Action::Quit;
+        }
+
+        if let Some(confirm) = self.confirm.take() {
+            return match key.code {
+                KeyCode::Char('y') | KeyCode::Enter => Action::Send {
+                    to: confirm.to,
+                    amount: confirm.amount,
+                },
+                // Go back to the form, so the send can be corrected.
+                KeyCode::Char('n') | KeyCode::Esc => {
+                    self.send_form = Some(SendForm {
+                        to: confirm.to,
+                        amount: confirm.amount,
+                        focus: None,
+                    });
+                    Action::None
+                }
+                _ => {
+                    self.confirm = Some(confirm);
+                    Action::None
+                }
+            };
+        }
+
+        let form = match &mut self.send_form {
+            Some(form) => form,
+            None => {
+                return match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => Action::Quit,
+                    KeyCode::Char('r') => Action::Refresh,
+                    KeyCode::Char('s') => {
+                        self.send_form = Some(SendForm::default());
+                        Action::None
+                    }
+                    _ => Action::None,
+                };
+            }
+        };
+
+        match key.code {
+            KeyCode::Esc => self.send_form = None,
+            KeyCode::Tab | KeyCode::BackTab | KeyCode::Up | KeyCode::Down => {
+                form.focus = match form.focus.unwrap_or(Field::To) {
+                    Field::To => Some(Field::Amount),
+                    Field::Amount => Some(Field::To),
+                };
+            }
+            KeyCode::Backspace => {
+                form.focused().pop();
+            }
+            KeyCode::Char(c) => form.focused().push(c),
+            KeyCode::Enter => {
+                let form = self.send_form.take().expect("send form is open");
+                return Action::Review {
+                    to: form.to.trim().to_string(),
+                    amount: form.amount.trim().to_string(),
+                };
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn draw<B: Backend>(&self, f: &mut Frame<B>, state: &ClientState) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Min(6),
+                Constraint::Length(10),
+                Constraint::Length(1),
+            ])
+            .split(f.size());
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(rows[1]);
+
+        self.draw_sync(f, rows[0], state);
+        draw_balance(f, columns[0], state);
+        self.draw_delegations(f, columns[1], state);
+        self.draw_activity(f, rows[2], state);
+
+        let help = if self.confirm.is_some() {
+            "y: send  n: edit"
+        } else if self.send_form.is_some() {
+            "Tab: next field  Enter: review  Esc: cancel"
+        } else {
+            "s: send  r: refresh  q: quit"
+        };
+        f.render_widget(
+            Paragraph::new(Span::styled(
+                help,
+                Style::default().add_modifier(Modifier::DIM),
+            )),
+            rows[3],
+        );
+
+        if let Some(form) = &self.send_form {
+            draw_send_form(f, form);
+        }
+        if let Some(confirm) = &self.confirm {
+            draw_confirm(f, confirm);
+        }
+    }
+
+    fn draw_sync<B: Backend>(&self, f: &mut Frame<B>, area: Rect, state: &ClientState) {
+        let synced = state.last_block_height().unwrap_or_default();
+        let (ratio, label) = match self.chain_height {
+            Some(height) if height > 0 => (
+                (synced as f64 / height as f64).min(1.0),
+                format!("{} / {}", synced, height),
+            ),
+            _ => (0.0, format!("{} / ?", synced)),
+        };
+
+        let gauge = Gauge::default()
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("Sync ({})", self.status)),
+            )
+            .gauge_style(Style::default().fg(Color::Green))
+            .ratio(ratio)
+            .label(label);
+        f.render_widget(gauge, area);
+    }
+
+    fn draw_delegations<B: Backend>(&self, f: &mut Frame<B>, area: Rect, state: &ClientState) {
+        let mut rows = Vec::new();
+
+        for (denom, notes_by_address) in state.unspent_notes_by_denom_and_address() {
+            let dt = if let Ok(dt) = DelegationToken::try_from(denom.clone()) {
+                dt
+            } else {
+                continue;
+            };
+
+            let delegation = Value {
+                amount: notes_by_address
+                    .values()
+                    .flat_map(|notes| notes.iter().map(|n| n.as_ref().amount()))
+                    .sum::<u64>(),
+                asset_id: dt.id(),
+            };
+
+            // The validator list may not have been fetched yet, or may be stale.
+            let info = self
+                .validators
+                .iter()
+                .find(|v| v.validator.identity_key == dt.validator());
+            let (name, unbonded) = match info {
+                Some(info) => (
+                    info.validator.name.clone(),
+                    Value {
+                        amount: info.rate_data.unbonded_amount(delegation.amount),
+                        asset_id: *STAKING_TOKEN_ASSET_ID,
+                    }
+                    .try_format(state.asset_cache())
+                    .unwrap_or_default(),
+                ),
+                None => (dt.validator().to_string(), "?".to_string()),
+            };
+
+            rows.push(Row::new(vec![
+                name,
+                unbonded,
+                delegation
+                    .try_format(state.asset_cache())
+                    .unwrap_or_default(),
+            ]));
+        }
+
+        let table = Table::new(rows)
+            .header(
+                Row::new(vec!["Validator", "Value", "Tokens"])
+                    .style(Style::default().add_modifier(Modifier::BOLD)),
+            )
+            .block(Block::default().borders(Borders::ALL).title("Delegations"))
+            .widths(&[
+                Constraint::Percentage(40),
+                Constraint::Percentage(30),
+                Constraint::Percentage(30),
+            ]);
+        f.render_widget(table, area);
+    }
+
+    fn draw_activity<B: Backend>(&self, f: &mut Frame<B>, area: Rect, state: &ClientState) {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(area);
+
+        // Notes in submitted transactions are pending until they are confirmed on-chain or time
+        // out.
+        let pending = state
+            .unspent_notes()
+            .filter_map(|(_, denom, note)| {
+                let (sign, kind) = match note {
+                    UnspentNote::Ready(_) => return None,
+                    UnspentNote::SubmittedSpend(_) => ("-", "spend"),
+                    UnspentNote::SubmittedChange(_) => ("+", "change"),
+                };
+                let value = denom
+                    .value(note.as_ref().amount())
+                    .try_format(state.asset_cache())
+                    .unwrap_or_default();
+                Some(ListItem::new(format!("{}{} ({})", sign, value, kind)))
+            })
+            .collect::<Vec<_>>();
+        f.render_widget(
+            List::new(pending).block(Block::default().borders(Borders::ALL).title("Pending")),
+            columns[0],
+        );
+
+        let activity = self
+            .activity
+            .iter()
+            .map(|line| ListItem::new(line.as_str()))
+            .collect::<Vec<_>>();
+        f.render_widget(
+            List::new(activity).block(Block::default().borders(Borders::ALL).title("Activity")),
+            columns[1],
+        );
+    }
+}
+
+fn draw_balance<B: Backend>(f: &mut Frame<B>, area: Rect, state: &ClientState) {
+    let rows = state
+        .unspent_notes_by_denom_and_address()
+        .into_iter()
+        .map(|(denom, by_address)| {
+            let tally = tally_notes(by_address.into_values().flatten());
+            let tally = format_tally(&denom, state.asset_cache(), &tally);
+            Row::new(vec![
+                tally.total,
+                tally.spendable,
+                tally.pending_in,
+                tally.pending_out,
+            ])
+        })
+        .collect::<Vec<_>>();
+
+    let table = Table::new(rows)
+        .header(
+            Row::new(vec!["Total", "Spendable", "Pending in", "Pending out"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().borders(Borders::ALL).title("Balance"))
+        .widths(&[
+            Constraint::Percentage(25),
+            Constraint::Percentage(25),
+            Constraint::Percentage(25),
+            Constraint::Percentage(25),
+        ]);
+    f.render_widget(table, area);
+}
+
+fn draw_send_form<B: Backend>(f: &mut Frame<B>, form: &SendForm) {
+    let area = centered(f.size(), 60, 8);
+    let focus = form.focus.unwrap_or(Field::To);
+    let field = |label: &'static str, value: &str, focused: bool| {
+        let style = if focused {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        Spans::from(vec![
+            Span::styled(label, style.add_modifier(Modifier::BOLD)),
+            Span::styled(value.to_string(), style),
+        ])
+    };
+
+    let text = vec![
+        Spans::from(""),
+        field("To:     ", &form.to, focus == Field::To),
+        Spans::from(""),
+        field("Amount: ", &form.amount, focus == Field::Amount),
+        Spans::from(""),
+        Spans::from(Span::styled(
+            "e.g. 1.5penumbra; the configured fee is paid in upenumbra",
+            Style::default().add_modifier(Modifier::DIM),
+        )),
+    ];
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(text).block(Block::default().borders(Borders::ALL).title("Send")),
+        area,
+    );
+}
+
+fn draw_confirm<B: Backend>(f: &mut Frame<B>, confirm: &Confirm) {
+    let area = centered(f.size(), 80, 12);
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+
+    let text = vec![
+        Spans::from(""),
+        Spans::from(vec![
+            Span::styled("Amount: ", bold),
+            Span::raw(confirm.value.as_str()),
+        ]),
+        Spans::from(vec![
+            Span::styled("Fee:    ", bold),
+            Span::raw(confirm.fee.as_str()),
+        ]),
+        Spans::from(""),
+        Spans::from(Span::styled("To:", bold)),
+        Spans::from(confirm.to.as_str()),
+        Spans::from(""),
+        Spans::from(Span::styled(
+            "Send this transaction? (y/n)",
+            Style::default().fg(Color::Yellow),
+        )),
+    ];
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(text)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).title("Confirm send")),
+        area,
+    );
+}
+
+/// Returns a rectangle of the given size (clamped to fit) centered in `area`.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+async fn validators(opt: &Opt, state: &ClientState) -> Result<Vec<validator::Info>> {
+    let mut client = opt.oblivious_client().await?;
+
+    let validators = client
+        .validator_info(ValidatorInfoRequest {
+            show_inactive: true,
+            chain_id: state.chain_id().unwrap_or_default(),
+            ..Default::default()
+        })
+        .await?
+        .into_inner()
+        .try_collect::<Vec<_>>()
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<Vec<validator::Info>, _>>()?;
+
+    Ok(validators)
+}
+
+/// Sends `amount` to `to`, returning the transaction ID.
+async fn send(opt: &Opt, state: &mut ClientStateFile, to: &str, amount: &str) -> Result<[u8; 32]> {
+    let value = amount.parse::<Value>()?;
+    let to = to
+        .parse()
+        .map_err(|_| anyhow::anyhow!("address is invalid"))?;
+
+    let transaction = state.build_send(
+        &mut OsRng,
+        &[value],
+        opt.fee(None),
+        to,
+        None,
+        None,
+        &SelectionStrategy::default(),
+    )?;
+
+    opt.submit_transaction(&transaction).await?;
+    // Only commit the state if the transaction was submitted successfully, so that we don't
+    // store pending notes that will never appear on-chain.
+    state.commit()?;
+
+    Ok(transaction.id())
+}
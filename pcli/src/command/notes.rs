@@ -0,0 +1,112 @@
+use anyhow::{anyhow, Result};
+use comfy_table::{presets, Table};
+use penumbra_crypto::note;
+use penumbra_wallet::UnspentNote;
+use serde_json::json;
+use structopt::StructOpt;
+
+use crate::{ClientStateFile, Opt};
+
+#[derive(Debug, StructOpt)]
+pub enum NotesCmd {
+    /// List the wallet's unspent notes, along with their labels.
+    List,
+    /// Attach a local label to a note, replacing any existing label.
+    ///
+    /// Labels are stored only in the local wallet, and are never revealed to anyone.
+    Label {
+        /// The commitment of the note to label, as shown by `pcli notes list`.
+        commitment: String,
+        /// The label to attach, e.g. "rent payment".
+        label: String,
+    },
+    /// Remove the label from a note.
+    Unlabel {
+        /// The commitment of the note to unlabel.
+        commitment: String,
+    },
+}
+
+impl NotesCmd {
+    /// Determine if this command requires a network sync before it executes.
+    pub fn needs_sync(&self) -> bool {
+        match self {
+            NotesCmd::List => true,
+            NotesCmd::Label { .. } => false,
+            NotesCmd::Unlabel { .. } => false,
+        }
+    }
+
+    pub fn exec(&self, opt: &Opt, state: &mut ClientStateFile) -> Result<()> {
+        match self {
+            NotesCmd::List => {
+                let mut notes = Vec::new();
+                for (index, denom, note) in state.unspent_notes() {
+                    let status = match note {
+                        UnspentNote::Ready(_) => "spendable",
+                        UnspentNote::SubmittedSpend(_) => "pending out",
+                        UnspentNote::SubmittedChange(_) => "pending in",
+                    };
+                    let commitment = note.as_ref().commit();
+                    let value = denom
+                        .value(note.as_ref().amount())
+                        .try_format(state.asset_cache())
+                        .unwrap();
+                    let label = state.note_label(&commitment).map(ToString::to_string);
+                    notes.push((commitment, index, value, status, label));
+                }
+
+                if opt.output.is_json() {
+                    let notes = notes
+                        .into_iter()
+                        .map(|(commitment, index, value, status, label)| {
+                            json!({
+                                "commitment": commitment.to_string(),
+                                "address_index": index,
+                                "value": value,
+                                "status": status,
+                                "label": label,
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    return opt.output.print_json(&notes);
+                }
+
+                let mut table = Table::new();
+                table.load_preset(presets::NOTHING);
+                table.set_header(vec!["Commitment", "Address", "Value", "Status", "Label"]);
+                for (commitment, index, value, status, label) in notes {
+                    table.add_row(vec![
+                        commitment.to_string(),
+                        index.to_string(),
+                        value,
+                        status.to_string(),
+                        label.unwrap_or_default(),
+                    ]);
+                }
+                println!("{}", table);
+            }
+            NotesCmd::Label { commitment, label } => {
+                let commitment = parse_commitment(commitment)?;
+                state.set_note_label(commitment, label.clone())?;
+                state.commit()?;
+            }
+            NotesCmd::Unlabel { commitment } => {
+                let commitment = parse_commitment(commitment)?;
+                if state.remove_note_label(&commitment).is_none() {
+                    return Err(anyhow!("note {} has no label", commitment));
+                }
+                state.commit()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_commitment(commitment: &str) -> Result<note::Commitment> {
+    hex::decode(commitment)?
+        .as_slice()
+        .try_into()
+        .map_err(|_| anyhow!("invalid note commitment {}", commitment))
+}
@@ -9,6 +9,7 @@ use penumbra_crypto::{
 use penumbra_proto::client::oblivious::ValidatorInfoRequest;
 use penumbra_stake::{rate::RateData, validator};
 use rand_core::OsRng;
+use serde_json::json;
 use structopt::StructOpt;
 
 use crate::{ClientStateFile, Opt};
@@ -22,9 +23,9 @@ pub enum StakeCmd {
         to: String,
         /// The amount of stake to delegate.
         amount: String,
-        /// The transaction fee (paid in upenumbra).
-        #[structopt(long, default_value = "0")]
-        fee: u64,
+        /// The transaction fee (paid in upenumbra) [default: configured fee, or 0].
+        #[structopt(long)]
+        fee: Option<u64>,
         /// Optional. Only spend funds originally received by the given address index.
         #[structopt(long)]
         source: Option<u64>,
@@ -33,9 +34,9 @@ pub enum StakeCmd {
     Undelegate {
         /// The amount of delegation tokens to undelegate.
         amount: String,
-        /// The transaction fee (paid in upenumbra).
-        #[structopt(long, default_value = "0")]
-        fee: u64,
+        /// The transaction fee (paid in upenumbra) [default: configured fee, or 0].
+        #[structopt(long)]
+        fee: Option<u64>,
         /// Optional. Only spend funds originally received by the given address index.
         #[structopt(long)]
         source: Option<u64>,
@@ -50,9 +51,9 @@ pub enum StakeCmd {
         to: String,
         /// The amount of stake to delegate.
         amount: String,
-        /// The transaction fee (paid in upenumbra).
-        #[structopt(long, default_value = "0")]
-        fee: u64,
+        /// The transaction fee (paid in upenumbra) [default: configured fee, or 0].
+        #[structopt(long)]
+        fee: Option<u64>,
         /// Optional. Only spend funds originally received by the given address index.
         #[structopt(long)]
         source: Option<u64>,
@@ -100,13 +101,24 @@ impl StakeCmd {
                     .into_inner()
                     .try_into()?;
 
-                let transaction =
-                    state.build_delegate(&mut OsRng, rate_data, unbonded_amount, *fee, *source)?;
+                let transaction = state.build_delegate(
+                    &mut OsRng,
+                    rate_data,
+                    unbonded_amount,
+                    opt.fee(*fee),
+                    *source,
+                )?;
 
                 opt.submit_transaction(&transaction).await?;
                 // Only commit the state if the transaction was submitted successfully,
                 // so that we don't store pending notes that will never appear on-chain.
                 state.commit()?;
+
+                if opt.output.is_json() {
+                    opt.output.print_json(&json!({
+                        "transaction_id": hex::encode(transaction.id()),
+                    }))?;
+                }
             }
             StakeCmd::Undelegate {
                 amount,
@@ -139,7 +151,7 @@ impl StakeCmd {
                     &mut OsRng,
                     rate_data,
                     delegation_amount,
-                    *fee,
+                    opt.fee(*fee),
                     *source,
                 )?;
 
@@ -147,6 +159,12 @@ impl StakeCmd {
                 // Only commit the state if the transaction was submitted successfully,
                 // so that we don't store pending notes that will never appear on-chain.
                 state.commit()?;
+
+                if opt.output.is_json() {
+                    opt.output.print_json(&json!({
+                        "transaction_id": hex::encode(transaction.id()),
+                    }))?;
+                }
             }
             StakeCmd::Redelegate { .. } => {
                 todo!()
@@ -158,6 +176,7 @@ impl StakeCmd {
                     .validator_info(ValidatorInfoRequest {
                         show_inactive: true,
                         chain_id: state.chain_id().unwrap_or_default(),
+                        ..Default::default()
                     })
                     .await?
                     .into_inner()
@@ -169,6 +188,7 @@ impl StakeCmd {
 
                 let notes = state.unspent_notes_by_denom_and_address();
                 let mut total = 0;
+                let mut delegations = Vec::new();
 
                 let mut table = Table::new();
                 table.load_preset(presets::NOTHING);
@@ -211,6 +231,14 @@ impl StakeCmd {
                         format!("{:.4}", rate),
                         delegation.try_format(state.asset_cache()).unwrap(),
                     ]);
+                    delegations.push(json!({
+                        "name": info.validator.name,
+                        "identity_key": info.validator.identity_key.to_string(),
+                        "value": unbonded.amount,
+                        "exchange_rate": rate,
+                        "delegation_denom": denom.to_string(),
+                        "delegation_amount": delegation.amount,
+                    }));
 
                     total += unbonded.amount;
                 }
@@ -245,7 +273,16 @@ impl StakeCmd {
                     String::new(),
                     String::new(),
                 ]);
-                println!("{}", table);
+
+                if opt.output.is_json() {
+                    opt.output.print_json(&json!({
+                        "delegations": delegations,
+                        "unbonded": unbonded.amount,
+                        "total": total.amount,
+                    }))?;
+                } else {
+                    println!("{}", table);
+                }
             }
             StakeCmd::ListValidators {
                 show_inactive,
@@ -257,6 +294,7 @@ impl StakeCmd {
                     .validator_info(ValidatorInfoRequest {
                         show_inactive: *show_inactive,
                         chain_id: state.chain_id().unwrap_or_default(),
+                        ..Default::default()
                     })
                     .await?
                     .into_inner()
@@ -274,6 +312,31 @@ impl StakeCmd {
                     .map(|v| v.status.voting_power)
                     .sum::<u64>() as f64;
 
+                if opt.output.is_json() {
+                    let validators = validators
+                        .into_iter()
+                        .map(|v| {
+                            json!({
+                                "identity_key": v.validator.identity_key.to_string(),
+                                "name": v.validator.name,
+                                "website": v.validator.website,
+                                "description": v.validator.description,
+                                "state": v.status.state.to_string(),
+                                "voting_power": v.status.voting_power,
+                                "voting_power_percent": 100.0 * (v.status.voting_power as f64) / total_voting_power,
+                                "commission_bps": v
+                                    .validator
+                                    .funding_streams
+                                    .as_ref()
+                                    .iter()
+                                    .map(|fs| fs.rate_bps)
+                                    .sum::<u16>(),
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    return opt.output.print_json(&validators);
+                }
+
                 let mut table = Table::new();
                 table.load_preset(presets::NOTHING);
                 table.set_header(vec![
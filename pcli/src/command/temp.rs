@@ -2,6 +2,8 @@ use anyhow::Result;
 use penumbra_crypto::parse_v0_testnet_address;
 use structopt::StructOpt;
 
+use crate::Opt;
+
 #[derive(Debug, StructOpt)]
 pub enum TmpCmd {
     /// Migrate Penumbra testnet address from v0 to v1 format.
@@ -19,9 +21,14 @@ impl TmpCmd {
         }
     }
 
-    pub async fn exec(&self) -> Result<()> {
+    pub async fn exec(&self, opt: &Opt) -> Result<()> {
         match self {
             TmpCmd::AddressMigrate { address } => match parse_v0_testnet_address(address.clone()) {
+                Ok(new_address) if opt.output.is_json() => {
+                    opt.output.print_json(&serde_json::json!({
+                        "address": new_address.to_string(),
+                    }))?
+                }
                 Ok(new_address) => println!("{}", new_address),
                 Err(err) => return Err(err),
             },
@@ -1,10 +1,27 @@
-use anyhow::{anyhow, Result};
-use penumbra_crypto::{memo, merkle::TreeExt, Value};
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context as _, Result};
+use penumbra_crypto::{
+    keys::threshold::{
+        self, dkg, KeyShare, ParticipantIndex, SignatureShare, SigningCommitments, SigningPackage,
+    },
+    memo,
+    merkle::TreeExt,
+    rdsa::{Signature, SpendAuth},
+    Address, FieldExt, Fr, Value,
+};
 use penumbra_transaction::Transaction;
+use penumbra_wallet::SelectionStrategy;
 use rand_core::OsRng;
+use serde::{de::DeserializeOwned, Serialize};
 use structopt::StructOpt;
 
-use crate::{ClientStateFile, Opt};
+use crate::{ClientStateFile, Opt, OutputFormat};
 
 #[derive(Debug, StructOpt)]
 pub enum TxCmd {
@@ -15,15 +32,20 @@ pub enum TxCmd {
         to: String,
         /// The amounts to send, written as typed values 1.87penumbra, 12cubes, etc.
         values: Vec<String>,
-        /// The transaction fee (paid in upenumbra).
-        #[structopt(long, default_value = "0")]
-        fee: u64,
+        /// The transaction fee (paid in upenumbra) [default: configured fee, or 0].
+        #[structopt(long)]
+        fee: Option<u64>,
         /// Optional. Only spend funds originally received by the given address index.
         #[structopt(long)]
         source: Option<u64>,
         /// Optional. Set the transaction's memo field to the provided text.
         #[structopt(long)]
         memo: Option<String>,
+        /// How to choose which notes to spend: `private` spends as few notes as possible,
+        /// chosen at random; `minimize-inputs` spends the largest notes first; and
+        /// `minimize-change` spends the notes closest to the amount sent.
+        #[structopt(long, default_value = "private", possible_values = SelectionStrategy::VARIANTS)]
+        selection: SelectionStrategy,
     },
     /// Sweeps small notes of the same denomination into a few larger notes.
     ///
@@ -34,6 +56,125 @@ pub enum TxCmd {
     ///
     /// Currently, only zero-fee sweep transactions are implemented.
     Sweep,
+    /// Take part in authorizing a spend with a share of a threshold spend authorization key.
+    ///
+    /// Each participant first runs `dkg` together with the others to generate their key share.
+    ///
+    /// Threshold signing then takes two rounds: each signer runs `commit` and sends the printed
+    /// commitments to a coordinator, who runs `plan` on the unsigned transaction and `package` to
+    /// combine the commitments with its sighash and a spend's randomizer; each signer then runs
+    /// `sign` on the package and sends the printed signature share back to the coordinator, who
+    /// runs `aggregate` and inserts the signature into the transaction with `attach`.
+    Sign {
+        /// Sign with a share of a threshold key. This is currently the only kind of signing
+        /// done separately from building a transaction.
+        #[structopt(long)]
+        threshold: bool,
+        #[structopt(subcommand)]
+        step: ThresholdSignCmd,
+    },
+    /// Submit a transaction saved to a file, such as one authorized with `sign --threshold`.
+    Submit {
+        /// The file containing the encoded transaction.
+        #[structopt(parse(from_os_str))]
+        transaction: PathBuf,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum ThresholdSignCmd {
+    /// Generate a share of a new threshold key together with the other participants, saving it
+    /// to a file.
+    ///
+    /// Packages are exchanged through files in `--dir`: this participant's round 1 package is
+    /// written to `round1-<index>.json`, to be copied to every other participant, and its round 2
+    /// packages to `round2-<index>-<receiver>.json`, each to be sent *privately* to its receiver.
+    /// The command waits for the other participants' packages to be copied into `--dir` under
+    /// the same names.
+    Dkg {
+        /// This participant's index, from 1 to the number of participants.
+        #[structopt(long)]
+        index: ParticipantIndex,
+        /// The number of participants needed to sign.
+        #[structopt(long)]
+        min_signers: u16,
+        /// The number of participants.
+        #[structopt(long)]
+        participants: u16,
+        /// The directory the packages are exchanged through.
+        #[structopt(long, parse(from_os_str), default_value = ".")]
+        dir: PathBuf,
+        /// The file to save the key share to, which must not already exist.
+        #[structopt(long, parse(from_os_str), default_value = "share.json")]
+        key_share: PathBuf,
+    },
+    /// Generate nonces for a signature, saving them to a file and printing the commitments to
+    /// them.
+    Commit {
+        /// The JSON file containing this signer's key share.
+        #[structopt(long, parse(from_os_str))]
+        key_share: PathBuf,
+        /// The file to save the secret nonces to, which must not already exist.
+        #[structopt(long, parse(from_os_str))]
+        nonces: PathBuf,
+    },
+    /// Print the sighash of an unsigned transaction and the spend authorization randomizer of
+    /// each of its spends, which are the message and randomizer to `package` for each spend.
+    Plan {
+        /// The file containing the encoded transaction.
+        #[structopt(long, parse(from_os_str))]
+        transaction: PathBuf,
+    },
+    /// Combine the signers' commitments with the message to sign, printing a signing package.
+    Package {
+        /// The message to sign, as hex.
+        #[structopt(long)]
+        message: String,
+        /// The spend authorization randomizer, as hex.
+        #[structopt(long)]
+        randomizer: String,
+        /// The JSON files containing each signer's commitments.
+        #[structopt(parse(from_os_str))]
+        commitments: Vec<PathBuf>,
+    },
+    /// Sign a signing package with nonces saved by `commit`, printing a signature share.
+    ///
+    /// The nonces file is deleted, since nonces must never be used twice.
+    Sign {
+        /// The JSON file containing this signer's key share.
+        #[structopt(long, parse(from_os_str))]
+        key_share: PathBuf,
+        /// The file the nonces were saved to by `commit`.
+        #[structopt(long, parse(from_os_str))]
+        nonces: PathBuf,
+        /// The JSON file containing the signing package.
+        #[structopt(long, parse(from_os_str))]
+        package: PathBuf,
+    },
+    /// Combine the signers' signature shares, printing the signature as hex.
+    Aggregate {
+        /// The JSON file containing any participant's key share, for the public key information.
+        #[structopt(long, parse(from_os_str))]
+        key_share: PathBuf,
+        /// The JSON file containing the signing package.
+        #[structopt(long, parse(from_os_str))]
+        package: PathBuf,
+        /// The JSON files containing each signer's signature share.
+        #[structopt(parse(from_os_str))]
+        shares: Vec<PathBuf>,
+    },
+    /// Insert a signature printed by `aggregate` into an unsigned transaction, authorizing one of
+    /// its spends. The transaction file is updated in place.
+    Attach {
+        /// The file containing the encoded transaction.
+        #[structopt(long, parse(from_os_str))]
+        transaction: PathBuf,
+        /// The index of the spend the signature authorizes, as listed by `plan`.
+        #[structopt(long)]
+        spend: usize,
+        /// The signature, as hex.
+        signature: String,
+    },
 }
 
 impl TxCmd {
@@ -42,6 +183,8 @@ impl TxCmd {
         match self {
             TxCmd::Send { .. } => true,
             TxCmd::Sweep { .. } => true,
+            TxCmd::Sign { .. } => false,
+            TxCmd::Submit { .. } => false,
         }
     }
 
@@ -53,33 +196,307 @@ impl TxCmd {
                 fee,
                 source: from,
                 memo,
+                selection,
             } => {
                 // Parse all of the values provided.
                 let values = values
                     .iter()
                     .map(|v| v.parse())
                     .collect::<Result<Vec<Value>, _>>()?;
-                let to = to
-                    .parse()
-                    .map_err(|_| anyhow::anyhow!("address is invalid"))?;
+                let to = Address::parse_with_diagnostics(to)
+                    .map_err(|e| anyhow!("destination address is invalid: {}", e))?;
 
-                let transaction =
-                    state.build_send(&mut OsRng, &values, *fee, to, *from, memo.clone())?;
+                let transaction = state.build_send(
+                    &mut OsRng,
+                    &values,
+                    opt.fee(*fee),
+                    to,
+                    *from,
+                    memo.clone(),
+                    selection,
+                )?;
 
                 opt.submit_transaction(&transaction).await?;
                 // Only commit the state if the transaction was submitted
                 // successfully, so that we don't store pending notes that will
                 // never appear on-chain.
                 state.commit()?;
+
+                if opt.output.is_json() {
+                    opt.output.print_json(&serde_json::json!({
+                        "transaction_id": hex::encode(transaction.id()),
+                    }))?;
+                }
             }
             TxCmd::Sweep => {
                 sweep(opt, state).await?;
             }
+            TxCmd::Sign { threshold, step } => {
+                if !threshold {
+                    return Err(anyhow!(
+                        "only threshold signing is supported; other transactions are signed as they are built"
+                    ));
+                }
+                step.exec(opt.output).await?;
+            }
+            TxCmd::Submit { transaction } => {
+                let transaction = read_transaction(transaction)?;
+                opt.submit_transaction(&transaction).await?;
+
+                if opt.output.is_json() {
+                    opt.output.print_json(&serde_json::json!({
+                        "transaction_id": hex::encode(transaction.id()),
+                    }))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ThresholdSignCmd {
+    pub async fn exec(&self, output: OutputFormat) -> Result<()> {
+        match self {
+            ThresholdSignCmd::Dkg {
+                index,
+                min_signers,
+                participants,
+                dir,
+                key_share,
+            } => {
+                // Check that the key share can be saved before doing any work.
+                if key_share.exists() {
+                    return Err(anyhow!("key share file {:?} already exists", key_share));
+                }
+                let others = (1..=*participants)
+                    .filter(|other| other != index)
+                    .collect::<Vec<_>>();
+
+                let (round1_secret, round1_package) =
+                    dkg::round1(&mut OsRng, *index, *min_signers, *participants)?;
+                write_json(&dir.join(format!("round1-{}.json", index)), &round1_package)?;
+                let round1_paths = others
+                    .iter()
+                    .map(|sender| dir.join(format!("round1-{}.json", sender)))
+                    .collect::<Vec<_>>();
+                let round1_packages: Vec<dkg::Round1Package> =
+                    wait_for_json(output, &round1_paths).await?;
+                for ((path, sender), package) in
+                    round1_paths.iter().zip(&others).zip(&round1_packages)
+                {
+                    check_sender(path, *sender, package.sender)?;
+                }
+
+                let (round2_secret, round2_packages) =
+                    dkg::round2(round1_secret, &round1_packages)?;
+                for package in &round2_packages {
+                    let path = dir.join(format!("round2-{}-{}.json", index, package.receiver));
+                    serde_json::to_writer(create_secret_file(&path)?, package)?;
+                    output.status(format_args!(
+                        "send {} privately to participant {}",
+                        path.display(),
+                        package.receiver
+                    ));
+                }
+                let received_paths = others
+                    .iter()
+                    .map(|sender| dir.join(format!("round2-{}-{}.json", sender, index)))
+                    .collect::<Vec<_>>();
+                let received: Vec<dkg::Round2Package> =
+                    wait_for_json(output, &received_paths).await?;
+                for ((path, sender), package) in received_paths.iter().zip(&others).zip(&received) {
+                    check_sender(path, *sender, package.sender)?;
+                }
+
+                let share = dkg::finish(round2_secret, &received)?;
+                serde_json::to_writer_pretty(create_secret_file(key_share)?, &share)?;
+                // The received shares are secret, and are no longer needed.
+                for path in &received_paths {
+                    std::fs::remove_file(path)
+                        .with_context(|| format!("cannot remove file {:?}", path))?;
+                }
+                let group_key: [u8; 32] = share.public.group_key()?.into();
+                if output.is_json() {
+                    output.print_json(&serde_json::json!({
+                        "key_share_file": key_share.display().to_string(),
+                        "group_key": hex::encode(group_key),
+                    }))?;
+                } else {
+                    println!(
+                        "saved key share to {}; group spend verification key: {}",
+                        key_share.display(),
+                        hex::encode(group_key)
+                    );
+                }
+            }
+            ThresholdSignCmd::Commit { key_share, nonces } => {
+                let key_share: KeyShare = read_json(key_share)?;
+                let (signing_nonces, commitments) = threshold::commit(&mut OsRng, &key_share);
+
+                serde_json::to_writer(create_secret_file(nonces)?, &signing_nonces)?;
+                output.print_json(&commitments)?;
+            }
+            ThresholdSignCmd::Plan { transaction } => {
+                let transaction = read_transaction(transaction)?;
+                output.print_json(&serde_json::json!({
+                    "sighash": hex::encode(transaction.transaction_body.sighash()),
+                    "randomizers": transaction
+                        .spend_auth_randomizers()
+                        .iter()
+                        .map(|randomizer| hex::encode(randomizer.to_bytes()))
+                        .collect::<Vec<_>>(),
+                }))?;
+            }
+            ThresholdSignCmd::Package {
+                message,
+                randomizer,
+                commitments,
+            } => {
+                let message = hex::decode(message).context("message is not valid hex")?;
+                let randomizer = Fr::from_bytes(
+                    hex::decode(randomizer)
+                        .context("randomizer is not valid hex")?
+                        .try_into()
+                        .map_err(|_| anyhow!("randomizer must be 32 bytes"))?,
+                )
+                .map_err(|_| anyhow!("randomizer is not a valid scalar"))?;
+                let commitments = commitments
+                    .iter()
+                    .map(read_json)
+                    .collect::<Result<Vec<SigningCommitments>>>()?;
+
+                output.print_json(&SigningPackage::new(message, randomizer, commitments)?)?;
+            }
+            ThresholdSignCmd::Sign {
+                key_share,
+                nonces,
+                package,
+            } => {
+                let key_share: KeyShare = read_json(key_share)?;
+                let package: SigningPackage = read_json(package)?;
+                let signing_nonces = read_json(nonces)?;
+
+                // Remove the nonces before signing, so they can't be reused even if signing fails.
+                std::fs::remove_file(nonces)
+                    .with_context(|| format!("cannot remove nonces file {:?}", nonces))?;
+                output.print_json(&threshold::sign(&package, signing_nonces, &key_share)?)?;
+            }
+            ThresholdSignCmd::Aggregate {
+                key_share,
+                package,
+                shares,
+            } => {
+                let key_share: KeyShare = read_json(key_share)?;
+                let package: SigningPackage = read_json(package)?;
+                let shares = shares
+                    .iter()
+                    .map(read_json)
+                    .collect::<Result<Vec<SignatureShare>>>()?;
+
+                let signature = threshold::aggregate(&package, &shares, &key_share.public)?;
+                let signature: [u8; 64] = signature.into();
+                if output.is_json() {
+                    output.print_json(&serde_json::json!({
+                        "signature": hex::encode(signature),
+                    }))?;
+                } else {
+                    println!("{}", hex::encode(signature));
+                }
+            }
+            ThresholdSignCmd::Attach {
+                transaction: path,
+                spend,
+                signature,
+            } => {
+                let signature: [u8; 64] = hex::decode(signature)
+                    .context("signature is not valid hex")?
+                    .try_into()
+                    .map_err(|_| anyhow!("signature must be 64 bytes"))?;
+
+                let mut transaction = read_transaction(path)?;
+                transaction.authorize_spend(*spend, Signature::<SpendAuth>::from(signature))?;
+                std::fs::write(path, Vec::<u8>::from(transaction))
+                    .with_context(|| format!("cannot write transaction file {:?}", path))?;
+            }
         }
         Ok(())
     }
 }
 
+/// Creates a file which must not already exist, readable and writable only by its owner, for
+/// saving secrets to.
+fn create_secret_file(path: &Path) -> Result<File> {
+    let mut options = File::options();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .with_context(|| format!("cannot create file {:?}", path))
+}
+
+/// Waits for each of `paths` to contain a complete JSON value, checking once a second, since
+/// they're copied into place by the other participants as the protocol runs.
+async fn wait_for_json<T: DeserializeOwned>(
+    output: OutputFormat,
+    paths: &[PathBuf],
+) -> Result<Vec<T>> {
+    let mut values = Vec::with_capacity(paths.len());
+    for path in paths {
+        let mut announced = false;
+        let value = loop {
+            if let Ok(value) = read_json(path) {
+                break value;
+            }
+            if !announced {
+                output.status(format_args!("waiting for {}", path.display()));
+                announced = true;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        };
+        values.push(value);
+    }
+    Ok(values)
+}
+
+/// Checks that a DKG package was sent by the participant its file is named for, so that a
+/// misplaced or copied package file is caught rather than counted for the wrong participant.
+fn check_sender(path: &Path, expected: ParticipantIndex, sender: ParticipantIndex) -> Result<()> {
+    if sender != expected {
+        return Err(anyhow!(
+            "{:?} should be from participant {}, but was sent by participant {}",
+            path,
+            expected,
+            sender
+        ));
+    }
+    Ok(())
+}
+
+fn read_transaction(path: &Path) -> Result<Transaction> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("cannot read transaction file {:?}", path))?;
+    Transaction::try_from(bytes).with_context(|| format!("cannot parse transaction {:?}", path))
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    serde_json::to_writer_pretty(
+        File::create(path).with_context(|| format!("cannot create file {:?}", path))?,
+        value,
+    )?;
+    Ok(())
+}
+
+fn read_json<T: DeserializeOwned>(path: &PathBuf) -> Result<T> {
+    serde_json::from_reader(BufReader::new(
+        File::open(path).with_context(|| format!("cannot open file {:?}", path))?,
+    ))
+    .with_context(|| format!("cannot parse file {:?}", path))
+}
+
 // This code is done outside of the client state as a test case for whether it's
 // possible to use that interface to implement bespoke note handling.
 //
@@ -169,7 +586,15 @@ async fn sweep(opt: &Opt, state: &mut ClientStateFile) -> Result<()> {
     }
 
     // Print a message to the user, so they can find out what we did.
-    if num_sweeps > 0 {
+    if opt.output.is_json() {
+        opt.output.print_json(&serde_json::json!({
+            "swept_notes": num_sweeps * SWEEP_COUNT,
+            "new_outputs": num_sweeps,
+        }))?;
+        if num_sweeps == 0 {
+            std::process::exit(9);
+        }
+    } else if num_sweeps > 0 {
         println!(
             "swept {} notes into {} new outputs; rerun to sweep further",
             num_sweeps * SWEEP_COUNT,
@@ -19,9 +19,9 @@ pub enum ValidatorCmd {
         /// The JSON file containing the ValidatorDefinition to upload
         #[structopt(long)]
         file: String,
-        /// The transaction fee (paid in upenumbra).
-        #[structopt(long, default_value = "0")]
-        fee: u64,
+        /// The transaction fee (paid in upenumbra) [default: configured fee, or 0].
+        #[structopt(long)]
+        fee: Option<u64>,
         /// Optional. Only spend funds originally received by the given address index.
         #[structopt(long)]
         source: Option<u64>,
@@ -66,7 +66,13 @@ impl ValidatorCmd {
                         .clone(),
                 );
 
-                println!("{}", ik);
+                if opt.output.is_json() {
+                    opt.output.print_json(&serde_json::json!({
+                        "identity_key": ik.to_string(),
+                    }))?;
+                } else {
+                    println!("{}", ik);
+                }
             }
             ValidatorCmd::UploadDefinition { file, fee, source } => {
                 // The definitions are stored in a JSON document,
@@ -86,7 +92,7 @@ impl ValidatorCmd {
                 // Sign the validator definition with the wallet's spend key.
                 let protobuf_serialized: ProtoValidator = new_validator.clone().into();
                 let v_bytes = protobuf_serialized.encode_to_vec();
-                let signing_key = state.wallet().spend_key().spend_auth_key().clone();
+                let signing_key = state.wallet().spend_key().spend_auth_key();
                 let auth_sig = signing_key.sign(&mut OsRng, &v_bytes);
                 let vd = validator::Definition {
                     validator: new_validator,
@@ -94,14 +100,20 @@ impl ValidatorCmd {
                 };
                 // Construct a new transaction and include the validator definition.
                 let transaction =
-                    state.build_validator_definition(&mut OsRng, vd, *fee, *source)?;
+                    state.build_validator_definition(&mut OsRng, vd, opt.fee(*fee), *source)?;
 
                 opt.submit_transaction(&transaction).await?;
                 // Only commit the state if the transaction was submitted
                 // successfully, so that we don't store pending notes that will
                 // never appear on-chain.
                 state.commit()?;
-                println!("Uploaded validator definition");
+                if opt.output.is_json() {
+                    opt.output.print_json(&serde_json::json!({
+                        "transaction_id": hex::encode(transaction.id()),
+                    }))?;
+                } else {
+                    println!("Uploaded validator definition");
+                }
             }
             ValidatorCmd::TemplateDefinition { file } => {
                 let (_label, address) = state.wallet().address_by_index(0)?;
@@ -147,6 +159,7 @@ impl ValidatorCmd {
                     .validator_status(ValidatorStatusRequest {
                         chain_id: "".to_string(), // TODO: fill in
                         identity_key: Some(identity_key.into()),
+                        ..Default::default()
                     })
                     .await?
                     .into_inner()
@@ -166,6 +179,7 @@ impl ValidatorCmd {
                     .validator_info(ValidatorInfoRequest {
                         show_inactive: true,
                         chain_id: state.chain_id().unwrap_or_default(),
+                        ..Default::default()
                     })
                     .await?
                     .into_inner()
@@ -1,15 +1,21 @@
-use std::{fs::File, io::BufReader, path::PathBuf, str::FromStr};
+use std::{
+    fs::File,
+    io::{BufReader, Write},
+    path::PathBuf,
+    str::FromStr,
+};
 
 use anyhow::{anyhow, Context as _, Result};
 use directories::ProjectDirs;
 use penumbra_crypto::keys::{SeedPhrase, SpendSeed};
-use penumbra_wallet::{ClientState, Wallet};
+use penumbra_wallet::{Checkpoint, ClientState, Wallet};
 use rand_core::OsRng;
 use serde::Deserialize;
 use sha2::{Digest, Sha256};
 use structopt::StructOpt;
+use zeroize::Zeroizing;
 
-use crate::ClientStateFile;
+use crate::{ClientStateFile, Opt};
 
 #[derive(Debug, StructOpt)]
 pub enum WalletCmd {
@@ -25,8 +31,39 @@ pub enum WalletCmd {
     },
     /// Export the spend seed for the wallet.
     Export,
+    /// Export the full viewing key for the wallet, which can view but not spend its notes.
+    ///
+    /// The full viewing key can be used to initialize a view service with `pviewd init`.
+    ExportFvk,
     /// Generate a new seed phrase.
-    Generate,
+    Generate {
+        /// Optional. Begin scanning from the checkpoint in the given JSON file, rather than from
+        /// genesis.
+        ///
+        /// Since a new wallet cannot have received notes before it was created, it's safe to
+        /// skip the chain history before a trusted checkpoint.
+        #[structopt(long, parse(from_os_str))]
+        checkpoint: Option<PathBuf>,
+    },
+    /// Export a checkpoint of the public chain state as of the last sync.
+    ///
+    /// The checkpoint can be used to initialize new wallets with `pcli wallet generate --checkpoint`.
+    /// It does not contain any of this wallet's private data.
+    Checkpoint {
+        /// The JSON file to write the checkpoint to.
+        #[structopt(long, parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Export the wallet's full viewing key and notes in a documented, versioned format which
+    /// other Penumbra clients can import.
+    ///
+    /// The export does not contain the spend seed, but it does reveal all of the wallet's notes,
+    /// so it should be kept private.
+    ExportInterop {
+        /// The JSON file to write the export to.
+        #[structopt(long, parse(from_os_str))]
+        file: PathBuf,
+    },
     /// Keep the spend seed, but reset all other client state.
     Reset,
     /// Delete the entire wallet permanently.
@@ -40,48 +77,167 @@ impl WalletCmd {
             WalletCmd::Import { .. } => false,
             WalletCmd::ImportFromPhrase { .. } => false,
             WalletCmd::Export => false,
-            WalletCmd::Generate => false,
+            WalletCmd::ExportFvk => false,
+            WalletCmd::Generate { .. } => false,
+            WalletCmd::Checkpoint { .. } => false,
+            WalletCmd::ExportInterop { .. } => false,
             WalletCmd::Reset => false,
             WalletCmd::Delete => false,
         }
     }
 
-    pub fn exec(&self, wallet_path: PathBuf) -> Result<()> {
+    pub fn exec(&self, opt: &Opt, wallet_path: PathBuf) -> Result<()> {
         // Dispatch on the wallet command and return a new state if the command required a
-        // wallet state to be saved to disk
+        // wallet state to be saved to disk, along with anything to report about it once it has
+        // been saved when the output is JSON
         let state = match self {
             // These two commands return new wallets to be saved to disk:
-            WalletCmd::Generate => {
+            WalletCmd::Generate { checkpoint } => {
+                // Read the checkpoint before generating anything, so that a bad checkpoint file
+                // doesn't result in a seed phrase being printed and then discarded.
+                let checkpoint = checkpoint
+                    .as_ref()
+                    .map(|path| -> Result<Checkpoint> {
+                        let checkpoint: Checkpoint = serde_json::from_reader(BufReader::new(
+                            File::open(path)
+                                .with_context(|| format!("cannot open file {:?}", path))?,
+                        ))
+                        .context("could not parse checkpoint file")?;
+                        Ok(checkpoint)
+                    })
+                    .transpose()?;
+
                 let seed_phrase = SeedPhrase::generate(&mut OsRng);
 
+                let mut report = serde_json::json!({ "seed_phrase": seed_phrase.to_string() });
+
                 // xxx: Something better should be done here, this is in danger of being
                 // shared by users accidentally in log output.
-                println!(
-                    "YOUR PRIVATE SEED PHRASE: {}\nDO NOT SHARE WITH ANYONE!",
-                    seed_phrase
-                );
+                if !opt.output.is_json() {
+                    println!(
+                        "YOUR PRIVATE SEED PHRASE: {}\nDO NOT SHARE WITH ANYONE!",
+                        seed_phrase
+                    );
+                }
 
-                Some(ClientState::new(Wallet::from_seed_phrase(seed_phrase)))
+                let wallet = Wallet::from_seed_phrase(seed_phrase);
+                let state = match checkpoint {
+                    Some(checkpoint) => {
+                        report["checkpoint"] = serde_json::json!({
+                            "height": checkpoint.height,
+                            "root": checkpoint.root().to_string(),
+                        });
+                        if !opt.output.is_json() {
+                            println!(
+                                "Starting from checkpoint at height {} with note commitment tree root {}",
+                                checkpoint.height,
+                                checkpoint.root()
+                            );
+                        }
+                        ClientState::from_checkpoint(wallet, checkpoint)
+                    }
+                    None => ClientState::new(wallet),
+                };
+                Some((state, report))
             }
             WalletCmd::Import { spend_seed } => {
-                let seed = hex::decode(spend_seed)?;
+                let seed = Zeroizing::new(hex::decode(spend_seed)?);
                 let seed = SpendSeed::try_from(seed.as_slice())?;
-                Some(ClientState::new(Wallet::import(seed)))
+                Some((
+                    ClientState::new(Wallet::import(seed)),
+                    serde_json::json!({}),
+                ))
             }
-            WalletCmd::ImportFromPhrase { seed_phrase } => Some(ClientState::new(
-                Wallet::from_seed_phrase(SeedPhrase::from_str(seed_phrase)?),
+            WalletCmd::ImportFromPhrase { seed_phrase } => Some((
+                ClientState::new(Wallet::from_seed_phrase(SeedPhrase::from_str(seed_phrase)?)),
+                serde_json::json!({}),
             )),
             // The rest of these commands don't require a wallet state to be saved to disk:
             WalletCmd::Export => {
                 let state = ClientStateFile::load(wallet_path.clone())?;
-                let seed = state.wallet().spend_key().seed().clone();
-                println!("{}", hex::encode(&seed.0));
+                let seed = Zeroizing::new(hex::encode(&state.wallet().spend_key().seed().0));
+                if opt.output.is_json() {
+                    opt.output.print_json(&serde_json::json!({
+                        "spend_seed": seed.as_str(),
+                    }))?;
+                } else {
+                    println!("{}", seed.as_str());
+                }
+                None
+            }
+            WalletCmd::ExportFvk => {
+                let state = ClientStateFile::load(wallet_path.clone())?;
+                let fvk = state.wallet().full_viewing_key();
+                if opt.output.is_json() {
+                    opt.output.print_json(&serde_json::json!({
+                        "full_viewing_key": fvk.to_string(),
+                    }))?;
+                } else {
+                    println!("{}", fvk);
+                }
+                None
+            }
+            WalletCmd::Checkpoint { file } => {
+                let state = ClientStateFile::load(wallet_path.clone())?;
+                let checkpoint = state.checkpoint()?;
+                File::create(file)
+                    .with_context(|| format!("cannot create file {:?}", file))?
+                    .write_all(&serde_json::to_vec_pretty(&checkpoint)?)
+                    .context("could not write file")?;
+                if opt.output.is_json() {
+                    opt.output.print_json(&serde_json::json!({
+                        "checkpoint_file": file.display().to_string(),
+                        "height": checkpoint.height,
+                        "root": checkpoint.root().to_string(),
+                    }))?;
+                } else {
+                    println!(
+                        "Wrote checkpoint at height {} with note commitment tree root {} to {}",
+                        checkpoint.height,
+                        checkpoint.root(),
+                        file.display()
+                    );
+                }
+                None
+            }
+            WalletCmd::ExportInterop { file } => {
+                let state = ClientStateFile::load(wallet_path.clone())?;
+                let export = state.export_interop()?;
+                File::create(file)
+                    .with_context(|| format!("cannot create file {:?}", file))?
+                    .write_all(&serde_json::to_vec_pretty(&export)?)
+                    .context("could not write file")?;
+                if opt.output.is_json() {
+                    opt.output.print_json(&serde_json::json!({
+                        "export_file": file.display().to_string(),
+                        "notes": export.notes.len(),
+                        "sync_height": export.sync_height,
+                        "version": export.version,
+                    }))?;
+                } else {
+                    println!(
+                        "Wrote {} notes synced to height {} in interop format version {} to {}",
+                        export.notes.len(),
+                        export
+                            .sync_height
+                            .map(|height| height.to_string())
+                            .unwrap_or_else(|| "(none)".to_string()),
+                        export.version,
+                        file.display()
+                    );
+                }
                 None
             }
             WalletCmd::Delete => {
                 if wallet_path.is_file() {
                     std::fs::remove_file(&wallet_path)?;
-                    println!("Deleted wallet file at {}", wallet_path.display());
+                    if opt.output.is_json() {
+                        opt.output.print_json(&serde_json::json!({
+                            "deleted_wallet_file": wallet_path.display().to_string(),
+                        }))?;
+                    } else {
+                        println!("Deleted wallet file at {}", wallet_path.display());
+                    }
                 } else if wallet_path.exists() {
                     return Err(anyhow!(
                             "Expected wallet file at {} but found something that is not a file; refusing to delete it",
@@ -138,7 +294,7 @@ impl WalletCmd {
         };
 
         // If a new wallet should be saved to disk, save it and also archive it in the archive directory
-        if let Some(state) = state {
+        if let Some((state, mut report)) = state {
             // Never overwrite a wallet that already exists
             if wallet_path.exists() {
                 return Err(anyhow::anyhow!(
@@ -147,7 +303,9 @@ impl WalletCmd {
                 ));
             }
 
-            println!("Saving wallet to {}", wallet_path.display());
+            opt.output
+                .status(format_args!("Saving wallet to {}", wallet_path.display()));
+            report["wallet_file"] = serde_json::json!(wallet_path.display().to_string());
             ClientStateFile::save(state.clone(), wallet_path)?;
 
             // Archive the newly generated state
@@ -171,8 +329,16 @@ impl WalletCmd {
 
             // Save the wallet file in the archive directory
             let archive_path = wallet_archive_dir.join("penumbra_wallet.json");
-            println!("Saving backup wallet to {}", archive_path.display());
+            opt.output.status(format_args!(
+                "Saving backup wallet to {}",
+                archive_path.display()
+            ));
+            report["backup_wallet_file"] = serde_json::json!(archive_path.display().to_string());
             ClientStateFile::save(state, archive_path)?;
+
+            if opt.output.is_json() {
+                opt.output.print_json(&report)?;
+            }
         }
 
         Ok(())
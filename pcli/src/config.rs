@@ -0,0 +1,173 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::Opt;
+
+/// The default address of the pd+tendermint node.
+pub const DEFAULT_NODE: &str = "testnet.penumbra.zone";
+/// The default port for tendermint's RPC server.
+pub const DEFAULT_TENDERMINT_PORT: u16 = 26657;
+/// The default port for pd's gRPC server.
+pub const DEFAULT_PD_PORT: u16 = 8080;
+/// The default transaction fee, in upenumbra.
+pub const DEFAULT_FEE: u64 = 0;
+
+/// Persistent `pcli` settings, read from a TOML file in the platform config directory.
+///
+/// Every setting is optional: settings given on the command line take precedence over settings
+/// in the config file, which take precedence over the built-in defaults.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PcliConfig {
+    /// The address of the pd+tendermint node.
+    pub node: Option<String>,
+    /// The port to use to speak to tendermint's RPC server.
+    pub tendermint_port: Option<u16>,
+    /// The port to use to speak to pd's gRPC server.
+    pub pd_port: Option<u16>,
+    /// The transaction fee to use when none is specified (paid in upenumbra).
+    pub fee: Option<u64>,
+    /// The location of the wallet file.
+    pub wallet_location: Option<PathBuf>,
+    /// The chain ID the node is expected to be serving.
+    pub chain_id: Option<String>,
+    /// The address of a view service to read balances from, instead of scanning the chain.
+    pub view_service: Option<String>,
+    /// The number of threads to use for trial decryption while syncing.
+    pub decryption_threads: Option<usize>,
+}
+
+impl PcliConfig {
+    /// The names of the settings which can be changed with `pcli config set`.
+    pub const KEYS: &'static [&'static str] = &[
+        "node",
+        "tendermint_port",
+        "pd_port",
+        "fee",
+        "wallet_location",
+        "chain_id",
+        "view_service",
+        "decryption_threads",
+    ];
+
+    /// Loads the config file at `path`, returning the empty config if it does not exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents)
+                .with_context(|| format!("could not parse config file {}", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err).with_context(|| format!("cannot read {}", path.display())),
+        }
+    }
+
+    /// Saves the config to the file at `path`, creating its parent directory if necessary.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("cannot write {}", path.display()))
+    }
+
+    /// Returns a config with every setting populated with its built-in default.
+    pub fn with_defaults() -> Self {
+        Self {
+            node: Some(DEFAULT_NODE.to_string()),
+            tendermint_port: Some(DEFAULT_TENDERMINT_PORT),
+            pd_port: Some(DEFAULT_PD_PORT),
+            fee: Some(DEFAULT_FEE),
+            wallet_location: None,
+            chain_id: None,
+            view_service: None,
+            decryption_threads: None,
+        }
+    }
+
+    /// Sets the setting named `key` to `value`, or clears it if `value` is empty.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        fn parse<T: std::str::FromStr>(value: &str) -> Result<Option<T>>
+        where
+            T::Err: std::error::Error + Send + Sync + 'static,
+        {
+            if value.is_empty() {
+                Ok(None)
+            } else {
+                Ok(Some(value.parse()?))
+            }
+        }
+
+        match key {
+            "node" => self.node = parse(value)?,
+            "tendermint_port" => self.tendermint_port = parse(value)?,
+            "pd_port" => self.pd_port = parse(value)?,
+            "fee" => self.fee = parse(value)?,
+            "wallet_location" => self.wallet_location = parse(value)?,
+            "chain_id" => self.chain_id = parse(value)?,
+            "view_service" => self.view_service = parse(value)?,
+            "decryption_threads" => self.decryption_threads = parse(value)?,
+            other => {
+                return Err(anyhow!(
+                    "unknown config key {:?}, expected one of {:?}",
+                    other,
+                    Self::KEYS
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Opt {
+    /// Fills in any settings not given on the command line from the config file.
+    pub fn apply_config(&mut self, config: &PcliConfig) {
+        if self.node.is_none() {
+            self.node = config.node.clone();
+        }
+        if self.tendermint_port.is_none() {
+            self.tendermint_port = config.tendermint_port;
+        }
+        if self.pd_port.is_none() {
+            self.pd_port = config.pd_port;
+        }
+        if self.wallet_location.is_none() {
+            self.wallet_location = config
+                .wallet_location
+                .as_ref()
+                .map(|path| path.display().to_string());
+        }
+        if self.view_service.is_none() {
+            self.view_service = config.view_service.clone();
+        }
+        if self.decryption_threads.is_none() {
+            self.decryption_threads = config.decryption_threads;
+        }
+        self.config = config.clone();
+    }
+
+    /// The address of the pd+tendermint node.
+    pub fn node(&self) -> &str {
+        self.node.as_deref().unwrap_or(DEFAULT_NODE)
+    }
+
+    /// The port to use to speak to tendermint's RPC server.
+    pub fn tendermint_port(&self) -> u16 {
+        self.tendermint_port.unwrap_or(DEFAULT_TENDERMINT_PORT)
+    }
+
+    /// The port to use to speak to pd's gRPC server.
+    pub fn pd_port(&self) -> u16 {
+        self.pd_port.unwrap_or(DEFAULT_PD_PORT)
+    }
+
+    /// The transaction fee to use, given the fee specified on the command line, if any.
+    pub fn fee(&self, fee: Option<u64>) -> u64 {
+        fee.or(self.config.fee).unwrap_or(DEFAULT_FEE)
+    }
+
+    /// The chain ID the node is expected to be serving, if one is configured.
+    pub fn expected_chain_id(&self) -> Option<&str> {
+        self.config.chain_id.as_deref()
+    }
+}
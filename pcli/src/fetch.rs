@@ -1,6 +1,10 @@
 use anyhow::Result;
 use penumbra_chain::KnownAssets;
-use penumbra_proto::client::oblivious::{AssetListRequest, ChainParamsRequest};
+use penumbra_crypto::asset;
+use penumbra_proto::client::{
+    oblivious::{AssetListRequest, ChainParamsRequest},
+    specific::AssetLookupRequest,
+};
 use tracing::instrument;
 
 use crate::{ClientStateFile, Opt};
@@ -18,6 +22,15 @@ pub async fn assets(opt: &Opt, state: &mut ClientStateFile) -> Result<()> {
         state.asset_cache_mut().extend(std::iter::once(asset.denom));
     }
 
+    // Notes may have arrived for assets registered after the list was read, so look up any
+    // stragglers individually.
+    for id in state.unknown_asset_ids() {
+        match asset_denom(opt, state, &id).await? {
+            Some(denom) => state.asset_cache_mut().extend(std::iter::once(denom)),
+            None => tracing::warn!(%id, "node does not know the denomination of a received asset"),
+        }
+    }
+
     state.commit()?;
     tracing::info!("updated asset registry");
     Ok(())
@@ -30,7 +43,12 @@ pub async fn chain_params(opt: &Opt, state: &mut ClientStateFile) -> Result<()>
 
     let params = client
         .chain_params(tonic::Request::new(ChainParamsRequest {
-            chain_id: state.chain_id().unwrap_or_default(),
+            // If we don't know the chain ID yet, check the node against the configured one.
+            chain_id: state
+                .chain_id()
+                .or_else(|| opt.expected_chain_id().map(ToString::to_string))
+                .unwrap_or_default(),
+            ..Default::default()
         }))
         .await?
         .into_inner()
@@ -42,3 +60,29 @@ pub async fn chain_params(opt: &Opt, state: &mut ClientStateFile) -> Result<()>
     state.commit()?;
     Ok(())
 }
+
+/// Looks up the denomination of a single asset on the node, returning `None` if it is not
+/// registered.
+///
+/// Unlike fetching the whole asset list, this reveals interest in a specific asset to the node.
+#[instrument(skip(opt, state))]
+pub async fn asset_denom(
+    opt: &Opt,
+    state: &ClientStateFile,
+    id: &asset::Id,
+) -> Result<Option<asset::Denom>> {
+    let mut client = opt.specific_client().await?;
+
+    let rsp = client
+        .asset_lookup(tonic::Request::new(AssetLookupRequest {
+            chain_id: state.chain_id().unwrap_or_default(),
+            asset_id: Some((*id).into()),
+        }))
+        .await;
+
+    match rsp {
+        Ok(denom) => Ok(Some(denom.into_inner().try_into()?)),
+        Err(status) if status.code() == tonic::Code::NotFound => Ok(None),
+        Err(status) => Err(status.into()),
+    }
+}
@@ -4,15 +4,22 @@ use std::path::PathBuf;
 use anyhow::Result;
 use directories::ProjectDirs;
 use structopt::StructOpt;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 
 mod command;
+mod config;
 mod fetch;
+mod logging;
 mod network;
+mod output;
 mod state;
 mod sync;
 mod warning;
 
 use command::*;
+use config::PcliConfig;
+use logging::LogOpt;
+use output::OutputFormat;
 use state::ClientStateFile;
 use sync::sync;
 
@@ -23,37 +30,81 @@ use sync::sync;
     version = env!("VERGEN_GIT_SEMVER"),
 )]
 pub struct Opt {
-    /// The address of the pd+tendermint node.
-    #[structopt(short, long, default_value = "testnet.penumbra.zone")]
-    pub node: String,
-    /// The port to use to speak to tendermint's RPC server.
-    #[structopt(long, default_value = "26657")]
-    pub tendermint_port: u16,
-    /// The port to use to speak to pd's gRPC server.
-    #[structopt(long, default_value = "8080")]
-    pub pd_port: u16,
+    /// The address of the pd+tendermint node [default: testnet.penumbra.zone].
+    #[structopt(short, long)]
+    pub node: Option<String>,
+    /// The port to use to speak to tendermint's RPC server [default: 26657].
+    #[structopt(long)]
+    pub tendermint_port: Option<u16>,
+    /// The port to use to speak to pd's gRPC server [default: 8080].
+    #[structopt(long)]
+    pub pd_port: Option<u16>,
+    /// The address of a view service (`pviewd`) to read balances from, instead of scanning the
+    /// chain locally, e.g. `http://127.0.0.1:8081`.
+    #[structopt(long)]
+    pub view_service: Option<String>,
+    /// The number of threads to use for trial decryption while syncing [default: one per core].
+    #[structopt(long)]
+    pub decryption_threads: Option<usize>,
     #[structopt(subcommand)]
     pub cmd: Command,
     /// The location of the wallet file [default: platform appdata directory]
     #[structopt(short, long)]
     pub wallet_location: Option<String>,
+    /// The format of command output: `human` for text and tables, or `json` for structured output.
+    ///
+    /// When JSON output is selected, all diagnostic messages are written to stderr, so that
+    /// stdout contains only the JSON result of the command.
+    #[structopt(long, default_value = "human", possible_values = OutputFormat::VARIANTS)]
+    pub output: OutputFormat,
+    #[structopt(flatten)]
+    pub log: LogOpt,
+    /// Settings loaded from the config file, used where no command-line flag was given.
+    #[structopt(skip)]
+    pub config: PcliConfig,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let mut opt = Opt::from_args();
+
+    // Completion scripts are written to stdout, so they must not include the warning below.
+    if let Command::Completions(completions_cmd) = &opt.cmd {
+        return completions_cmd.exec();
+    }
+
     // Display a warning message to the user so they don't get upset when all their tokens are lost.
     if std::env::var("PCLI_UNLEASH_DANGER").is_err() {
-        warning::display();
+        warning::display(opt.output);
     }
 
-    tracing_subscriber::fmt::init();
-    let opt = Opt::from_args();
-
     let project_dir =
         ProjectDirs::from("zone", "penumbra", "pcli").expect("can access penumbra project dir");
-    // Currently we use just the data directory. Create it if it is missing.
+    // Create the data directory if it is missing.
     std::fs::create_dir_all(project_dir.data_dir()).expect("can create penumbra data directory");
 
+    // Keep stdout clean for machine-readable output. The dashboard puts the terminal in raw mode
+    // and draws over all of it, so its logs go to a file unless another was given.
+    let terminal = if let Command::Dash(_) = &opt.cmd {
+        if opt.log.log_file.is_none() {
+            opt.log.log_file = Some(project_dir.data_dir().join("dash.log"));
+        }
+        BoxMakeWriter::new(std::io::sink)
+    } else if opt.output.is_json() {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
+    opt.log.init(terminal)?;
+
+    // Settings from the config file fill in anything not given on the command line.
+    let config_path = project_dir.config_dir().join("config.toml");
+    if let Command::Config(config_cmd) = &opt.cmd {
+        config_cmd.exec(&opt, &config_path)?;
+        return Ok(());
+    }
+    opt.apply_config(&PcliConfig::load(&config_path)?);
+
     // We store wallet data in `penumbra_wallet.dat` in the state directory, unless
     // the user provides another location.
     let wallet_path = opt.wallet_location.as_ref().map_or_else(
@@ -61,10 +112,15 @@ async fn main() -> Result<()> {
         PathBuf::from,
     );
 
+    // Dynamic completion only reads the local wallet, without fetching anything from the node.
+    if let Command::Complete(complete_cmd) = &opt.cmd {
+        return complete_cmd.exec(wallet_path);
+    }
+
     // The wallet command takes the wallet_path directly, since it may need to create the client state,
     // so handle it specially here so that we can have common code for the other subcommands.
     if let Command::Wallet(wallet_cmd) = &opt.cmd {
-        wallet_cmd.exec(wallet_path)?;
+        wallet_cmd.exec(&opt, wallet_path)?;
         return Ok(());
     }
 
@@ -77,22 +133,43 @@ async fn main() -> Result<()> {
     }
     // From now on, we can .expect() on the chain params.
 
+    // A view service has already scanned the chain, so balances can be read from it directly.
+    if let (Command::Balance(balance_cmd), Some(_)) = (&opt.cmd, &opt.view_service) {
+        fetch::assets(&opt, &mut state).await?;
+        return balance_cmd.exec_view_service(&opt, &state).await;
+    }
+
     if opt.cmd.needs_sync() {
-        sync(&opt, &mut state).await?;
+        let rescan_from = match opt.cmd {
+            Command::Sync { rescan_from } => rescan_from,
+            _ => None,
+        };
+        if rescan_from.is_some() {
+            state.reset_for_rescan();
+        }
+        sync(&opt, &mut state, rescan_from).await?;
         fetch::assets(&opt, &mut state).await?;
     };
 
     match &opt.cmd {
         Command::Wallet(_) => unreachable!("wallet command already executed"),
-        Command::Sync => {
+        Command::Config(_) => unreachable!("config command already executed"),
+        Command::Completions(_) => unreachable!("completions command already executed"),
+        Command::Complete(_) => unreachable!("complete command already executed"),
+        Command::Sync { .. } => {
             // We have already synchronized the wallet above, so we can just return.
         }
         Command::Tx(tx_cmd) => tx_cmd.exec(&opt, &mut state).await?,
-        Command::Addr(addr_cmd) => addr_cmd.exec(&mut state)?,
-        Command::Balance(balance_cmd) => balance_cmd.exec(&state)?,
+        Command::Addr(addr_cmd) => addr_cmd.exec(&opt, &mut state)?,
+        Command::Balance(balance_cmd) => balance_cmd.exec(&opt, &state)?,
+        Command::Notes(cmd) => cmd.exec(&opt, &mut state)?,
+        Command::Dash(dash_cmd) => dash_cmd.exec(&opt, &mut state).await?,
+        Command::Chain(cmd) => cmd.exec(&opt, &state).await?,
+        Command::Assets(cmd) => cmd.exec(&opt, &mut state).await?,
         Command::Validator(cmd) => cmd.exec(&opt, &mut state).await?,
         Command::Stake(cmd) => cmd.exec(&opt, &mut state).await?,
-        Command::Tmp(cmd) => cmd.exec().await?,
+        Command::Bench(cmd) => cmd.exec(&opt, &state).await?,
+        Command::Tmp(cmd) => cmd.exec(&opt).await?,
     }
 
     Ok(())
@@ -3,6 +3,7 @@ use penumbra_proto::{
         oblivious::oblivious_query_client::ObliviousQueryClient,
         specific::specific_query_client::SpecificQueryClient,
     },
+    wallet::wallet_protocol_client::WalletProtocolClient,
     Protobuf,
 };
 use penumbra_transaction::Transaction;
@@ -22,7 +23,11 @@ impl Opt {
         let client = reqwest::Client::new();
         let req_id: u8 = rand::thread_rng().gen();
         let rsp: serde_json::Value = client
-            .post(format!(r#"http://{}:{}"#, self.node, self.tendermint_port))
+            .post(format!(
+                r#"http://{}:{}"#,
+                self.node(),
+                self.tendermint_port()
+            ))
             .json(&serde_json::json!(
                 {
                     "method": "broadcast_tx_sync",
@@ -74,7 +79,11 @@ impl Opt {
         let client = reqwest::Client::new();
         let req_id: u8 = rand::thread_rng().gen();
         let rsp: serde_json::Value = client
-            .post(format!(r#"http://{}:{}"#, self.node, self.tendermint_port))
+            .post(format!(
+                r#"http://{}:{}"#,
+                self.node(),
+                self.tendermint_port()
+            ))
             .json(&serde_json::json!(
                 {
                     "method": "broadcast_tx_async",
@@ -92,14 +101,46 @@ impl Opt {
         Ok(())
     }
 
+    /// Fetches the height of the latest block known to the node.
+    pub async fn latest_block_height(&self) -> Result<u64, anyhow::Error> {
+        let rsp: serde_json::Value = reqwest::get(format!(
+            r#"http://{}:{}/status"#,
+            self.node(),
+            self.tendermint_port()
+        ))
+        .await?
+        .json()
+        .await?;
+
+        let result = rsp.get("result").unwrap_or(&rsp);
+
+        result
+            .get("sync_info")
+            .and_then(|s| s.get("latest_block_height"))
+            .and_then(|h| h.as_str())
+            .ok_or_else(|| anyhow::anyhow!("could not parse JSON response"))?
+            .parse()
+            .map_err(Into::into)
+    }
+
     pub async fn specific_client(&self) -> Result<SpecificQueryClient<Channel>, anyhow::Error> {
-        SpecificQueryClient::connect(format!("http://{}:{}", self.node, self.pd_port))
+        SpecificQueryClient::connect(format!("http://{}:{}", self.node(), self.pd_port()))
             .await
             .map_err(Into::into)
     }
 
     pub async fn oblivious_client(&self) -> Result<ObliviousQueryClient<Channel>, anyhow::Error> {
-        ObliviousQueryClient::connect(format!("http://{}:{}", self.node, self.pd_port))
+        ObliviousQueryClient::connect(format!("http://{}:{}", self.node(), self.pd_port()))
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn view_client(&self) -> Result<WalletProtocolClient<Channel>, anyhow::Error> {
+        let view_service = self
+            .view_service
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("no view service configured"))?;
+        WalletProtocolClient::connect(view_service)
             .await
             .map_err(Into::into)
     }
@@ -0,0 +1,60 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::Result;
+use serde::Serialize;
+
+/// The format in which commands emit their results on stdout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text and tables.
+    Human,
+    /// Structured JSON, for consumption by scripts and other programs.
+    Json,
+}
+
+impl OutputFormat {
+    /// The names accepted on the command line.
+    pub const VARIANTS: &'static [&'static str] = &["human", "json"];
+
+    /// Returns true if output should be emitted as JSON.
+    pub fn is_json(&self) -> bool {
+        matches!(self, OutputFormat::Json)
+    }
+
+    /// Emit a single JSON document on stdout.
+    ///
+    /// Commands should emit exactly one JSON document per invocation, so that the output can be
+    /// parsed as a whole.
+    pub fn print_json<T: Serialize + ?Sized>(&self, value: &T) -> Result<()> {
+        println!("{}", serde_json::to_string_pretty(value)?);
+        Ok(())
+    }
+
+    /// Print a progress or status message meant for a person rather than a program.
+    ///
+    /// With JSON output the message goes to stderr, so that it isn't interleaved with the JSON
+    /// document on stdout.
+    pub fn status(&self, message: impl fmt::Display) {
+        if self.is_json() {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(OutputFormat::Human),
+            "json" => Ok(OutputFormat::Json),
+            other => Err(anyhow::anyhow!(
+                "unknown output format {:?}, expected one of {:?}",
+                other,
+                Self::VARIANTS
+            )),
+        }
+    }
+}
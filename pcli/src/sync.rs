@@ -1,16 +1,34 @@
 use anyhow::Result;
-use penumbra_proto::client::oblivious::CompactBlockRangeRequest;
+use futures::{stream::BoxStream, Stream, StreamExt, TryStreamExt};
+use penumbra_chain::CompactBlock;
+use penumbra_crypto::keys::IncomingViewingKey;
+use penumbra_proto::{chain as pb, client::oblivious::CompactBlockRangeRequest};
+use penumbra_wallet::{DecryptionPool, ScannedBlock};
 use tracing::instrument;
 
 use crate::{ClientStateFile, Opt};
 
+/// The maximum number of blocks which may be in flight at once: fetched from the node, but not yet
+/// applied to the client state.
+///
+/// Blocks are trial-decrypted concurrently up to this limit, while earlier blocks are applied to the
+/// note commitment tree in order.
+const SYNC_PIPELINE_DEPTH: usize = 64;
+
+/// The number of blocks between progress messages while syncing.
+const SYNC_PROGRESS_INTERVAL: usize = 1000;
+
+/// Synchronizes the client state with the chain.
+///
+/// If `rescan_from` is set, blocks below that height are not trial-decrypted, and only their note
+/// commitments (and any notes the client state already knows about) are recorded.
 #[instrument(skip(opt, state), fields(start_height = state.last_block_height()))]
-pub async fn sync(opt: &Opt, state: &mut ClientStateFile) -> Result<()> {
+pub async fn sync(opt: &Opt, state: &mut ClientStateFile, rescan_from: Option<u64>) -> Result<()> {
     tracing::info!("starting client sync");
     let mut client = opt.oblivious_client().await?;
 
     let start_height = state.last_block_height().map(|h| h + 1).unwrap_or(0);
-    let mut stream = client
+    let stream = client
         .compact_block_range(tonic::Request::new(CompactBlockRangeRequest {
             start_height,
             end_height: 0,
@@ -21,13 +39,18 @@ pub async fn sync(opt: &Opt, state: &mut ClientStateFile) -> Result<()> {
         .await?
         .into_inner();
 
+    let ivk = state.wallet().incoming_viewing_key().clone();
+    let pool = DecryptionPool::new(opt.decryption_threads)?;
+    let mut scanned_blocks = scan(stream, ivk, pool, rescan_from.unwrap_or(0));
+
+    // The client state (including the note commitment tree) is only written out once, at the end
+    // of the sync: serializing it is expensive, and wallets with many notes would otherwise spend
+    // most of the sync doing it.
     let mut count = 0;
-    while let Some(block) = stream.message().await? {
-        state.scan_block(block.try_into()?)?;
-        // very basic form of intermediate checkpointing
+    while let Some(block) = scanned_blocks.try_next().await? {
+        state.apply_scanned_block(block)?;
         count += 1;
-        if count % 1000 == 1 {
-            state.commit()?;
+        if count % SYNC_PROGRESS_INTERVAL == 0 {
             tracing::info!(height = ?state.last_block_height().unwrap(), "syncing...");
         }
     }
@@ -37,3 +60,35 @@ pub async fn sync(opt: &Opt, state: &mut ClientStateFile) -> Result<()> {
     tracing::info!(end_height = ?state.last_block_height().unwrap(), "finished sync");
     Ok(())
 }
+
+/// Trial-decrypts a stream of compact blocks, yielding them in order, ready to be applied to the
+/// client state.
+///
+/// Blocks are trial-decrypted on `pool`, and blocks below `decrypt_from` are not trial-decrypted.
+pub fn scan(
+    blocks: impl Stream<Item = Result<pb::CompactBlock, tonic::Status>> + Send + 'static,
+    ivk: IncomingViewingKey,
+    pool: DecryptionPool,
+    decrypt_from: u64,
+) -> BoxStream<'static, Result<ScannedBlock>> {
+    // Fetching, trial decryption, and state updates are pipelined: while the client state is
+    // updated with one block, later blocks are being decrypted on the blocking thread pool, and
+    // later blocks still are being fetched from the node.
+    blocks
+        .map_err(anyhow::Error::from)
+        .map_ok(move |block| {
+            let ivk = ivk.clone();
+            let pool = pool.clone();
+            async move {
+                let block = CompactBlock::try_from(block)?;
+                if block.height < decrypt_from {
+                    return Ok(ScannedBlock::without_decryption(block));
+                }
+                let scanned =
+                    tokio::task::spawn_blocking(move || pool.trial_decrypt(&ivk, block)).await?;
+                Ok::<_, anyhow::Error>(scanned)
+            }
+        })
+        .try_buffered(SYNC_PIPELINE_DEPTH)
+        .boxed()
+}
@@ -1,5 +1,8 @@
-pub fn display() {
-    println!(
+use crate::OutputFormat;
+
+pub fn display(output: OutputFormat) {
+    // Don't interleave the warning with machine-readable output.
+    let message = format!(
         "
                                \x1b[1;31m⛔️WARNING️️⛔️:
         
@@ -15,5 +18,11 @@ The danger is unleashed only if you execute this software.
 🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥🔥
 
                            \x1b[1;31mUSE AT YOUR OWN RISK\x1b[0m"
-    )
+    );
+
+    if output.is_json() {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
 }
@@ -0,0 +1,43 @@
+-- The heights of the blocks which have been indexed
+CREATE TABLE blocks (
+    height          BIGINT PRIMARY KEY NOT NULL
+);
+
+-- Transactions accepted in each block
+CREATE TABLE transactions (
+    tx_hash         BLOB PRIMARY KEY NOT NULL,
+    height          BIGINT NOT NULL,
+    tx_index        INTEGER NOT NULL,
+    tx_bytes        BLOB NOT NULL
+);
+
+CREATE INDEX transactions_height_idx ON transactions (height, tx_index);
+
+-- Nullifiers revealed by accepted transactions
+CREATE TABLE nullifiers (
+    nullifier       BLOB PRIMARY KEY NOT NULL,
+    height          BIGINT NOT NULL,
+    tx_hash         BLOB NOT NULL
+);
+
+CREATE INDEX nullifiers_height_idx ON nullifiers (height);
+
+-- Note commitments created by accepted transactions
+CREATE TABLE note_commitments (
+    note_commitment BLOB PRIMARY KEY NOT NULL,
+    height          BIGINT NOT NULL,
+    tx_hash         BLOB NOT NULL
+);
+
+CREATE INDEX note_commitments_height_idx ON note_commitments (height);
+
+-- Changes to the voting power of validators in the consensus set
+CREATE TABLE validator_changes (
+    height          BIGINT NOT NULL,
+    consensus_key   BLOB NOT NULL,
+    power           BIGINT NOT NULL, -- 0 if the validator left the consensus set
+    PRIMARY KEY (height, consensus_key)
+);
+
+-- used to find the previous power of a validator
+CREATE INDEX validator_changes_key_idx ON validator_changes (consensus_key, height);
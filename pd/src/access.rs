@@ -0,0 +1,327 @@
+//! Limits on access to `pd`'s gRPC endpoints, so that operators can expose them publicly.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::IpAddr,
+    num::NonZeroU32,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use futures::{FutureExt, StreamExt};
+use metrics::counter;
+use sha2::{Digest, Sha256};
+use tonic::{
+    body::BoxBody,
+    transport::{server::TcpConnectInfo, Body},
+    Status,
+};
+
+use crate::pd_metrics::GRPC_REQUESTS_REJECTED;
+
+/// The number of clients whose request rate is tracked before idle clients are forgotten.
+const MAX_TRACKED_CLIENTS: usize = 16384;
+
+/// Which requests the gRPC endpoints accept.
+///
+/// By default, every request is accepted.
+#[derive(Clone, Debug, Default)]
+pub struct AccessPolicy {
+    /// The rate at which each client IP address may make requests.
+    pub rate_limit: Option<RateLimit>,
+    /// The maximum size of a request body, in bytes.
+    pub max_request_size: Option<usize>,
+    /// If set, requests must carry an `authorization: Bearer <token>` header with this token.
+    pub auth_token: Option<String>,
+}
+
+/// A limit on the rate of requests, allowing short bursts above the sustained rate.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    /// The sustained number of requests allowed per second.
+    pub per_second: NonZeroU32,
+    /// The number of requests which can be made at once after a period of inactivity.
+    pub burst: NonZeroU32,
+}
+
+/// A [`tower::Layer`] which rejects gRPC requests not allowed by an [`AccessPolicy`].
+#[derive(Clone, Debug)]
+pub struct AccessLayer {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    max_request_size: Option<usize>,
+    /// The hash of the auth token, so that checking a request's token takes the same time
+    /// however much of it is correct.
+    auth_token_hash: Option<Vec<u8>>,
+    rate_limiter: Option<RateLimiter>,
+}
+
+impl AccessLayer {
+    pub fn new(policy: AccessPolicy) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                max_request_size: policy.max_request_size,
+                auth_token_hash: policy
+                    .auth_token
+                    .map(|token| Sha256::digest(token.as_bytes()).to_vec()),
+                rate_limiter: policy.rate_limit.map(RateLimiter::new),
+            }),
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for AccessLayer {
+    type Service = Access<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Access {
+            inner,
+            access: self.inner.clone(),
+        }
+    }
+}
+
+/// A gRPC service wrapped by [`AccessLayer`].
+#[derive(Clone, Debug)]
+pub struct Access<S> {
+    inner: S,
+    access: Arc<Inner>,
+}
+
+impl Inner {
+    /// Checks a request against the policy, returning the status to reject it with if it isn't
+    /// allowed.
+    fn check(&self, req: &http::Request<Body>) -> Result<(), (&'static str, Status)> {
+        if let Some(expected) = &self.auth_token_hash {
+            let token = req
+                .headers()
+                .get(http::header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "));
+            match token {
+                Some(token)
+                    if Sha256::digest(token.as_bytes()).as_slice() == expected.as_slice() => {}
+                _ => {
+                    return Err((
+                        "unauthenticated",
+                        Status::unauthenticated("missing or invalid bearer token"),
+                    ))
+                }
+            }
+        }
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            let addr = req
+                .extensions()
+                .get::<TcpConnectInfo>()
+                .and_then(|info| info.remote_addr());
+            if let Some(addr) = addr {
+                if !rate_limiter.allow(addr.ip()) {
+                    return Err((
+                        "rate_limit",
+                        Status::resource_exhausted("rate limit exceeded"),
+                    ));
+                }
+            }
+        }
+
+        if let Some(max) = self.max_request_size {
+            let size = req
+                .headers()
+                .get(http::header::CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<usize>().ok());
+            if matches!(size, Some(size) if size > max) {
+                return Err((
+                    "request_size",
+                    Status::resource_exhausted(format!("request exceeds {} bytes", max)),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<S> tower::Service<http::Request<Body>> for Access<S>
+where
+    S: tower::Service<http::Request<Body>, Response = http::Response<BoxBody>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        if let Err((reason, status)) = self.access.check(&req) {
+            tracing::debug!(?reason, "rejecting gRPC request");
+            counter!(GRPC_REQUESTS_REJECTED, 1, "reason" => reason);
+            return futures::future::ready(Ok(status.to_http())).boxed();
+        }
+
+        // Streamed request bodies don't declare their length up front, so the size of the body
+        // is also checked as it's received.
+        let req = match self.access.max_request_size {
+            Some(max) => req.map(|body| {
+                let mut received = 0;
+                Body::wrap_stream(body.map(move |chunk| {
+                    let chunk = chunk?;
+                    received += chunk.len();
+                    if received > max {
+                        counter!(GRPC_REQUESTS_REJECTED, 1, "reason" => "request_size");
+                        return Err(anyhow::anyhow!("request exceeds {} bytes", max));
+                    }
+                    Ok(chunk)
+                }))
+            }),
+            None => req,
+        };
+
+        self.inner.call(req).boxed()
+    }
+}
+
+/// A token bucket for each client IP address.
+#[derive(Debug)]
+struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            per_second: limit.per_second.get().into(),
+            burst: limit.burst.get().into(),
+            buckets: Default::default(),
+        }
+    }
+
+    /// Records a request from `ip`, returning whether it's within the limit.
+    fn allow(&self, ip: IpAddr) -> bool {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            // A client whose bucket has refilled is indistinguishable from a new one.
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * self.per_second
+                    < self.burst
+            });
+        }
+
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        bucket.tokens = (bucket.tokens
+            + now.duration_since(bucket.updated).as_secs_f64() * self.per_second)
+            .min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        num::NonZeroU32,
+        time::{Duration, Instant},
+    };
+
+    use super::{RateLimit, RateLimiter, MAX_TRACKED_CLIENTS};
+
+    fn limiter(per_second: u32, burst: u32) -> RateLimiter {
+        RateLimiter::new(RateLimit {
+            per_second: NonZeroU32::new(per_second).unwrap(),
+            burst: NonZeroU32::new(burst).unwrap(),
+        })
+    }
+
+    fn ip(i: usize) -> IpAddr {
+        Ipv4Addr::from(i as u32).into()
+    }
+
+    #[test]
+    fn burst_then_limit() {
+        let limiter = limiter(1, 3);
+        for _ in 0..3 {
+            assert!(limiter.allow(ip(0)));
+        }
+        assert!(!limiter.allow(ip(0)));
+        // Each client has its own bucket.
+        assert!(limiter.allow(ip(1)));
+    }
+
+    #[test]
+    fn refill_over_time() {
+        let limiter = limiter(1, 2);
+        assert!(limiter.allow(ip(0)));
+        assert!(limiter.allow(ip(0)));
+        assert!(!limiter.allow(ip(0)));
+
+        // Pretend the client's last request was a second and a half ago.
+        let earlier = Instant::now() - Duration::from_millis(1500);
+        limiter
+            .buckets
+            .lock()
+            .unwrap()
+            .get_mut(&ip(0))
+            .unwrap()
+            .updated = earlier;
+        assert!(limiter.allow(ip(0)));
+        assert!(!limiter.allow(ip(0)));
+    }
+
+    #[test]
+    fn evict_idle_clients() {
+        let limiter = limiter(1, 1);
+        for i in 0..MAX_TRACKED_CLIENTS {
+            assert!(limiter.allow(ip(i)));
+        }
+
+        // Let the first half of the clients' buckets refill.
+        let earlier = Instant::now() - Duration::from_secs(10);
+        for (addr, bucket) in limiter.buckets.lock().unwrap().iter_mut() {
+            if let IpAddr::V4(v4) = addr {
+                if (u32::from(*v4) as usize) < MAX_TRACKED_CLIENTS / 2 {
+                    bucket.updated = earlier;
+                }
+            }
+        }
+
+        // A new client evicts the idle clients, but not the ones still limited.
+        assert!(limiter.allow(ip(MAX_TRACKED_CLIENTS)));
+        assert_eq!(
+            limiter.buckets.lock().unwrap().len(),
+            MAX_TRACKED_CLIENTS / 2 + 1
+        );
+        assert!(!limiter.allow(ip(MAX_TRACKED_CLIENTS - 1)));
+        assert!(limiter.allow(ip(0)));
+    }
+}
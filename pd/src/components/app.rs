@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use jmt::{RootHash, Version};
 use penumbra_chain::{genesis, View as _};
@@ -6,7 +6,7 @@ use penumbra_component::Component;
 use penumbra_ibc::IBCComponent;
 use penumbra_shielded_pool::ShieldedPool;
 use penumbra_stake::component::Staking;
-use penumbra_storage::{State, StateExt, Storage};
+use penumbra_storage::{State, StateExt, StateKey, StateTransaction, Storage};
 use penumbra_transaction::Transaction;
 use tendermint::abci::{self, types::ValidatorUpdate};
 
@@ -43,6 +43,22 @@ impl App {
         Ok((root_hash, version))
     }
 
+    /// Checks a transaction against the current state and, if it is valid, executes it.
+    ///
+    /// The checks and the execution run in a [`StateTransaction`], so a transaction that fails its
+    /// stateful checks leaves no writes behind. Execution itself panics on error, and the panic
+    /// drops the [`StateTransaction`], discarding any writes made before it.
+    #[instrument(skip(self, tx))]
+    pub async fn deliver_tx(&mut self, tx: &Transaction) -> Result<()> {
+        let state_tx = StateTransaction::begin(&self.state).await;
+        if let Err(e) = self.check_tx_stateful(tx).await {
+            state_tx.abort().await?;
+            return Err(e);
+        }
+        self.execute_tx(tx).await;
+        state_tx.commit().await
+    }
+
     // TODO: should this just be returned by `commit`? both are called during every `EndBlock`
     pub async fn tm_validator_updates(&self) -> Result<Vec<ValidatorUpdate>> {
         self.staking.tm_validator_updates().await
@@ -72,7 +88,10 @@ impl Component for App {
             .await;
         // TODO: do we actually need to store the app state here?
         self.state
-            .put_domain(b"genesis/app_state".into(), app_state.clone())
+            .put_typed(
+                &StateKey::new("genesis").join("app_state"),
+                app_state.clone(),
+            )
             .await;
         // The genesis block height is 0
         self.state.put_block_height(0).await;
@@ -111,6 +130,29 @@ impl Component for App {
 
     #[instrument(skip(self, tx))]
     async fn check_tx_stateful(&self, tx: &Transaction) -> Result<()> {
+        // The transaction must be bound to this chain, so that it can't be replayed from another
+        // chain (such as an earlier testnet), and it must not have expired.
+        let body = tx.transaction_body();
+        let chain_id = self.state.get_chain_id().await?;
+        if body.chain_id != chain_id {
+            return Err(anyhow!(
+                "transaction is for chain {:?}, not {:?}",
+                body.chain_id,
+                chain_id
+            ));
+        }
+        // In the mempool, the state is that of the last committed block rather than the block
+        // the transaction will be included in, so a transaction expiring at the next height is
+        // accepted there, and rejected when the block is executed.
+        let height = self.state.get_block_height().await?;
+        if body.expiry_height != 0 && u64::from(body.expiry_height) < height {
+            return Err(anyhow!(
+                "transaction expired at height {}, and the current height is {}",
+                body.expiry_height,
+                height
+            ));
+        }
+
         self.staking.check_tx_stateful(tx).await?;
         self.ibc.check_tx_stateful(tx).await?;
 
@@ -136,3 +178,45 @@ impl Component for App {
         self.shielded_pool.end_block(end_block).await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use penumbra_chain::genesis;
+    use penumbra_component::Component;
+    use penumbra_crypto::merkle::{NoteCommitmentTree, TreeExt};
+    use penumbra_storage::Storage;
+    use penumbra_transaction::Transaction;
+    use rand_core::OsRng;
+
+    use super::App;
+
+    /// Returns in-memory storage and an app which has run the default genesis for `chain_id`,
+    /// without committing it.
+    async fn genesis(chain_id: &str) -> (Storage, App) {
+        let storage = Storage::in_memory();
+        let mut app = App::new(storage.state().await.unwrap()).await;
+        let mut app_state = genesis::AppState::default();
+        app_state.chain_params.chain_id = chain_id.to_string();
+        app.init_chain(&app_state).await;
+        (storage, app)
+    }
+
+    #[tokio::test]
+    async fn failed_transaction_leaves_no_writes() {
+        let (storage, mut app) = genesis("penumbra-test").await;
+
+        let tx = Transaction::build_with_root(NoteCommitmentTree::new(0).root2())
+            .set_fee(0)
+            .set_chain_id("penumbra-other".to_string())
+            .finalize(&mut OsRng)
+            .unwrap();
+        assert!(app.deliver_tx(&tx).await.is_err());
+
+        // The state commits, so no transaction layer was left open, and it has the same root as a
+        // chain which never saw the transaction.
+        let (root, _) = app.commit(storage).await.unwrap();
+        let (reference_storage, mut reference) = genesis("penumbra-test").await;
+        let (reference_root, _) = reference.commit(reference_storage).await.unwrap();
+        assert_eq!(root, reference_root);
+    }
+}
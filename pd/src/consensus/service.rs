@@ -5,17 +5,20 @@ use std::{
 };
 
 use futures::FutureExt;
-use penumbra_storage::Storage;
+use penumbra_storage::{RetentionPolicy, Storage};
 use tendermint::{
     abci::{ConsensusRequest, ConsensusResponse},
     block,
 };
-use tokio::sync::{mpsc, oneshot, watch};
-use tokio_util::sync::PollSender;
+use tokio::{
+    sync::{mpsc, oneshot, watch},
+    task::JoinHandle,
+};
+use tokio_util::sync::{CancellationToken, PollSender};
 use tower_abci::BoxError;
 
 use super::{Message, Worker};
-use crate::RequestExt;
+use crate::{Events, Indexer, RequestExt};
 
 #[derive(Clone)]
 pub struct Consensus {
@@ -23,7 +26,21 @@ pub struct Consensus {
 }
 
 impl Consensus {
-    pub async fn new(storage: Storage) -> anyhow::Result<(Self, watch::Receiver<block::Height>)> {
+    /// Creates the consensus service, spawning a worker to execute its requests.
+    ///
+    /// When `shutdown` is cancelled, the worker finishes the request it's executing, flushes the
+    /// storage, and exits, completing the returned handle.
+    pub async fn new(
+        storage: Storage,
+        retention: RetentionPolicy,
+        indexer: Option<Indexer>,
+        events: Events,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<(
+        Self,
+        watch::Receiver<block::Height>,
+        JoinHandle<anyhow::Result<()>>,
+    )> {
         let (queue_tx, queue_rx) = mpsc::channel(10);
         let initial_height = match storage.latest_version().await? {
             Some(version) => version.try_into().unwrap(),
@@ -31,15 +48,20 @@ impl Consensus {
         };
         let (height_tx, height_rx) = watch::channel(initial_height);
 
-        tokio::task::Builder::new()
-            .name("consensus::Worker")
-            .spawn(Worker::new(storage, queue_rx, height_tx).await?.run());
+        let worker = tokio::task::Builder::new().name("consensus::Worker").spawn(
+            Worker::new(
+                storage, retention, indexer, events, queue_rx, height_tx, shutdown,
+            )
+            .await?
+            .run(),
+        );
 
         Ok((
             Self {
                 queue: PollSender::new(queue_tx),
             },
             height_rx,
+            worker,
         ))
     }
 }
@@ -1,51 +1,103 @@
+use std::time::Instant;
+
 use anyhow::{anyhow, Result};
+use metrics::{counter, gauge, histogram, increment_counter};
 
 use penumbra_proto::Protobuf;
 
 use penumbra_chain::genesis;
 use penumbra_component::Component;
-use penumbra_storage::Storage;
+use penumbra_storage::{RetentionPolicy, Storage};
 use penumbra_transaction::Transaction;
 use tendermint::{
     abci::{self, ConsensusRequest as Request, ConsensusResponse as Response},
     block,
 };
 use tokio::sync::{mpsc, watch};
+use tokio_util::sync::CancellationToken;
 use tracing::Instrument;
 
 use super::Message;
-use crate::App;
+use crate::{
+    indexer::BlockIndex,
+    pd_metrics::{
+        BLOCK_EXECUTION_DURATION, BLOCK_HEIGHT, BLOCK_TRANSACTIONS, NOTES, SPENT_NULLIFIERS,
+        STATE_SIZE, TRANSACTIONS, TRANSACTIONS_REJECTED,
+    },
+    App, Events, Indexer,
+};
+
+/// How often (in blocks) to prune old versions of the state, if pruning is enabled.
+const PRUNE_INTERVAL: u64 = 100;
 
 pub struct Worker {
     queue: mpsc::Receiver<Message>,
     height_tx: watch::Sender<block::Height>,
+    shutdown: CancellationToken,
     storage: Storage,
+    retention: RetentionPolicy,
     app: App,
+    /// The latest version of the state when the app was created or last committed.
+    version: Option<jmt::Version>,
+    /// The indexer, if enabled, and the subscribers to events.
+    indexer: Option<Indexer>,
+    events: Events,
+    /// The contents of the current block, to be indexed and published as events.
+    block_index: BlockIndex,
+    /// When the current block began, and how many transactions it has executed so far.
+    block_start: Option<Instant>,
+    block_transactions: u64,
 }
 
 impl Worker {
     pub async fn new(
         storage: Storage,
+        retention: RetentionPolicy,
+        indexer: Option<Indexer>,
+        events: Events,
         queue: mpsc::Receiver<Message>,
         height_tx: watch::Sender<block::Height>,
+        shutdown: CancellationToken,
     ) -> Result<Self> {
         let app = App::new(storage.state().await?).await;
+        let version = storage.latest_version().await?;
 
         Ok(Self {
             queue,
             height_tx,
+            shutdown,
             storage,
+            retention,
             app,
+            version,
+            indexer,
+            events,
+            block_index: Default::default(),
+            block_start: None,
+            block_transactions: 0,
         })
     }
 
+    /// Executes consensus requests until the queue is closed or shutdown is requested.
+    ///
+    /// Shutdown is only checked between requests, so a block being committed is always finished
+    /// (and a partially executed block is discarded, to be replayed by Tendermint on restart).
+    /// The storage is flushed before returning.
     pub async fn run(mut self) -> Result<()> {
-        while let Some(Message {
-            req,
-            rsp_sender,
-            span,
-        }) = self.queue.recv().await
-        {
+        loop {
+            let Message {
+                req,
+                rsp_sender,
+                span,
+            } = tokio::select! {
+                biased;
+                _ = self.shutdown.cancelled() => break,
+                message = self.queue.recv() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+            };
+
             // The send only fails if the receiver was dropped, which happens
             // if the caller didn't propagate the message back to tendermint
             // for some reason -- but that's not our problem.
@@ -65,11 +117,14 @@ impl Worker {
                 Request::DeliverTx(deliver_tx) => {
                     Response::DeliverTx(match self.deliver_tx(deliver_tx).instrument(span).await {
                         Ok(()) => abci::response::DeliverTx::default(),
-                        Err(e) => abci::response::DeliverTx {
-                            code: 1,
-                            log: e.to_string(),
-                            ..Default::default()
-                        },
+                        Err(e) => {
+                            increment_counter!(TRANSACTIONS_REJECTED);
+                            abci::response::DeliverTx {
+                                code: 1,
+                                log: e.to_string(),
+                                ..Default::default()
+                            }
+                        }
                     })
                 }
                 Request::EndBlock(end_block) => Response::EndBlock(
@@ -86,7 +141,9 @@ impl Worker {
                 ),
             });
         }
-        Ok(())
+
+        tracing::info!(version = ?self.version, "consensus worker stopped, flushing storage");
+        self.storage.flush().await
     }
 
     /// Initializes the chain based on the genesis data.
@@ -98,14 +155,30 @@ impl Worker {
         init_chain: abci::request::InitChain,
     ) -> Result<abci::response::InitChain> {
         tracing::info!(?init_chain);
+
+        // If the state was imported with `pd import`, the chain restarts from it, so the genesis
+        // app state isn't used.
+        if let Some(version) = self.storage.latest_version().await? {
+            let app_hash =
+                imported_app_hash(&self.storage, version, init_chain.initial_height.value())
+                    .await?;
+            tracing::info!(
+                version,
+                app_hash = %hex::encode(&app_hash),
+                "restarting chain from imported state"
+            );
+            // Returning no validators tells Tendermint to use the ones in its genesis file, which
+            // must be the validator set of the imported state.
+            return Ok(abci::response::InitChain {
+                consensus_params: Some(init_chain.consensus_params),
+                validators: Vec::new(),
+                app_hash: app_hash.into(),
+            });
+        }
+
         // Note that errors cannot be handled in InitChain, the application must crash.
         let app_state: genesis::AppState = serde_json::from_slice(&init_chain.app_state_bytes)
             .expect("can parse app_state in genesis file");
-
-        // Check that we haven't got a duplicated InitChain message for some reason:
-        if self.storage.latest_version().await?.is_some() {
-            return Err(anyhow!("database already initialized"));
-        }
         self.app.init_chain(&app_state).await;
 
         // Extract the Tendermint validators from the app state
@@ -117,7 +190,8 @@ impl Worker {
         let validators = self.app.tm_validator_updates().await?;
 
         // Note: App::commit resets internal components, so we don't need to do that ourselves.
-        let (jmt_root, _) = self.app.commit(self.storage.clone()).await?;
+        let (jmt_root, version) = self.app.commit(self.storage.clone()).await?;
+        self.version = Some(version);
 
         let app_hash = jmt_root.0.to_vec();
 
@@ -139,6 +213,22 @@ impl Worker {
         &mut self,
         begin_block: abci::request::BeginBlock,
     ) -> Result<abci::response::BeginBlock> {
+        self.block_start = Some(Instant::now());
+        self.block_transactions = 0;
+        self.block_index = BlockIndex::new(begin_block.header.height.value());
+
+        // If state sync restored the state from a snapshot, the app was created over the empty
+        // state before the snapshot was imported, so recreate it over the restored state.
+        let latest = self.storage.latest_version().await?;
+        if latest != self.version {
+            tracing::info!(?latest, "state was restored from a snapshot, reloading app");
+            self.app = App::new(self.storage.state().await?).await;
+            self.version = latest;
+            if let Some(version) = latest {
+                let _ = self.height_tx.send(version.try_into().unwrap());
+            }
+        }
+
         self.app.begin_block(&begin_block).await;
         // TODO(events): consider creating + returning Events to Tendermint here.
         Ok(Default::default())
@@ -153,15 +243,23 @@ impl Worker {
     /// so it is not safe to assume all checks performed in `CheckTx` were done.
     async fn deliver_tx(&mut self, deliver_tx: abci::request::DeliverTx) -> Result<()> {
         // Verify the transaction is well-formed...
+        let tx_bytes = deliver_tx.tx.clone();
         let transaction = Transaction::decode(deliver_tx.tx)?;
         // ... and statelessly valid...
         App::check_tx_stateless(&transaction)?;
-        // ... and statefully valid.
-        self.app.check_tx_stateful(&transaction).await?;
-        // Now execute the transaction. It's important to panic on error here, since if
-        // we fail to execute the transaction here, it's because of an internal
-        // error and we may have left the chain in an inconsistent state.
-        self.app.execute_tx(&transaction).await;
+        // ... and statefully valid, and if so, execute it. Execution panics on error, since if
+        // we fail to execute the transaction here, it's because of an internal error and we may
+        // have left the chain in an inconsistent state.
+        self.app.deliver_tx(&transaction).await?;
+
+        self.block_transactions += 1;
+        increment_counter!(TRANSACTIONS);
+        counter!(NOTES, transaction.output_bodies().len() as u64);
+        counter!(
+            SPENT_NULLIFIERS,
+            transaction.spent_nullifiers().len() as u64
+        );
+        self.block_index.push_transaction(tx_bytes, transaction);
         Ok(())
     }
 
@@ -181,6 +279,8 @@ impl Worker {
             ?validator_updates,
             "sending validator updates to tendermint"
         );
+        self.block_index
+            .set_validator_updates(validator_updates.clone());
 
         Ok(abci::response::EndBlock {
             validator_updates,
@@ -193,22 +293,132 @@ impl Worker {
         // Begin sidecar code
 
         // Note: App::commit resets internal components, so we don't need to do that ourselves.
-        let (jmt_root, _) = self.app.commit(self.storage.clone()).await?;
+        let (jmt_root, version) = self.app.commit(self.storage.clone()).await?;
+        self.version = Some(version);
         let app_hash = jmt_root.0.to_vec();
-        let _ = self.height_tx.send(
-            self.storage
-                .latest_version()
-                .await?
-                .expect("just committed version")
-                .try_into()
-                .unwrap(),
-        );
+        let _ = self.height_tx.send(version.try_into().unwrap());
 
         tracing::info!(app_hash = ?hex::encode(&app_hash), "finished block commit");
 
+        if let Some(start) = self.block_start.take() {
+            histogram!(BLOCK_EXECUTION_DURATION, start.elapsed());
+            histogram!(BLOCK_TRANSACTIONS, self.block_transactions as f64);
+        }
+        gauge!(BLOCK_HEIGHT, version as f64);
+
+        let block_index = std::mem::take(&mut self.block_index);
+        self.events.publish_block(&block_index, &app_hash);
+        if let Some(indexer) = &self.indexer {
+            // The index isn't part of consensus, so failing to index a block shouldn't halt the
+            // chain.
+            if let Err(e) = indexer.index_block(block_index).await {
+                tracing::warn!(?e, "failed to index block");
+            }
+        }
+        match self.storage.size().await {
+            Ok(Some(size)) => gauge!(STATE_SIZE, size as f64),
+            Ok(None) => {}
+            Err(e) => tracing::warn!(?e, "failed to measure the size of the state"),
+        }
+
+        if !self.retention.is_archive() && version % PRUNE_INTERVAL == 0 {
+            self.prune_in_background(version);
+        }
+
         Ok(abci::response::Commit {
             data: app_hash.into(),
             retain_height: 0u32.into(),
         })
     }
+
+    /// Prunes the versions of the storage and the index which the retention policy no longer keeps
+    /// as of `version`, then compacts the storage to reclaim the space freed, all without blocking
+    /// block execution.
+    ///
+    /// Pruning is an optimization, so failing to prune shouldn't halt the chain. If the previous
+    /// prune is still running, this one fails, and the versions are pruned next time.
+    fn prune_in_background(&self, version: u64) {
+        let storage = self.storage.clone();
+        let indexer = self.indexer.clone();
+        let retention = self.retention;
+        tokio::task::Builder::new()
+            .name("prune_storage")
+            .spawn(async move {
+                let deleted = match storage.prune(&retention).await {
+                    Ok(deleted) => deleted,
+                    Err(e) => {
+                        tracing::warn!(?e, "failed to prune old versions of the state");
+                        0
+                    }
+                };
+                if let Some(indexer) = indexer {
+                    if let Err(e) = indexer.prune(&retention, version).await {
+                        tracing::warn!(?e, "failed to prune the index");
+                    }
+                }
+                if deleted > 0 {
+                    if let Err(e) = storage.compact().await {
+                        tracing::warn!(?e, "failed to compact storage");
+                    }
+                }
+            });
+    }
+}
+
+/// Returns the app hash with which a chain restarts from state imported at `version`, checking
+/// that the genesis `initial_height` continues from it.
+///
+/// The root hash of the imported state was already checked against the one recorded in the
+/// export, and the one given to `pd import --expected-root`.
+async fn imported_app_hash(
+    storage: &Storage,
+    version: jmt::Version,
+    initial_height: u64,
+) -> Result<Vec<u8>> {
+    if initial_height != version + 1 {
+        return Err(anyhow!(
+            "state was imported at height {}, but the genesis initial_height is {} rather than {}",
+            version,
+            initial_height,
+            version + 1
+        ));
+    }
+    let root = jmt::JellyfishMerkleTree::new(storage)
+        .get_root_hash(version)
+        .await?;
+    Ok(root.0.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use penumbra_storage::Storage;
+
+    use super::imported_app_hash;
+
+    #[tokio::test]
+    async fn restart_from_imported_state() {
+        // Commit two blocks of state, and export the state as of the second.
+        let storage = Storage::in_memory();
+        let mut root = None;
+        for height in 0..2u8 {
+            let state = storage.state().await.unwrap();
+            let mut overlay = state.write().await;
+            overlay.put(format!("test/{}", height), vec![height]);
+            root = Some(overlay.commit(storage.clone()).await.unwrap().0);
+        }
+        let mut export = Vec::new();
+        storage.export_snapshot(1, &mut export).await.unwrap();
+
+        let imported = Storage::in_memory();
+        assert_eq!(imported.import_snapshot(&export[..]).await.unwrap(), 1);
+        let version = imported.latest_version().await.unwrap().unwrap();
+
+        // The chain restarts at the next height, with the exported root as its app hash.
+        assert_eq!(
+            imported_app_hash(&imported, version, 2).await.unwrap(),
+            root.unwrap().0.to_vec()
+        );
+        assert!(imported_app_hash(&imported, version, 1).await.is_err());
+        assert!(imported_app_hash(&imported, version, 3).await.is_err());
+    }
 }
@@ -0,0 +1,167 @@
+//! Events pushed to subscribers as blocks are committed, so that clients don't have to poll.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use async_stream::try_stream;
+use futures::stream::StreamExt;
+use penumbra_proto::{
+    client::subscription::{
+        event, event_subscription_server::EventSubscription, Event, NewBlock, NullifierSpent,
+        SubscribeRequest, ValidatorChange,
+    },
+    crypto::Nullifier,
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tonic::Status;
+use tracing::instrument;
+
+use crate::indexer::BlockIndex;
+
+/// The number of events buffered for each subscriber, beyond which a subscriber which hasn't
+/// received them is disconnected.
+const CAPACITY: usize = 4096;
+
+/// The publisher of events to subscribers, which also serves subscriptions over gRPC.
+#[derive(Clone, Debug)]
+pub struct Events {
+    tx: broadcast::Sender<Arc<Event>>,
+    /// The voting power of each validator in the last committed block, by consensus key.
+    powers: Arc<Mutex<BTreeMap<Vec<u8>, u64>>>,
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(CAPACITY).0,
+            powers: Default::default(),
+        }
+    }
+}
+
+impl Events {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Publishes the events of a committed block to every subscriber.
+    ///
+    /// Since the consensus set isn't recorded before the node starts, the first block published
+    /// reports the power of every validator as a change.
+    pub fn publish_block(&self, block: &BlockIndex, app_hash: &[u8]) {
+        let height = block.height();
+        let mut events = Vec::new();
+
+        let mut transactions = 0;
+        for (tx_hash, _, transaction) in block.transactions() {
+            transactions += 1;
+            for nullifier in transaction.spent_nullifiers() {
+                events.push(event::Event::NullifierSpent(NullifierSpent {
+                    nullifier: Some(nullifier.into()),
+                    tx_hash: tx_hash.to_vec(),
+                }));
+            }
+        }
+
+        let mut powers = self.powers.lock().unwrap();
+        for update in block.validator_updates() {
+            let consensus_key = update.pub_key.to_bytes();
+            let power = update.power.value();
+            if powers.insert(consensus_key.clone(), power).unwrap_or(0) != power {
+                events.push(event::Event::ValidatorChange(ValidatorChange {
+                    consensus_key,
+                    power,
+                }));
+            }
+        }
+        drop(powers);
+
+        events.push(event::Event::NewBlock(NewBlock {
+            app_hash: app_hash.to_vec(),
+            transactions,
+        }));
+
+        for event in events {
+            // Sending only fails if there are no subscribers.
+            let _ = self.tx.send(Arc::new(Event {
+                height,
+                event: Some(event),
+            }));
+        }
+    }
+}
+
+/// The events a subscriber asked for.
+struct Filter {
+    new_blocks: bool,
+    spent_nullifiers: bool,
+    validator_changes: bool,
+    nullifiers: HashSet<Vec<u8>>,
+}
+
+impl Filter {
+    fn new(request: SubscribeRequest) -> Self {
+        let all = !(request.new_blocks || request.spent_nullifiers || request.validator_changes);
+        Self {
+            new_blocks: all || request.new_blocks,
+            spent_nullifiers: all || request.spent_nullifiers,
+            validator_changes: all || request.validator_changes,
+            nullifiers: request
+                .nullifiers
+                .into_iter()
+                .map(|nullifier| nullifier.inner)
+                .collect(),
+        }
+    }
+
+    fn matches(&self, event: &Event) -> bool {
+        match &event.event {
+            Some(event::Event::NewBlock(_)) => self.new_blocks,
+            Some(event::Event::NullifierSpent(NullifierSpent {
+                nullifier: Some(Nullifier { inner }),
+                ..
+            })) => {
+                self.spent_nullifiers
+                    && (self.nullifiers.is_empty() || self.nullifiers.contains(inner))
+            }
+            Some(event::Event::ValidatorChange(_)) => self.validator_changes,
+            _ => false,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl EventSubscription for Events {
+    type SubscribeStream = Pin<Box<dyn futures::Stream<Item = Result<Event, Status>> + Send>>;
+
+    #[instrument(skip(self, request))]
+    async fn subscribe(
+        &self,
+        request: tonic::Request<SubscribeRequest>,
+    ) -> Result<tonic::Response<Self::SubscribeStream>, Status> {
+        let filter = Filter::new(request.into_inner());
+        let mut rx = self.tx.subscribe();
+
+        let events = try_stream! {
+            loop {
+                match rx.recv().await {
+                    Ok(event) if filter.matches(&event) => yield Event::clone(&event),
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(missed)) => {
+                        Err::<(), _>(Status::data_loss(format!(
+                            "subscriber fell behind and missed {} events",
+                            missed
+                        )))?;
+                    }
+                    // The node is shutting down.
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        };
+
+        Ok(tonic::Response::new(events.boxed()))
+    }
+}
@@ -0,0 +1,202 @@
+//! HTTP endpoints reporting whether `pd` is alive and ready to serve, for orchestrators like
+//! Kubernetes.
+//!
+//! - `/healthz` succeeds as long as the storage can be read, so a failing check means `pd` should
+//!   be restarted;
+//! - `/readyz` additionally requires that Tendermint is reachable and has caught up with the
+//!   network, and that `pd` isn't shutting down, so a failing check means `pd` shouldn't be sent
+//!   traffic.
+//!
+//! Both respond with a JSON report of the checks, with status 200 if they pass and 503 otherwise.
+
+use std::{convert::Infallible, net::SocketAddr, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use hyper::{
+    client::HttpConnector,
+    service::{make_service_fn, service_fn},
+    Body, Client, Method, Request, Response, Server, StatusCode, Uri,
+};
+use penumbra_storage::Storage;
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+
+/// How long to wait for Tendermint to report its status.
+const TENDERMINT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The health and readiness endpoints.
+#[derive(Clone, Debug)]
+pub struct Health {
+    storage: Storage,
+    /// The `/status` endpoint of Tendermint's RPC server.
+    tendermint_status: Uri,
+    client: Client<HttpConnector>,
+    shutdown: CancellationToken,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    ok: bool,
+    shutting_down: bool,
+    storage: StorageReport,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tendermint: Option<TendermintReport>,
+}
+
+#[derive(Debug, Serialize)]
+struct StorageReport {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct TendermintReport {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    catching_up: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latest_block_height: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Health {
+    /// Creates the endpoints, which query Tendermint's RPC server at `tendermint_rpc` and report
+    /// `pd` as unready once `shutdown` is cancelled.
+    pub fn new(
+        storage: Storage,
+        tendermint_rpc: &str,
+        shutdown: CancellationToken,
+    ) -> Result<Self> {
+        let tendermint_status = format!("{}/status", tendermint_rpc.trim_end_matches('/'))
+            .parse()
+            .with_context(|| format!("invalid Tendermint RPC address {:?}", tendermint_rpc))?;
+
+        Ok(Self {
+            storage,
+            tendermint_status,
+            client: Client::new(),
+            shutdown,
+        })
+    }
+
+    /// Serves the endpoints on `addr`.
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        let health = Arc::new(self);
+        let make_service = make_service_fn(move |_| {
+            let health = health.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let health = health.clone();
+                    async move { Ok::<_, Infallible>(health.respond(req).await) }
+                }))
+            }
+        });
+
+        Server::try_bind(&addr)?.serve(make_service).await?;
+        Ok(())
+    }
+
+    async fn respond(&self, req: Request<Body>) -> Response<Body> {
+        let report = match (req.method(), req.uri().path()) {
+            (&Method::GET, "/healthz") => self.check(false).await,
+            (&Method::GET, "/readyz") => self.check(true).await,
+            _ => {
+                return Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(Body::empty())
+                    .expect("response is valid")
+            }
+        };
+
+        let status = if report.ok {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        Response::builder()
+            .status(status)
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                serde_json::to_vec(&report).expect("report can be serialized"),
+            ))
+            .expect("response is valid")
+    }
+
+    /// Checks the storage and, for readiness, Tendermint and whether `pd` is shutting down.
+    async fn check(&self, ready: bool) -> Report {
+        let storage = match self.storage.latest_version().await {
+            Ok(height) => StorageReport {
+                ok: true,
+                height,
+                error: None,
+            },
+            Err(e) => StorageReport {
+                ok: false,
+                height: None,
+                error: Some(e.to_string()),
+            },
+        };
+        let shutting_down = self.shutdown.is_cancelled();
+
+        if !ready {
+            return Report {
+                ok: storage.ok,
+                shutting_down,
+                storage,
+                tendermint: None,
+            };
+        }
+
+        let tendermint = match self.tendermint_sync_info().await {
+            Ok((catching_up, latest_block_height)) => TendermintReport {
+                ok: !catching_up,
+                catching_up: Some(catching_up),
+                latest_block_height: Some(latest_block_height),
+                error: None,
+            },
+            Err(e) => TendermintReport {
+                ok: false,
+                catching_up: None,
+                latest_block_height: None,
+                error: Some(e.to_string()),
+            },
+        };
+
+        Report {
+            ok: storage.ok && tendermint.ok && !shutting_down,
+            shutting_down,
+            storage,
+            tendermint: Some(tendermint),
+        }
+    }
+
+    /// Asks Tendermint whether it's catching up with the network, and the height of its latest
+    /// block.
+    async fn tendermint_sync_info(&self) -> Result<(bool, u64)> {
+        let rsp = tokio::time::timeout(
+            TENDERMINT_TIMEOUT,
+            self.client.get(self.tendermint_status.clone()),
+        )
+        .await
+        .map_err(|_| anyhow!("timed out connecting to Tendermint"))?
+        .context("could not connect to Tendermint")?;
+        let body = hyper::body::to_bytes(rsp.into_body()).await?;
+        let status: serde_json::Value = serde_json::from_slice(&body)?;
+
+        let sync_info = &status["result"]["sync_info"];
+        let catching_up = sync_info["catching_up"]
+            .as_bool()
+            .ok_or_else(|| anyhow!("Tendermint status is missing catching_up"))?;
+        // Tendermint encodes heights as strings.
+        let latest_block_height = sync_info["latest_block_height"]
+            .as_str()
+            .and_then(|height| height.parse().ok())
+            .ok_or_else(|| anyhow!("Tendermint status is missing latest_block_height"))?;
+
+        Ok((catching_up, latest_block_height))
+    }
+}
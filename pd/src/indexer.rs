@@ -0,0 +1,425 @@
+//! An optional index of the contents of each block, kept in a SQLite database alongside the chain
+//! state, so that transactions can be looked up by hash or nullifier, and blocks by height.
+//!
+//! The index is written after each block is committed, and isn't part of consensus: a node which
+//! didn't index a block (for instance, because its indexer was only enabled later) simply has no
+//! record of it.
+
+use std::{path::Path, pin::Pin};
+
+use anyhow::Result;
+use async_stream::try_stream;
+use bytes::Bytes;
+use futures::stream::{StreamExt, TryStreamExt};
+use penumbra_proto::{
+    self as proto,
+    client::index::{
+        index_query_server::IndexQuery, BlockRangeRequest, IndexedBlock, IndexedTransaction,
+        TransactionByHashRequest, ValidatorPowerChange,
+    },
+    crypto::{NoteCommitment, Nullifier},
+    Message,
+};
+use penumbra_storage::RetentionPolicy;
+use penumbra_transaction::Transaction;
+use sha2::{Digest, Sha256};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    Pool, Row, Sqlite,
+};
+use tendermint::abci::types::ValidatorUpdate;
+use tonic::Status;
+use tracing::instrument;
+
+#[derive(Clone, Debug)]
+pub struct Indexer {
+    pool: Pool<Sqlite>,
+}
+
+/// The contents of a block to be indexed, accumulated as the block is executed.
+#[derive(Debug, Default)]
+pub struct BlockIndex {
+    height: u64,
+    transactions: Vec<(Bytes, Transaction)>,
+    validator_updates: Vec<ValidatorUpdate>,
+}
+
+impl BlockIndex {
+    pub fn new(height: u64) -> Self {
+        Self {
+            height,
+            ..Default::default()
+        }
+    }
+
+    /// Records a transaction accepted in the block, along with its encoding.
+    pub fn push_transaction(&mut self, tx_bytes: Bytes, transaction: Transaction) {
+        self.transactions.push((tx_bytes, transaction));
+    }
+
+    /// Records the validator updates returned to Tendermint at the end of the block.
+    pub fn set_validator_updates(&mut self, validator_updates: Vec<ValidatorUpdate>) {
+        self.validator_updates = validator_updates;
+    }
+
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    /// The transactions accepted in the block, in order, with the SHA-256 hashes of their
+    /// encodings and the encodings themselves.
+    pub fn transactions(&self) -> impl Iterator<Item = ([u8; 32], &Bytes, &Transaction)> {
+        self.transactions.iter().map(|(tx_bytes, transaction)| {
+            let tx_hash = Sha256::digest(tx_bytes).into();
+            (tx_hash, tx_bytes, transaction)
+        })
+    }
+
+    /// The validator updates returned to Tendermint at the end of the block.
+    pub fn validator_updates(&self) -> &[ValidatorUpdate] {
+        &self.validator_updates
+    }
+}
+
+impl Indexer {
+    /// Opens the index database at `path`, creating it if it does not exist.
+    pub async fn load(path: &Path) -> Result<Self> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let pool = SqlitePoolOptions::new().connect_with(options).await?;
+        sqlx::migrate!("./index_migrations").run(&pool).await?;
+
+        Ok(Self { pool })
+    }
+
+    /// Writes the index of a committed block, replacing any previous index of the same height.
+    #[instrument(skip(self, block), fields(height = block.height))]
+    pub async fn index_block(&self, block: BlockIndex) -> Result<()> {
+        let height = block.height as i64;
+        let mut dbtx = self.pool.begin().await?;
+
+        // If the node restarted after indexing a block but before committing it, Tendermint
+        // replays the block, so clear out the previous index of it.
+        for table in [
+            "blocks",
+            "transactions",
+            "nullifiers",
+            "note_commitments",
+            "validator_changes",
+        ] {
+            sqlx::query(&format!("DELETE FROM {} WHERE height = ?", table))
+                .bind(height)
+                .execute(&mut dbtx)
+                .await?;
+        }
+
+        sqlx::query("INSERT INTO blocks (height) VALUES (?)")
+            .bind(height)
+            .execute(&mut dbtx)
+            .await?;
+
+        for (index, (tx_hash, tx_bytes, transaction)) in block.transactions().enumerate() {
+            let tx_hash = &tx_hash[..];
+            // A transaction could in principle be included twice, in which case we index the
+            // first occurrence.
+            sqlx::query(
+                "INSERT OR IGNORE INTO transactions (tx_hash, height, tx_index, tx_bytes) \
+                 VALUES (?, ?, ?, ?)",
+            )
+            .bind(tx_hash)
+            .bind(height)
+            .bind(index as i64)
+            .bind(tx_bytes.as_ref())
+            .execute(&mut dbtx)
+            .await?;
+
+            for nullifier in transaction.spent_nullifiers() {
+                sqlx::query("INSERT OR IGNORE INTO nullifiers (nullifier, height, tx_hash) VALUES (?, ?, ?)")
+                    .bind(&nullifier.to_bytes()[..])
+                    .bind(height)
+                    .bind(tx_hash)
+                    .execute(&mut dbtx)
+                    .await?;
+            }
+
+            for body in transaction.output_bodies() {
+                let note_commitment: [u8; 32] = body.note_commitment.into();
+                sqlx::query(
+                    "INSERT OR IGNORE INTO note_commitments (note_commitment, height, tx_hash) \
+                     VALUES (?, ?, ?)",
+                )
+                .bind(&note_commitment[..])
+                .bind(height)
+                .bind(tx_hash)
+                .execute(&mut dbtx)
+                .await?;
+            }
+        }
+
+        // Tendermint is sent the power of every validator at the end of each block, so only
+        // record the ones which differ from the last recorded power.
+        for update in block.validator_updates() {
+            let consensus_key = update.pub_key.to_bytes();
+            let power = update.power.value() as i64;
+            let previous: Option<i64> = sqlx::query(
+                "SELECT power FROM validator_changes \
+                 WHERE consensus_key = ? AND height < ? \
+                 ORDER BY height DESC LIMIT 1",
+            )
+            .bind(&consensus_key)
+            .bind(height)
+            .fetch_optional(&mut dbtx)
+            .await?
+            .map(|row| row.get("power"));
+
+            if previous.unwrap_or(0) != power {
+                sqlx::query(
+                    "INSERT INTO validator_changes (height, consensus_key, power) VALUES (?, ?, ?)",
+                )
+                .bind(height)
+                .bind(&consensus_key)
+                .bind(power)
+                .execute(&mut dbtx)
+                .await?;
+            }
+        }
+
+        dbtx.commit().await?;
+        tracing::debug!(transactions = block.transactions.len(), "indexed block");
+        Ok(())
+    }
+
+    /// Deletes the index of the blocks whose versions of the state are not retained by `policy`,
+    /// so that the index covers the same history as the state, returning the number of blocks
+    /// deleted.
+    #[instrument(skip(self))]
+    pub async fn prune(&self, policy: &RetentionPolicy, latest: u64) -> Result<u64> {
+        let before = match policy.prune_before(latest) {
+            Some(before) => before as i64,
+            None => return Ok(0),
+        };
+        let keep_every = policy.keep_every.unwrap_or(0) as i64;
+        let mut dbtx = self.pool.begin().await?;
+
+        let mut deleted = 0;
+        for table in [
+            "blocks",
+            "transactions",
+            "nullifiers",
+            "note_commitments",
+            "validator_changes",
+        ] {
+            let result = sqlx::query(&format!(
+                "DELETE FROM {} WHERE height < ? AND (? = 0 OR height % ? != 0)",
+                table
+            ))
+            .bind(before)
+            .bind(keep_every)
+            .bind(keep_every)
+            .execute(&mut dbtx)
+            .await?;
+            if table == "blocks" {
+                deleted = result.rows_affected();
+            }
+        }
+
+        dbtx.commit().await?;
+        tracing::debug!(before, deleted, "pruned index");
+        Ok(deleted)
+    }
+
+    /// The height of the latest indexed block, if any.
+    pub async fn latest_height(&self) -> Result<Option<u64>> {
+        let height: Option<i64> = sqlx::query("SELECT MAX(height) AS height FROM blocks")
+            .fetch_one(&self.pool)
+            .await?
+            .get("height");
+        Ok(height.map(|height| height as u64))
+    }
+
+    /// Looks up an accepted transaction by the hash of its encoding.
+    pub async fn transaction_by_hash(&self, tx_hash: &[u8]) -> Result<Option<IndexedTransaction>> {
+        sqlx::query(
+            "SELECT tx_hash, height, tx_index, tx_bytes FROM transactions WHERE tx_hash = ?",
+        )
+        .bind(tx_hash)
+        .fetch_optional(&self.pool)
+        .await?
+        .map(|row| indexed_transaction(&row))
+        .transpose()
+    }
+
+    /// Looks up the accepted transaction which revealed the given nullifier.
+    pub async fn transaction_by_nullifier(
+        &self,
+        nullifier: &[u8],
+    ) -> Result<Option<IndexedTransaction>> {
+        let tx_hash: Option<Vec<u8>> =
+            sqlx::query("SELECT tx_hash FROM nullifiers WHERE nullifier = ?")
+                .bind(nullifier)
+                .fetch_optional(&self.pool)
+                .await?
+                .map(|row| row.get("tx_hash"));
+
+        match tx_hash {
+            Some(tx_hash) => self.transaction_by_hash(&tx_hash).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the index of the block at `height`, if it was indexed.
+    pub async fn block(&self, height: u64) -> Result<Option<IndexedBlock>> {
+        let height = height as i64;
+        let indexed = sqlx::query("SELECT height FROM blocks WHERE height = ?")
+            .bind(height)
+            .fetch_optional(&self.pool)
+            .await?;
+        if indexed.is_none() {
+            return Ok(None);
+        }
+
+        let transactions = sqlx::query(
+            "SELECT tx_hash, height, tx_index, tx_bytes FROM transactions \
+             WHERE height = ? ORDER BY tx_index",
+        )
+        .bind(height)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(indexed_transaction)
+        .collect::<Result<_>>()?;
+
+        let nullifiers =
+            sqlx::query("SELECT nullifier FROM nullifiers WHERE height = ? ORDER BY rowid")
+                .bind(height)
+                .fetch_all(&self.pool)
+                .await?
+                .iter()
+                .map(|row| Nullifier {
+                    inner: row.get("nullifier"),
+                })
+                .collect();
+
+        let note_commitments = sqlx::query(
+            "SELECT note_commitment FROM note_commitments WHERE height = ? ORDER BY rowid",
+        )
+        .bind(height)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| NoteCommitment {
+            inner: row.get("note_commitment"),
+        })
+        .collect();
+
+        let validator_changes = sqlx::query(
+            "SELECT consensus_key, power FROM validator_changes WHERE height = ? ORDER BY rowid",
+        )
+        .bind(height)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| ValidatorPowerChange {
+            consensus_key: row.get("consensus_key"),
+            power: row.get::<i64, _>("power") as u64,
+        })
+        .collect();
+
+        Ok(Some(IndexedBlock {
+            height: height as u64,
+            transactions,
+            nullifiers,
+            note_commitments,
+            validator_changes,
+        }))
+    }
+}
+
+fn indexed_transaction(row: &sqlx::sqlite::SqliteRow) -> Result<IndexedTransaction> {
+    let tx_bytes: Vec<u8> = row.get("tx_bytes");
+    Ok(IndexedTransaction {
+        height: row.get::<i64, _>("height") as u64,
+        index: row.get::<i64, _>("tx_index") as u32,
+        tx_hash: row.get("tx_hash"),
+        transaction: Some(proto::transaction::Transaction::decode(
+            tx_bytes.as_slice(),
+        )?),
+    })
+}
+
+#[tonic::async_trait]
+impl IndexQuery for Indexer {
+    type BlockRangeStream =
+        Pin<Box<dyn futures::Stream<Item = Result<IndexedBlock, tonic::Status>> + Send>>;
+
+    #[instrument(skip(self, request))]
+    async fn transaction_by_hash(
+        &self,
+        request: tonic::Request<TransactionByHashRequest>,
+    ) -> Result<tonic::Response<IndexedTransaction>, Status> {
+        let transaction = Indexer::transaction_by_hash(self, &request.get_ref().tx_hash)
+            .await
+            .map_err(|_| Status::unavailable("database error"))?
+            .ok_or_else(|| Status::not_found("transaction not found"))?;
+
+        Ok(tonic::Response::new(transaction))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn transaction_by_nullifier(
+        &self,
+        request: tonic::Request<Nullifier>,
+    ) -> Result<tonic::Response<IndexedTransaction>, Status> {
+        let transaction = Indexer::transaction_by_nullifier(self, &request.get_ref().inner)
+            .await
+            .map_err(|_| Status::unavailable("database error"))?
+            .ok_or_else(|| Status::not_found("nullifier not found"))?;
+
+        Ok(tonic::Response::new(transaction))
+    }
+
+    #[instrument(
+        skip(self, request),
+        fields(
+            start_height = request.get_ref().start_height,
+            end_height = request.get_ref().end_height,
+        ),
+    )]
+    async fn block_range(
+        &self,
+        request: tonic::Request<BlockRangeRequest>,
+    ) -> Result<tonic::Response<Self::BlockRangeStream>, Status> {
+        let BlockRangeRequest {
+            start_height,
+            end_height,
+        } = request.into_inner();
+
+        // Treat end_height = 0 as a request for every block indexed so far.
+        let end_height = match end_height {
+            0 => self
+                .latest_height()
+                .await
+                .map_err(|_| Status::unavailable("database error"))?
+                .map(|height| height + 1)
+                .unwrap_or(0),
+            end_height => end_height,
+        };
+
+        let indexer = self.clone();
+        let blocks = try_stream! {
+            for height in start_height..end_height {
+                // Blocks which were never indexed are skipped.
+                if let Some(block) = indexer.block(height).await? {
+                    yield block;
+                }
+            }
+        };
+
+        Ok(tonic::Response::new(
+            blocks
+                .map_err(|_: anyhow::Error| Status::unavailable("database error"))
+                .boxed(),
+        ))
+    }
+}
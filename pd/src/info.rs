@@ -5,7 +5,7 @@ use std::{
 };
 
 use futures::FutureExt;
-use penumbra_storage::{State, Storage};
+use penumbra_storage::{Snapshot, Storage};
 use tendermint::abci::{self, response::Echo, InfoRequest, InfoResponse};
 use tower_abci::BoxError;
 use tracing::Instrument;
@@ -27,8 +27,22 @@ impl Info {
         Self { storage }
     }
 
-    async fn state_tonic(&self) -> Result<State, tonic::Status> {
-        self.storage.state_tonic().await
+    /// Takes a read-only snapshot of the latest committed state, so that queries don't contend
+    /// with the consensus worker's writes.
+    async fn snapshot_tonic(&self) -> Result<Snapshot, tonic::Status> {
+        self.storage.latest_snapshot_tonic().await
+    }
+
+    /// Takes a read-only snapshot of the state as of the given block height, or of the latest
+    /// committed state if the height is 0, for queries of historical state.
+    async fn snapshot_at_tonic(&self, height: u64) -> Result<Snapshot, tonic::Status> {
+        if height == 0 {
+            return self.snapshot_tonic().await;
+        }
+        self.storage
+            .snapshot(height)
+            .await
+            .map_err(|e| tonic::Status::not_found(e.to_string()))
     }
 
     async fn info(&self, info: abci::request::Info) -> Result<abci::response::Info, anyhow::Error> {
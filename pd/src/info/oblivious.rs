@@ -39,7 +39,7 @@ impl ObliviousQuery for Info {
         &self,
         request: tonic::Request<ChainParamsRequest>,
     ) -> Result<tonic::Response<ChainParams>, Status> {
-        let state = self.state_tonic().await?;
+        let state = self.snapshot_at_tonic(request.get_ref().height).await?;
         state.check_chain_id(&request.get_ref().chain_id).await?;
 
         let chain_params = state
@@ -55,7 +55,7 @@ impl ObliviousQuery for Info {
         &self,
         request: tonic::Request<AssetListRequest>,
     ) -> Result<tonic::Response<KnownAssets>, Status> {
-        let state = self.state_tonic().await?;
+        let state = self.snapshot_tonic().await?;
         state.check_chain_id(&request.get_ref().chain_id).await?;
 
         let known_assets = state
@@ -70,7 +70,7 @@ impl ObliviousQuery for Info {
         &self,
         request: tonic::Request<ValidatorInfoRequest>,
     ) -> Result<tonic::Response<Self::ValidatorInfoStream>, Status> {
-        let state = self.state_tonic().await?;
+        let state = self.snapshot_at_tonic(request.get_ref().height).await?;
         state.check_chain_id(&request.get_ref().chain_id).await?;
 
         let validators = state
@@ -111,7 +111,7 @@ impl ObliviousQuery for Info {
         &self,
         request: tonic::Request<CompactBlockRangeRequest>,
     ) -> Result<tonic::Response<Self::CompactBlockRangeStream>, Status> {
-        let state = self.state_tonic().await?;
+        let state = self.snapshot_tonic().await?;
         state.check_chain_id(&request.get_ref().chain_id).await?;
 
         let CompactBlockRangeRequest {
@@ -145,7 +145,7 @@ impl ObliviousQuery for Info {
             for height in start_height..end_height {
                 let block = state.compact_block(height)
                     .await?
-                    .expect("compact block for in-range height must be present");
+                    .ok_or_else(|| anyhow::anyhow!("missing compact block for height {}", height))?;
                 yield block.to_proto();
             }
         };
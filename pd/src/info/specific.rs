@@ -2,11 +2,16 @@ use penumbra_chain::View as _;
 use penumbra_proto::{
     self as proto,
     chain::NoteSource,
-    client::specific::{specific_query_server::SpecificQuery, ValidatorStatusRequest},
+    client::specific::{
+        specific_query_server::SpecificQuery, AssetLookupRequest, KeyValueRequest,
+        KeyValueResponse, ValidatorStatusRequest, ValidatorUptimeRequest, ValidatorUptimeResponse,
+    },
     crypto::NoteCommitment,
+    Message,
 };
 use penumbra_shielded_pool::View as _;
 use penumbra_stake::component::View as _;
+use penumbra_storage::StateRead;
 
 use tonic::Status;
 use tracing::instrument;
@@ -26,7 +31,7 @@ impl SpecificQuery for Info {
         &self,
         request: tonic::Request<NoteCommitment>,
     ) -> Result<tonic::Response<NoteSource>, Status> {
-        let state = self.state_tonic().await?;
+        let state = self.snapshot_tonic().await?;
         let cm = request
             .into_inner()
             .try_into()
@@ -46,7 +51,7 @@ impl SpecificQuery for Info {
         &self,
         request: tonic::Request<ValidatorStatusRequest>,
     ) -> Result<tonic::Response<proto::stake::ValidatorStatus>, Status> {
-        let state = self.state_tonic().await?;
+        let state = self.snapshot_at_tonic(request.get_ref().height).await?;
         state.check_chain_id(&request.get_ref().chain_id).await?;
 
         let id = request
@@ -65,12 +70,46 @@ impl SpecificQuery for Info {
         Ok(tonic::Response::new(status.into()))
     }
 
+    #[instrument(skip(self, request))]
+    async fn validator_uptime(
+        &self,
+        request: tonic::Request<ValidatorUptimeRequest>,
+    ) -> Result<tonic::Response<ValidatorUptimeResponse>, Status> {
+        let state = self.snapshot_at_tonic(request.get_ref().height).await?;
+        state.check_chain_id(&request.get_ref().chain_id).await?;
+
+        let id = request
+            .into_inner()
+            .identity_key
+            .ok_or_else(|| Status::invalid_argument("missing identity key"))?
+            .try_into()
+            .map_err(|_| Status::invalid_argument("invalid identity key"))?;
+
+        // Uptime is only tracked once a validator has entered the consensus set.
+        let uptime = state
+            .validator_uptime(&id)
+            .await
+            .map_err(|_| Status::unavailable("database error"))?
+            .ok_or_else(|| Status::not_found("validator has no uptime record"))?;
+        let missed_blocks_maximum = state
+            .missed_blocks_maximum()
+            .await
+            .map_err(|_| Status::unavailable("database error"))?;
+
+        Ok(tonic::Response::new(ValidatorUptimeResponse {
+            as_of_block_height: uptime.as_of_block_height(),
+            window_len: uptime.window_len() as u32,
+            missed_blocks: uptime.num_missed_blocks() as u32,
+            missed_blocks_maximum,
+        }))
+    }
+
     #[instrument(skip(self, request))]
     async fn next_validator_rate(
         &self,
         request: tonic::Request<proto::crypto::IdentityKey>,
     ) -> Result<tonic::Response<proto::stake::RateData>, Status> {
-        let state = self.state_tonic().await?;
+        let state = self.snapshot_tonic().await?;
         let identity_key = request
             .into_inner()
             .try_into()
@@ -86,4 +125,62 @@ impl SpecificQuery for Info {
             None => Err(Status::not_found("next validator rate not found")),
         }
     }
+
+    #[instrument(skip(self, request))]
+    async fn asset_lookup(
+        &self,
+        request: tonic::Request<AssetLookupRequest>,
+    ) -> Result<tonic::Response<proto::crypto::Denom>, Status> {
+        let state = self.snapshot_tonic().await?;
+        state.check_chain_id(&request.get_ref().chain_id).await?;
+
+        let id = request
+            .into_inner()
+            .asset_id
+            .ok_or_else(|| Status::invalid_argument("missing asset id"))?
+            .try_into()
+            .map_err(|_| Status::invalid_argument("invalid asset id"))?;
+
+        let denom = state
+            .denom_by_asset(&id)
+            .await
+            .map_err(|_| Status::unavailable("database error"))?
+            .ok_or_else(|| Status::not_found("asset not found"))?;
+
+        Ok(tonic::Response::new(denom.into()))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn key_value(
+        &self,
+        request: tonic::Request<KeyValueRequest>,
+    ) -> Result<tonic::Response<KeyValueResponse>, Status> {
+        let request = request.into_inner();
+        let state = self.snapshot_at_tonic(request.height).await?;
+        state.check_chain_id(&request.chain_id).await?;
+
+        let value = state
+            .get_bytes(&request.key)
+            .await
+            .map_err(|_| Status::unavailable("database error"))?
+            .unwrap_or_default();
+
+        // The snapshot keeps its version from being pruned while the proof is computed.
+        let proof = if request.proof {
+            let (_, proof) = self
+                .storage
+                .get_with_proof(&request.key, state.version())
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?;
+            proof.encode_to_vec()
+        } else {
+            Vec::new()
+        };
+
+        Ok(tonic::Response::new(KeyValueResponse {
+            value,
+            height: state.version(),
+            proof,
+        }))
+    }
 }
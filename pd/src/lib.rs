@@ -4,7 +4,11 @@
 #![recursion_limit = "512"]
 #![allow(clippy::clone_on_copy)]
 
+mod access;
 mod consensus;
+mod events;
+mod health;
+mod indexer;
 mod info;
 mod mempool;
 mod pd_metrics;
@@ -12,13 +16,19 @@ mod request_ext;
 mod snapshot;
 
 pub mod components;
+pub mod logging;
+pub mod migrations;
 pub mod testnet;
 
 use request_ext::RequestExt;
 
+pub use access::{AccessLayer, AccessPolicy, RateLimit};
 pub use components::App;
 pub use consensus::Consensus;
+pub use events::Events;
+pub use health::Health;
+pub use indexer::Indexer;
 pub use info::Info;
 pub use mempool::Mempool;
-pub use pd_metrics::register_all_metrics;
+pub use pd_metrics::{register_all_metrics, GrpcMetricsLayer};
 pub use snapshot::Snapshot;
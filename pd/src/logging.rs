@@ -0,0 +1,108 @@
+use std::{fs::OpenOptions, path::PathBuf, str::FromStr, sync::Mutex};
+
+use anyhow::{Context, Result};
+use structopt::StructOpt;
+use tracing_subscriber::{
+    fmt::writer::BoxMakeWriter, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer,
+};
+
+/// The format in which log messages are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines of text.
+    Pretty,
+    /// One JSON object per line, for consumption by log aggregation systems.
+    Json,
+}
+
+impl LogFormat {
+    /// The names accepted on the command line.
+    pub const VARIANTS: &'static [&'static str] = &["pretty", "json"];
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            other => Err(anyhow::anyhow!(
+                "unknown log format {:?}, expected one of {:?}",
+                other,
+                Self::VARIANTS
+            )),
+        }
+    }
+}
+
+/// Options controlling where and how log messages are written.
+#[derive(Debug, StructOpt)]
+pub struct LogOpt {
+    /// The format of log messages: `pretty` for human-readable text, or `json` for one JSON
+    /// object per line.
+    #[structopt(long, default_value = "pretty", possible_values = LogFormat::VARIANTS)]
+    pub log_format: LogFormat,
+    /// Append log messages to this file, rather than writing them to the terminal.
+    #[structopt(long)]
+    pub log_file: Option<PathBuf>,
+    /// Comma-separated verbosity directives, e.g. `info,pd=debug,penumbra_stake=trace`.
+    ///
+    /// These are applied on top of any directives in the `RUST_LOG` environment variable, so they
+    /// take precedence where the two overlap.
+    #[structopt(long)]
+    pub log_level: Option<String>,
+}
+
+impl LogOpt {
+    /// Installs the global tracing subscriber.
+    ///
+    /// Log messages are written to stdout unless a log file was specified. The subscriber also
+    /// serves the `tokio-console` instrumentation, which is filtered separately from the logs.
+    pub fn init(&self) -> Result<()> {
+        let writer = match &self.log_file {
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("cannot open log file {}", path.display()))?;
+                BoxMakeWriter::new(Mutex::new(file))
+            }
+            None => BoxMakeWriter::new(std::io::stdout),
+        };
+
+        let fmt_layer = tracing_subscriber::fmt::layer()
+            .with_ansi(self.log_file.is_none())
+            .with_writer(writer);
+        let fmt_layer = match self.log_format {
+            LogFormat::Pretty => fmt_layer.boxed(),
+            LogFormat::Json => fmt_layer.json().boxed(),
+        };
+
+        tracing_subscriber::registry()
+            .with(
+                console_subscriber::ConsoleLayer::builder()
+                    .with_default_env()
+                    .spawn(),
+            )
+            .with(fmt_layer.with_filter(self.filter()?))
+            .init();
+
+        Ok(())
+    }
+
+    /// Combines the `RUST_LOG` directives with the directives given on the command line.
+    fn filter(&self) -> Result<EnvFilter> {
+        let mut filter =
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("error"));
+        for directive in self.log_level.iter().flat_map(|d| d.split(',')) {
+            filter = filter.add_directive(
+                directive
+                    .parse()
+                    .with_context(|| format!("invalid log directive {:?}", directive))?,
+            );
+        }
+        Ok(filter)
+    }
+}
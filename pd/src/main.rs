@@ -1,11 +1,14 @@
 #![allow(clippy::clone_on_copy)]
 use std::{
     net::{Ipv4Addr, SocketAddr},
+    num::NonZeroU32,
     path::PathBuf,
+    str::FromStr,
 };
 
 use anyhow::Context;
 use metrics_exporter_prometheus::PrometheusBuilder;
+use pd::logging::LogOpt;
 use penumbra_chain::{genesis::Allocation, params::ChainParams};
 use penumbra_crypto::{
     keys::{SpendKey, SpendSeed},
@@ -13,13 +16,16 @@ use penumbra_crypto::{
     DelegationToken,
 };
 use penumbra_proto::client::{
+    index::index_query_server::IndexQueryServer,
     oblivious::oblivious_query_server::ObliviousQueryServer,
     specific::specific_query_server::SpecificQueryServer,
+    subscription::event_subscription_server::EventSubscriptionServer,
 };
 use penumbra_stake::{validator::Validator, FundingStream, FundingStreams};
-use penumbra_storage::Storage;
+use penumbra_storage::{RetentionPolicy, RocksDb, Storage};
 use rand_core::OsRng;
 use structopt::StructOpt;
+use tokio_util::sync::CancellationToken;
 use tonic::transport::Server;
 
 #[derive(Debug, StructOpt)]
@@ -32,6 +38,8 @@ struct Opt {
     /// Command to run.
     #[structopt(subcommand)]
     cmd: Command,
+    #[structopt(flatten)]
+    log: LogOpt,
 }
 
 #[derive(Debug, StructOpt)]
@@ -39,8 +47,65 @@ enum Command {
     /// Start running the ABCI and wallet services.
     Start {
         /// The path used to store the Rocks database.
-        #[structopt(short, long)]
-        rocks_path: PathBuf,
+        #[structopt(short, long, required_unless = "in-memory")]
+        rocks_path: Option<PathBuf>,
+        /// Keep the chain state in memory rather than in RocksDB.
+        ///
+        /// The state is discarded when pd exits, so this is only useful for ephemeral test nodes.
+        #[structopt(long, conflicts_with = "rocks-path")]
+        in_memory: bool,
+        /// Whether to keep the whole history of the chain state and the index (`archive`), or
+        /// only their recent history (`pruned`), pruning and compacting older versions
+        /// automatically.
+        ///
+        /// Historical queries for heights which a pruned node doesn't keep are refused.
+        #[structopt(long, default_value = "archive", possible_values = &["archive", "pruned"])]
+        mode: Mode,
+        /// In pruned mode, keep only this many of the most recent versions of the chain state.
+        #[structopt(long, default_value = "10000")]
+        keep_recent: u64,
+        /// In pruned mode, also keep every version at a multiple of this height.
+        #[structopt(long)]
+        keep_every: Option<u64>,
+        /// The maximum size, in bytes, of the in-memory cache of state tree nodes (0 to disable).
+        #[structopt(long, default_value = "268435456")]
+        node_cache_size: usize,
+        /// Verify the checksum of each state tree node read from RocksDB, failing with an error
+        /// if a node has been corrupted on disk.
+        #[structopt(long)]
+        verify_checksums: bool,
+        /// Index the transactions, nullifiers, note commitments, and validator changes of each
+        /// block into the SQLite database at this path, and serve queries against the index.
+        #[structopt(long, parse(from_os_str))]
+        index_path: Option<PathBuf>,
+        /// The directory in which to keep snapshots of the state, to serve to peers joining the
+        /// network with Tendermint state sync.
+        #[structopt(long, parse(from_os_str))]
+        snapshot_dir: Option<PathBuf>,
+        /// Take a snapshot of the state every this many blocks.
+        ///
+        /// By default, no snapshots are taken.
+        #[structopt(long, requires = "snapshot-dir")]
+        snapshot_interval: Option<u64>,
+        /// Limit each client IP address to this many gRPC requests per second.
+        #[structopt(long)]
+        grpc_rate_limit: Option<NonZeroU32>,
+        /// The number of gRPC requests a client can make at once, above the rate limit
+        /// [default: the rate limit].
+        #[structopt(long, requires = "grpc-rate-limit")]
+        grpc_rate_limit_burst: Option<NonZeroU32>,
+        /// Reject gRPC requests larger than this many bytes.
+        ///
+        /// By default, requests of any size are accepted.
+        #[structopt(long)]
+        grpc_max_request_size: Option<usize>,
+        /// Require gRPC requests to carry the bearer token in this file in their `authorization`
+        /// header.
+        ///
+        /// The token can also be given in the `PD_GRPC_AUTH_TOKEN` environment variable. It isn't
+        /// accepted as an argument, since arguments are visible to other users of the machine.
+        #[structopt(long, parse(from_os_str))]
+        grpc_auth_token_file: Option<PathBuf>,
         /// Bind the services to this host.
         #[structopt(short, long, default_value = "127.0.0.1")]
         host: String,
@@ -53,6 +118,58 @@ enum Command {
         /// Bind the metrics endpoint to this port.
         #[structopt(short, long, default_value = "9000")]
         metrics_port: u16,
+        /// Bind the `/healthz` and `/readyz` endpoints to this port.
+        #[structopt(long, default_value = "9001")]
+        health_port: u16,
+        /// The address of Tendermint's RPC server, queried to report readiness.
+        #[structopt(long, default_value = "http://127.0.0.1:26657")]
+        tendermint_rpc: String,
+    },
+
+    /// Check the consistency of the state database, reporting any corruption.
+    ///
+    /// `pd` must not be running on the database while it is checked.
+    Fsck {
+        /// The path of the Rocks database to check.
+        #[structopt(short, long)]
+        rocks_path: PathBuf,
+        /// The version (block height) whose root hash to recompute [default: the latest].
+        #[structopt(long)]
+        version: Option<u64>,
+    },
+
+    /// Export the chain state at a height to a file, from which `pd import` can recreate it.
+    ///
+    /// The export is deterministic: exporting the same height of the same chain always produces
+    /// the same file. `pd` must not be running on the database while it is exported.
+    Export {
+        /// The path of the Rocks database to export from.
+        #[structopt(short, long)]
+        rocks_path: PathBuf,
+        /// The height whose state to export [default: the latest].
+        #[structopt(long)]
+        height: Option<u64>,
+        /// The file to write the export to.
+        #[structopt(short, long, parse(from_os_str))]
+        output: PathBuf,
+    },
+
+    /// Create a new state database from a file written by `pd export`.
+    ///
+    /// This is used to restart a chain from an exported state, for instance to upgrade to a
+    /// version of `pd` which can't run the existing chain. The new chain's genesis must begin at
+    /// the height after the exported one, with the exported root hash as its app hash; when
+    /// `pd start` opens the imported state, it runs any outstanding state migrations.
+    Import {
+        /// The path of the Rocks database to create, which must not contain any state.
+        #[structopt(short, long)]
+        rocks_path: PathBuf,
+        /// The file written by `pd export`.
+        #[structopt(short, long, parse(from_os_str))]
+        input: PathBuf,
+        /// Refuse to import the state unless its root hash (in hex) is this one.
+        #[structopt(long)]
+        expected_root: Option<String>,
     },
 
     /// Generates a directory structure containing necessary files to run a
@@ -70,10 +187,10 @@ enum Command {
         /// Whether to preserve the chain ID (useful for public testnets) or append a random suffix (useful for dev/testing).
         #[structopt(long)]
         preserve_chain_id: bool,
-        /// Path to CSV file containing initial allocations [default: latest testnet].
+        /// Path to CSV or TOML file containing initial allocations [default: latest testnet].
         #[structopt(long, parse(from_os_str))]
         allocations_input_file: Option<PathBuf>,
-        /// Path to JSON file containing initial validator configs [default: latest testnet].
+        /// Path to JSON or TOML file containing initial validator configs [default: latest testnet].
         #[structopt(long, parse(from_os_str))]
         validators_input_file: Option<PathBuf>,
         /// Path to directory to store output in. Must not exist.
@@ -88,6 +205,27 @@ enum Command {
     },
 }
 
+/// How much of the history of the chain a node keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Keep every version of the chain state.
+    Archive,
+    /// Keep only recent versions of the chain state, according to the retention flags.
+    Pruned,
+}
+
+impl FromStr for Mode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "archive" => Ok(Mode::Archive),
+            "pruned" => Ok(Mode::Pruned),
+            _ => Err(anyhow::anyhow!("unknown mode {:?}", s)),
+        }
+    }
+}
+
 // Extracted from tonic's remote_addr implementation; we'd like to instrument
 // spans with the remote addr at the server level rather than at the individual
 // request level, but the hook available to do that gives us an http::Request
@@ -101,29 +239,112 @@ fn remote_addr(req: &http::Request<()>) -> Option<SocketAddr> {
         .and_then(|i| i.remote_addr())
 }
 
+/// Reads the gRPC bearer token from `file` if one is given, or else from the
+/// `PD_GRPC_AUTH_TOKEN` environment variable.
+fn grpc_auth_token(file: Option<PathBuf>) -> anyhow::Result<Option<String>> {
+    let token = match file {
+        Some(file) => Some(
+            std::fs::read_to_string(&file)
+                .with_context(|| format!("Unable to read gRPC auth token from {}", file.display()))?
+                .trim()
+                .to_string(),
+        ),
+        None => std::env::var("PD_GRPC_AUTH_TOKEN").ok(),
+    };
+    if token.as_deref() == Some("") {
+        return Err(anyhow::anyhow!("the gRPC auth token is empty"));
+    }
+    Ok(token)
+}
+
+/// Waits for a signal asking `pd` to shut down, returning its name.
+async fn shutdown_signal() -> anyhow::Result<&'static str> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = signal(SignalKind::terminate())?;
+    tokio::select! {
+        _ = sigterm.recv() => Ok("SIGTERM"),
+        x = tokio::signal::ctrl_c() => x.map(|_| "SIGINT").map_err(Into::into),
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    console_subscriber::init();
     let opt = Opt::from_args();
+    opt.log.init()?;
 
     match opt.cmd {
         Command::Start {
             rocks_path,
+            in_memory,
+            mode,
+            keep_recent,
+            keep_every,
+            node_cache_size,
+            verify_checksums,
+            index_path,
+            snapshot_dir,
+            snapshot_interval,
+            grpc_rate_limit,
+            grpc_rate_limit_burst,
+            grpc_max_request_size,
+            grpc_auth_token_file,
             host,
             abci_port,
             grpc_port,
             metrics_port,
+            health_port,
+            tendermint_rpc,
         } => {
-            tracing::info!(?host, ?abci_port, ?grpc_port, "starting pd");
+            tracing::info!(?host, ?abci_port, ?grpc_port, ?mode, "starting pd");
+
+            let retention = match mode {
+                Mode::Archive => RetentionPolicy::default(),
+                Mode::Pruned => RetentionPolicy {
+                    keep_recent: Some(keep_recent),
+                    keep_every,
+                },
+            };
 
-            let storage = Storage::load(rocks_path)
+            let storage = match rocks_path {
+                Some(rocks_path) if !in_memory => Storage::open(
+                    RocksDb::open(rocks_path)
+                        .await?
+                        .verify_checksums(verify_checksums),
+                    &pd::migrations::all(),
+                )
                 .await
-                .context("Unable to initialize RocksDB storage")?;
+                .context("Unable to initialize RocksDB storage")?,
+                _ => Storage::in_memory(),
+            }
+            .with_node_cache(node_cache_size)
+            .with_retention(retention);
+
+            let indexer = match index_path {
+                Some(index_path) => Some(
+                    pd::Indexer::load(&index_path)
+                        .await
+                        .context("Unable to initialize the index database")?,
+                ),
+                None => None,
+            };
 
-            let (consensus, height_rx) = pd::Consensus::new(storage.clone()).await?;
+            let shutdown = CancellationToken::new();
+            let events = pd::Events::new();
+            let (consensus, height_rx, consensus_worker) = pd::Consensus::new(
+                storage.clone(),
+                retention,
+                indexer.clone(),
+                events.clone(),
+                shutdown.clone(),
+            )
+            .await?;
+            let snapshot = pd::Snapshot::new(storage.clone(), snapshot_dir);
+            if let Some(interval) = snapshot_interval {
+                snapshot.take_every(interval, height_rx.clone()).await?;
+            }
             let mempool = pd::Mempool::new(storage.clone(), height_rx).await?;
             let info = pd::Info::new(storage.clone());
-            let snapshot = pd::Snapshot {};
 
             let abci_server = tokio::task::Builder::new().name("abci_server").spawn(
                 tower_abci::Server::builder()
@@ -136,6 +357,15 @@ async fn main() -> anyhow::Result<()> {
                     .listen(format!("{}:{}", host, abci_port)),
             );
 
+            let access = pd::AccessPolicy {
+                rate_limit: grpc_rate_limit.map(|per_second| pd::RateLimit {
+                    per_second,
+                    burst: grpc_rate_limit_burst.unwrap_or(per_second),
+                }),
+                max_request_size: grpc_max_request_size,
+                auth_token: grpc_auth_token(grpc_auth_token_file)?,
+            };
+
             let grpc_server = tokio::task::Builder::new().name("grpc_server").spawn(
                 Server::builder()
                     .trace_fn(|req| match remote_addr(req) {
@@ -144,8 +374,12 @@ async fn main() -> anyhow::Result<()> {
                         }
                         None => tracing::error_span!("grpc"),
                     })
+                    .layer(pd::GrpcMetricsLayer)
+                    .layer(pd::AccessLayer::new(access))
                     .add_service(ObliviousQueryServer::new(info.clone()))
                     .add_service(SpecificQueryServer::new(info.clone()))
+                    .add_service(EventSubscriptionServer::new(events))
+                    .add_optional_service(indexer.map(IndexQueryServer::new))
                     .serve(
                         format!("{}:{}", host, grpc_port)
                             .parse()
@@ -165,13 +399,136 @@ async fn main() -> anyhow::Result<()> {
 
             pd::register_all_metrics();
 
+            let health = pd::Health::new(storage.clone(), &tendermint_rpc, shutdown.clone())?;
+            let health_server = tokio::task::Builder::new().name("health_server").spawn(
+                health.serve(
+                    format!("{}:{}", host, health_port)
+                        .parse()
+                        .expect("this is a valid address"),
+                ),
+            );
+
             // TODO: better error reporting
-            // We error out if either service errors, rather than keep running
+            // We error out if any service errors, rather than keep running
             tokio::select! {
                 x = abci_server => x?.map_err(|e| anyhow::anyhow!(e))?,
                 x = grpc_server => x?.map_err(|e| anyhow::anyhow!(e))?,
+                x = health_server => x??,
+                signal = shutdown_signal() => {
+                    tracing::info!(signal = signal?, "shutting down");
+                    // Let the block being committed, if any, finish, and flush the storage.
+                    shutdown.cancel();
+                    consensus_worker.await??;
+                    tracing::info!("shut down cleanly");
+                }
             };
         }
+        Command::Fsck {
+            rocks_path,
+            version,
+        } => {
+            let backend = RocksDb::open(rocks_path)
+                .await
+                .context("Unable to open RocksDB storage")?
+                .verify_checksums(true);
+            let storage = Storage::open(backend, &pd::migrations::all()).await?;
+            let report = storage.fsck(version).await?;
+
+            println!(
+                "checked {} nodes; root hash at version {} is {}",
+                report.nodes_checked,
+                report.version,
+                hex::encode(report.root_hash.0)
+            );
+            for corruption in &report.corruptions {
+                println!("{}", corruption);
+            }
+            if !report.is_ok() {
+                return Err(anyhow::anyhow!(
+                    "found {} inconsistencies in the state database",
+                    report.corruptions.len()
+                ));
+            }
+        }
+        Command::Export {
+            rocks_path,
+            height,
+            output,
+        } => {
+            let storage = Storage::new(
+                RocksDb::open(rocks_path)
+                    .await
+                    .context("Unable to open RocksDB storage")?,
+            );
+            let height = match height {
+                Some(height) => height,
+                None => storage
+                    .latest_version()
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("there is no state to export"))?,
+            };
+            // Hold a snapshot of the height, so that it's checked to exist.
+            let _pin = storage.snapshot(height).await?;
+
+            let mut writer = tokio::io::BufWriter::new(
+                tokio::fs::File::create(&output)
+                    .await
+                    .with_context(|| format!("cannot create file {:?}", output))?,
+            );
+            storage.export_snapshot(height, &mut writer).await?;
+            writer.into_inner().sync_all().await?;
+
+            let (version, root) =
+                penumbra_storage::read_snapshot_header(tokio::fs::File::open(&output).await?)
+                    .await?;
+            println!(
+                "exported state at height {} with root hash {} to {:?}",
+                version,
+                hex::encode(root.0),
+                output
+            );
+        }
+        Command::Import {
+            rocks_path,
+            input,
+            expected_root,
+        } => {
+            let input = &input;
+            let open_input = move || async move {
+                tokio::fs::File::open(input)
+                    .await
+                    .map(tokio::io::BufReader::new)
+                    .with_context(|| format!("cannot open file {:?}", input))
+            };
+
+            let (version, root) = penumbra_storage::read_snapshot_header(open_input().await?)
+                .await
+                .with_context(|| format!("{:?} is not a state export", input))?;
+            if let Some(expected_root) = expected_root {
+                if hex::decode(&expected_root)? != root.0 {
+                    return Err(anyhow::anyhow!(
+                        "export has root hash {}, not {}",
+                        hex::encode(root.0),
+                        expected_root
+                    ));
+                }
+            }
+
+            // The state isn't migrated here, since migrations run when `pd start` opens it.
+            let storage = Storage::new(
+                RocksDb::open(rocks_path)
+                    .await
+                    .context("Unable to open RocksDB storage")?,
+            );
+            storage.import_snapshot(open_input().await?).await?;
+            storage.flush().await?;
+
+            println!(
+                "imported state at height {} with root hash {}",
+                version,
+                hex::encode(root.0)
+            );
+        }
         Command::GenerateTestnet {
             // TODO this config is gated on a "populate persistent peers"
             // setting in the Go tendermint binary. Populating the persistent
@@ -233,14 +590,7 @@ async fn main() -> anyhow::Result<()> {
             // Parse allocations from input file or default to latest testnet allocations computed
             // in the build script
             let mut allocations = if let Some(allocations_input_file) = allocations_input_file {
-                let allocations_file = File::open(&allocations_input_file)
-                    .with_context(|| format!("cannot open file {:?}", allocations_input_file))?;
-                parse_allocations(allocations_file).with_context(|| {
-                    format!(
-                        "could not parse allocations file {:?}",
-                        allocations_input_file
-                    )
-                })?
+                read_allocations(&allocations_input_file)?
             } else {
                 static LATEST_ALLOCATIONS: &str =
                     include_str!(env!("PD_LATEST_TESTNET_ALLOCATIONS"));
@@ -255,14 +605,7 @@ async fn main() -> anyhow::Result<()> {
             // Parse validators from input file or default to latest testnet validators computed in
             // the build script
             let testnet_validators = if let Some(validators_input_file) = validators_input_file {
-                let validators_file = File::open(&validators_input_file)
-                    .with_context(|| format!("cannot open file {:?}", validators_input_file))?;
-                parse_validators(validators_file).with_context(|| {
-                    format!(
-                        "could not parse validators file {:?}",
-                        validators_input_file
-                    )
-                })?
+                read_validators(&validators_input_file)?
             } else {
                 static LATEST_VALIDATORS: &str = include_str!(env!("PD_LATEST_TESTNET_VALIDATORS"));
                 parse_validators(std::io::Cursor::new(LATEST_VALIDATORS)).with_context(|| {
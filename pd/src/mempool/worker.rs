@@ -1,5 +1,8 @@
+use std::time::Instant;
+
 use anyhow::Result;
 use bytes::Bytes;
+use metrics::{gauge, histogram, increment_counter};
 
 use penumbra_component::Component;
 use penumbra_proto::Protobuf;
@@ -10,13 +13,21 @@ use tokio::sync::{mpsc, watch};
 use tracing::Instrument;
 
 use super::Message;
-use crate::App;
+use crate::{
+    pd_metrics::{
+        MEMPOOL_CHECKTX_DURATION, MEMPOOL_TRANSACTIONS_ACCEPTED, MEMPOOL_TRANSACTIONS_PENDING,
+        MEMPOOL_TRANSACTIONS_REJECTED,
+    },
+    App,
+};
 
 pub struct Worker {
     queue: mpsc::Receiver<Message>,
     storage: Storage,
     app: App,
     height_rx: watch::Receiver<block::Height>,
+    /// The number of transactions accepted since the ephemeral mempool state was last reset.
+    pending: u64,
 }
 
 impl Worker {
@@ -32,6 +43,7 @@ impl Worker {
             storage,
             app,
             height_rx,
+            pending: 0,
         })
     }
 
@@ -43,8 +55,7 @@ impl Worker {
     async fn check_and_execute_tx(&mut self, tx_bytes: Bytes) -> Result<()> {
         let tx = Transaction::decode(tx_bytes.as_ref())?;
         App::check_tx_stateless(&tx)?;
-        self.app.check_tx_stateful(&tx).await?;
-        self.app.execute_tx(&tx).await;
+        self.app.deliver_tx(&tx).await?;
         Ok(())
     }
 
@@ -60,6 +71,8 @@ impl Worker {
                         let height = self.height_rx.borrow().value();
                         tracing::info!(?height, "resetting ephemeral mempool state");
                         self.app = App::new(self.storage.state().await?).await;
+                        self.pending = 0;
+                        gauge!(MEMPOOL_TRANSACTIONS_PENDING, 0.0);
                     } else {
                         tracing::info!("consensus worker shut down, shutting down mempool worker");
                         // The consensus worker shut down, we should too.
@@ -73,11 +86,19 @@ impl Worker {
                         span,
                     }) = message {
                         // ... and then execute it if it was valid.
-                        let _ = rsp_sender.send(
-                            self.check_and_execute_tx(tx_bytes)
-                                .instrument(span)
-                                .await
-                        );
+                        let start = Instant::now();
+                        let result = self.check_and_execute_tx(tx_bytes)
+                            .instrument(span)
+                            .await;
+                        histogram!(MEMPOOL_CHECKTX_DURATION, start.elapsed());
+                        if result.is_ok() {
+                            self.pending += 1;
+                            increment_counter!(MEMPOOL_TRANSACTIONS_ACCEPTED);
+                            gauge!(MEMPOOL_TRANSACTIONS_PENDING, self.pending as f64);
+                        } else {
+                            increment_counter!(MEMPOOL_TRANSACTIONS_REJECTED);
+                        }
+                        let _ = rsp_sender.send(result);
                     } else {
                         // The queue is closed, so we're done.
                         return Ok(());
@@ -0,0 +1,41 @@
+//! Migrations of the chain state between app versions.
+//!
+//! When a new version of `pd` changes the layout or encoding of the chain state, it should
+//! register a [`Migration`](penumbra_storage::Migration) to the next app version here, so that
+//! nodes upgraded at the same height rewrite their state identically on restart.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use penumbra_storage::{reindex_keys, Migration, Migrations, State, StateKey};
+
+/// Returns every migration of the chain state, in order.
+pub fn all() -> Migrations {
+    Migrations::new().register(1, IndexExistingKeys)
+}
+
+/// Records the keys written before storage kept an index of keys, so that prefix iteration and
+/// snapshot export see the whole state.
+///
+/// The tree holds only the hashes of keys, so the keys are listed by each component from what it
+/// knows of its own state. No values change, so the only change to the root hash is the new app
+/// version.
+struct IndexExistingKeys;
+
+#[async_trait]
+impl Migration for IndexExistingKeys {
+    fn description(&self) -> &str {
+        "index the keys written before the key index existed"
+    }
+
+    async fn migrate(&self, state: &State) -> Result<()> {
+        let mut keys = vec![StateKey::<()>::new("genesis").join("app_state").to_string()];
+        keys.extend(penumbra_chain::existing_state_keys());
+        keys.extend(penumbra_shielded_pool::existing_state_keys(state).await?);
+        keys.extend(penumbra_stake::existing_state_keys(state).await?);
+        keys.extend(penumbra_ibc::existing_state_keys(state).await?);
+
+        let indexed = reindex_keys(state, keys).await?;
+        tracing::info!(indexed, "indexed existing keys");
+        Ok(())
+    }
+}
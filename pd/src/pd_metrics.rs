@@ -1,8 +1,150 @@
-use metrics::register_counter;
+//! Metrics recorded by `pd`, exported to Prometheus at the `/metrics` endpoint of the metrics port.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use futures::FutureExt;
+use metrics::{describe_counter, describe_gauge, describe_histogram, histogram, Unit};
+
+pub const BLOCK_EXECUTION_DURATION: &str = "node_block_execution_duration_seconds";
+pub const BLOCK_TRANSACTIONS: &str = "node_block_transactions";
+pub const BLOCK_HEIGHT: &str = "node_block_height";
+pub const TRANSACTIONS: &str = "node_transactions_total";
+pub const TRANSACTIONS_REJECTED: &str = "node_transactions_rejected_total";
+pub const NOTES: &str = "node_notes_total";
+pub const SPENT_NULLIFIERS: &str = "node_spent_nullifiers_total";
+pub const STATE_SIZE: &str = "node_state_size_bytes";
+pub const GRPC_REQUEST_DURATION: &str = "node_grpc_request_duration_seconds";
+pub const GRPC_REQUESTS_REJECTED: &str = "node_grpc_requests_rejected_total";
+pub const MEMPOOL_CHECKTX_DURATION: &str = "node_mempool_checktx_duration_seconds";
+pub const MEMPOOL_TRANSACTIONS_ACCEPTED: &str = "node_mempool_transactions_accepted_total";
+pub const MEMPOOL_TRANSACTIONS_REJECTED: &str = "node_mempool_transactions_rejected_total";
+pub const MEMPOOL_TRANSACTIONS_PENDING: &str = "node_mempool_transactions_pending";
 
 /// Registers all metrics tracked by `pd`.
 pub fn register_all_metrics() {
-    register_counter!("node_spent_nullifiers_total");
-    register_counter!("node_notes_total");
-    register_counter!("node_transactions_total");
+    describe_histogram!(
+        BLOCK_EXECUTION_DURATION,
+        Unit::Seconds,
+        "The time taken to execute a block, from BeginBlock to the end of Commit"
+    );
+    describe_histogram!(
+        BLOCK_TRANSACTIONS,
+        Unit::Count,
+        "The number of transactions executed in each block"
+    );
+    describe_gauge!(
+        BLOCK_HEIGHT,
+        Unit::Count,
+        "The height of the most recently committed block"
+    );
+    describe_counter!(
+        TRANSACTIONS,
+        Unit::Count,
+        "The number of transactions executed in blocks"
+    );
+    describe_counter!(
+        TRANSACTIONS_REJECTED,
+        Unit::Count,
+        "The number of transactions in blocks which were rejected as invalid"
+    );
+    describe_counter!(
+        NOTES,
+        Unit::Count,
+        "The number of note commitments inserted into the note commitment tree by transactions"
+    );
+    describe_counter!(
+        SPENT_NULLIFIERS,
+        Unit::Count,
+        "The number of nullifiers revealed by transactions"
+    );
+    describe_gauge!(
+        STATE_SIZE,
+        Unit::Bytes,
+        "The approximate size of the chain state on disk"
+    );
+    describe_histogram!(
+        GRPC_REQUEST_DURATION,
+        Unit::Seconds,
+        "The time taken to answer a gRPC request, until the response (or the start of a \
+         streaming response) is ready"
+    );
+    describe_counter!(
+        GRPC_REQUESTS_REJECTED,
+        Unit::Count,
+        "The number of gRPC requests rejected by the access policy, labeled by the reason"
+    );
+    describe_histogram!(
+        MEMPOOL_CHECKTX_DURATION,
+        Unit::Seconds,
+        "The time taken to check a transaction submitted to the mempool"
+    );
+    describe_counter!(
+        MEMPOOL_TRANSACTIONS_ACCEPTED,
+        Unit::Count,
+        "The number of transactions accepted into the mempool"
+    );
+    describe_counter!(
+        MEMPOOL_TRANSACTIONS_REJECTED,
+        Unit::Count,
+        "The number of transactions rejected from the mempool as invalid"
+    );
+    describe_gauge!(
+        MEMPOOL_TRANSACTIONS_PENDING,
+        Unit::Count,
+        "The number of transactions accepted into the mempool since the last block"
+    );
+    penumbra_storage::register_metrics();
+}
+
+/// A [`tower::Layer`] which records the latency of each gRPC request, labeled by its method.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GrpcMetricsLayer;
+
+impl<S> tower::Layer<S> for GrpcMetricsLayer {
+    type Service = GrpcMetrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcMetrics { inner }
+    }
+}
+
+/// A gRPC service wrapped by [`GrpcMetricsLayer`].
+#[derive(Clone, Debug)]
+pub struct GrpcMetrics<S> {
+    inner: S,
+}
+
+impl<S, B> tower::Service<http::Request<B>> for GrpcMetrics<S>
+where
+    S: tower::Service<http::Request<B>>,
+    S::Future: Send + 'static,
+    S::Response: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        // The path of a gRPC request is `/{package}.{service}/{method}`
+        let method = req.uri().path().to_string();
+        let start = Instant::now();
+        let rsp = self.inner.call(req);
+
+        async move {
+            let rsp = rsp.await;
+            histogram!(GRPC_REQUEST_DURATION, start.elapsed(), "method" => method);
+            rsp
+        }
+        .boxed()
+    }
 }
@@ -1,15 +1,278 @@
+//! Tendermint state sync, which lets a new node start from a recent snapshot of the chain state
+//! instead of replaying every block since genesis.
+//!
+//! A node serving snapshots exports the state every few blocks (as produced by
+//! [`Storage::export_snapshot`]) into its snapshot directory, keeping only the most recent
+//! [`KEEP_SNAPSHOTS`]. Each snapshot file is offered to peers in chunks of [`CHUNK_BYTES`], and
+//! its metadata is the concatenation of the SHA-256 hashes of its chunks, so that a restoring node
+//! can check each chunk as it arrives and refetch bad ones from other peers. The hash of the
+//! snapshot is the hash of its metadata.
+
 use std::{
     future::Future,
+    io::SeekFrom,
+    path::{Path, PathBuf},
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
 };
 
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
 use futures::FutureExt;
-use tendermint::abci::{SnapshotRequest, SnapshotResponse};
+use penumbra_storage::Storage;
+use sha2::{Digest, Sha256};
+use tendermint::{
+    abci::{
+        request, response, response::ApplySnapshotChunkResult, types, SnapshotRequest,
+        SnapshotResponse,
+    },
+    block,
+};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader, BufWriter},
+    sync::{watch, Mutex},
+};
 use tower_abci::BoxError;
+use tracing::Instrument;
+
+use crate::RequestExt;
+
+/// The version of the snapshot format, which changes whenever the format of the state export or
+/// its division into chunks does.
+const FORMAT: u32 = 1;
+
+/// The size of each chunk of a snapshot (the last may be shorter).
+///
+/// Tendermint rejects chunks larger than 16 MiB.
+const CHUNK_BYTES: u64 = 10 * 1024 * 1024;
+
+/// The number of snapshots to keep in the snapshot directory.
+const KEEP_SNAPSHOTS: usize = 2;
 
 #[derive(Clone, Debug)]
-pub struct Snapshot {}
+pub struct Snapshot {
+    storage: Storage,
+    /// The directory in which snapshots are kept, if this node serves snapshots.
+    dir: Option<PathBuf>,
+    /// The snapshot currently being restored by state sync, if any.
+    restore: Arc<Mutex<Option<Restore>>>,
+}
+
+/// A snapshot offered by Tendermint, whose chunks are written to a temporary file as they are
+/// applied, until the whole snapshot can be imported.
+#[derive(Debug)]
+struct Restore {
+    snapshot: types::Snapshot,
+    app_hash: Bytes,
+    file: File,
+    next_chunk: u32,
+}
+
+impl Snapshot {
+    /// Creates the state sync service, serving the snapshots in `dir` (if any) to peers.
+    ///
+    /// Every node can restore its state from a snapshot, whether or not it serves them.
+    pub fn new(storage: Storage, dir: Option<PathBuf>) -> Self {
+        Self {
+            storage,
+            dir,
+            restore: Default::default(),
+        }
+    }
+
+    /// Spawns a task which snapshots the state every `interval` blocks, as they are committed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if this node has no snapshot directory, or it can't be created.
+    pub async fn take_every(
+        &self,
+        interval: u64,
+        mut height_rx: watch::Receiver<block::Height>,
+    ) -> Result<()> {
+        let dir = self
+            .dir
+            .clone()
+            .ok_or_else(|| anyhow!("taking snapshots requires a snapshot directory"))?;
+        fs::create_dir_all(&dir).await?;
+        let storage = self.storage.clone();
+
+        tokio::task::Builder::new()
+            .name("snapshot::take_every")
+            .spawn(async move {
+                while height_rx.changed().await.is_ok() {
+                    let height = height_rx.borrow().value();
+                    if interval == 0 || height % interval != 0 {
+                        continue;
+                    }
+                    // Taking a snapshot is best-effort, so failing to shouldn't stop the node.
+                    if let Err(e) = take(&storage, &dir, height).await {
+                        tracing::warn!(?e, height, "failed to take snapshot");
+                    }
+                }
+            });
+
+        Ok(())
+    }
+
+    async fn list_snapshots(&self) -> Result<response::ListSnapshots> {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return Ok(Default::default()),
+        };
+
+        let mut snapshots = Vec::new();
+        for height in heights(dir).await? {
+            let metadata = fs::read(metadata_path(dir, height)).await?;
+            snapshots.push(types::Snapshot {
+                height: height.try_into()?,
+                format: FORMAT,
+                chunks: (metadata.len() / 32).try_into()?,
+                hash: Sha256::digest(&metadata).to_vec().into(),
+                metadata: metadata.into(),
+            });
+        }
+
+        Ok(response::ListSnapshots { snapshots })
+    }
+
+    async fn load_snapshot_chunk(
+        &self,
+        load: request::LoadSnapshotChunk,
+    ) -> Result<response::LoadSnapshotChunk> {
+        let dir = match &self.dir {
+            Some(dir) if load.format == FORMAT => dir,
+            // An empty chunk tells Tendermint that we don't have it
+            _ => return Ok(Default::default()),
+        };
+
+        let path = snapshot_path(dir, load.height.value());
+        let mut file = match File::open(&path).await {
+            Ok(file) => file,
+            Err(e) => {
+                tracing::warn!(?e, ?path, "requested snapshot is not available");
+                return Ok(Default::default());
+            }
+        };
+        file.seek(SeekFrom::Start(load.chunk as u64 * CHUNK_BYTES))
+            .await?;
+        let mut chunk = Vec::new();
+        file.take(CHUNK_BYTES).read_to_end(&mut chunk).await?;
+
+        Ok(response::LoadSnapshotChunk {
+            chunk: chunk.into(),
+        })
+    }
+
+    async fn offer_snapshot(
+        &self,
+        offer: request::OfferSnapshot,
+    ) -> Result<response::OfferSnapshot> {
+        let snapshot = offer.snapshot;
+        tracing::info!(height = ?snapshot.height, chunks = snapshot.chunks, "offered snapshot");
+
+        if snapshot.format != FORMAT {
+            return Ok(response::OfferSnapshot::RejectFormat);
+        }
+        if let Some(latest) = self.storage.latest_version().await? {
+            tracing::error!(latest, "cannot restore a snapshot over existing state");
+            return Ok(response::OfferSnapshot::Abort);
+        }
+        if snapshot.chunks == 0
+            || snapshot.metadata.len() != snapshot.chunks as usize * 32
+            || snapshot.hash.as_ref() != Sha256::digest(&snapshot.metadata).as_slice()
+        {
+            tracing::warn!("snapshot metadata does not match its hash or number of chunks");
+            return Ok(response::OfferSnapshot::Reject);
+        }
+
+        let file = File::from_std(tempfile::tempfile()?);
+        *self.restore.lock().await = Some(Restore {
+            snapshot,
+            app_hash: offer.app_hash,
+            file,
+            next_chunk: 0,
+        });
+
+        Ok(response::OfferSnapshot::Accept)
+    }
+
+    async fn apply_snapshot_chunk(
+        &self,
+        apply: request::ApplySnapshotChunk,
+    ) -> Result<response::ApplySnapshotChunk> {
+        let result = |result| response::ApplySnapshotChunk {
+            result,
+            ..Default::default()
+        };
+
+        let mut guard = self.restore.lock().await;
+        let restore = match guard.as_mut() {
+            Some(restore) => restore,
+            None => {
+                tracing::error!("no snapshot is being restored");
+                return Ok(result(ApplySnapshotChunkResult::Abort));
+            }
+        };
+
+        // Tendermint applies chunks in order, so we only need to append each one to the file.
+        if apply.index != restore.next_chunk {
+            tracing::warn!(
+                index = apply.index,
+                expected = restore.next_chunk,
+                "snapshot chunk applied out of order"
+            );
+            return Ok(result(ApplySnapshotChunkResult::RetrySnapshot));
+        }
+        let start = apply.index as usize * 32;
+        if Sha256::digest(&apply.chunk).as_slice() != &restore.snapshot.metadata[start..start + 32]
+        {
+            tracing::warn!(index = apply.index, sender = ?apply.sender, "snapshot chunk has the wrong hash");
+            return Ok(response::ApplySnapshotChunk {
+                result: ApplySnapshotChunkResult::Retry,
+                refetch_chunks: vec![apply.index],
+                reject_senders: vec![apply.sender],
+            });
+        }
+        restore.file.write_all(&apply.chunk).await?;
+        restore.next_chunk += 1;
+        tracing::debug!(
+            index = apply.index,
+            chunks = restore.snapshot.chunks,
+            "applied snapshot chunk"
+        );
+
+        if restore.next_chunk < restore.snapshot.chunks {
+            return Ok(result(ApplySnapshotChunkResult::Accept));
+        }
+
+        // That was the last chunk, so import the snapshot.
+        let mut restore = guard.take().expect("snapshot is being restored");
+        restore.file.flush().await?;
+        restore.file.seek(SeekFrom::Start(0)).await?;
+        let mut reader = BufReader::new(restore.file);
+
+        // Check the snapshot against the app hash Tendermint trusts before writing anything, since
+        // the storage can't be reset if the import is wrong.
+        let (version, root) = penumbra_storage::read_snapshot_header(&mut reader).await?;
+        if version != restore.snapshot.height.value() || &root.0[..] != restore.app_hash.as_ref() {
+            tracing::warn!(
+                version,
+                root = %hex::encode(root.0),
+                app_hash = %hex::encode(&restore.app_hash),
+                "snapshot does not match the trusted app hash"
+            );
+            return Ok(result(ApplySnapshotChunkResult::RejectSnapshot));
+        }
+        reader.seek(SeekFrom::Start(0)).await?;
+        self.storage.import_snapshot(reader).await?;
+
+        tracing::info!(version, "restored state from snapshot");
+        Ok(result(ApplySnapshotChunkResult::Accept))
+    }
+}
 
 impl tower::Service<SnapshotRequest> for Snapshot {
     type Response = SnapshotResponse;
@@ -22,17 +285,231 @@ impl tower::Service<SnapshotRequest> for Snapshot {
     }
 
     fn call(&mut self, req: SnapshotRequest) -> Self::Future {
-        // No-op, we don't implement snapshot support
         use SnapshotRequest as Request;
         use SnapshotResponse as Response;
+        let span = req.create_span();
+        let self2 = self.clone();
+
         async move {
             Ok(match req {
-                Request::ListSnapshots => Response::ListSnapshots(Default::default()),
-                Request::OfferSnapshot(_) => Response::OfferSnapshot(Default::default()),
-                Request::LoadSnapshotChunk(_) => Response::LoadSnapshotChunk(Default::default()),
-                Request::ApplySnapshotChunk(_) => Response::ApplySnapshotChunk(Default::default()),
+                Request::ListSnapshots => {
+                    Response::ListSnapshots(self2.list_snapshots().await.unwrap_or_else(|e| {
+                        tracing::warn!(?e, "failed to list snapshots");
+                        Default::default()
+                    }))
+                }
+                Request::LoadSnapshotChunk(load) => Response::LoadSnapshotChunk(
+                    self2.load_snapshot_chunk(load).await.unwrap_or_else(|e| {
+                        tracing::warn!(?e, "failed to load snapshot chunk");
+                        Default::default()
+                    }),
+                ),
+                // Abort state sync on any internal error while restoring, rather than retrying
+                // against storage which may have been partially written.
+                Request::OfferSnapshot(offer) => {
+                    Response::OfferSnapshot(self2.offer_snapshot(offer).await.unwrap_or_else(|e| {
+                        tracing::error!(?e, "failed to accept snapshot");
+                        response::OfferSnapshot::Abort
+                    }))
+                }
+                Request::ApplySnapshotChunk(apply) => Response::ApplySnapshotChunk(
+                    self2.apply_snapshot_chunk(apply).await.unwrap_or_else(|e| {
+                        tracing::error!(?e, "failed to apply snapshot chunk");
+                        response::ApplySnapshotChunk {
+                            result: ApplySnapshotChunkResult::Abort,
+                            ..Default::default()
+                        }
+                    }),
+                ),
             })
         }
+        .instrument(span)
         .boxed()
     }
 }
+
+/// Exports the state at `height` into `dir`, then deletes all but the most recent snapshots.
+async fn take(storage: &Storage, dir: &Path, height: u64) -> Result<()> {
+    // Hold a snapshot of the version, so that it can't be pruned during the export.
+    let _pin = storage.snapshot(height).await?;
+
+    // Write the export and its metadata under temporary names, so that a partially written
+    // snapshot is never listed.
+    let partial = dir.join(format!("{}.snapshot.partial", height));
+    let mut writer = BufWriter::new(File::create(&partial).await?);
+    storage.export_snapshot(height, &mut writer).await?;
+    writer.into_inner().sync_all().await?;
+
+    let mut file = BufReader::new(File::open(&partial).await?);
+    let mut metadata = Vec::new();
+    loop {
+        let mut chunk = Vec::new();
+        (&mut file)
+            .take(CHUNK_BYTES)
+            .read_to_end(&mut chunk)
+            .await?;
+        if chunk.is_empty() {
+            break;
+        }
+        metadata.extend_from_slice(&Sha256::digest(&chunk));
+    }
+    fs::write(metadata_path(dir, height), &metadata).await?;
+    fs::rename(&partial, snapshot_path(dir, height)).await?;
+    tracing::info!(height, chunks = metadata.len() / 32, "took snapshot");
+
+    let heights = heights(dir).await?;
+    for &old in heights
+        .iter()
+        .take(heights.len().saturating_sub(KEEP_SNAPSHOTS))
+    {
+        fs::remove_file(snapshot_path(dir, old)).await?;
+        fs::remove_file(metadata_path(dir, old)).await?;
+    }
+
+    Ok(())
+}
+
+/// The heights of the complete snapshots in `dir`, in increasing order.
+async fn heights(dir: &Path) -> Result<Vec<u64>> {
+    let mut heights = Vec::new();
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        let height = name
+            .to_str()
+            .and_then(|name| name.strip_suffix(".snapshot"))
+            .and_then(|height| height.parse().ok());
+        if let Some(height) = height {
+            if fs::metadata(metadata_path(dir, height)).await.is_ok() {
+                heights.push(height);
+            }
+        }
+    }
+    heights.sort_unstable();
+    Ok(heights)
+}
+
+fn snapshot_path(dir: &Path, height: u64) -> PathBuf {
+    dir.join(format!("{}.snapshot", height))
+}
+
+fn metadata_path(dir: &Path, height: u64) -> PathBuf {
+    dir.join(format!("{}.metadata", height))
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use penumbra_storage::Storage;
+    use tendermint::abci::{request, response, response::ApplySnapshotChunkResult, types};
+
+    use super::{take, Snapshot, FORMAT};
+
+    /// Commits two blocks to in-memory storage, snapshots the second into a temporary directory,
+    /// and returns the service serving it, the snapshot, its chunks, and its app hash.
+    async fn serve_snapshot() -> (tempfile::TempDir, types::Snapshot, Vec<Bytes>, Bytes) {
+        let storage = Storage::in_memory();
+        let mut root = None;
+        for height in 0..2u8 {
+            let state = storage.state().await.unwrap();
+            let mut overlay = state.write().await;
+            overlay.put(format!("test/{}", height), vec![height]);
+            root = Some(overlay.commit(storage.clone()).await.unwrap().0);
+        }
+        let dir = tempfile::tempdir().unwrap();
+        take(&storage, dir.path(), 1).await.unwrap();
+
+        let server = Snapshot::new(storage, Some(dir.path().to_owned()));
+        let mut snapshots = server.list_snapshots().await.unwrap().snapshots;
+        assert_eq!(snapshots.len(), 1);
+        let snapshot = snapshots.remove(0);
+        assert_eq!(snapshot.height.value(), 1);
+
+        let mut chunks = Vec::new();
+        for chunk in 0..snapshot.chunks {
+            let load = request::LoadSnapshotChunk {
+                height: snapshot.height,
+                format: FORMAT,
+                chunk,
+            };
+            chunks.push(server.load_snapshot_chunk(load).await.unwrap().chunk);
+        }
+
+        let app_hash = Bytes::copy_from_slice(&root.unwrap().0);
+        (dir, snapshot, chunks, app_hash)
+    }
+
+    async fn offer(restorer: &Snapshot, snapshot: &types::Snapshot, app_hash: Bytes) {
+        let offer = request::OfferSnapshot {
+            snapshot: snapshot.clone(),
+            app_hash,
+        };
+        assert!(matches!(
+            restorer.offer_snapshot(offer).await.unwrap(),
+            response::OfferSnapshot::Accept
+        ));
+    }
+
+    async fn apply(restorer: &Snapshot, index: u32, chunk: Bytes) -> response::ApplySnapshotChunk {
+        let apply = request::ApplySnapshotChunk {
+            index,
+            chunk,
+            sender: "peer".to_string(),
+        };
+        restorer.apply_snapshot_chunk(apply).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn restore_from_snapshot() {
+        let (_dir, snapshot, chunks, app_hash) = serve_snapshot().await;
+        let storage = Storage::in_memory();
+        let restorer = Snapshot::new(storage.clone(), None);
+        offer(&restorer, &snapshot, app_hash).await;
+
+        // A corrupted chunk is refetched, from another peer.
+        let mut corrupted = chunks[0].to_vec();
+        *corrupted.last_mut().unwrap() ^= 1;
+        let response = apply(&restorer, 0, corrupted.into()).await;
+        assert!(matches!(response.result, ApplySnapshotChunkResult::Retry));
+        assert_eq!(response.refetch_chunks, vec![0]);
+        assert_eq!(response.reject_senders, vec!["peer".to_string()]);
+
+        for (index, chunk) in chunks.into_iter().enumerate() {
+            let response = apply(&restorer, index as u32, chunk).await;
+            assert!(matches!(response.result, ApplySnapshotChunkResult::Accept));
+        }
+        assert_eq!(storage.latest_version().await.unwrap(), Some(1));
+        assert_eq!(
+            storage.get_at_version("test/0", 1).await.unwrap(),
+            Some(vec![0])
+        );
+        assert_eq!(
+            storage.get_at_version("test/1", 1).await.unwrap(),
+            Some(vec![1])
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_snapshot_with_wrong_app_hash() {
+        let (_dir, snapshot, chunks, app_hash) = serve_snapshot().await;
+        let storage = Storage::in_memory();
+        let restorer = Snapshot::new(storage.clone(), None);
+        let mut wrong_hash = app_hash.to_vec();
+        wrong_hash[0] ^= 1;
+        offer(&restorer, &snapshot, wrong_hash.into()).await;
+
+        let last = chunks.len() - 1;
+        for (index, chunk) in chunks.into_iter().enumerate() {
+            let response = apply(&restorer, index as u32, chunk).await;
+            if index < last {
+                assert!(matches!(response.result, ApplySnapshotChunkResult::Accept));
+            } else {
+                assert!(matches!(
+                    response.result,
+                    ApplySnapshotChunkResult::RejectSnapshot
+                ));
+            }
+        }
+        assert_eq!(storage.latest_version().await.unwrap(), None);
+    }
+}
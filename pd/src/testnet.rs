@@ -1,4 +1,11 @@
-use std::{env::current_dir, fmt, io::Read, path::PathBuf, str::FromStr};
+use std::{
+    env::current_dir,
+    fmt,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use anyhow::{Context, Result};
 use directories::UserDirs;
@@ -28,6 +35,70 @@ pub fn parse_validators(input: impl Read) -> Result<Vec<TestnetValidator>> {
     Ok(serde_json::from_reader(input)?)
 }
 
+/// Parses allocations from a TOML file with an `[[allocation]]` table for each allocation.
+pub fn parse_allocations_toml(mut input: impl Read) -> Result<Vec<genesis::Allocation>> {
+    #[derive(Deserialize)]
+    struct AllocationsFile {
+        #[serde(default, rename = "allocation")]
+        allocations: Vec<TestnetAllocation>,
+    }
+
+    let mut contents = String::new();
+    input.read_to_string(&mut contents)?;
+    let file: AllocationsFile = toml::from_str(&contents)?;
+    file.allocations
+        .into_iter()
+        .enumerate()
+        .map(|(entry, record)| {
+            record
+                .try_into()
+                .with_context(|| format!("invalid address in entry {} of allocations file", entry))
+        })
+        .collect()
+}
+
+/// Parses validators from a TOML file with a `[[validator]]` table for each validator.
+pub fn parse_validators_toml(mut input: impl Read) -> Result<Vec<TestnetValidator>> {
+    #[derive(Deserialize)]
+    struct ValidatorsFile {
+        #[serde(default, rename = "validator")]
+        validators: Vec<TestnetValidator>,
+    }
+
+    let mut contents = String::new();
+    input.read_to_string(&mut contents)?;
+    let file: ValidatorsFile = toml::from_str(&contents)?;
+    Ok(file.validators)
+}
+
+/// Reads allocations from a file, which is parsed as TOML if it has a `.toml` extension, and as
+/// CSV otherwise.
+pub fn read_allocations(path: &Path) -> Result<Vec<genesis::Allocation>> {
+    let file = File::open(path).with_context(|| format!("cannot open file {:?}", path))?;
+    if is_toml(path) {
+        parse_allocations_toml(file)
+    } else {
+        parse_allocations(file)
+    }
+    .with_context(|| format!("could not parse allocations file {:?}", path))
+}
+
+/// Reads validators from a file, which is parsed as TOML if it has a `.toml` extension, and as
+/// JSON otherwise.
+pub fn read_validators(path: &Path) -> Result<Vec<TestnetValidator>> {
+    let file = File::open(path).with_context(|| format!("cannot open file {:?}", path))?;
+    if is_toml(path) {
+        parse_validators_toml(file)
+    } else {
+        parse_validators(file)
+    }
+    .with_context(|| format!("could not parse validators file {:?}", path))
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some("toml")
+}
+
 fn string_u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
 where
     D: de::Deserializer<'de>,
@@ -55,6 +126,14 @@ where
         {
             Ok(v)
         }
+
+        // TOML integers are signed.
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            u64::try_from(v).map_err(E::custom)
+        }
     }
 
     deserializer.deserialize_any(U64StringVisitor)
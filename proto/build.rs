@@ -62,6 +62,8 @@ fn main() -> Result<()> {
         &[
             "proto/client/oblivious.proto",
             "proto/client/specific.proto",
+            "proto/client/index.proto",
+            "proto/client/subscription.proto",
             "proto/wallet.proto",
         ],
         &["proto/", "ibc-go-vendor/"],
@@ -51,6 +51,12 @@ pub mod client {
     pub mod specific {
         tonic::include_proto!("penumbra.client.specific");
     }
+    pub mod index {
+        tonic::include_proto!("penumbra.client.index");
+    }
+    pub mod subscription {
+        tonic::include_proto!("penumbra.client.subscription");
+    }
 }
 
 /// IBC protocol structures.
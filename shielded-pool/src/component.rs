@@ -10,14 +10,14 @@ use penumbra_crypto::{
     asset::{self, Asset, Denom},
     ka,
     merkle::{self, Frontier, NoteCommitmentTree, TreeExt},
-    note, Address, Note, Nullifier, One, Value, STAKING_TOKEN_ASSET_ID,
+    note, Address, Note, Nullifier, One, Value, STAKING_TOKEN_ASSET_ID, STAKING_TOKEN_DENOM,
 };
-use penumbra_storage::{State, StateExt};
+use penumbra_storage::{State, StateExt, StateRead};
 use penumbra_transaction::{action::output, Action, Transaction};
 use tendermint::abci;
 use tracing::instrument;
 
-use crate::CommissionAmounts;
+use crate::{state_key, CommissionAmounts};
 
 // Stub component
 pub struct ShieldedPool {
@@ -42,6 +42,13 @@ impl Component for ShieldedPool {
 
     #[instrument(name = "shielded_pool", skip(self, app_state))]
     async fn init_chain(&mut self, app_state: &genesis::AppState) {
+        // Rewards are minted in the staking token, so it must be registered even if no genesis
+        // allocation uses it.
+        self.state
+            .register_denom(&STAKING_TOKEN_DENOM)
+            .await
+            .unwrap();
+
         for allocation in &app_state.allocations {
             tracing::info!(?allocation, "processing allocation");
 
@@ -362,7 +369,7 @@ impl ShieldedPool {
         self.state
             .write()
             .await
-            .put(b"shielded_pool/nct_data".into(), nct_data);
+            .put(state_key::note_commitment_tree().to_string(), nct_data);
         Ok(())
     }
 
@@ -374,7 +381,7 @@ impl ShieldedPool {
         if let Ok(Some(bytes)) = state
             .read()
             .await
-            .get(b"shielded_pool/nct_data".into())
+            .get(state_key::note_commitment_tree().as_str())
             .await
         {
             bincode::deserialize(&bytes).map_err(Into::into)
@@ -388,16 +395,19 @@ impl ShieldedPool {
 ///
 /// TODO: should this be split into Read and Write traits?
 #[async_trait]
-pub trait View: StateExt {
+pub trait View: StateRead {
     async fn token_supply(&self, asset_id: &asset::Id) -> Result<Option<u64>> {
-        self.get_proto(format!("shielded_pool/assets/{}/token_supply", asset_id).into())
+        self.get_typed_proto(&state_key::token_supply(asset_id))
             .await
     }
 
     #[instrument(skip(self))]
-    async fn update_token_supply(&self, asset_id: &asset::Id, change: i64) -> Result<()> {
-        let key = format!("shielded_pool/assets/{}/token_supply", asset_id).into();
-        let current_supply = match self.get_proto(key).await {
+    async fn update_token_supply(&self, asset_id: &asset::Id, change: i64) -> Result<()>
+    where
+        Self: StateExt,
+    {
+        let key = state_key::token_supply(asset_id);
+        let current_supply = match self.get_typed_proto(&key).await {
             Ok(Some(value)) => value,
             Ok(None) => 0u64,
             // We want to handle the MissingRootError specially here, so that we can
@@ -428,24 +438,26 @@ pub trait View: StateExt {
         };
         tracing::debug!(?current_supply, ?new_supply);
 
-        self.put_proto(key, new_supply).await;
+        self.put_typed_proto(&key, new_supply).await;
         Ok(())
     }
 
     async fn known_assets(&self) -> Result<KnownAssets> {
         Ok(self
-            .get_domain("shielded_pool/known_assets".into())
+            .get_typed(&state_key::known_assets())
             .await?
             .unwrap_or_default())
     }
 
     async fn denom_by_asset(&self, asset_id: &asset::Id) -> Result<Option<Denom>> {
-        self.get_domain(format!("shielded_pool/assets/{}/denom", asset_id).into())
-            .await
+        self.get_typed(&state_key::denom_by_asset(asset_id)).await
     }
 
     #[instrument(skip(self))]
-    async fn register_denom(&self, denom: &Denom) -> Result<()> {
+    async fn register_denom(&self, denom: &Denom) -> Result<()>
+    where
+        Self: StateExt,
+    {
         let id = denom.id();
         if self.denom_by_asset(&id).await?.is_some() {
             tracing::debug!(?denom, ?id, "skipping existing denom");
@@ -453,11 +465,8 @@ pub trait View: StateExt {
         } else {
             tracing::debug!(?denom, ?id, "registering new denom");
             // We want to be able to query for the denom by asset ID...
-            self.put_domain(
-                format!("shielded_pool/assets/{}/denom", id).into(),
-                denom.clone(),
-            )
-            .await;
+            self.put_typed(&state_key::denom_by_asset(&id), denom.clone())
+                .await;
             // ... and we want to record it in the list of known asset IDs
             // (this requires reading the whole list, which is sad, but hopefully
             // we don't do this often).
@@ -466,50 +475,52 @@ pub trait View: StateExt {
                 id,
                 denom: denom.clone(),
             });
-            self.put_domain("shielded_pool/known_assets".into(), known_assets)
+            self.put_typed(&state_key::known_assets(), known_assets)
                 .await;
             Ok(())
         }
     }
 
-    async fn set_note_source(&self, note_commitment: &note::Commitment, source: NoteSource) {
-        self.put_domain(
-            format!("shielded_pool/note_source/{}", note_commitment).into(),
-            source,
-        )
-        .await
+    async fn set_note_source(&self, note_commitment: &note::Commitment, source: NoteSource)
+    where
+        Self: StateExt,
+    {
+        self.put_typed(&state_key::note_source(note_commitment), source)
+            .await
     }
 
     async fn note_source(&self, note_commitment: &note::Commitment) -> Result<Option<NoteSource>> {
-        self.get_domain(format!("shielded_pool/note_source/{}", note_commitment).into())
+        self.get_typed(&state_key::note_source(note_commitment))
             .await
     }
 
-    async fn set_compact_block(&self, compact_block: CompactBlock) {
-        self.put_domain(
-            format!("shielded_pool/compact_block/{}", compact_block.height).into(),
+    async fn set_compact_block(&self, compact_block: CompactBlock)
+    where
+        Self: StateExt,
+    {
+        self.put_typed(
+            &state_key::compact_block(compact_block.height),
             compact_block,
         )
         .await
     }
 
     async fn compact_block(&self, height: u64) -> Result<Option<CompactBlock>> {
-        self.get_domain(format!("shielded_pool/compact_block/{}", height).into())
-            .await
+        self.get_typed(&state_key::compact_block(height)).await
     }
 
-    async fn set_nct_anchor(&self, height: u64, anchor: merkle::Root) {
+    async fn set_nct_anchor(&self, height: u64, anchor: merkle::Root)
+    where
+        Self: StateExt,
+    {
         tracing::debug!(?height, ?anchor, "writing anchor");
 
         // Write the NCT anchor both as a value, so we can look it up,
-        self.put_domain(
-            format!("shielded_pool/nct_anchor/{}", height).into(),
-            anchor.clone(),
-        )
-        .await;
+        self.put_typed(&state_key::anchor_by_height(height), anchor.clone())
+            .await;
         // and as a key, so we can query for it.
-        self.put_proto(
-            format!("shielded_pool/valid_anchors/{}", anchor).into(),
+        self.put_typed_proto(
+            &state_key::anchor_lookup(&anchor),
             // We don't use the value for validity checks, but writing the height
             // here lets us find out what height the anchor was for.
             height,
@@ -520,7 +531,7 @@ pub trait View: StateExt {
     /// Checks whether a claimed NCT anchor is a previous valid state root.
     async fn check_claimed_anchor(&self, anchor: &merkle::Root) -> Result<()> {
         if let Some(anchor_height) = self
-            .get_proto::<u64>(format!("shielded_pool/valid_anchors/{}", anchor).into())
+            .get_typed_proto(&state_key::anchor_lookup(anchor))
             .await?
         {
             tracing::debug!(?anchor, ?anchor_height, "anchor is valid");
@@ -534,9 +545,12 @@ pub trait View: StateExt {
     }
 
     #[instrument(skip(self))]
-    async fn spend_nullifier(&self, nullifier: Nullifier, source: NoteSource) {
-        self.put_proto(
-            format!("shielded_pool/spent_nullifiers/{}", nullifier).into(),
+    async fn spend_nullifier(&self, nullifier: Nullifier, source: NoteSource)
+    where
+        Self: StateExt,
+    {
+        self.put_typed_proto(
+            &state_key::spent_nullifier_lookup(&nullifier),
             // We don't use the value for validity checks, but writing the source
             // here lets us find out what transaction spent the nullifier.
             // TODO: NoteSource proto?
@@ -548,7 +562,7 @@ pub trait View: StateExt {
     #[instrument(skip(self))]
     async fn check_nullifier_unspent(&self, nullifier: Nullifier) -> Result<()> {
         if let Some(source_bytes) = self
-            .get_proto::<Vec<u8>>(format!("shielded_pool/spent_nullifiers/{}", nullifier).into())
+            .get_typed_proto(&state_key::spent_nullifier_lookup(&nullifier))
             .await?
         {
             // TODO: NoteSource proto?
@@ -568,17 +582,16 @@ pub trait View: StateExt {
     // be used with IBC transfers, and fix up the path and proto
 
     async fn commission_amounts(&self, height: u64) -> Result<Option<CommissionAmounts>> {
-        self.get_domain(format!("staking/commission_amounts/{}", height).into())
-            .await
+        self.get_typed(&state_key::commission_amounts(height)).await
     }
 
-    async fn set_commission_amounts(&self, height: u64, notes: CommissionAmounts) {
-        self.put_domain(
-            format!("staking/commission_amounts/{}", height).into(),
-            notes,
-        )
-        .await
+    async fn set_commission_amounts(&self, height: u64, notes: CommissionAmounts)
+    where
+        Self: StateExt,
+    {
+        self.put_typed(&state_key::commission_amounts(height), notes)
+            .await
     }
 }
 
-impl<T: StateExt> View for T {}
+impl<T: StateRead> View for T {}
@@ -1,5 +1,7 @@
 mod commission;
 mod component;
+mod state_key;
 
 pub use commission::{CommissionAmount, CommissionAmounts};
 pub use component::{ShieldedPool, View};
+pub use state_key::existing_state_keys;
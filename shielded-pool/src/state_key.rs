@@ -0,0 +1,103 @@
+//! The keys under which the shielded pool's state is stored.
+
+use anyhow::Result;
+use penumbra_chain::{sync::CompactBlock, KnownAssets, NoteSource, View as _};
+use penumbra_crypto::{asset, merkle, note, Nullifier};
+use penumbra_storage::{State, StateKey, StateRead};
+
+use crate::{CommissionAmounts, View as _};
+
+const DOMAIN: &str = "shielded_pool";
+
+pub fn token_supply(asset_id: &asset::Id) -> StateKey<u64> {
+    StateKey::new(DOMAIN)
+        .join("assets")
+        .join(asset_id)
+        .join("token_supply")
+}
+
+pub fn known_assets() -> StateKey<KnownAssets> {
+    StateKey::new(DOMAIN).join("known_assets")
+}
+
+pub fn denom_by_asset(asset_id: &asset::Id) -> StateKey<asset::Denom> {
+    StateKey::new(DOMAIN)
+        .join("assets")
+        .join(asset_id)
+        .join("denom")
+}
+
+pub fn note_source(note_commitment: &note::Commitment) -> StateKey<NoteSource> {
+    StateKey::new(DOMAIN)
+        .join("note_source")
+        .join(note_commitment)
+}
+
+pub fn compact_block(height: u64) -> StateKey<CompactBlock> {
+    StateKey::new(DOMAIN).join("compact_block").join(height)
+}
+
+pub fn anchor_by_height(height: u64) -> StateKey<merkle::Root> {
+    StateKey::new(DOMAIN).join("nct_anchor").join(height)
+}
+
+/// The value is the height at which the anchor was the NCT root.
+pub fn anchor_lookup(anchor: &merkle::Root) -> StateKey<u64> {
+    StateKey::new(DOMAIN).join("valid_anchors").join(anchor)
+}
+
+/// The value is the encoded [`NoteSource`] of the spend.
+pub fn spent_nullifier_lookup(nullifier: &Nullifier) -> StateKey<Vec<u8>> {
+    StateKey::new(DOMAIN)
+        .join("spent_nullifiers")
+        .join(nullifier)
+}
+
+/// The value is the bincode-encoded note commitment tree.
+pub fn note_commitment_tree() -> StateKey<Vec<u8>> {
+    StateKey::new(DOMAIN).join("nct_data")
+}
+
+// Commission amounts were written by the staking component before they moved here, so they stay
+// in its domain to keep existing chain state readable.
+pub fn commission_amounts(height: u64) -> StateKey<CommissionAmounts> {
+    StateKey::new("staking")
+        .join("commission_amounts")
+        .join(height)
+}
+
+/// Returns the keys which may have been written before the key index existed, for
+/// [`reindex_keys`](penumbra_storage::reindex_keys).
+///
+/// Per-block keys are listed for every height up to the current one, and the keys derived from
+/// notes, nullifiers and anchors are found by reading back each block's compact block and anchor.
+pub async fn existing_state_keys(state: &State) -> Result<Vec<String>> {
+    let mut keys = vec![
+        known_assets().to_string(),
+        note_commitment_tree().to_string(),
+    ];
+    for asset in state.known_assets().await?.0 {
+        keys.push(token_supply(&asset.id).to_string());
+        keys.push(denom_by_asset(&asset.id).to_string());
+    }
+
+    for height in 0..=state.get_block_height().await? {
+        keys.push(compact_block(height).to_string());
+        keys.push(anchor_by_height(height).to_string());
+        keys.push(commission_amounts(height).to_string());
+
+        if let Some(anchor) = state.get_typed(&anchor_by_height(height)).await? {
+            keys.push(anchor_lookup(&anchor).to_string());
+        }
+        if let Some(compact_block) = state.compact_block(height).await? {
+            for output in compact_block.outputs {
+                keys.push(note_source(&output.note_commitment).to_string());
+            }
+            for nullifier in compact_block.nullifiers {
+                keys.push(spent_nullifier_lookup(&nullifier).to_string());
+            }
+        }
+    }
+
+    Ok(keys)
+}
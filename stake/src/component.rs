@@ -8,7 +8,7 @@ use penumbra_component::Component;
 use penumbra_crypto::{DelegationToken, IdentityKey, STAKING_TOKEN_ASSET_ID};
 use penumbra_proto::Protobuf;
 use penumbra_shielded_pool::{CommissionAmount, CommissionAmounts, View as _};
-use penumbra_storage::{State, StateExt};
+use penumbra_storage::{State, StateExt, StateRead};
 use penumbra_transaction::{
     action::{Delegate, Undelegate},
     Action, Transaction,
@@ -25,6 +25,7 @@ use tracing::instrument;
 
 use crate::{
     rate::{BaseRateData, RateData},
+    state_key,
     validator::{self, Validator},
     DelegationChanges, Uptime,
 };
@@ -33,6 +34,14 @@ use crate::{
 // https://github.com/tendermint/tendermint/blob/master/types/validator_set.go#L25
 const MAX_VOTING_POWER: i64 = 1152921504606846975;
 
+/// Computes the address by which Tendermint identifies a validator in commit info and evidence,
+/// which is the SHA-256 hash of its consensus key, truncated to 20 bytes.
+fn validator_address(consensus_key: &PublicKey) -> [u8; 20] {
+    Sha256::digest(&consensus_key.to_bytes()).as_slice()[0..20]
+        .try_into()
+        .unwrap()
+}
+
 // Staking component
 pub struct Staking {
     state: State,
@@ -423,12 +432,7 @@ impl Staking {
                 .ok_or_else(|| anyhow::anyhow!("validator missing status"))?;
 
             if info.status.state == validator::State::Active {
-                // for some reason last_commit_info has truncated sha256 hashes
-                let ck_bytes = info.validator.consensus_key.to_bytes();
-                let addr: [u8; 20] = Sha256::digest(&ck_bytes).as_slice()[0..20]
-                    .try_into()
-                    .unwrap();
-
+                let addr = validator_address(&info.validator.consensus_key);
                 let voted = did_address_vote.get(&addr).cloned().unwrap_or(false);
                 let mut uptime = self
                     .state
@@ -515,16 +519,29 @@ impl Staking {
     }
 
     async fn slash_validator_by_evidence(&mut self, evidence: &Evidence) -> Result<()> {
-        let ck = tendermint::PublicKey::from_raw_ed25519(&evidence.validator.address)
-            .ok_or_else(|| anyhow::anyhow!("invalid ed25519 consensus pubkey from tendermint"))
-            .unwrap();
-
+        // Evidence identifies the validator by its address, not its consensus key.
         let validator = self
             .state
-            .validator_by_consensus_key(&ck)
+            .validator_by_address(&evidence.validator.address)
             .await?
             .ok_or_else(|| anyhow::anyhow!("attempted to slash validator not found in JMT"))?;
 
+        // Evidence can arrive after the misbehaving validator has left the consensus set and
+        // finished unbonding, or after it has already been slashed (for instance, by earlier
+        // evidence in the same block), in which case there's nothing left to slash.
+        let state = self
+            .state
+            .validator_state(&validator.identity_key)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("validator missing state"))?;
+        if !matches!(
+            state,
+            validator::State::Active | validator::State::Unbonding { .. }
+        ) {
+            tracing::info!(identity_key = ?validator.identity_key, ?state, "ignoring evidence against validator which can't be slashed");
+            return Ok(());
+        }
+
         let slashing_penalty = self
             .state
             .get_chain_params()
@@ -945,60 +962,59 @@ impl Component for Staking {
 ///
 /// TODO: should this be split into Read and Write traits?
 #[async_trait]
-pub trait View: StateExt {
+pub trait View: StateRead {
     async fn current_base_rate(&self) -> Result<BaseRateData> {
-        self.get_domain("staking/base_rate/current".into())
+        self.get_typed(&state_key::current_base_rate())
             .await
             .map(|rate_data| rate_data.expect("rate data must be set after init_chain"))
     }
 
     async fn next_base_rate(&self) -> Result<BaseRateData> {
-        self.get_domain("staking/base_rate/next".into())
+        self.get_typed(&state_key::next_base_rate())
             .await
             .map(|rate_data| rate_data.expect("rate data must be set after init_chain"))
     }
 
     #[instrument(skip(self))]
-    async fn set_base_rates(&self, current: BaseRateData, next: BaseRateData) {
+    async fn set_base_rates(&self, current: BaseRateData, next: BaseRateData)
+    where
+        Self: StateExt,
+    {
         tracing::debug!("setting base rates");
-        self.put_domain("staking/base_rate/current".into(), current)
+        self.put_typed(&state_key::current_base_rate(), current)
             .await;
-        self.put_domain("staking/base_rate/next".into(), next).await;
+        self.put_typed(&state_key::next_base_rate(), next).await;
     }
 
     async fn current_validator_rate(&self, identity_key: &IdentityKey) -> Result<Option<RateData>> {
-        self.get_domain(format!("staking/validators/{}/rate/current", identity_key).into())
+        self.get_typed(&state_key::current_rate_by_validator(identity_key))
             .await
     }
 
     async fn next_validator_rate(&self, identity_key: &IdentityKey) -> Result<Option<RateData>> {
-        self.get_domain(format!("staking/validators/{}/rate/next", identity_key).into())
+        self.get_typed(&state_key::next_rate_by_validator(identity_key))
             .await
     }
 
     #[instrument(skip(self))]
-    async fn set_validator_power(
-        &self,
-        identity_key: &IdentityKey,
-        voting_power: u64,
-    ) -> Result<()> {
+    async fn set_validator_power(&self, identity_key: &IdentityKey, voting_power: u64) -> Result<()>
+    where
+        Self: StateExt,
+    {
         tracing::debug!("setting validator power");
         if voting_power as i64 > MAX_VOTING_POWER || (voting_power as i64) < 0 {
             return Err(anyhow::anyhow!("invalid voting power"));
         }
 
-        self.put_proto(
-            format!("staking/validators/{}/power", identity_key).into(),
-            voting_power,
-        )
-        .await;
+        self.put_typed_proto(&state_key::power_by_validator(identity_key), voting_power)
+            .await;
 
         Ok(())
     }
 
     #[instrument(skip(self))]
     async fn validator_power(&self, identity_key: &IdentityKey) -> Result<Option<u64>> {
-        self.get_proto(format!("staking/validators/{}/power", identity_key).into())
+        self.get_typed_proto(&state_key::power_by_validator(identity_key))
             .await
     }
 
@@ -1008,18 +1024,17 @@ pub trait View: StateExt {
         identity_key: &IdentityKey,
         current_rates: RateData,
         next_rates: RateData,
-    ) {
+    ) where
+        Self: StateExt,
+    {
         tracing::debug!("setting validator rates");
-        self.put_domain(
-            format!("staking/validators/{}/rate/current", identity_key).into(),
+        self.put_typed(
+            &state_key::current_rate_by_validator(identity_key),
             current_rates,
         )
         .await;
-        self.put_domain(
-            format!("staking/validators/{}/rate/next", identity_key).into(),
-            next_rates,
-        )
-        .await;
+        self.put_typed(&state_key::next_rate_by_validator(identity_key), next_rates)
+            .await;
     }
 
     #[instrument(skip(self))]
@@ -1027,7 +1042,10 @@ pub trait View: StateExt {
         &self,
         identity_key: &IdentityKey,
         state: validator::State,
-    ) -> Result<()> {
+    ) -> Result<()>
+    where
+        Self: StateExt,
+    {
         // Enforce state machine semantics here and update voting powers
         // for tendermint appropriately
 
@@ -1083,18 +1101,14 @@ pub trait View: StateExt {
         };
 
         tracing::debug!("setting validator state");
-        self.put_domain(
-            format!("staking/validators/{}/state", identity_key).into(),
-            state,
-        )
-        .await;
+        self.put_typed(&state_key::state_by_validator(identity_key), state)
+            .await;
 
         Ok(())
     }
 
     async fn validator(&self, identity_key: &IdentityKey) -> Result<Option<Validator>> {
-        self.get_domain(format!("staking/validators/{}", identity_key).into())
-            .await
+        self.get_typed(&state_key::validator(identity_key)).await
     }
 
     // Tendermint validators are referenced to us by their Tendermint consensus key,
@@ -1102,8 +1116,8 @@ pub trait View: StateExt {
     async fn validator_by_consensus_key(&self, ck: &PublicKey) -> Result<Option<Validator>> {
         // We maintain an internal mapping of consensus keys to identity keys to make this
         // lookup more efficient.
-        let identity_key: Option<IdentityKey> = self
-            .get_domain(format!("staking/consensus_key/{}", ck.to_hex()).into())
+        let identity_key = self
+            .get_typed(&state_key::validator_id_by_consensus_key(ck))
             .await?;
 
         if identity_key.is_none() {
@@ -1115,7 +1129,23 @@ pub trait View: StateExt {
         self.validator(&identity_key).await
     }
 
-    async fn slash_validator(&self, validator: Validator, slashing_penalty: u64) -> Result<()> {
+    /// Finds the validator with the given Tendermint address, as used in evidence.
+    async fn validator_by_address(&self, address: &[u8; 20]) -> Result<Option<Validator>> {
+        // Evidence is rare, so there's no index of addresses; just check every validator.
+        for identity_key in self.validator_list().await? {
+            if let Some(validator) = self.validator(&identity_key).await? {
+                if &validator_address(&validator.consensus_key) == address {
+                    return Ok(Some(validator));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    async fn slash_validator(&self, validator: Validator, slashing_penalty: u64) -> Result<()>
+    where
+        Self: StateExt,
+    {
         tracing::info!(?validator, ?slashing_penalty, "slashing validator");
 
         // Mark the state as "slashed" in the JMT, and apply the slashing penalty.
@@ -1147,7 +1177,10 @@ pub trait View: StateExt {
     }
 
     // Used for updating an existing validator's definition.
-    async fn update_validator(&self, validator: Validator) -> Result<()> {
+    async fn update_validator(&self, validator: Validator) -> Result<()>
+    where
+        Self: StateExt,
+    {
         tracing::debug!(?validator);
         let id = validator.identity_key.clone();
         // If the validator isn't already in the JMT, we can't update it.
@@ -1155,8 +1188,7 @@ pub trait View: StateExt {
             .await?
             .ok_or_else(|| anyhow::anyhow!("updated validator not found in JMT"))?;
 
-        self.put_domain(format!("staking/validators/{}", id).into(), validator)
-            .await;
+        self.put_typed(&state_key::validator(&id), validator).await;
 
         Ok(())
     }
@@ -1171,12 +1203,14 @@ pub trait View: StateExt {
         next_rates: RateData,
         state: validator::State,
         power: u64,
-    ) -> Result<()> {
+    ) -> Result<()>
+    where
+        Self: StateExt,
+    {
         tracing::debug!(?validator);
         let id = validator.identity_key.clone();
 
-        self.put_domain(format!("staking/validators/{}", id).into(), validator)
-            .await;
+        self.put_typed(&state_key::validator(&id), validator).await;
         self.register_denom(&DelegationToken::from(&id).denom())
             .await?;
 
@@ -1185,7 +1219,7 @@ pub trait View: StateExt {
 
         // We can't call `set_validator_state` here because it requires an existing validator state,
         // so we manually initialize the state for new validators.
-        self.put_domain(format!("staking/validators/{}/state", &id).into(), state)
+        self.put_typed(&state_key::state_by_validator(&id), state)
             .await;
         self.set_validator_power(&id, power).await?;
 
@@ -1215,7 +1249,7 @@ pub trait View: StateExt {
         &self,
         identity_key: &IdentityKey,
     ) -> Result<Option<validator::State>> {
-        self.get_domain(format!("staking/validators/{}/state", identity_key).into())
+        self.get_typed(&state_key::state_by_validator(identity_key))
             .await
     }
 
@@ -1239,46 +1273,49 @@ pub trait View: StateExt {
 
     async fn validator_list(&self) -> Result<Vec<IdentityKey>> {
         Ok(self
-            .get_domain("staking/validators/list".into())
+            .get_typed(&state_key::validator_list())
             .await?
             .map(|list: validator::List| list.0)
             .unwrap_or_default())
     }
 
-    async fn set_validator_list(&self, validators: Vec<IdentityKey>) {
-        self.put_domain(
-            "staking/validators/list".into(),
-            validator::List(validators),
-        )
-        .await;
+    async fn set_validator_list(&self, validators: Vec<IdentityKey>)
+    where
+        Self: StateExt,
+    {
+        self.put_typed(&state_key::validator_list(), validator::List(validators))
+            .await;
     }
 
     async fn delegation_changes(&self, height: block::Height) -> Result<DelegationChanges> {
         Ok(self
-            .get_domain(format!("staking/delegation_changes/{}", height.value()).into())
+            .get_typed(&state_key::delegation_changes_by_height(height.value()))
             .await?
             .ok_or_else(|| anyhow!("missing delegation changes for block {}", height))?)
     }
 
-    async fn set_delegation_changes(&self, height: block::Height, changes: DelegationChanges) {
-        self.put_domain(
-            format!("staking/delegation_changes/{}", height.value()).into(),
+    async fn set_delegation_changes(&self, height: block::Height, changes: DelegationChanges)
+    where
+        Self: StateExt,
+    {
+        self.put_typed(
+            &state_key::delegation_changes_by_height(height.value()),
             changes,
         )
         .await
     }
 
     async fn validator_uptime(&self, identity_key: &IdentityKey) -> Result<Option<Uptime>> {
-        self.get_domain(format!("staking/validator_uptime/{}", identity_key).into())
+        self.get_typed(&state_key::uptime_by_validator(identity_key))
             .await
     }
 
-    async fn set_validator_uptime(&self, identity_key: &IdentityKey, uptime: Uptime) {
-        self.put_domain(
-            format!("staking/validator_uptime/{}", identity_key).into(),
-            uptime,
-        )
-        .await
+    async fn set_validator_uptime(&self, identity_key: &IdentityKey, uptime: Uptime)
+    where
+        Self: StateExt,
+    {
+        self.put_typed(&state_key::uptime_by_validator(identity_key), uptime)
+            .await
     }
 
     async fn signed_blocks_window_len(&self) -> Result<u64> {
@@ -1290,4 +1327,4 @@ pub trait View: StateExt {
     }
 }
 
-impl<T: StateExt + Send + Sync> View for T {}
+impl<T: StateRead + Send + Sync> View for T {}
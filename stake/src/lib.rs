@@ -3,6 +3,7 @@ use penumbra_crypto::IdentityKey;
 
 mod changes;
 mod funding_stream;
+mod state_key;
 mod uptime;
 
 pub mod component;
@@ -11,4 +12,5 @@ pub mod validator;
 
 pub use changes::DelegationChanges;
 pub use funding_stream::{FundingStream, FundingStreams};
+pub use state_key::existing_state_keys;
 pub use uptime::Uptime;
@@ -0,0 +1,108 @@
+//! The keys under which the staking state is stored.
+
+use anyhow::Result;
+use penumbra_chain::View as _;
+use penumbra_crypto::IdentityKey;
+use penumbra_storage::{State, StateKey};
+use tendermint::PublicKey;
+
+use crate::{
+    component::View as _,
+    rate::{BaseRateData, RateData},
+    validator::{self, Validator},
+    DelegationChanges, Uptime,
+};
+
+const DOMAIN: &str = "staking";
+
+pub fn current_base_rate() -> StateKey<BaseRateData> {
+    StateKey::new(DOMAIN).join("base_rate").join("current")
+}
+
+pub fn next_base_rate() -> StateKey<BaseRateData> {
+    StateKey::new(DOMAIN).join("base_rate").join("next")
+}
+
+pub fn validator(id: &IdentityKey) -> StateKey<Validator> {
+    StateKey::new(DOMAIN).join("validators").join(id)
+}
+
+pub fn current_rate_by_validator(id: &IdentityKey) -> StateKey<RateData> {
+    StateKey::new(DOMAIN)
+        .join("validators")
+        .join(id)
+        .join("rate")
+        .join("current")
+}
+
+pub fn next_rate_by_validator(id: &IdentityKey) -> StateKey<RateData> {
+    StateKey::new(DOMAIN)
+        .join("validators")
+        .join(id)
+        .join("rate")
+        .join("next")
+}
+
+pub fn power_by_validator(id: &IdentityKey) -> StateKey<u64> {
+    StateKey::new(DOMAIN)
+        .join("validators")
+        .join(id)
+        .join("power")
+}
+
+pub fn state_by_validator(id: &IdentityKey) -> StateKey<validator::State> {
+    StateKey::new(DOMAIN)
+        .join("validators")
+        .join(id)
+        .join("state")
+}
+
+pub fn validator_id_by_consensus_key(ck: &PublicKey) -> StateKey<IdentityKey> {
+    StateKey::new(DOMAIN)
+        .join("consensus_key")
+        .join(ck.to_hex())
+}
+
+pub fn validator_list() -> StateKey<validator::List> {
+    StateKey::new(DOMAIN).join("validators").join("list")
+}
+
+pub fn delegation_changes_by_height(height: u64) -> StateKey<DelegationChanges> {
+    StateKey::new(DOMAIN)
+        .join("delegation_changes")
+        .join(height)
+}
+
+pub fn uptime_by_validator(id: &IdentityKey) -> StateKey<Uptime> {
+    StateKey::new(DOMAIN).join("validator_uptime").join(id)
+}
+
+/// Returns the keys which may have been written before the key index existed, for
+/// [`reindex_keys`](penumbra_storage::reindex_keys).
+///
+/// Per-validator keys are listed for every validator in the validator list, and delegation changes
+/// for every height up to the current one.
+pub async fn existing_state_keys(state: &State) -> Result<Vec<String>> {
+    let mut keys = vec![
+        current_base_rate().to_string(),
+        next_base_rate().to_string(),
+        validator_list().to_string(),
+    ];
+    for id in state.validator_list().await? {
+        keys.push(validator(&id).to_string());
+        keys.push(current_rate_by_validator(&id).to_string());
+        keys.push(next_rate_by_validator(&id).to_string());
+        keys.push(power_by_validator(&id).to_string());
+        keys.push(state_by_validator(&id).to_string());
+        keys.push(uptime_by_validator(&id).to_string());
+        if let Some(validator) = state.validator(&id).await? {
+            keys.push(validator_id_by_consensus_key(&validator.consensus_key).to_string());
+        }
+    }
+
+    for height in 0..=state.get_block_height().await? {
+        keys.push(delegation_changes_by_height(height).to_string());
+    }
+
+    Ok(keys)
+}
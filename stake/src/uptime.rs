@@ -81,6 +81,16 @@ impl Uptime {
     pub fn num_missed_blocks(&self) -> usize {
         self.signatures.iter_zeros().len()
     }
+
+    /// The height of the last block whose signature was recorded.
+    pub fn as_of_block_height(&self) -> u64 {
+        self.as_of_block_height
+    }
+
+    /// The number of blocks over which missed blocks are counted.
+    pub fn window_len(&self) -> usize {
+        self.signatures.len()
+    }
 }
 
 impl Protobuf<pb::Uptime> for Uptime {}
@@ -128,6 +138,8 @@ mod tests {
             uptime.mark_height_as_signed(h as u64, h % 4 != 0).unwrap();
         }
         assert_eq!(uptime.num_missed_blocks(), window / 4);
+        assert_eq!(uptime.as_of_block_height(), window as u64);
+        assert_eq!(uptime.window_len(), window);
 
         // Now miss no blocks and check that the old data is forgotten
         for h in (window + 1)..(2 * window + 1) {
@@ -0,0 +1,109 @@
+use std::fmt::Debug;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use jmt::storage::{LeafNode, Node, NodeBatch, NodeKey};
+
+mod memory;
+mod rocksdb;
+
+pub use self::rocksdb::RocksDb;
+pub use memory::Memory;
+
+/// A database in which the nodes of the Jellyfish Merkle Tree are persisted.
+///
+/// The JMT is versioned by block height, and every node key begins with the version at which the
+/// node was written, so backends need only provide ordered storage of encoded nodes: the
+/// [`Storage`](crate::Storage) handle takes care of building the tree on top.
+///
+/// Since the tree is keyed by the hashes of the keys, backends also keep an index of the keys
+/// themselves, which is used to iterate over the keys with a given prefix.
+#[async_trait]
+pub trait StateBackend: Debug + Send + Sync + 'static {
+    /// Gets the node with the given key, or `None` if it does not exist.
+    async fn get_node(&self, node_key: &NodeKey) -> Result<Option<Node>>;
+
+    /// Writes a batch of nodes.
+    async fn put_nodes(&self, node_batch: NodeBatch) -> Result<()>;
+
+    /// Returns the keys of all nodes written at versions before `version`.
+    async fn node_keys_before(&self, version: jmt::Version) -> Result<Vec<NodeKey>>;
+
+    /// Deletes the nodes with the given keys.
+    async fn delete_nodes(&self, node_keys: Vec<NodeKey>) -> Result<()>;
+
+    /// Records in the stale node index that the nodes with the given keys were replaced at
+    /// version `stale_since`, so are only reachable from the versions before it.
+    async fn put_stale_nodes(
+        &self,
+        stale_since: jmt::Version,
+        node_keys: Vec<NodeKey>,
+    ) -> Result<()>;
+
+    /// Returns every entry of the stale node index for nodes replaced at or before `version`,
+    /// with the version at which each was replaced, in order of that version.
+    async fn stale_nodes_through(
+        &self,
+        version: jmt::Version,
+    ) -> Result<Vec<(jmt::Version, NodeKey)>>;
+
+    /// Deletes the given stale nodes together with their entries in the stale node index.
+    async fn delete_stale_nodes(&self, entries: Vec<(jmt::Version, NodeKey)>) -> Result<()>;
+
+    /// Removes the given entries from the stale node index without deleting their nodes, which
+    /// are to be kept for good.
+    async fn forget_stale_nodes(&self, entries: Vec<(jmt::Version, NodeKey)>) -> Result<()>;
+
+    /// Writes an entry to the write-ahead log, replacing any existing entry.
+    ///
+    /// The entry must be written atomically: after a crash, either the whole entry or no entry
+    /// should be read back.
+    async fn write_wal(&self, entry: Vec<u8>) -> Result<()>;
+
+    /// Reads the entry in the write-ahead log, if there is one.
+    async fn read_wal(&self) -> Result<Option<Vec<u8>>>;
+
+    /// Removes the entry from the write-ahead log.
+    async fn clear_wal(&self) -> Result<()>;
+
+    /// Records that the given (unhashed) keys have been written to the tree.
+    async fn put_keys(&self, keys: Vec<String>) -> Result<()>;
+
+    /// Returns, in order, every recorded key which begins with `prefix`.
+    async fn keys_with_prefix(&self, prefix: &str) -> Result<Vec<String>>;
+
+    /// Gets the leaf node with the greatest node key, if the tree is nonempty.
+    async fn get_rightmost_leaf(&self) -> Result<Option<(NodeKey, LeafNode)>>;
+
+    /// Compacts the underlying storage, reclaiming the space used by deleted and overwritten
+    /// entries and reducing the work done by subsequent reads.
+    ///
+    /// Backends which don't need compaction can rely on the default, which does nothing.
+    async fn compact(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Writes any buffered writes through to durable storage, so that the backend can be closed
+    /// without relying on recovery when it's next opened.
+    ///
+    /// Backends which don't buffer writes can rely on the default, which does nothing.
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Returns the approximate size of the stored state in bytes, or `None` if the backend can't
+    /// measure it cheaply.
+    ///
+    /// Backends which can't measure their size can rely on the default, which returns `None`.
+    async fn size(&self) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Returns the latest version of the tree, or `None` if the tree is empty.
+    async fn latest_version(&self) -> Result<Option<jmt::Version>> {
+        Ok(self
+            .get_rightmost_leaf()
+            .await?
+            .map(|(node_key, _)| node_key.version()))
+    }
+}
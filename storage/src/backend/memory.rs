@@ -0,0 +1,149 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use jmt::storage::{LeafNode, Node, NodeBatch, NodeKey};
+use tokio::sync::RwLock;
+
+use super::StateBackend;
+
+/// A [`StateBackend`] which holds the tree in memory.
+///
+/// Nothing is persisted, so this is only suitable for tests and ephemeral nodes.
+#[derive(Clone, Debug, Default)]
+pub struct Memory {
+    nodes: Arc<RwLock<BTreeMap<Vec<u8>, Vec<u8>>>>,
+    keys: Arc<RwLock<BTreeSet<String>>>,
+    wal: Arc<RwLock<Option<Vec<u8>>>>,
+    /// The stale node index, holding the version at which each node was replaced and its encoded
+    /// key.
+    stale: Arc<RwLock<BTreeSet<(jmt::Version, Vec<u8>)>>>,
+}
+
+#[async_trait]
+impl StateBackend for Memory {
+    async fn get_node(&self, node_key: &NodeKey) -> Result<Option<Node>> {
+        self.nodes
+            .read()
+            .await
+            .get(&node_key.encode()?)
+            .map(|bytes| Node::decode(bytes))
+            .transpose()
+    }
+
+    async fn put_nodes(&self, node_batch: NodeBatch) -> Result<()> {
+        let mut nodes = self.nodes.write().await;
+        for (node_key, node) in node_batch {
+            nodes.insert(node_key.encode()?, node.encode()?);
+        }
+        Ok(())
+    }
+
+    async fn node_keys_before(&self, version: jmt::Version) -> Result<Vec<NodeKey>> {
+        let mut node_keys = Vec::new();
+        for key_bytes in self.nodes.read().await.keys() {
+            let node_key = NodeKey::decode(key_bytes)?;
+            if node_key.version() >= version {
+                break;
+            }
+            node_keys.push(node_key);
+        }
+        Ok(node_keys)
+    }
+
+    async fn delete_nodes(&self, node_keys: Vec<NodeKey>) -> Result<()> {
+        let mut nodes = self.nodes.write().await;
+        for node_key in node_keys {
+            nodes.remove(&node_key.encode()?);
+        }
+        Ok(())
+    }
+
+    async fn put_stale_nodes(
+        &self,
+        stale_since: jmt::Version,
+        node_keys: Vec<NodeKey>,
+    ) -> Result<()> {
+        let mut stale = self.stale.write().await;
+        for node_key in node_keys {
+            stale.insert((stale_since, node_key.encode()?));
+        }
+        Ok(())
+    }
+
+    async fn stale_nodes_through(
+        &self,
+        version: jmt::Version,
+    ) -> Result<Vec<(jmt::Version, NodeKey)>> {
+        self.stale
+            .read()
+            .await
+            .iter()
+            .take_while(|(stale_since, _)| *stale_since <= version)
+            .map(|(stale_since, key_bytes)| Ok((*stale_since, NodeKey::decode(key_bytes)?)))
+            .collect()
+    }
+
+    async fn delete_stale_nodes(&self, entries: Vec<(jmt::Version, NodeKey)>) -> Result<()> {
+        let mut nodes = self.nodes.write().await;
+        let mut stale = self.stale.write().await;
+        for (stale_since, node_key) in entries {
+            let key_bytes = node_key.encode()?;
+            nodes.remove(&key_bytes);
+            stale.remove(&(stale_since, key_bytes));
+        }
+        Ok(())
+    }
+
+    async fn forget_stale_nodes(&self, entries: Vec<(jmt::Version, NodeKey)>) -> Result<()> {
+        let mut stale = self.stale.write().await;
+        for (stale_since, node_key) in entries {
+            stale.remove(&(stale_since, node_key.encode()?));
+        }
+        Ok(())
+    }
+
+    async fn write_wal(&self, entry: Vec<u8>) -> Result<()> {
+        *self.wal.write().await = Some(entry);
+        Ok(())
+    }
+
+    async fn read_wal(&self) -> Result<Option<Vec<u8>>> {
+        Ok(self.wal.read().await.clone())
+    }
+
+    async fn clear_wal(&self) -> Result<()> {
+        *self.wal.write().await = None;
+        Ok(())
+    }
+
+    async fn put_keys(&self, keys: Vec<String>) -> Result<()> {
+        self.keys.write().await.extend(keys);
+        Ok(())
+    }
+
+    async fn keys_with_prefix(&self, prefix: &str) -> Result<Vec<String>> {
+        Ok(self
+            .keys
+            .read()
+            .await
+            .range(prefix.to_string()..)
+            .take_while(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_rightmost_leaf(&self) -> Result<Option<(NodeKey, LeafNode)>> {
+        // Encoded node keys sort in the same order as the node keys, like they do in RocksDB.
+        match self.nodes.read().await.iter().next_back() {
+            Some((key_bytes, value_bytes)) => match Node::decode(value_bytes)? {
+                Node::Leaf(leaf_node) => Ok(Some((NodeKey::decode(key_bytes)?, leaf_node))),
+                _ => Ok(None),
+            },
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,578 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use jmt::storage::{LeafNode, Node, NodeBatch, NodeKey};
+use rocksdb::{Direction, IteratorMode, Options, WriteBatch, WriteOptions, DB};
+use tracing::{instrument, Span};
+
+use super::StateBackend;
+
+/// The column family holding the index of unhashed keys.
+///
+/// It's created empty when opening a database written before the index existed, so the keys
+/// already in the tree must be indexed by a migration; see [`reindex_keys`](crate::reindex_keys).
+///
+/// Nodes are kept in the default column family, which must contain nothing else, since the
+/// rightmost leaf is found by seeking to its end.
+const KEYS_CF: &str = "keys";
+
+/// The column family holding the write-ahead log, which has at most one entry, under [`WAL_KEY`].
+const WAL_CF: &str = "wal";
+const WAL_KEY: &[u8] = b"pending";
+
+/// The column family holding a CRC-32 checksum of each node, under the same key as the node.
+///
+/// Nodes written before checksums were introduced have no checksum, and are never verified.
+const CHECKSUMS_CF: &str = "checksums";
+
+/// The column family holding the stale node index, keyed by the big-endian version at which each
+/// node was replaced followed by its encoded node key, so that entries are ordered by that version.
+///
+/// It's created empty when opening a database written before the index existed, so nodes
+/// replaced before then are never pruned.
+const STALE_CF: &str = "stale";
+
+/// Encodes the key of an entry in the stale node index.
+fn stale_key(stale_since: jmt::Version, node_key: &NodeKey) -> Result<Vec<u8>> {
+    let mut key_bytes = stale_since.to_be_bytes().to_vec();
+    key_bytes.extend_from_slice(&node_key.encode()?);
+    Ok(key_bytes)
+}
+
+/// A [`StateBackend`] which persists the tree in a RocksDB database.
+#[derive(Clone, Debug)]
+pub struct RocksDb {
+    db: Arc<DB>,
+    verify_checksums: bool,
+}
+
+impl RocksDb {
+    /// Opens the RocksDB database at `path`, creating it if it does not exist.
+    pub async fn open(path: PathBuf) -> Result<Self> {
+        let span = Span::current();
+        tokio::task::Builder::new()
+            .name("open_rocksdb")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    tracing::info!(?path, "opening rocksdb");
+                    let mut opts = Options::default();
+                    opts.create_if_missing(true);
+                    opts.create_missing_column_families(true);
+                    Ok(Self {
+                        db: Arc::new(DB::open_cf(
+                            &opts,
+                            path,
+                            [KEYS_CF, WAL_CF, CHECKSUMS_CF, STALE_CF],
+                        )?),
+                        verify_checksums: false,
+                    })
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    /// Verifies the checksum of every node read from the database, so that a node corrupted on
+    /// disk is reported as an error rather than being used to compute an incorrect root hash.
+    ///
+    /// Checksums are always written, but are only verified if this is enabled.
+    pub fn verify_checksums(mut self, enabled: bool) -> Self {
+        self.verify_checksums = enabled;
+        self
+    }
+}
+
+/// Computes the checksum of a node, covering its key as well as its contents so that a node
+/// stored under the wrong key is also detected.
+fn checksum(key_bytes: &[u8], value_bytes: &[u8]) -> [u8; 4] {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(key_bytes);
+    hasher.update(value_bytes);
+    hasher.finalize().to_le_bytes()
+}
+
+/// Checks the stored checksum of a node, if it has one.
+fn verify_checksum(db: &DB, key_bytes: &[u8], value_bytes: &[u8]) -> Result<()> {
+    let cf = db
+        .cf_handle(CHECKSUMS_CF)
+        .expect("checksums column family is created on open");
+    let stored = match db.get_pinned_cf(cf, key_bytes)? {
+        Some(stored) => stored,
+        None => return Ok(()),
+    };
+    let computed = checksum(key_bytes, value_bytes);
+    if stored.as_ref() != computed {
+        let node_key = NodeKey::decode(key_bytes)?;
+        return Err(anyhow!(
+            "checksum mismatch for node {:?} at version {}: stored {}, computed {}",
+            node_key,
+            node_key.version(),
+            hex::encode(stored.as_ref()),
+            hex::encode(computed)
+        ));
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl StateBackend for RocksDb {
+    #[instrument(skip(self))]
+    async fn get_node(&self, node_key: &NodeKey) -> Result<Option<Node>> {
+        let db = self.db.clone();
+        let node_key = node_key.clone();
+        let verify_checksums = self.verify_checksums;
+
+        // The reads have to happen on a separate spawn_blocking task, but we
+        // want tracing events to occur in the context of the current span, so
+        // propagate it explicitly:
+        let span = Span::current();
+
+        tokio::task::Builder::new()
+            .name("RocksDb::get_node")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    let key_bytes = node_key.encode()?;
+                    let value = match db.get_pinned(&key_bytes)? {
+                        Some(db_slice) => {
+                            if verify_checksums {
+                                verify_checksum(&db, &key_bytes, &db_slice)?;
+                            }
+                            Some(Node::decode(&db_slice)?)
+                        }
+                        None => None,
+                    };
+
+                    tracing::trace!(?node_key, ?value);
+                    Ok(value)
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    #[instrument(skip(self, node_batch))]
+    async fn put_nodes(&self, node_batch: NodeBatch) -> Result<()> {
+        let db = self.db.clone();
+        let span = Span::current();
+
+        tokio::task::Builder::new()
+            .name("RocksDb::put_nodes")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    let checksums_cf = db
+                        .cf_handle(CHECKSUMS_CF)
+                        .expect("checksums column family is created on open");
+                    let mut batch = WriteBatch::default();
+                    for (node_key, node) in node_batch {
+                        let key_bytes = &node_key.encode()?;
+                        let value_bytes = &node.encode()?;
+                        tracing::trace!(?key_bytes, value_bytes = ?hex::encode(&value_bytes));
+                        batch.put_cf(checksums_cf, key_bytes, checksum(key_bytes, value_bytes));
+                        batch.put(key_bytes, value_bytes);
+                    }
+                    db.write(batch)?;
+
+                    Ok(())
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    #[instrument(skip(self))]
+    async fn node_keys_before(&self, version: jmt::Version) -> Result<Vec<NodeKey>> {
+        let db = self.db.clone();
+        let span = Span::current();
+
+        tokio::task::Builder::new()
+            .name("RocksDb::node_keys_before")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    // Node keys begin with their version, so they are ordered by version.
+                    let mut node_keys = Vec::new();
+                    for (key_bytes, _) in db.iterator(IteratorMode::Start) {
+                        let node_key = NodeKey::decode(&key_bytes)?;
+                        if node_key.version() >= version {
+                            break;
+                        }
+                        node_keys.push(node_key);
+                    }
+                    Ok(node_keys)
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    #[instrument(skip(self, node_keys))]
+    async fn delete_nodes(&self, node_keys: Vec<NodeKey>) -> Result<()> {
+        let db = self.db.clone();
+        let span = Span::current();
+
+        tokio::task::Builder::new()
+            .name("RocksDb::delete_nodes")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    let checksums_cf = db
+                        .cf_handle(CHECKSUMS_CF)
+                        .expect("checksums column family is created on open");
+                    let mut batch = WriteBatch::default();
+                    for node_key in node_keys {
+                        let key_bytes = node_key.encode()?;
+                        batch.delete_cf(checksums_cf, &key_bytes);
+                        batch.delete(key_bytes);
+                    }
+                    db.write(batch)?;
+                    Ok(())
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    #[instrument(skip(self, node_keys))]
+    async fn put_stale_nodes(
+        &self,
+        stale_since: jmt::Version,
+        node_keys: Vec<NodeKey>,
+    ) -> Result<()> {
+        let db = self.db.clone();
+        let span = Span::current();
+
+        tokio::task::Builder::new()
+            .name("RocksDb::put_stale_nodes")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    let cf = db
+                        .cf_handle(STALE_CF)
+                        .expect("stale column family is created on open");
+                    let mut batch = WriteBatch::default();
+                    for node_key in &node_keys {
+                        batch.put_cf(cf, stale_key(stale_since, node_key)?, []);
+                    }
+                    db.write(batch)?;
+                    Ok(())
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    #[instrument(skip(self))]
+    async fn stale_nodes_through(
+        &self,
+        version: jmt::Version,
+    ) -> Result<Vec<(jmt::Version, NodeKey)>> {
+        let db = self.db.clone();
+        let span = Span::current();
+
+        tokio::task::Builder::new()
+            .name("RocksDb::stale_nodes_through")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    let cf = db
+                        .cf_handle(STALE_CF)
+                        .expect("stale column family is created on open");
+                    let mut entries = Vec::new();
+                    for (key_bytes, _) in db.iterator_cf(cf, IteratorMode::Start) {
+                        if key_bytes.len() < 8 {
+                            return Err(anyhow!("malformed stale node index entry"));
+                        }
+                        let (since_bytes, node_key_bytes) = key_bytes.split_at(8);
+                        let stale_since = jmt::Version::from_be_bytes(
+                            since_bytes.try_into().expect("length was checked"),
+                        );
+                        if stale_since > version {
+                            break;
+                        }
+                        entries.push((stale_since, NodeKey::decode(node_key_bytes)?));
+                    }
+                    Ok(entries)
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    #[instrument(skip(self, entries))]
+    async fn delete_stale_nodes(&self, entries: Vec<(jmt::Version, NodeKey)>) -> Result<()> {
+        let db = self.db.clone();
+        let span = Span::current();
+
+        tokio::task::Builder::new()
+            .name("RocksDb::delete_stale_nodes")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    let checksums_cf = db
+                        .cf_handle(CHECKSUMS_CF)
+                        .expect("checksums column family is created on open");
+                    let stale_cf = db
+                        .cf_handle(STALE_CF)
+                        .expect("stale column family is created on open");
+                    // Each node is deleted in the same write as its entry, so neither outlives
+                    // the other if pruning is interrupted.
+                    let mut batch = WriteBatch::default();
+                    for (stale_since, node_key) in &entries {
+                        let key_bytes = node_key.encode()?;
+                        batch.delete_cf(stale_cf, stale_key(*stale_since, node_key)?);
+                        batch.delete_cf(checksums_cf, &key_bytes);
+                        batch.delete(key_bytes);
+                    }
+                    db.write(batch)?;
+                    Ok(())
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    #[instrument(skip(self, entries))]
+    async fn forget_stale_nodes(&self, entries: Vec<(jmt::Version, NodeKey)>) -> Result<()> {
+        let db = self.db.clone();
+        let span = Span::current();
+
+        tokio::task::Builder::new()
+            .name("RocksDb::forget_stale_nodes")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    let cf = db
+                        .cf_handle(STALE_CF)
+                        .expect("stale column family is created on open");
+                    let mut batch = WriteBatch::default();
+                    for (stale_since, node_key) in &entries {
+                        batch.delete_cf(cf, stale_key(*stale_since, node_key)?);
+                    }
+                    db.write(batch)?;
+                    Ok(())
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    #[instrument(skip(self, entry), fields(len = entry.len()))]
+    async fn write_wal(&self, entry: Vec<u8>) -> Result<()> {
+        let db = self.db.clone();
+        let span = Span::current();
+
+        tokio::task::Builder::new()
+            .name("RocksDb::write_wal")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    let cf = db
+                        .cf_handle(WAL_CF)
+                        .expect("wal column family is created on open");
+                    // A single put is atomic, and must reach disk before any nodes are written.
+                    let mut opts = WriteOptions::default();
+                    opts.set_sync(true);
+                    db.put_cf_opt(cf, WAL_KEY, entry, &opts)?;
+                    Ok(())
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    async fn read_wal(&self) -> Result<Option<Vec<u8>>> {
+        let db = self.db.clone();
+        let span = Span::current();
+
+        tokio::task::Builder::new()
+            .name("RocksDb::read_wal")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    let cf = db
+                        .cf_handle(WAL_CF)
+                        .expect("wal column family is created on open");
+                    Ok(db.get_cf(cf, WAL_KEY)?)
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    async fn clear_wal(&self) -> Result<()> {
+        let db = self.db.clone();
+        let span = Span::current();
+
+        tokio::task::Builder::new()
+            .name("RocksDb::clear_wal")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    let cf = db
+                        .cf_handle(WAL_CF)
+                        .expect("wal column family is created on open");
+                    db.delete_cf(cf, WAL_KEY)?;
+                    Ok(())
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    #[instrument(skip(self, keys))]
+    async fn put_keys(&self, keys: Vec<String>) -> Result<()> {
+        let db = self.db.clone();
+        let span = Span::current();
+
+        tokio::task::Builder::new()
+            .name("RocksDb::put_keys")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    let cf = db
+                        .cf_handle(KEYS_CF)
+                        .expect("keys column family is created on open");
+                    for key in keys {
+                        db.put_cf(cf, key.as_bytes(), [])?;
+                    }
+
+                    Ok(())
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    #[instrument(skip(self))]
+    async fn keys_with_prefix(&self, prefix: &str) -> Result<Vec<String>> {
+        let db = self.db.clone();
+        let prefix = prefix.to_string();
+        let span = Span::current();
+
+        tokio::task::Builder::new()
+            .name("RocksDb::keys_with_prefix")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    let cf = db
+                        .cf_handle(KEYS_CF)
+                        .expect("keys column family is created on open");
+                    db.iterator_cf(
+                        cf,
+                        IteratorMode::From(prefix.as_bytes(), Direction::Forward),
+                    )
+                    .map(|(key, _)| key)
+                    .take_while(|key| key.starts_with(prefix.as_bytes()))
+                    .map(|key| Ok(String::from_utf8(key.into_vec())?))
+                    .collect()
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    #[instrument(skip(self))]
+    async fn compact(&self) -> Result<()> {
+        let db = self.db.clone();
+        let span = Span::current();
+
+        tokio::task::Builder::new()
+            .name("RocksDb::compact")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    // Nodes are in the default column family, which has no handle.
+                    let column_families = [
+                        None,
+                        Some(KEYS_CF),
+                        Some(WAL_CF),
+                        Some(CHECKSUMS_CF),
+                        Some(STALE_CF),
+                    ];
+                    let total = column_families.len();
+                    for (i, name) in column_families.into_iter().enumerate() {
+                        match name {
+                            None => db.compact_range(None::<&[u8]>, None::<&[u8]>),
+                            Some(name) => {
+                                let cf = db
+                                    .cf_handle(name)
+                                    .expect("column family is created on open");
+                                db.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>)
+                            }
+                        }
+                        tracing::info!(
+                            column_family = name.unwrap_or("default"),
+                            progress = ?format!("{}/{}", i + 1, total),
+                            "compacted column family"
+                        );
+                    }
+                    Ok(())
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    #[instrument(skip(self))]
+    async fn flush(&self) -> Result<()> {
+        let db = self.db.clone();
+        let span = Span::current();
+
+        tokio::task::Builder::new()
+            .name("RocksDb::flush")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    db.flush()?;
+                    for name in [KEYS_CF, WAL_CF, CHECKSUMS_CF, STALE_CF] {
+                        let cf = db
+                            .cf_handle(name)
+                            .expect("column family is created on open");
+                        db.flush_cf(cf)?;
+                    }
+                    tracing::info!("flushed rocksdb");
+                    Ok(())
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    async fn size(&self) -> Result<Option<u64>> {
+        // Properties are read from RocksDB's in-memory statistics, so this doesn't block.
+        const SST_FILES_SIZE: &str = "rocksdb.total-sst-files-size";
+        let mut size = self.db.property_int_value(SST_FILES_SIZE)?.unwrap_or(0);
+        for name in [KEYS_CF, WAL_CF, CHECKSUMS_CF, STALE_CF] {
+            let cf = self
+                .db
+                .cf_handle(name)
+                .expect("column family is created on open");
+            size += self
+                .db
+                .property_int_value_cf(cf, SST_FILES_SIZE)?
+                .unwrap_or(0);
+        }
+        Ok(Some(size))
+    }
+
+    async fn get_rightmost_leaf(&self) -> Result<Option<(NodeKey, LeafNode)>> {
+        let db = self.db.clone();
+        let verify_checksums = self.verify_checksums;
+        let span = Span::current();
+
+        tokio::task::Builder::new()
+            .name("RocksDb::get_rightmost_leaf")
+            .spawn_blocking(move || {
+                span.in_scope(|| {
+                    let mut iter = db.raw_iterator();
+                    let mut ret = None;
+                    iter.seek_to_last();
+
+                    if iter.valid() {
+                        if verify_checksums {
+                            verify_checksum(&db, iter.key().unwrap(), iter.value().unwrap())?;
+                        }
+                        let node_key = NodeKey::decode(iter.key().unwrap())?;
+                        let node = Node::decode(iter.value().unwrap())?;
+
+                        if let Node::Leaf(leaf_node) = node {
+                            ret = Some((node_key, leaf_node));
+                        }
+                    } else {
+                        // There are no keys in the database
+                    }
+                    Ok(ret)
+                })
+            })
+            .await
+            .unwrap()
+    }
+}
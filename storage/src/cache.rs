@@ -0,0 +1,122 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+use jmt::storage::{Node, NodeKey};
+use lru::LruCache;
+use metrics::{gauge, increment_counter};
+
+use crate::metrics::{NODE_CACHE_HITS, NODE_CACHE_MISSES, NODE_CACHE_SIZE};
+
+/// A least-recently-used cache of tree nodes read from the backend, bounded by the total
+/// (encoded) size of the nodes it holds.
+///
+/// Nodes are never modified once written, so cached nodes never become stale; they only need to
+/// be evicted when they are deleted by pruning.
+#[derive(Debug)]
+pub(crate) struct NodeCache {
+    inner: Mutex<Inner>,
+    capacity: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Inner {
+    /// Each node, together with its encoded size.
+    nodes: LruCache<NodeKey, (Node, usize)>,
+    /// The sum of the sizes of the cached nodes.
+    size: usize,
+}
+
+/// Statistics about the node cache.
+#[derive(Clone, Copy, Debug)]
+pub struct CacheStats {
+    /// The number of reads answered from the cache.
+    pub hits: u64,
+    /// The number of reads which went to the backend.
+    pub misses: u64,
+    /// The number of nodes in the cache.
+    pub nodes: usize,
+    /// The total encoded size of the nodes in the cache, in bytes.
+    pub size: usize,
+    /// The maximum size of the cache, in bytes.
+    pub capacity: usize,
+}
+
+impl NodeCache {
+    /// Creates an empty cache holding at most `capacity` bytes of nodes.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                nodes: LruCache::unbounded(),
+                size: 0,
+            }),
+            capacity,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Looks up a node, recording a hit or a miss.
+    pub fn get(&self, node_key: &NodeKey) -> Option<Node> {
+        let node = self
+            .inner
+            .lock()
+            .unwrap()
+            .nodes
+            .get(node_key)
+            .map(|(node, _)| node.clone());
+        match node {
+            Some(_) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                increment_counter!(NODE_CACHE_HITS);
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                increment_counter!(NODE_CACHE_MISSES);
+            }
+        }
+        node
+    }
+
+    /// Adds a node read from the backend, evicting the least recently used nodes as needed.
+    pub fn insert(&self, node_key: NodeKey, node: Node, size: usize) {
+        if size > self.capacity {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        if let Some((_, old_size)) = inner.nodes.put(node_key, (node, size)) {
+            inner.size -= old_size;
+        }
+        inner.size += size;
+        while inner.size > self.capacity {
+            match inner.nodes.pop_lru() {
+                Some((_, (_, evicted_size))) => inner.size -= evicted_size,
+                None => break,
+            }
+        }
+        gauge!(NODE_CACHE_SIZE, inner.size as f64);
+    }
+
+    /// Removes every node from the cache.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.nodes.clear();
+        inner.size = 0;
+        gauge!(NODE_CACHE_SIZE, 0.0);
+    }
+
+    /// Returns the cache's hit and miss counts and current size.
+    pub fn stats(&self) -> CacheStats {
+        let inner = self.inner.lock().unwrap();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            nodes: inner.nodes.len(),
+            size: inner.size,
+            capacity: self.capacity,
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use std::{sync::atomic::Ordering, time::Instant};
+
+use anyhow::{anyhow, Result};
+use metrics::{gauge, histogram, increment_counter};
+
+use crate::{
+    metrics::{COMPACTIONS, COMPACTION_DURATION, COMPACTION_IN_PROGRESS},
+    Storage,
+};
+
+/// Clears the compaction marker when compaction finishes, whether or not it succeeded.
+struct CompactionGuard<'a>(&'a Storage);
+
+impl Drop for CompactionGuard<'_> {
+    fn drop(&mut self) {
+        self.0.compacting.store(false, Ordering::SeqCst);
+        gauge!(COMPACTION_IN_PROGRESS, 0.0);
+    }
+}
+
+impl Storage {
+    /// Compacts the backend, reclaiming the space used by pruned nodes and reducing read
+    /// amplification.
+    ///
+    /// Compaction can take a long time on a large database, but doesn't block reads or commits,
+    /// so it's best run in the background, e.g. after pruning. Fails if a compaction is already
+    /// in progress.
+    pub async fn compact(&self) -> Result<()> {
+        if self.compacting.swap(true, Ordering::SeqCst) {
+            return Err(anyhow!("compaction is already in progress"));
+        }
+        let _guard = CompactionGuard(self);
+        gauge!(COMPACTION_IN_PROGRESS, 1.0);
+
+        tracing::info!("compacting storage");
+        let start = Instant::now();
+        self.backend.compact().await?;
+        let elapsed = start.elapsed();
+        histogram!(COMPACTION_DURATION, elapsed);
+        increment_counter!(COMPACTIONS);
+        tracing::info!(?elapsed, "compacted storage");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+use std::{collections::BTreeMap, fmt};
+
+use anyhow::{anyhow, Result};
+use jmt::{
+    storage::{Node, NodeKey},
+    RootHash, Version,
+};
+
+use crate::Storage;
+
+/// An inconsistency in the stored tree, found by [`Storage::fsck`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Corruption {
+    /// An internal node refers to a child which isn't stored.
+    MissingChild { parent: NodeKey, child: NodeKey },
+    /// The hash of a node recorded in its parent doesn't match the hash recomputed from the
+    /// leaves below it.
+    HashMismatch {
+        node_key: NodeKey,
+        recorded: [u8; 32],
+        computed: [u8; 32],
+    },
+    /// A node is stored, but isn't reachable from the root of any stored version.
+    Orphaned { node_key: NodeKey },
+}
+
+impl fmt::Display for Corruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Corruption::MissingChild { parent, child } => write!(
+                f,
+                "node {:?} (version {}) is missing its child {:?}",
+                parent,
+                parent.version(),
+                child
+            ),
+            Corruption::HashMismatch {
+                node_key,
+                recorded,
+                computed,
+            } => write!(
+                f,
+                "node {:?} (version {}) has hash {}, but its parent records {}",
+                node_key,
+                node_key.version(),
+                hex::encode(computed),
+                hex::encode(recorded)
+            ),
+            Corruption::Orphaned { node_key } => write!(
+                f,
+                "node {:?} (version {}) is not reachable from any version",
+                node_key,
+                node_key.version()
+            ),
+        }
+    }
+}
+
+/// The result of checking the consistency of the stored tree.
+#[derive(Clone, Debug)]
+pub struct FsckReport {
+    /// The version whose root hash was recomputed.
+    pub version: Version,
+    /// The root hash of `version`, recomputed from its leaves.
+    pub root_hash: RootHash,
+    /// The number of nodes stored across all versions.
+    pub nodes_checked: usize,
+    /// The inconsistencies found, if any.
+    pub corruptions: Vec<Corruption>,
+}
+
+impl FsckReport {
+    /// Returns whether no inconsistencies were found.
+    pub fn is_ok(&self) -> bool {
+        self.corruptions.is_empty()
+    }
+}
+
+impl Storage {
+    /// Checks the consistency of the stored tree, reading every node from the backend.
+    ///
+    /// The root hash of `version` (or of the latest version, if `None`) is recomputed from its
+    /// leaves, checking that every child exists and that the hash recorded for each node by its
+    /// parent matches its contents. Every other stored version is checked in the same way, and
+    /// any stored nodes which aren't reachable from a version are reported as orphaned.
+    ///
+    /// This reads the entire tree, and is intended to be run on a database which isn't in use,
+    /// to diagnose corruption before it shows up as an app hash mismatch.
+    pub async fn fsck(&self, version: Option<Version>) -> Result<FsckReport> {
+        let latest = self
+            .latest_version()
+            .await?
+            .ok_or_else(|| anyhow!("cannot check an empty tree"))?;
+        let version = version.unwrap_or(latest);
+
+        let mut checker = Checker {
+            storage: self,
+            hashes: BTreeMap::new(),
+            corruptions: Vec::new(),
+        };
+
+        let root_hash = checker.check_version(version).await?.ok_or_else(|| {
+            anyhow!(
+                "version {} is not stored (it may have been pruned)",
+                version
+            )
+        })?;
+
+        // Versions which have been pruned have no root, and are skipped.
+        for other in (0..=latest).filter(|v| *v != version) {
+            checker.check_version(other).await?;
+        }
+
+        let stored = self.backend.node_keys_before(latest + 1).await?;
+        let nodes_checked = stored.len();
+        for node_key in stored {
+            if !checker.hashes.contains_key(&node_key) {
+                checker.corruptions.push(Corruption::Orphaned { node_key });
+            }
+        }
+
+        let report = FsckReport {
+            version,
+            root_hash: RootHash(root_hash),
+            nodes_checked,
+            corruptions: checker.corruptions,
+        };
+        if report.is_ok() {
+            tracing::info!(version, nodes_checked, "tree is consistent");
+        } else {
+            tracing::error!(
+                version,
+                nodes_checked,
+                corruptions = report.corruptions.len(),
+                "tree is corrupt"
+            );
+        }
+        Ok(report)
+    }
+}
+
+struct Checker<'a> {
+    storage: &'a Storage,
+    /// The recomputed hash of every node visited so far, or `None` if it is missing.
+    hashes: BTreeMap<NodeKey, Option<[u8; 32]>>,
+    corruptions: Vec<Corruption>,
+}
+
+impl<'a> Checker<'a> {
+    /// Recomputes the root hash of `version`, returning `None` if it has no root.
+    async fn check_version(&mut self, version: Version) -> Result<Option<[u8; 32]>> {
+        let root_key = NodeKey::new_empty_path(version);
+        if self.storage.backend.get_node(&root_key).await?.is_none() {
+            return Ok(None);
+        }
+
+        // Visit the nodes in post-order, so that every child is hashed before its parent.
+        let mut stack = vec![(root_key.clone(), false)];
+        while let Some((node_key, children_done)) = stack.pop() {
+            if !children_done && self.hashes.contains_key(&node_key) {
+                continue;
+            }
+            let node = match self.storage.backend.get_node(&node_key).await? {
+                Some(node) => node,
+                // Reported as a missing child by the parent.
+                None => {
+                    self.hashes.insert(node_key, None);
+                    continue;
+                }
+            };
+            match node {
+                Node::Internal(internal) if !children_done => {
+                    stack.push((node_key.clone(), true));
+                    for (nibble, child) in internal.children_sorted() {
+                        stack.push((node_key.gen_child_node_key(child.version, *nibble), false));
+                    }
+                }
+                Node::Internal(internal) => {
+                    for (nibble, child) in internal.children_sorted() {
+                        let child_key = node_key.gen_child_node_key(child.version, *nibble);
+                        match self.hashes.get(&child_key).copied().flatten() {
+                            None => self.corruptions.push(Corruption::MissingChild {
+                                parent: node_key.clone(),
+                                child: child_key,
+                            }),
+                            Some(computed) if computed != child.hash => {
+                                self.corruptions.push(Corruption::HashMismatch {
+                                    node_key: child_key,
+                                    recorded: child.hash,
+                                    computed,
+                                })
+                            }
+                            Some(_) => {}
+                        }
+                    }
+                    // Having checked the children's recorded hashes, the internal node's hash
+                    // is the one computed from its leaves.
+                    self.hashes.insert(node_key, Some(internal.hash()));
+                }
+                node => {
+                    self.hashes.insert(node_key, Some(node.hash()));
+                }
+            }
+        }
+
+        Ok(self.hashes.get(&root_key).copied().flatten())
+    }
+}
@@ -1,12 +1,42 @@
 use std::sync::Arc;
 
-use jmt::WriteOverlay;
 use tokio::sync::RwLock;
 
+mod backend;
+mod cache;
+mod compaction;
+mod fsck;
+pub mod metrics;
+mod migration;
+mod overlay;
 mod overlay_ext;
+mod proof;
+mod pruning;
+mod snapshot;
+mod state_key;
 mod storage;
+mod subscription;
+mod transaction;
+mod wal;
 
-pub use overlay_ext::StateExt;
+pub use self::metrics::register_metrics;
+pub use backend::{Memory, RocksDb, StateBackend};
+pub use cache::CacheStats;
+pub use fsck::{Corruption, FsckReport};
+pub use migration::{app_version, reindex_keys, Migration, Migrations};
+pub use overlay::WriteOverlay;
+pub use overlay_ext::{StateExt, StateRead};
+pub use proof::verify_proof;
+pub use pruning::RetentionPolicy;
+pub use snapshot::{read_snapshot_header, Snapshot};
+pub use state_key::StateKey;
 pub use storage::Storage;
+pub use subscription::StateChange;
+pub use transaction::StateTransaction;
 
-pub type State = Arc<RwLock<WriteOverlay<Storage>>>;
+/// A shared, writable view of the chain state, holding the pending writes of the block (or
+/// mempool transactions) being executed.
+///
+/// Only the worker executing blocks should hold a `State`; readers such as RPC handlers should
+/// use a [`Snapshot`] of a committed version instead, which doesn't take the overlay's lock.
+pub type State = Arc<RwLock<WriteOverlay>>;
@@ -0,0 +1,96 @@
+//! Metrics recorded by the storage layer.
+//!
+//! These are recorded through the [`metrics`] facade, so they are exported by whichever recorder
+//! the application installs; call [`register_metrics`] once the recorder is installed to describe
+//! them.
+
+use metrics::{describe_counter, describe_gauge, describe_histogram, Unit};
+
+pub const NODE_READ_DURATION: &str = "storage_node_read_duration_seconds";
+pub const NODE_CACHE_HITS: &str = "storage_node_cache_hits_total";
+pub const NODE_CACHE_MISSES: &str = "storage_node_cache_misses_total";
+pub const NODE_CACHE_SIZE: &str = "storage_node_cache_size_bytes";
+pub const NODE_BATCH_WRITE_DURATION: &str = "storage_node_batch_write_duration_seconds";
+pub const NODES_WRITTEN: &str = "storage_nodes_written_total";
+pub const NODES_PRUNED: &str = "storage_nodes_pruned_total";
+pub const COMMIT_BYTES: &str = "storage_commit_bytes";
+pub const COMMIT_DURATION: &str = "storage_commit_duration_seconds";
+pub const OVERLAY_GET_DURATION: &str = "storage_overlay_get_duration_seconds";
+pub const OVERLAY_PUTS: &str = "storage_overlay_puts_total";
+pub const COMPACTIONS: &str = "storage_compactions_total";
+pub const COMPACTION_DURATION: &str = "storage_compaction_duration_seconds";
+pub const COMPACTION_IN_PROGRESS: &str = "storage_compaction_in_progress";
+
+/// Describes all metrics recorded by the storage layer.
+pub fn register_metrics() {
+    describe_histogram!(
+        NODE_READ_DURATION,
+        Unit::Seconds,
+        "The time taken to read a tree node, including from the cache"
+    );
+    describe_counter!(
+        NODE_CACHE_HITS,
+        Unit::Count,
+        "The number of tree node reads answered from the cache"
+    );
+    describe_counter!(
+        NODE_CACHE_MISSES,
+        Unit::Count,
+        "The number of tree node reads which went to the backend"
+    );
+    describe_gauge!(
+        NODE_CACHE_SIZE,
+        Unit::Bytes,
+        "The total encoded size of the tree nodes in the cache"
+    );
+    describe_histogram!(
+        NODE_BATCH_WRITE_DURATION,
+        Unit::Seconds,
+        "The time taken to write a batch of tree nodes, including the write-ahead log"
+    );
+    describe_counter!(
+        NODES_WRITTEN,
+        Unit::Count,
+        "The number of tree nodes written to the backend"
+    );
+    describe_counter!(
+        NODES_PRUNED,
+        Unit::Count,
+        "The number of tree nodes deleted by pruning"
+    );
+    describe_histogram!(
+        COMMIT_BYTES,
+        Unit::Bytes,
+        "The encoded size of the tree nodes written by each commit"
+    );
+    describe_histogram!(
+        COMMIT_DURATION,
+        Unit::Seconds,
+        "The time taken to commit pending writes to the tree"
+    );
+    describe_histogram!(
+        OVERLAY_GET_DURATION,
+        Unit::Seconds,
+        "The time taken to read a key from the state"
+    );
+    describe_counter!(
+        OVERLAY_PUTS,
+        Unit::Count,
+        "The number of writes made to the state"
+    );
+    describe_counter!(
+        COMPACTIONS,
+        Unit::Count,
+        "The number of compactions of the backend which have completed"
+    );
+    describe_histogram!(
+        COMPACTION_DURATION,
+        Unit::Seconds,
+        "The time taken to compact the backend"
+    );
+    describe_gauge!(
+        COMPACTION_IN_PROGRESS,
+        Unit::Count,
+        "Whether the backend is currently being compacted (1) or not (0)"
+    );
+}
@@ -0,0 +1,172 @@
+use std::{collections::BTreeMap, fmt};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use jmt::Version;
+
+use crate::{State, StateExt, StateKey, StateRead, Storage, WriteOverlay};
+
+/// The key under which the app version of the state is stored.
+///
+/// A state with no app version stored was created before any migrations existed, and is
+/// treated as being at version 0.
+pub fn app_version() -> StateKey<u64> {
+    StateKey::new("app").join("version")
+}
+
+/// A change to the layout or encoding of the chain state, made when upgrading to a new version
+/// of the application.
+///
+/// Migrations are run against the latest committed state when it is loaded, and their writes are
+/// committed along with the first block executed afterwards. A migration must be deterministic,
+/// so that every node running it computes the same state.
+#[async_trait]
+pub trait Migration: Send + Sync + 'static {
+    /// A short description of the migration, for logging.
+    fn description(&self) -> &str;
+
+    /// Rewrites the state from the previous app version to this migration's version.
+    async fn migrate(&self, state: &State) -> Result<()>;
+}
+
+/// The migrations of the chain state, keyed by the app version they migrate to.
+#[derive(Default)]
+pub struct Migrations {
+    migrations: BTreeMap<u64, Box<dyn Migration>>,
+}
+
+impl Migrations {
+    /// Creates an empty registry, for an app still at version 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the migration from app version `version - 1` to `version`.
+    ///
+    /// # Panics
+    ///
+    /// If `version` is 0, or a migration to `version` is already registered.
+    pub fn register(mut self, version: u64, migration: impl Migration) -> Self {
+        assert!(version > 0, "there is no migration to app version 0");
+        assert!(
+            !self.migrations.contains_key(&version),
+            "duplicate migration to app version {}",
+            version
+        );
+        self.migrations.insert(version, Box::new(migration));
+        self
+    }
+
+    /// The app version after every registered migration has been run.
+    pub fn latest_version(&self) -> u64 {
+        self.migrations.keys().next_back().copied().unwrap_or(0)
+    }
+}
+
+impl fmt::Debug for Migrations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.migrations
+                    .iter()
+                    .map(|(version, migration)| (version, migration.description())),
+            )
+            .finish()
+    }
+}
+
+/// Records `keys` in the key index, for a migration from a state written before the index existed.
+///
+/// The JMT stores only the hashes of keys, so the index can't be rebuilt from the tree itself:
+/// instead, each key which has a value is rewritten with the same value. This leaves the root hash
+/// unchanged, but makes the key pending, so that it is indexed when the migration's writes are
+/// committed. Keys with no value are skipped, so callers may pass keys which might not exist.
+///
+/// Returns the number of keys which had a value.
+pub async fn reindex_keys(state: &State, keys: impl IntoIterator<Item = String>) -> Result<usize> {
+    let mut overlay = state.write().await;
+    let mut indexed = 0;
+    for key in keys {
+        if let Some(value) = overlay.get(&key).await? {
+            overlay.put(key, value);
+            indexed += 1;
+        }
+    }
+    Ok(indexed)
+}
+
+/// The writes made by migrations, to be committed on top of the version they were run against.
+#[derive(Debug)]
+pub(crate) struct MigratedWrites {
+    pub(crate) version: Version,
+    pub(crate) writes: BTreeMap<String, Vec<u8>>,
+}
+
+impl Storage {
+    /// Runs the migrations needed to bring the latest version of the state up to the latest app
+    /// version.
+    ///
+    /// The migrations' writes aren't committed immediately, since every version of the tree
+    /// corresponds to a block. Instead, they are included in every [`State`] created on top of the
+    /// latest version, and so are committed with the next block.
+    pub(crate) async fn migrate(&self, migrations: &Migrations) -> Result<()> {
+        let latest = self.latest_version().await?;
+        let state = self.state().await?;
+
+        let current = match latest {
+            Some(_) => state.get_typed_proto(&app_version()).await?.unwrap_or(0),
+            // A new state starts at the latest app version.
+            None => migrations.latest_version(),
+        };
+        if current > migrations.latest_version() {
+            return Err(anyhow!(
+                "state has app version {}, but the latest known app version is {}",
+                current,
+                migrations.latest_version()
+            ));
+        } else if latest.is_some() && current == migrations.latest_version() {
+            tracing::debug!(app_version = current, "state is at the latest app version");
+            return Ok(());
+        }
+
+        for (&version, migration) in migrations.migrations.range(current + 1..) {
+            tracing::info!(
+                from = version - 1,
+                to = version,
+                description = migration.description(),
+                "running state migration"
+            );
+            migration.migrate(&state).await?;
+        }
+        state
+            .put_typed_proto(&app_version(), migrations.latest_version())
+            .await;
+
+        let writes = state.read().await.pending_writes().await?;
+        *self.migrated.lock().unwrap() = Some(MigratedWrites {
+            version: latest.unwrap_or(WriteOverlay::PRE_GENESIS_VERSION),
+            writes,
+        });
+        Ok(())
+    }
+
+    /// Returns the migrated writes to include in a new state on top of `version`, if any.
+    pub(crate) fn migrated_writes(&self, version: Version) -> Vec<(String, Vec<u8>)> {
+        match &*self.migrated.lock().unwrap() {
+            Some(migrated) if migrated.version == version => migrated
+                .writes
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Forgets the migrated writes once they have been committed on top of `version`.
+    pub(crate) fn clear_migrated_writes(&self, version: Version) {
+        let mut migrated = self.migrated.lock().unwrap();
+        if matches!(&*migrated, Some(m) if m.version == version) {
+            *migrated = None;
+        }
+    }
+}
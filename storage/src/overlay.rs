@@ -0,0 +1,316 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use anyhow::{anyhow, Result};
+use jmt::{KeyHash, RootHash, Version};
+
+use metrics::{histogram, increment_counter};
+
+use crate::{
+    metrics::{COMMIT_DURATION, OVERLAY_GET_DURATION, OVERLAY_PUTS},
+    pruning::VersionPin,
+    StateChange, Storage,
+};
+
+/// A set of pending writes on top of a version of the tree held in [`Storage`].
+///
+/// This wraps the JMT's own [`jmt::WriteOverlay`], which stores writes by key hash, and
+/// additionally remembers the (unhashed) keys which have been written, so that the keys in the
+/// state can be enumerated by prefix.
+///
+/// Writes can also be grouped into nested layers, which are either merged into the layer below
+/// or discarded as a unit; see [`StateTransaction`](crate::StateTransaction).
+pub struct WriteOverlay {
+    inner: jmt::WriteOverlay<Storage>,
+    storage: Storage,
+    /// The keys written since the last commit.
+    pending_keys: BTreeSet<String>,
+    /// Prevents the version underlying the overlay from being pruned.
+    pin: VersionPin,
+    /// The writes made in each open transaction layer, innermost last.
+    layers: Vec<BTreeMap<String, Vec<u8>>>,
+    /// The layers abandoned by dropped transactions, which are discarded on the next write.
+    abandoned: AbandonedLayers,
+}
+
+/// The shallowest transaction layer abandoned by a dropped transaction, if any, shared between an
+/// overlay and its transactions.
+///
+/// A transaction can be dropped while the state is locked, so rather than discarding its layer
+/// itself, it records the layer here. Reads ignore the abandoned layers, and the next write
+/// discards them, along with any layers opened on top of them.
+#[derive(Clone, Debug)]
+pub(crate) struct AbandonedLayers(Arc<AtomicUsize>);
+
+impl AbandonedLayers {
+    /// The value recorded when no layer is abandoned.
+    const NONE: usize = usize::MAX;
+
+    fn new() -> Self {
+        Self(Arc::new(AtomicUsize::new(Self::NONE)))
+    }
+
+    /// Records that the layer at `depth` was abandoned.
+    pub(crate) fn abandon(&self, depth: usize) {
+        self.0.fetch_min(depth, Ordering::SeqCst);
+    }
+
+    /// Returns the depth of the shallowest abandoned layer, if any.
+    fn shallowest(&self) -> Option<usize> {
+        Some(self.0.load(Ordering::SeqCst)).filter(|depth| *depth != Self::NONE)
+    }
+
+    /// Returns and clears the depth of the shallowest abandoned layer, if any.
+    fn take(&self) -> Option<usize> {
+        Some(self.0.swap(Self::NONE, Ordering::SeqCst)).filter(|depth| *depth != Self::NONE)
+    }
+}
+
+impl WriteOverlay {
+    /// The version of the tree before the first commit, so that the first commit is at version 0.
+    pub const PRE_GENESIS_VERSION: Version = jmt::WriteOverlay::<Storage>::PRE_GENESIS_VERSION;
+
+    /// The value written to the tree in place of a deleted key, which the JMT can't remove.
+    ///
+    /// Reads treat a key holding this value as having no value. A lone `0xff` byte is a truncated
+    /// varint, so no protobuf encoding is equal to it.
+    pub const TOMBSTONE: &'static [u8] = &[0xff];
+
+    /// Maps a value read from the tree to `None` if the key was deleted.
+    pub(crate) fn live(value: Option<Vec<u8>>) -> Option<Vec<u8>> {
+        value.filter(|value| value != Self::TOMBSTONE)
+    }
+
+    /// Creates an empty overlay on top of the given version of the tree.
+    ///
+    /// Fails if the version is being pruned.
+    pub fn new(storage: Storage, version: Version) -> Result<Self> {
+        Ok(Self {
+            inner: jmt::WriteOverlay::new(storage.clone(), version),
+            pin: storage.pin(version)?,
+            storage,
+            pending_keys: BTreeSet::new(),
+            layers: Vec::new(),
+            abandoned: AbandonedLayers::new(),
+        })
+    }
+
+    /// Reads the value of `key`, from the pending writes if it was written, and from the
+    /// underlying tree otherwise.
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        for layer in self.live_layers().iter().rev() {
+            if let Some(value) = layer.get(key) {
+                return Ok(Self::live(Some(value.clone())));
+            }
+        }
+        let start = Instant::now();
+        let value = self.inner.get(KeyHash::from(key.as_bytes())).await;
+        histogram!(OVERLAY_GET_DURATION, start.elapsed());
+        Ok(Self::live(value?))
+    }
+
+    /// Reads the value of `key` as it was committed at an earlier `version`, ignoring any pending
+    /// writes.
+    pub async fn get_at_version(&self, key: &str, version: Version) -> Result<Option<Vec<u8>>> {
+        self.storage.get_at_version(key, version).await
+    }
+
+    /// Writes `value` to `key`, to be persisted on the next commit.
+    ///
+    /// If a transaction layer is open, the write is held in the innermost layer until it is
+    /// merged or discarded.
+    pub fn put(&mut self, key: String, value: Vec<u8>) {
+        increment_counter!(OVERLAY_PUTS);
+        self.discard_abandoned();
+        match self.layers.last_mut() {
+            Some(layer) => {
+                layer.insert(key, value);
+            }
+            None => {
+                self.inner.put(KeyHash::from(key.as_bytes()), value);
+                self.pending_keys.insert(key);
+            }
+        }
+    }
+
+    /// Returns the values of the keys written since the last commit, excluding any open
+    /// transaction layers.
+    pub(crate) async fn pending_writes(&self) -> Result<BTreeMap<String, Vec<u8>>> {
+        let mut writes = BTreeMap::new();
+        for key in &self.pending_keys {
+            if let Some(value) = self.inner.get(KeyHash::from(key.as_bytes())).await? {
+                writes.insert(key.clone(), value);
+            }
+        }
+        Ok(writes)
+    }
+
+    /// Deletes `key`, so that it has no value once the deletion is committed.
+    ///
+    /// The key remains in the tree (and in the key index), holding [`Self::TOMBSTONE`].
+    pub fn delete(&mut self, key: String) {
+        self.put(key, Self::TOMBSTONE.to_vec());
+    }
+
+    /// Opens a new transaction layer, returning the number of open layers.
+    pub(crate) fn push_layer(&mut self) -> usize {
+        self.discard_abandoned();
+        self.layers.push(BTreeMap::new());
+        self.layers.len()
+    }
+
+    /// Returns a handle through which transactions record the layers they abandon when dropped.
+    pub(crate) fn abandoned_layers(&self) -> AbandonedLayers {
+        self.abandoned.clone()
+    }
+
+    /// Returns the open transaction layers, excluding those abandoned by dropped transactions.
+    fn live_layers(&self) -> &[BTreeMap<String, Vec<u8>>] {
+        match self.abandoned.shallowest() {
+            Some(depth) => &self.layers[..(depth - 1).min(self.layers.len())],
+            None => &self.layers,
+        }
+    }
+
+    /// Discards the layers abandoned by dropped transactions, and any layers opened on top of
+    /// them.
+    fn discard_abandoned(&mut self) {
+        if let Some(depth) = self.abandoned.take() {
+            if depth <= self.layers.len() {
+                tracing::debug!(depth, "aborted dropped state transaction");
+                self.layers.truncate(depth - 1);
+            }
+        }
+    }
+
+    /// Merges the innermost transaction layer into the one below it (or the overlay itself).
+    ///
+    /// `depth` is the number of open layers returned by [`Self::push_layer`], which guards against
+    /// merging a layer other than the one the caller opened.
+    pub(crate) fn merge_layer(&mut self, depth: usize) -> Result<()> {
+        self.discard_abandoned();
+        self.check_depth(depth)?;
+        let layer = self.layers.pop().expect("depth was checked");
+        for (key, value) in layer {
+            self.put(key, value);
+        }
+        Ok(())
+    }
+
+    /// Discards the innermost transaction layer.
+    pub(crate) fn discard_layer(&mut self, depth: usize) -> Result<()> {
+        self.discard_abandoned();
+        self.check_depth(depth)?;
+        self.layers.pop();
+        Ok(())
+    }
+
+    fn check_depth(&self, depth: usize) -> Result<()> {
+        if self.layers.len() == depth {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "expected {} open transaction layers, found {}: a nested transaction was not finished",
+                depth,
+                self.layers.len()
+            ))
+        }
+    }
+
+    /// Returns, in order, every key beginning with `prefix` which has been written either to the
+    /// overlay or to the underlying tree.
+    ///
+    /// Keys are never forgotten by the index, so some of the returned keys may not have a value
+    /// at this overlay's version.
+    pub async fn keys_with_prefix(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut keys = self
+            .storage
+            .keys_with_prefix(prefix)
+            .await?
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+        keys.extend(
+            self.pending_keys
+                .range(prefix.to_string()..)
+                .take_while(|key| key.starts_with(prefix))
+                .cloned(),
+        );
+        for layer in self.live_layers() {
+            keys.extend(
+                layer
+                    .range(prefix.to_string()..)
+                    .take_while(|(key, _)| key.starts_with(prefix))
+                    .map(|(key, _)| key.clone()),
+            );
+        }
+        Ok(keys.into_iter().collect())
+    }
+
+    /// Commits the pending writes to `storage`, clearing the overlay, and returns the root hash
+    /// and version of the new tree.
+    ///
+    /// Fails if a transaction layer is still open.
+    pub async fn commit(&mut self, storage: Storage) -> Result<(RootHash, Version)> {
+        self.discard_abandoned();
+        if !self.layers.is_empty() {
+            return Err(anyhow!(
+                "cannot commit with {} open transaction layers",
+                self.layers.len()
+            ));
+        }
+
+        let base_version = self.pin.version();
+        let keys = std::mem::take(&mut self.pending_keys);
+        let mut changes = self.watched_changes(&storage, &keys).await?;
+
+        // Index the keys before writing the tree, so that every key in the tree is indexed even if
+        // the commit is interrupted.
+        storage.put_keys(keys.into_iter().collect()).await?;
+        let start = Instant::now();
+        let (root_hash, version) = self.inner.commit(storage.clone()).await?;
+        histogram!(COMMIT_DURATION, start.elapsed());
+        self.pin = self.storage.pin(version)?;
+        storage.clear_migrated_writes(base_version);
+
+        for change in &mut changes {
+            change.version = version;
+        }
+        storage.publish(&changes);
+
+        Ok((root_hash, version))
+    }
+
+    /// Collects the changes to be committed to keys which `storage` has subscribers for, with
+    /// their version left to be filled in once it is known.
+    async fn watched_changes(
+        &self,
+        storage: &Storage,
+        keys: &BTreeSet<String>,
+    ) -> Result<Vec<StateChange>> {
+        let base_version = self.pin.version();
+        let mut changes = Vec::new();
+        for key in keys.iter().filter(|key| storage.is_watched(key)) {
+            let new = Self::live(self.inner.get(KeyHash::from(key.as_bytes())).await?);
+            let old = if base_version == Self::PRE_GENESIS_VERSION {
+                None
+            } else {
+                storage.get_at_version(key, base_version).await?
+            };
+            if old != new {
+                changes.push(StateChange {
+                    key: key.clone(),
+                    old,
+                    new,
+                    version: base_version,
+                });
+            }
+        }
+        Ok(changes)
+    }
+}
@@ -1,63 +1,43 @@
 use std::fmt::Debug;
 
 use anyhow::{anyhow, Result};
+use async_stream::try_stream;
 use async_trait::async_trait;
-use jmt::KeyHash;
+use futures::stream::{BoxStream, StreamExt};
+use jmt::Version;
 use penumbra_proto::{Message, Protobuf};
 use tracing::instrument;
 
-use crate::State;
+use crate::{State, StateKey};
 
-/// An extension trait that allows writing proto-encoded domain types to
-/// a shared [`State`].
+/// An extension trait that allows reading proto-encoded domain types from
+/// the chain state, either through a shared [`State`] or a read-only
+/// [`Snapshot`](crate::Snapshot).
 #[async_trait]
-pub trait StateExt: Send + Sync + Sized + Clone + 'static {
-    /// Reads a domain type from the state, using the proto encoding.
-    async fn get_domain<D, P>(&self, key: KeyHash) -> Result<Option<D>>
-    where
-        D: Protobuf<P> + TryFrom<P> + Clone + Debug,
-        // TODO: does this get less awful if P is an associated type of D?
-        P: Message + Default + From<D>,
-        <D as TryFrom<P>>::Error: Into<anyhow::Error>;
+pub trait StateRead: Send + Sync + Sized + Clone + 'static {
+    /// Reads the raw bytes stored under `key`.
+    async fn get_bytes(&self, key: &str) -> Result<Option<Vec<u8>>>;
 
-    /// Puts a domain type into the state, using the proto encoding.
-    async fn put_domain<D, P>(&self, key: KeyHash, value: D)
-    where
-        D: Protobuf<P> + Send + TryFrom<P> + Clone + Debug,
-        // TODO: does this get less awful if P is an associated type of D?
-        P: Message + Default + From<D>,
-        <D as TryFrom<P>>::Error: Into<anyhow::Error>;
+    /// Reads the raw bytes stored under `key` as they were committed at the given version (block
+    /// height).
+    async fn get_bytes_at_version(&self, key: &str, version: Version) -> Result<Option<Vec<u8>>>;
 
-    /// Reads a proto type from the state.
+    /// Iterates, in key order, over the raw values of all keys beginning with `prefix`.
     ///
-    /// It's probably preferable to use [`StateExt::get_domain`] instead,
-    /// but there are cases where it's convenient to use the proto directly.
-    async fn get_proto<P>(&self, key: KeyHash) -> Result<Option<P>>
-    where
-        P: Message + Default + Debug;
+    /// For a [`State`], this includes writes which have not yet been committed. Values are read
+    /// as the stream is polled, so writes made while iterating may or may not be observed.
+    fn iter_prefix(&self, prefix: &str) -> BoxStream<'static, Result<(String, Vec<u8>)>>;
 
-    /// Puts a proto type into the state.
-    ///
-    /// It's probably preferable to use [`StateExt::put_domain`] instead,
-    /// but there are cases where it's convenient to use the proto directly.
-    async fn put_proto<P>(&self, key: KeyHash, value: P)
-    where
-        P: Message + Debug;
-}
-
-#[async_trait]
-impl StateExt for State {
+    /// Reads a domain type from the state, using the proto encoding.
     #[instrument(skip(self, key))]
-    async fn get_domain<D, P>(&self, key: KeyHash) -> Result<Option<D>>
+    async fn get_domain<D, P>(&self, key: String) -> Result<Option<D>>
     where
-        D: Protobuf<P>,
+        D: Protobuf<P> + TryFrom<P> + Clone + Debug,
         // TODO: does this get less awful if P is an associated type of D?
-        P: Message + Default,
-        P: From<D>,
-        D: TryFrom<P> + Clone + Debug,
+        P: Message + Default + From<D>,
         <D as TryFrom<P>>::Error: Into<anyhow::Error>,
     {
-        match self.get_proto(key).await {
+        match self.get_proto(key.clone()).await {
             Ok(Some(p)) => match D::try_from(p) {
                 Ok(d) => {
                     tracing::trace!(?key, value = ?d);
@@ -73,26 +53,40 @@ impl StateExt for State {
         }
     }
 
-    #[instrument(skip(self, key, value))]
-    async fn put_domain<D, P>(&self, key: KeyHash, value: D)
+    /// Reads a domain type from the state as it was committed at the given version (block
+    /// height), using the proto encoding.
+    ///
+    /// Unlike [`StateRead::get_domain`], this ignores any writes which have not been committed.
+    #[instrument(skip(self, key))]
+    async fn get_at_version<D, P>(&self, key: String, version: Version) -> Result<Option<D>>
     where
-        D: Protobuf<P>,
-        // TODO: does this get less awful if P is an associated type of D?
-        P: Message + Default,
-        P: From<D>,
-        D: TryFrom<P> + Clone + Send + Debug,
+        D: Protobuf<P> + TryFrom<P> + Clone + Debug,
+        P: Message + Default + From<D>,
         <D as TryFrom<P>>::Error: Into<anyhow::Error>,
     {
-        tracing::trace!(?key, ?value);
-        self.put_proto(key, P::from(value)).await;
+        let bytes = match self.get_bytes_at_version(&key, version).await? {
+            None => {
+                tracing::trace!(?key, version, "no entry in tree");
+                return Ok(None);
+            }
+            Some(bytes) => bytes,
+        };
+
+        let value = D::try_from(P::decode(bytes.as_slice())?).map_err(Into::into)?;
+        tracing::trace!(?key, version, ?value);
+        Ok(Some(value))
     }
 
+    /// Reads a proto type from the state.
+    ///
+    /// It's probably preferable to use [`StateRead::get_domain`] instead,
+    /// but there are cases where it's convenient to use the proto directly.
     #[instrument(skip(self, key))]
-    async fn get_proto<P>(&self, key: KeyHash) -> Result<Option<P>>
+    async fn get_proto<P>(&self, key: String) -> Result<Option<P>>
     where
         P: Message + Default + Debug,
     {
-        let bytes = match self.read().await.get(key).await? {
+        let bytes = match self.get_bytes(&key).await? {
             None => return Ok(None),
             Some(bytes) => bytes,
         };
@@ -102,11 +96,144 @@ impl StateExt for State {
             .map(|v| Some(v))
     }
 
+    /// Reads the domain type stored under a typed key, using the proto encoding.
+    async fn get_typed<D, P>(&self, key: &StateKey<D>) -> Result<Option<D>>
+    where
+        D: Protobuf<P> + TryFrom<P> + Clone + Debug,
+        P: Message + Default + From<D>,
+        <D as TryFrom<P>>::Error: Into<anyhow::Error>,
+    {
+        self.get_domain(key.to_string()).await
+    }
+
+    /// Reads the proto type stored under a typed key.
+    async fn get_typed_proto<P>(&self, key: &StateKey<P>) -> Result<Option<P>>
+    where
+        P: Message + Default + Debug,
+    {
+        self.get_proto(key.to_string()).await
+    }
+
+    /// Iterates, in key order, over all keys beginning with `prefix`, decoding their values as a
+    /// domain type using the proto encoding.
+    fn iter_prefix_domain<D, P>(&self, prefix: &str) -> BoxStream<'static, Result<(String, D)>>
+    where
+        D: Protobuf<P> + TryFrom<P> + Send + 'static,
+        P: Message + Default + From<D> + 'static,
+        <D as TryFrom<P>>::Error: Into<anyhow::Error>,
+    {
+        self.iter_prefix(prefix)
+            .map(|entry| {
+                let (key, bytes) = entry?;
+                let value = D::try_from(P::decode(bytes.as_slice())?).map_err(Into::into)?;
+                Ok((key, value))
+            })
+            .boxed()
+    }
+}
+
+/// An extension trait that allows writing proto-encoded domain types to
+/// a shared [`State`].
+#[async_trait]
+pub trait StateExt: StateRead {
+    /// Puts a domain type into the state, using the proto encoding.
+    async fn put_domain<D, P>(&self, key: String, value: D)
+    where
+        D: Protobuf<P> + Send + TryFrom<P> + Clone + Debug,
+        // TODO: does this get less awful if P is an associated type of D?
+        P: Message + Default + From<D>,
+        <D as TryFrom<P>>::Error: Into<anyhow::Error>;
+
+    /// Puts a proto type into the state.
+    ///
+    /// It's probably preferable to use [`StateExt::put_domain`] instead,
+    /// but there are cases where it's convenient to use the proto directly.
+    async fn put_proto<P>(&self, key: String, value: P)
+    where
+        P: Message + Debug;
+
+    /// Deletes the value of a key from the state.
+    async fn delete(&self, key: String);
+
+    /// Deletes the value stored under a typed key.
+    async fn delete_typed<T>(&self, key: &StateKey<T>) {
+        self.delete(key.to_string()).await
+    }
+
+    /// Puts a domain type into the state under a typed key, using the proto encoding.
+    async fn put_typed<D, P>(&self, key: &StateKey<D>, value: D)
+    where
+        D: Protobuf<P> + Send + TryFrom<P> + Clone + Debug,
+        P: Message + Default + From<D>,
+        <D as TryFrom<P>>::Error: Into<anyhow::Error>,
+    {
+        self.put_domain(key.to_string(), value).await
+    }
+
+    /// Puts a proto type into the state under a typed key.
+    async fn put_typed_proto<P>(&self, key: &StateKey<P>, value: P)
+    where
+        P: Message + Debug,
+    {
+        self.put_proto(key.to_string(), value).await
+    }
+}
+
+#[async_trait]
+impl StateRead for State {
+    async fn get_bytes(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.read().await.get(key).await
+    }
+
+    async fn get_bytes_at_version(&self, key: &str, version: Version) -> Result<Option<Vec<u8>>> {
+        self.read().await.get_at_version(key, version).await
+    }
+
+    fn iter_prefix(&self, prefix: &str) -> BoxStream<'static, Result<(String, Vec<u8>)>> {
+        let state = self.clone();
+        let prefix = prefix.to_string();
+        try_stream! {
+            // The key index records every key ever written, so skip any keys which have no value
+            // at this version of the state.
+            let keys = state.read().await.keys_with_prefix(&prefix).await?;
+            for key in keys {
+                let value = state.read().await.get(&key).await?;
+                if let Some(value) = value {
+                    yield (key, value);
+                }
+            }
+        }
+        .boxed()
+    }
+}
+
+#[async_trait]
+impl StateExt for State {
     #[instrument(skip(self, key, value))]
-    async fn put_proto<P>(&self, key: KeyHash, value: P)
+    async fn put_domain<D, P>(&self, key: String, value: D)
+    where
+        D: Protobuf<P>,
+        // TODO: does this get less awful if P is an associated type of D?
+        P: Message + Default,
+        P: From<D>,
+        D: TryFrom<P> + Clone + Send + Debug,
+        <D as TryFrom<P>>::Error: Into<anyhow::Error>,
+    {
+        tracing::trace!(?key, ?value);
+        self.put_proto(key, P::from(value)).await;
+    }
+
+    #[instrument(skip(self, key, value))]
+    async fn put_proto<P>(&self, key: String, value: P)
     where
         P: Message + Debug,
     {
         self.write().await.put(key, value.encode_to_vec());
     }
+
+    #[instrument(skip(self, key))]
+    async fn delete(&self, key: String) {
+        tracing::trace!(?key, "deleting key");
+        self.write().await.delete(key);
+    }
 }
@@ -0,0 +1,38 @@
+use anyhow::{anyhow, Result};
+use ics23::CommitmentProof;
+use jmt::RootHash;
+
+/// Verifies an ICS23 proof produced by [`Storage::get_with_proof`](crate::Storage::get_with_proof)
+/// against the root hash (app hash) of the tree at the version the proof was made for.
+///
+/// If `value` is `Some`, the proof must show that `key` has that value; if it is `None`, the proof
+/// must show that `key` is absent from the tree.
+pub fn verify_proof(
+    proof: &CommitmentProof,
+    root: RootHash,
+    key: &str,
+    value: Option<&[u8]>,
+) -> Result<()> {
+    let spec = jmt::ics23_spec();
+    let root = root.0.to_vec();
+
+    let verified = match value {
+        Some(value) => ics23::verify_membership(proof, &spec, &root, key.as_bytes(), value),
+        None => ics23::verify_non_membership(proof, &spec, &root, key.as_bytes()),
+    };
+
+    if verified {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "invalid {} proof for key {:?} against root {}",
+            if value.is_some() {
+                "existence"
+            } else {
+                "non-existence"
+            },
+            key,
+            hex::encode(root)
+        ))
+    }
+}
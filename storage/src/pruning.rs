@@ -0,0 +1,365 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Result};
+use jmt::{
+    storage::{Node, NodeBatch, NodeKey, TreeReader},
+    Version,
+};
+
+use metrics::counter;
+
+use crate::{metrics::NODES_PRUNED, Storage};
+
+/// Which versions of the tree to keep when pruning.
+///
+/// The default policy keeps every version.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Keep only this many of the most recent versions (at least 1), or all versions if `None`.
+    pub keep_recent: Option<u64>,
+    /// Additionally keep every version which is a multiple of this number.
+    pub keep_every: Option<u64>,
+}
+
+impl RetentionPolicy {
+    /// Whether this policy keeps every version, as an archive node does.
+    pub fn is_archive(&self) -> bool {
+        self.keep_recent.is_none()
+    }
+
+    /// Returns the version below which versions may be pruned, given the latest version.
+    pub fn prune_before(&self, latest: Version) -> Option<Version> {
+        self.keep_recent
+            .map(|n| (latest + 1).saturating_sub(n.max(1)))
+    }
+
+    /// Returns whether `version` should be kept even though it is not recent.
+    pub fn keeps(&self, version: Version) -> bool {
+        match self.keep_every {
+            Some(k) if k > 0 => version % k == 0,
+            _ => false,
+        }
+    }
+
+    /// Returns whether any version in `versions` should be kept even though it is not recent.
+    fn keeps_any(&self, versions: Range<Version>) -> bool {
+        match self.keep_every {
+            Some(k) if k > 0 => {
+                // The first multiple of `k` at or after the start of the range.
+                let first = match versions.start % k {
+                    0 => Some(versions.start),
+                    r => versions.start.checked_add(k - r),
+                };
+                first.map_or(false, |v| v < versions.end)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns whether `version` is retained by this policy, given the latest version.
+    ///
+    /// Versions which are not retained may not have been pruned yet, since pruning runs
+    /// periodically, but they should be treated as if they had been.
+    pub fn retains(&self, version: Version, latest: Version) -> bool {
+        match self.prune_before(latest) {
+            Some(before) if version < before => self.keeps(version),
+            _ => true,
+        }
+    }
+}
+
+/// The versions of the tree currently referenced by open states, which must not be pruned.
+#[derive(Debug, Default)]
+pub(crate) struct Pins {
+    /// The number of open references to each version.
+    counts: BTreeMap<Version, usize>,
+    /// While pruning is in progress, the version below which versions are being pruned.
+    pruning_before: Option<Version>,
+}
+
+/// A reference to a version of the tree, which prevents it from being pruned until dropped.
+#[derive(Debug)]
+pub(crate) struct VersionPin {
+    pins: Arc<Mutex<Pins>>,
+    version: Version,
+}
+
+impl VersionPin {
+    /// The pinned version.
+    pub(crate) fn version(&self) -> Version {
+        self.version
+    }
+}
+
+impl Drop for VersionPin {
+    fn drop(&mut self) {
+        let mut pins = self.pins.lock().unwrap();
+        if let Some(count) = pins.counts.get_mut(&self.version) {
+            *count -= 1;
+            if *count == 0 {
+                pins.counts.remove(&self.version);
+            }
+        }
+    }
+}
+
+/// Resets the pruning marker when pruning finishes, whether or not it succeeded.
+struct PruningGuard(Arc<Mutex<Pins>>);
+
+impl Drop for PruningGuard {
+    fn drop(&mut self) {
+        self.0.lock().unwrap().pruning_before = None;
+    }
+}
+
+impl Storage {
+    /// Prevents `version` from being pruned for as long as the returned pin is held.
+    ///
+    /// Fails if the version is being pruned.
+    pub(crate) fn pin(&self, version: Version) -> Result<VersionPin> {
+        let mut pins = self.pins.lock().unwrap();
+        if let Some(before) = pins.pruning_before {
+            if version < before && !pins.counts.contains_key(&version) {
+                return Err(anyhow!("version {} is being pruned", version));
+            }
+        }
+        *pins.counts.entry(version).or_default() += 1;
+        Ok(VersionPin {
+            pins: self.pins.clone(),
+            version,
+        })
+    }
+
+    /// Returns the keys of the nodes of the previous version of the tree which are replaced by
+    /// `node_batch`, and so are stale from the batch's version onwards.
+    ///
+    /// A commit rewrites every node on the path to each key it writes, at the position the node
+    /// had, so a node of the previous version is replaced exactly when the batch has a node at its
+    /// position. Every other node is shared with the new version, so only the rewritten paths are
+    /// read.
+    pub(crate) async fn replaced_nodes(&self, node_batch: &NodeBatch) -> Result<Vec<NodeKey>> {
+        let version = match node_batch.keys().next() {
+            Some(node_key) if node_key.version() > 0 => node_key.version(),
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut stale = Vec::new();
+        let mut stack = vec![(
+            NodeKey::new_empty_path(version - 1),
+            NodeKey::new_empty_path(version),
+        )];
+        while let Some((old, new)) = stack.pop() {
+            if !node_batch.contains_key(&new) {
+                continue;
+            }
+            match self.get_node_option(&old).await? {
+                Some(Node::Internal(internal)) => {
+                    for (nibble, child) in internal.children_sorted() {
+                        stack.push((
+                            old.gen_child_node_key(child.version, *nibble),
+                            new.gen_child_node_key(version, *nibble),
+                        ));
+                    }
+                }
+                Some(_) => {}
+                // The previous version is empty, or this position was empty in it.
+                None => continue,
+            }
+            stale.push(old);
+        }
+        Ok(stale)
+    }
+
+    /// Deletes every version of the tree before `version`, except those still referenced by
+    /// open states, returning the number of nodes deleted.
+    pub async fn prune_to(&self, version: Version) -> Result<usize> {
+        self.prune_versions(version, &RetentionPolicy::default())
+            .await
+    }
+
+    /// Deletes the versions of the tree which are not kept by `policy`, except those still
+    /// referenced by open states, returning the number of nodes deleted.
+    ///
+    /// Only the nodes in the stale node index are considered, so the cost of pruning depends on
+    /// how much the tree has changed since it was last pruned, rather than on its size.
+    pub async fn prune(&self, policy: &RetentionPolicy) -> Result<usize> {
+        let latest = match self.latest_version().await? {
+            Some(latest) => latest,
+            None => return Ok(0),
+        };
+        match policy.prune_before(latest) {
+            Some(before) if before > 0 => self.prune_versions(before, policy).await,
+            _ => Ok(0),
+        }
+    }
+
+    /// Deletes the nodes which are only reachable from versions before `before` that are neither
+    /// kept by `policy` nor pinned.
+    ///
+    /// A node written at version `w` and replaced at version `s` is reachable from exactly the
+    /// versions `w..s`, so it can be deleted once `s <= before`, unless one of those versions is
+    /// kept or pinned. A node kept by the policy is dropped from the stale node index, since it
+    /// will never be pruned, while a pinned node stays in the index until its pin is released.
+    async fn prune_versions(&self, before: Version, policy: &RetentionPolicy) -> Result<usize> {
+        let latest = self
+            .latest_version()
+            .await?
+            .ok_or_else(|| anyhow!("cannot prune an empty tree"))?;
+        if before > latest {
+            return Err(anyhow!(
+                "cannot prune the latest version {} (requested pruning before {})",
+                latest,
+                before
+            ));
+        }
+
+        // Mark the versions as being pruned, so that no new states can be opened on them, and
+        // collect the versions which are already in use.
+        let pinned = {
+            let mut pins = self.pins.lock().unwrap();
+            if pins.pruning_before.is_some() {
+                return Err(anyhow!("pruning is already in progress"));
+            }
+            pins.pruning_before = Some(before);
+            pins.counts
+                .range(..before)
+                .map(|(version, _)| *version)
+                .collect::<BTreeSet<_>>()
+        };
+        let _guard = PruningGuard(self.pins.clone());
+        if !pinned.is_empty() {
+            tracing::info!(?pinned, "not pruning versions referenced by open states");
+        }
+
+        let mut deletable = Vec::new();
+        let mut kept = Vec::new();
+        let mut still_pinned = 0;
+        for (stale_since, node_key) in self.backend.stale_nodes_through(before).await? {
+            let reachable_from = node_key.version()..stale_since;
+            if pinned.range(reachable_from.clone()).next().is_some() {
+                still_pinned += 1;
+            } else if policy.keeps_any(reachable_from) {
+                kept.push((stale_since, node_key));
+            } else {
+                deletable.push((stale_since, node_key));
+            }
+        }
+
+        let deleted = deletable.len();
+        self.backend.delete_stale_nodes(deletable).await?;
+        self.backend.forget_stale_nodes(kept).await?;
+        // Deleted nodes may be cached, and it's simpler to start afresh than to find them.
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+        counter!(NODES_PRUNED, deleted as u64);
+        tracing::info!(before, deleted, still_pinned, "pruned tree");
+
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use jmt::{
+        storage::{Node, NodeKey},
+        Version,
+    };
+
+    use super::RetentionPolicy;
+    use crate::Storage;
+
+    /// Commits `versions` versions to in-memory storage, each adding a key and overwriting an
+    /// earlier one, so that nodes are replaced throughout the tree.
+    async fn storage_with_versions(versions: u64) -> Storage {
+        let storage = Storage::in_memory();
+        for version in 0..versions {
+            let state = storage.state().await.unwrap();
+            let mut overlay = state.write().await;
+            overlay.put(format!("test/{}", version), vec![0]);
+            overlay.put(format!("test/{}", version / 2), vec![version as u8]);
+            overlay.commit(storage.clone()).await.unwrap();
+        }
+        storage
+    }
+
+    /// Returns the keys of every node reachable from the given versions, by walking the tree.
+    async fn reachable(
+        storage: &Storage,
+        versions: impl IntoIterator<Item = Version>,
+    ) -> BTreeSet<NodeKey> {
+        let mut reachable = BTreeSet::new();
+        for version in versions {
+            let mut stack = vec![NodeKey::new_empty_path(version)];
+            while let Some(node_key) = stack.pop() {
+                if !reachable.insert(node_key.clone()) {
+                    continue;
+                }
+                if let Some(Node::Internal(internal)) =
+                    storage.backend.get_node(&node_key).await.unwrap()
+                {
+                    for (nibble, child) in internal.children_sorted() {
+                        stack.push(node_key.gen_child_node_key(child.version, *nibble));
+                    }
+                }
+            }
+        }
+        reachable
+    }
+
+    async fn stored(storage: &Storage) -> BTreeSet<NodeKey> {
+        storage
+            .backend
+            .node_keys_before(Version::MAX)
+            .await
+            .unwrap()
+            .into_iter()
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn prune_deletes_exactly_the_unreachable_nodes() {
+        let storage = storage_with_versions(10).await;
+        let policy = RetentionPolicy {
+            keep_recent: Some(3),
+            keep_every: Some(4),
+        };
+        let retained = reachable(&storage, [0, 4, 7, 8, 9]).await;
+        let before = stored(&storage).await.len();
+
+        let deleted = storage.prune(&policy).await.unwrap();
+        assert_eq!(deleted, before - retained.len());
+        assert_eq!(stored(&storage).await, retained);
+        // The nodes kept for versions 0 and 4 are dropped from the index.
+        assert!(storage
+            .backend
+            .stale_nodes_through(Version::MAX)
+            .await
+            .unwrap()
+            .iter()
+            .all(|(stale_since, _)| *stale_since > 7));
+
+        // Pruning again finds nothing more to delete.
+        assert_eq!(storage.prune(&policy).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn prune_keeps_pinned_versions_until_released() {
+        let storage = storage_with_versions(10).await;
+        let state = storage.state_at(2).await.unwrap();
+
+        storage.prune_to(8).await.unwrap();
+        assert_eq!(stored(&storage).await, reachable(&storage, [2, 8, 9]).await);
+        assert!(storage.get_at_version("test/2", 2).await.unwrap().is_some());
+
+        drop(state);
+        assert!(storage.prune_to(8).await.unwrap() > 0);
+        assert_eq!(stored(&storage).await, reachable(&storage, [8, 9]).await);
+    }
+}
@@ -0,0 +1,426 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use async_stream::try_stream;
+use async_trait::async_trait;
+use futures::{
+    future::BoxFuture,
+    stream::{BoxStream, StreamExt},
+};
+use jmt::{
+    storage::{LeafNode, Node, NodeKey, TreeReader},
+    JellyfishMerkleTree, KeyHash, RootHash, Version,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{pruning::VersionPin, StateRead, Storage, WriteOverlay};
+
+/// A read-only view of a committed version of the chain state.
+///
+/// Unlike a [`State`](crate::State), a `Snapshot` has no write overlay and takes no locks, so
+/// any number of snapshots can be read concurrently with block execution. The version is
+/// protected from pruning until every clone of the snapshot is dropped.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    storage: Storage,
+    version: Version,
+    _pin: Arc<VersionPin>,
+}
+
+impl Snapshot {
+    /// The version (block height) of the state this snapshot reads.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+}
+
+#[async_trait]
+impl StateRead for Snapshot {
+    async fn get_bytes(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.storage.get_at_version(key, self.version).await
+    }
+
+    async fn get_bytes_at_version(&self, key: &str, version: Version) -> Result<Option<Vec<u8>>> {
+        self.storage.get_at_version(key, version).await
+    }
+
+    fn iter_prefix(&self, prefix: &str) -> BoxStream<'static, Result<(String, Vec<u8>)>> {
+        let snapshot = self.clone();
+        let prefix = prefix.to_string();
+        try_stream! {
+            // The key index records every key ever written, including after this version.
+            let keys = snapshot.storage.keys_with_prefix(&prefix).await?;
+            for key in keys {
+                let value = snapshot.get_bytes(&key).await?;
+                if let Some(value) = value {
+                    yield (key, value);
+                }
+            }
+        }
+        .boxed()
+    }
+}
+
+impl Storage {
+    /// Returns a read-only [`Snapshot`] of the given (already committed) version of the tree.
+    pub async fn snapshot(&self, version: Version) -> Result<Snapshot> {
+        match self.latest_version().await? {
+            Some(latest) if !self.retention.retains(version, latest) => {
+                Err(anyhow!("version {} has been pruned", version))
+            }
+            Some(latest) if version <= latest => Ok(Snapshot {
+                storage: self.clone(),
+                version,
+                _pin: Arc::new(self.pin(version)?),
+            }),
+            latest => Err(anyhow!(
+                "version {} has not been committed (latest version is {:?})",
+                version,
+                latest
+            )),
+        }
+    }
+
+    /// Returns a read-only [`Snapshot`] of the latest version of the tree.
+    pub async fn latest_snapshot(&self) -> Result<Snapshot> {
+        let version = self
+            .latest_version()
+            .await?
+            .ok_or_else(|| anyhow!("cannot snapshot an empty tree"))?;
+        self.snapshot(version).await
+    }
+
+    /// Like [`Self::latest_snapshot`], but bundles in a [`tonic`] error conversion.
+    ///
+    /// This is useful for implementing gRPC services that query the storage:
+    /// each gRPC request can take a [`Snapshot`] of the latest version at the
+    /// time the request was received, and then query it using component
+    /// `View`s, without contending with block execution.
+    pub async fn latest_snapshot_tonic(&self) -> std::result::Result<Snapshot, tonic::Status> {
+        self.latest_snapshot()
+            .await
+            .map_err(|e| tonic::Status::unavailable(e.to_string()))
+    }
+}
+
+/// The bytes every snapshot begins with.
+const MAGIC: &[u8; 16] = b"penumbra-snap-v1";
+
+/// The maximum number of entries in a chunk of a snapshot.
+const CHUNK_SIZE: u32 = 1024;
+
+/// The snapshot format is:
+///
+/// - the magic bytes [`MAGIC`];
+/// - the version of the tree, as a big-endian `u64`;
+/// - the root hash of the tree at that version, as 32 bytes;
+/// - a sequence of chunks, each consisting of a big-endian `u32` count of entries followed by
+///   that many entries, terminated by an empty chunk.
+///
+/// Each entry is a key and a value, each prefixed by its length as a big-endian `u32`. Entries
+/// appear in key order, so exporting the same version always produces the same snapshot.
+impl Storage {
+    /// Writes a snapshot of the entire tree at `version` to `writer`.
+    pub async fn export_snapshot<W>(&self, version: Version, mut writer: W) -> Result<()>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        let root = JellyfishMerkleTree::new(self)
+            .get_root_hash(version)
+            .await?;
+        tracing::info!(version, root = %hex::encode(root.0), "exporting snapshot");
+
+        writer.write_all(MAGIC).await?;
+        writer.write_u64(version).await?;
+        writer.write_all(&root.0).await?;
+
+        let mut chunk = Vec::new();
+        let mut total = 0;
+        for key in self.keys_with_prefix("").await? {
+            // The key index records every key ever written, including those written after this
+            // version, so skip any keys which have no value at this version. Deleted keys are
+            // exported, since their tombstones are part of the tree.
+            if let Some(value) = self.get_raw_at_version(&key, version).await? {
+                chunk.push((key, value));
+            }
+            if chunk.len() == CHUNK_SIZE as usize {
+                total += chunk.len();
+                write_chunk(&mut writer, &chunk).await?;
+                chunk.clear();
+            }
+        }
+        if !chunk.is_empty() {
+            total += chunk.len();
+            write_chunk(&mut writer, &chunk).await?;
+        }
+        // An empty chunk marks the end of the snapshot.
+        writer.write_u32(0).await?;
+        writer.flush().await?;
+
+        tracing::info!(version, entries = total, "exported snapshot");
+        Ok(())
+    }
+
+    /// Reads a snapshot produced by [`Storage::export_snapshot`] from `reader`, and writes it as
+    /// the tree at the snapshot's version, returning that version.
+    ///
+    /// The storage must be empty, and the snapshot is rejected if the root hash of the imported
+    /// tree does not match the one recorded in the snapshot. The snapshot is written to the tree
+    /// a chunk at a time, so only one chunk is held in memory; if the import fails, the nodes
+    /// written so far are deleted.
+    pub async fn import_snapshot<R>(&self, mut reader: R) -> Result<Version>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        if let Some(latest) = self.latest_version().await? {
+            return Err(anyhow!(
+                "cannot import a snapshot into nonempty storage (latest version is {})",
+                latest
+            ));
+        }
+
+        let (version, RootHash(expected_root)) = read_snapshot_header(&mut reader).await?;
+        tracing::info!(version, root = %hex::encode(expected_root), "importing snapshot");
+
+        match self.import_chunks(version, &mut reader).await {
+            Ok(RootHash(root)) if root == expected_root => Ok(version),
+            result => {
+                // The storage was empty, so every node in it was written by this import.
+                let node_keys = self.backend.node_keys_before(Version::MAX).await?;
+                self.backend.delete_nodes(node_keys).await?;
+                match result {
+                    Ok(RootHash(root)) => Err(anyhow!(
+                        "snapshot root hash mismatch: expected {}, computed {}",
+                        hex::encode(expected_root),
+                        hex::encode(root)
+                    )),
+                    Err(e) => Err(e),
+                }
+            }
+        }
+    }
+
+    /// Writes each chunk of a snapshot to the tree at `version` in turn, returning the root hash of
+    /// the resulting tree.
+    async fn import_chunks<R>(&self, version: Version, reader: &mut R) -> Result<RootHash>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let mut tree = ImportReader {
+            storage: self,
+            version,
+            started: false,
+        };
+        let mut root = None;
+        let mut last_key: Option<String> = None;
+        let mut entries = 0;
+        loop {
+            let count = reader.read_u32().await?;
+            if count == 0 {
+                break;
+            }
+            if count > CHUNK_SIZE {
+                return Err(anyhow!("snapshot chunk has too many entries ({})", count));
+            }
+
+            let mut keys = Vec::with_capacity(count as usize);
+            let mut value_set = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let key = String::from_utf8(read_bytes(reader).await?)?;
+                let value = read_bytes(reader).await?;
+                if last_key.as_ref().map(|last| last >= &key).unwrap_or(false) {
+                    return Err(anyhow!("snapshot entries are not in key order"));
+                }
+                value_set.push((KeyHash::from(key.as_bytes()), value));
+                keys.push(key.clone());
+                last_key = Some(key);
+            }
+
+            entries += keys.len();
+            root = Some(tree.put_chunk(value_set).await?);
+            // As in a normal commit, the keys are indexed before the tree is written.
+            self.put_keys(keys).await?;
+            tree.started = true;
+        }
+
+        // An empty snapshot is still written, as the empty tree.
+        let root = match root {
+            Some(root) => root,
+            None => tree.put_chunk(Vec::new()).await?,
+        };
+        tracing::info!(version, entries, "imported snapshot");
+        Ok(root)
+    }
+}
+
+/// Reads the tree while a snapshot is imported into it.
+///
+/// Each chunk is written as a new tree at the snapshot's version, on top of the tree at the
+/// previous version. Once the first chunk is written, this points the previous version's root at
+/// the partial tree already written at the snapshot's version, so each chunk is added to the tree
+/// built from the chunks before it. The nodes of that partial tree are overwritten in place as it
+/// grows, so they are read from the backend rather than the node cache.
+struct ImportReader<'s> {
+    storage: &'s Storage,
+    version: Version,
+    started: bool,
+}
+
+impl ImportReader<'_> {
+    async fn put_chunk(&self, value_set: Vec<(KeyHash, Vec<u8>)>) -> Result<RootHash> {
+        let (root, batch) = JellyfishMerkleTree::new(&*self)
+            .put_value_set(value_set, self.version)
+            .await?;
+        // The import writes a single version into empty storage, overwriting the partial tree in
+        // place, so it replaces no nodes of an earlier version.
+        self.storage
+            .write_nodes_logged(batch.node_batch, Vec::new())
+            .await?;
+        Ok(root)
+    }
+}
+
+impl TreeReader for ImportReader<'_> {
+    fn get_node_option<'future, 'a: 'future, 'n: 'future>(
+        &'a self,
+        node_key: &'n NodeKey,
+    ) -> BoxFuture<'future, Result<Option<Node>>> {
+        Box::pin(async move {
+            let previous_version = match self.version {
+                0 => WriteOverlay::PRE_GENESIS_VERSION,
+                version => version - 1,
+            };
+            if self.started && *node_key == NodeKey::new_empty_path(previous_version) {
+                let root_key = NodeKey::new_empty_path(self.version);
+                return self.storage.backend.get_node(&root_key).await;
+            }
+            self.storage.backend.get_node(node_key).await
+        })
+    }
+
+    fn get_rightmost_leaf<'future, 'a: 'future>(
+        &'a self,
+    ) -> BoxFuture<'future, Result<Option<(NodeKey, LeafNode)>>> {
+        self.storage.backend.get_rightmost_leaf()
+    }
+}
+
+/// Reads the version and root hash recorded at the start of a snapshot produced by
+/// [`Storage::export_snapshot`], leaving `reader` positioned at the snapshot's first chunk.
+///
+/// This allows the root hash of a snapshot to be checked against a trusted one before the
+/// snapshot is imported.
+pub async fn read_snapshot_header<R>(mut reader: R) -> Result<(Version, RootHash)>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    let mut magic = [0u8; 16];
+    reader.read_exact(&mut magic).await?;
+    if &magic != MAGIC {
+        return Err(anyhow!("not a state snapshot"));
+    }
+    let version = reader.read_u64().await?;
+    let mut root = [0u8; 32];
+    reader.read_exact(&mut root).await?;
+    Ok((version, RootHash(root)))
+}
+
+async fn write_chunk<W>(writer: &mut W, chunk: &[(String, Vec<u8>)]) -> Result<()>
+where
+    W: AsyncWrite + Unpin + Send,
+{
+    writer.write_u32(chunk.len() as u32).await?;
+    for (key, value) in chunk {
+        write_bytes(writer, key.as_bytes()).await?;
+        write_bytes(writer, value).await?;
+    }
+    Ok(())
+}
+
+async fn write_bytes<W>(writer: &mut W, bytes: &[u8]) -> Result<()>
+where
+    W: AsyncWrite + Unpin + Send,
+{
+    writer.write_u32(bytes.len().try_into()?).await?;
+    writer.write_all(bytes).await?;
+    Ok(())
+}
+
+async fn read_bytes<R>(reader: &mut R) -> Result<Vec<u8>>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    let len = reader.read_u32().await?;
+    let mut bytes = vec![0u8; len as usize];
+    reader.read_exact(&mut bytes).await?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use jmt::{JellyfishMerkleTree, RootHash};
+
+    use super::CHUNK_SIZE;
+    use crate::Storage;
+
+    /// Commits `heights` blocks to in-memory storage, each writing more entries than fit in a
+    /// chunk, and returns the storage with the root hash of its latest version.
+    async fn storage_with_blocks(heights: u64) -> (Storage, RootHash) {
+        let storage = Storage::in_memory();
+        let mut root = None;
+        for height in 0..heights {
+            let state = storage.state().await.unwrap();
+            let mut overlay = state.write().await;
+            for i in 0..CHUNK_SIZE + 100 {
+                overlay.put(format!("test/{}/{}", height, i), i.to_be_bytes().to_vec());
+            }
+            // Overwrite some of the previous block's entries too.
+            if height > 0 {
+                overlay.put(format!("test/{}/0", height - 1), b"updated".to_vec());
+            }
+            root = Some(overlay.commit(storage.clone()).await.unwrap().0);
+        }
+        (storage, root.unwrap())
+    }
+
+    #[tokio::test]
+    async fn export_import_round_trip() {
+        let (storage, root) = storage_with_blocks(3).await;
+        let mut export = Vec::new();
+        storage.export_snapshot(2, &mut export).await.unwrap();
+
+        let imported = Storage::in_memory();
+        assert_eq!(imported.import_snapshot(&export[..]).await.unwrap(), 2);
+        assert_eq!(imported.latest_version().await.unwrap(), Some(2));
+        assert_eq!(
+            JellyfishMerkleTree::new(&imported)
+                .get_root_hash(2)
+                .await
+                .unwrap(),
+            root
+        );
+        assert_eq!(
+            imported.get_at_version("test/1/0", 2).await.unwrap(),
+            Some(b"updated".to_vec())
+        );
+
+        // Exporting the imported tree reproduces the snapshot.
+        let mut reexport = Vec::new();
+        imported.export_snapshot(2, &mut reexport).await.unwrap();
+        assert_eq!(export, reexport);
+    }
+
+    #[tokio::test]
+    async fn import_rejects_wrong_root() {
+        let (storage, _) = storage_with_blocks(2).await;
+        let mut export = Vec::new();
+        storage.export_snapshot(1, &mut export).await.unwrap();
+        // The root hash follows the 16 magic bytes and the 8-byte version.
+        export[24] ^= 1;
+
+        let imported = Storage::in_memory();
+        assert!(imported.import_snapshot(&export[..]).await.is_err());
+        // The partially imported tree is removed, so the import can be retried.
+        assert_eq!(imported.latest_version().await.unwrap(), None);
+    }
+}
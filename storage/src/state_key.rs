@@ -0,0 +1,82 @@
+use std::{fmt, marker::PhantomData};
+
+/// The key under which a value of type `T` is stored in the chain state.
+///
+/// A key begins with the domain of the component which owns it, followed by any number of
+/// `/`-separated segments, so that keys owned by different components can't collide. Tying the
+/// value type to the key means the typed accessors on [`StateRead`](crate::StateRead) and
+/// [`StateExt`](crate::StateExt) can only read a value as the type it was written as.
+///
+/// Components should build their keys in one place, rather than at each use:
+///
+/// ```ignore
+/// pub fn validator(id: &IdentityKey) -> StateKey<Validator> {
+///     StateKey::new("staking").join("validators").join(id)
+/// }
+/// ```
+pub struct StateKey<T> {
+    path: String,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T> StateKey<T> {
+    /// Starts a key in the given component's domain.
+    ///
+    /// # Panics
+    ///
+    /// If the domain is empty or contains a `/`.
+    pub fn new(domain: &'static str) -> Self {
+        assert!(
+            !domain.is_empty() && !domain.contains('/'),
+            "invalid state key domain {:?}",
+            domain
+        );
+        Self {
+            path: domain.to_string(),
+            _value: PhantomData,
+        }
+    }
+
+    /// Appends a segment to the key.
+    pub fn join(mut self, segment: impl fmt::Display) -> Self {
+        self.path.push('/');
+        self.path.push_str(&segment.to_string());
+        self
+    }
+
+    /// Returns the key as a string, for use with the untyped accessors.
+    pub fn as_str(&self) -> &str {
+        &self.path
+    }
+}
+
+// These are implemented by hand, as deriving them would require `T` to implement them too.
+
+impl<T> Clone for StateKey<T> {
+    fn clone(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            _value: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for StateKey<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl<T> Eq for StateKey<T> {}
+
+impl<T> fmt::Debug for StateKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StateKey").field(&self.path).finish()
+    }
+}
+
+impl<T> fmt::Display for StateKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.path)
+    }
+}
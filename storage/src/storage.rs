@@ -1,42 +1,143 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+    time::Instant,
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use futures::future::BoxFuture;
+use ics23::CommitmentProof;
 use jmt::{
-    storage::{Node, NodeBatch, NodeKey, TreeReader, TreeWriter},
-    WriteOverlay,
+    storage::{LeafNode, Node, NodeBatch, NodeKey, TreeReader, TreeWriter},
+    JellyfishMerkleTree, KeyHash, Version,
 };
-use rocksdb::DB;
+use metrics::histogram;
 use tokio::sync::RwLock;
-use tracing::{instrument, Span};
+use tracing::instrument;
 
-use crate::State;
+use crate::{
+    backend::{Memory, RocksDb},
+    cache::{CacheStats, NodeCache},
+    metrics::NODE_READ_DURATION,
+    migration::{MigratedWrites, Migrations},
+    pruning::{Pins, RetentionPolicy},
+    subscription::Subscriptions,
+    State, StateBackend, WriteOverlay,
+};
 
+/// A handle to the persistent storage of the chain state, which is backed by a pluggable
+/// [`StateBackend`].
 #[derive(Clone, Debug)]
-pub struct Storage(Arc<DB>);
+pub struct Storage {
+    pub(crate) backend: Arc<dyn StateBackend>,
+    /// The versions referenced by open states, which must not be pruned.
+    pub(crate) pins: Arc<Mutex<Pins>>,
+    /// A cache of nodes read from the backend, if enabled.
+    pub(crate) cache: Option<Arc<NodeCache>>,
+    /// The subscribers to changes made by commits.
+    pub(crate) subscriptions: Arc<Mutex<Subscriptions>>,
+    /// The writes made by migrations, which have yet to be committed.
+    pub(crate) migrated: Arc<Mutex<Option<MigratedWrites>>>,
+    /// Whether the backend is being compacted.
+    pub(crate) compacting: Arc<AtomicBool>,
+    /// Which versions are retained, and so can be read.
+    pub(crate) retention: RetentionPolicy,
+}
 
 impl Storage {
-    pub async fn load(path: PathBuf) -> Result<Self> {
-        let span = Span::current();
-        tokio::task::Builder::new()
-            .name("open_rocksdb")
-            .spawn_blocking(move || {
-                span.in_scope(|| {
-                    tracing::info!(?path, "opening rocksdb");
-                    Ok(Self(Arc::new(DB::open_default(path)?)))
-                })
-            })
-            .await
-            .unwrap()
+    /// Opens the RocksDB database at `path`, creating it if it does not exist.
+    ///
+    /// If the last commit to the database was interrupted, it is completed from the write-ahead
+    /// log before the storage is returned. Then, if the state's app version is behind the latest
+    /// version in `migrations`, the outstanding migrations are run; see [`Migration`](crate::Migration).
+    pub async fn load(path: PathBuf, migrations: &Migrations) -> Result<Self> {
+        Self::open(RocksDb::open(path).await?, migrations).await
+    }
+
+    /// Opens a `Storage` using the given persistent backend, recovering from the write-ahead log
+    /// and running any outstanding migrations as in [`Storage::load`].
+    pub async fn open(backend: impl StateBackend, migrations: &Migrations) -> Result<Self> {
+        let storage = Self::new(backend);
+        storage.recover().await?;
+        storage.migrate(migrations).await?;
+        Ok(storage)
+    }
+
+    /// Creates a new `Storage` using the given backend.
+    pub fn new(backend: impl StateBackend) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            pins: Default::default(),
+            cache: None,
+            subscriptions: Default::default(),
+            migrated: Default::default(),
+            compacting: Default::default(),
+            retention: Default::default(),
+        }
+    }
+
+    /// Sets which versions of the tree are retained; the default is to retain every version.
+    ///
+    /// Historical versions which the policy doesn't retain can't be read, whether or not they
+    /// have been pruned yet. Pruning itself is done by [`Storage::prune`].
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Returns which versions of the tree are retained.
+    pub fn retention(&self) -> RetentionPolicy {
+        self.retention
+    }
+
+    /// Caches up to `capacity` bytes of the most recently read tree nodes in memory, so that
+    /// repeated reads of the same keys don't go to the backend each time.
+    ///
+    /// A capacity of 0 disables the cache.
+    pub fn with_node_cache(mut self, capacity: usize) -> Self {
+        self.cache = if capacity > 0 {
+            Some(Arc::new(NodeCache::new(capacity)))
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Returns the node cache's hit and miss counts and current size, if the cache is enabled.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|cache| cache.stats())
+    }
+
+    /// Creates a new, empty `Storage` held in memory.
+    pub fn in_memory() -> Self {
+        Self::new(Memory::default())
     }
 
     /// Returns the latest version (block height) of the tree recorded by the
     /// `Storage`, or `None` if the tree is empty.
     pub async fn latest_version(&self) -> Result<Option<jmt::Version>> {
-        Ok(self
-            .get_rightmost_leaf()
-            .await?
-            .map(|(node_key, _)| node_key.version()))
+        self.backend.latest_version().await
+    }
+
+    /// Returns the approximate size of the stored state in bytes, or `None` if the backend can't
+    /// measure it (as for the in-memory backend).
+    pub async fn size(&self) -> Result<Option<u64>> {
+        self.backend.size().await
+    }
+
+    /// Writes any buffered writes through to disk, so that the storage can be closed cleanly.
+    pub async fn flush(&self) -> Result<()> {
+        self.backend.flush().await
+    }
+
+    /// Records that the given (unhashed) keys have been written to the tree.
+    pub async fn put_keys(&self, keys: Vec<String>) -> Result<()> {
+        self.backend.put_keys(keys).await
+    }
+
+    /// Returns, in order, every key ever written to the tree which begins with `prefix`.
+    pub async fn keys_with_prefix(&self, prefix: &str) -> Result<Vec<String>> {
+        self.backend.keys_with_prefix(prefix).await
     }
 
     /// Returns a new [`State`] on top of the latest version of the tree.
@@ -46,67 +147,104 @@ impl Storage {
         let version = self
             .latest_version()
             .await?
-            .unwrap_or(WriteOverlay::<Storage>::PRE_GENESIS_VERSION);
+            .unwrap_or(WriteOverlay::PRE_GENESIS_VERSION);
 
         tracing::debug!("creating state for version {}", version);
-        Ok(Arc::new(RwLock::new(WriteOverlay::new(
-            self.clone(),
-            version,
-        ))))
+        let mut overlay = WriteOverlay::new(self.clone(), version)?;
+        for (key, value) in self.migrated_writes(version) {
+            overlay.put(key, value);
+        }
+        Ok(Arc::new(RwLock::new(overlay)))
     }
 
-    /// Like [`Self::state`], but bundles in a [`tonic`] error conversion.
+    /// Returns a new [`State`] on top of the given (already committed) version of the tree.
     ///
-    /// This is useful for implementing gRPC services that query the storage:
-    /// each gRPC request can create an ephemeral [`State`] pinning the current
-    /// version at the time the request was received, and then query it using
-    /// component `View`s to handle the request.
-    pub async fn state_tonic(&self) -> std::result::Result<State, tonic::Status> {
-        self.state()
-            .await
-            .map_err(|e| tonic::Status::internal(e.to_string()))
+    /// Writes to the returned state are never committed in practice, since committing would
+    /// conflict with the versions after it; it is intended for reading historical state.
+    pub async fn state_at(&self, version: Version) -> Result<State> {
+        match self.latest_version().await? {
+            Some(latest) if !self.retention.retains(version, latest) => {
+                Err(anyhow!("version {} has been pruned", version))
+            }
+            Some(latest) if version <= latest => {
+                tracing::debug!("creating state for historical version {}", version);
+                Ok(Arc::new(RwLock::new(WriteOverlay::new(
+                    self.clone(),
+                    version,
+                )?)))
+            }
+            latest => Err(anyhow!(
+                "version {} has not been committed (latest version is {:?})",
+                version,
+                latest
+            )),
+        }
+    }
+
+    /// Reads the committed value of `key` as of the given version of the tree.
+    pub async fn get_at_version(&self, key: &str, version: Version) -> Result<Option<Vec<u8>>> {
+        Ok(WriteOverlay::live(
+            self.get_raw_at_version(key, version).await?,
+        ))
+    }
+
+    /// Reads the value of `key` exactly as it is stored in the given version of the tree,
+    /// returning [`WriteOverlay::TOMBSTONE`] if it was deleted.
+    pub(crate) async fn get_raw_at_version(
+        &self,
+        key: &str,
+        version: Version,
+    ) -> Result<Option<Vec<u8>>> {
+        let (value, _proof) = JellyfishMerkleTree::new(self)
+            .get_with_proof(KeyHash::from(key.as_bytes()), version)
+            .await?;
+        Ok(value)
+    }
+
+    /// Reads the committed value of `key` as of the given version of the tree, together with an
+    /// ICS23 proof of its existence (or non-existence) in that version.
+    ///
+    /// The proof can be checked against the root hash of that version, which is the app hash
+    /// reported to Tendermint, using [`verify_proof`](crate::verify_proof).
+    ///
+    /// Unlike [`Storage::get_at_version`], the value is returned exactly as it is stored, so that
+    /// it matches the proof: a deleted key has the value [`WriteOverlay::TOMBSTONE`].
+    pub async fn get_with_proof(
+        &self,
+        key: &str,
+        version: Version,
+    ) -> Result<(Option<Vec<u8>>, CommitmentProof)> {
+        let tree = JellyfishMerkleTree::new(self);
+        let (value, _proof) = tree
+            .get_with_proof(KeyHash::from(key.as_bytes()), version)
+            .await?;
+        let proof = tree
+            .get_with_ics23_proof(key.as_bytes().to_vec(), version)
+            .await?;
+        Ok((value, proof))
     }
 }
 
 impl TreeWriter for Storage {
-    /// Writes a node batch into storage.
+    /// Writes a node batch into storage, recording the nodes of the previous version which it
+    /// replaces in the stale node index.
     //TODO: Change JMT traits to remove/simplify lifetimes & accept owned NodeBatch
     #[instrument(skip(self, node_batch))]
     fn write_node_batch<'future, 'a: 'future, 'n: 'future>(
         &'a mut self,
         node_batch: &'n NodeBatch,
     ) -> BoxFuture<'future, Result<()>> {
-        let db = self.0.clone();
+        let storage = self.clone();
         let node_batch = node_batch.clone();
-
-        // The writes have to happen on a separate spawn_blocking task, but we
-        // want tracing events to occur in the context of the current span, so
-        // propagate it explicitly:
-        let span = Span::current();
-
-        Box::pin(async {
-            tokio::task::Builder::new()
-                .name("Storage::write_node_batch")
-                .spawn_blocking(move || {
-                    span.in_scope(|| {
-                        for (node_key, node) in node_batch.clone() {
-                            let key_bytes = &node_key.encode()?;
-                            let value_bytes = &node.encode()?;
-                            tracing::trace!(?key_bytes, value_bytes = ?hex::encode(&value_bytes));
-                            db.put(key_bytes, value_bytes)?;
-                        }
-
-                        Ok(())
-                    })
-                })
-                .await
-                .unwrap()
+        Box::pin(async move {
+            let stale = storage.replaced_nodes(&node_batch).await?;
+            storage.write_nodes_logged(node_batch, stale).await
         })
     }
 }
 
-/// A reader interface for rocksdb. NOTE: it is up to the caller to ensure consistency between the
-/// rocksdb::DB handle and any write batches that may be applied through the writer interface.
+/// A reader interface for the backend. NOTE: it is up to the caller to ensure consistency between
+/// the backend and any write batches that may be applied through the writer interface.
 impl TreeReader for Storage {
     /// Gets node given a node key. Returns `None` if the node does not exist.
     #[instrument(skip(self))]
@@ -114,60 +252,27 @@ impl TreeReader for Storage {
         &'a self,
         node_key: &'n NodeKey,
     ) -> BoxFuture<'future, Result<Option<Node>>> {
-        let db = self.0.clone();
-        let node_key = node_key.clone();
-
-        let span = Span::current();
-
-        Box::pin(async {
-            tokio::task::Builder::new()
-                .name("Storage::get_node_option")
-                .spawn_blocking(move || {
-                    span.in_scope(|| {
-                        let value = db
-                            .get_pinned(&node_key.encode()?)?
-                            .map(|db_slice| Node::decode(&db_slice))
-                            .transpose()?;
-
-                        tracing::trace!(?node_key, ?value);
-                        Ok(value)
-                    })
-                })
-                .await
-                .unwrap()
+        Box::pin(async move {
+            let start = Instant::now();
+            let cached = self.cache.as_ref().and_then(|cache| cache.get(node_key));
+            let node = match cached {
+                Some(node) => Some(node),
+                None => {
+                    let node = self.backend.get_node(node_key).await?;
+                    if let (Some(cache), Some(node)) = (&self.cache, &node) {
+                        cache.insert(node_key.clone(), node.clone(), node.encode()?.len());
+                    }
+                    node
+                }
+            };
+            histogram!(NODE_READ_DURATION, start.elapsed());
+            Ok(node)
         })
     }
 
     fn get_rightmost_leaf<'future, 'a: 'future>(
         &'a self,
-    ) -> BoxFuture<'future, Result<Option<(NodeKey, jmt::storage::LeafNode)>>> {
-        let span = Span::current();
-        let db = self.0.clone();
-
-        Box::pin(async {
-            tokio::task::Builder::new()
-                .name("Storage::get_rightmost_leaf")
-                .spawn_blocking(move || {
-                    span.in_scope(|| {
-                        let mut iter = db.raw_iterator();
-                        let mut ret = None;
-                        iter.seek_to_last();
-
-                        if iter.valid() {
-                            let node_key = NodeKey::decode(iter.key().unwrap())?;
-                            let node = Node::decode(iter.value().unwrap())?;
-
-                            if let Node::Leaf(leaf_node) = node {
-                                ret = Some((node_key, leaf_node));
-                            }
-                        } else {
-                            // There are no keys in the database
-                        }
-                        Ok(ret)
-                    })
-                })
-                .await
-                .unwrap()
-        })
+    ) -> BoxFuture<'future, Result<Option<(NodeKey, LeafNode)>>> {
+        self.backend.get_rightmost_leaf()
     }
 }
@@ -0,0 +1,76 @@
+use jmt::Version;
+use tokio::sync::mpsc;
+
+use crate::Storage;
+
+/// A change to the value of a key, made by a commit to the [`Storage`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateChange {
+    pub key: String,
+    /// The value before the commit, or `None` if the key had no value.
+    pub old: Option<Vec<u8>>,
+    /// The value after the commit, or `None` if the key has no value.
+    pub new: Option<Vec<u8>>,
+    /// The version created by the commit.
+    pub version: Version,
+}
+
+/// The subscribers to changes of the state, and the key prefixes they are interested in.
+#[derive(Debug, Default)]
+pub(crate) struct Subscriptions {
+    subscribers: Vec<(String, mpsc::UnboundedSender<StateChange>)>,
+}
+
+impl Storage {
+    /// Subscribes to changes to the keys beginning with `prefix`.
+    ///
+    /// After each commit, the returned channel receives a [`StateChange`] for every key with the
+    /// prefix whose value was changed by the commit, in key order. Writes which leave a value
+    /// unchanged are not reported. The subscription ends when the receiver is dropped.
+    ///
+    /// The channel is unbounded, so that a slow subscriber can't hold up block execution;
+    /// subscribers should keep up with the chain to avoid buffering changes indefinitely.
+    pub fn subscribe(&self, prefix: &str) -> mpsc::UnboundedReceiver<StateChange> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .subscribers
+            .push((prefix.to_string(), tx));
+        tracing::debug!(prefix, "added state subscription");
+        rx
+    }
+
+    /// Returns whether any subscriber is interested in changes to `key`.
+    pub(crate) fn is_watched(&self, key: &str) -> bool {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .subscribers
+            .iter()
+            .any(|(prefix, _)| key.starts_with(prefix.as_str()))
+    }
+
+    /// Sends each change to the subscribers interested in it, dropping any subscribers whose
+    /// receivers have been dropped.
+    pub(crate) fn publish(&self, changes: &[StateChange]) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        subscriptions.subscribers.retain(|(prefix, tx)| {
+            for change in changes
+                .iter()
+                .filter(|c| c.key.starts_with(prefix.as_str()))
+            {
+                // Sending only fails if the receiver was dropped, which is checked below.
+                let _ = tx.send(change.clone());
+            }
+            if tx.is_closed() {
+                tracing::debug!(
+                    prefix = prefix.as_str(),
+                    "removed closed state subscription"
+                );
+                return false;
+            }
+            true
+        });
+    }
+}
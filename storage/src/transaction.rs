@@ -0,0 +1,113 @@
+use anyhow::Result;
+
+use crate::{overlay::AbandonedLayers, State};
+
+/// A group of writes to a [`State`] which are applied or discarded as a unit.
+///
+/// While the transaction is open, every write to the state (through any clone of it) is held in
+/// a new layer on top of the existing pending writes, and reads see those writes. Calling
+/// [`commit`](Self::commit) merges the layer into the one below, while [`abort`](Self::abort)
+/// discards it, rolling the state back to where it was when the transaction began.
+///
+/// Transactions can be nested, but must be finished in the reverse of the order they began.
+/// Dropping an unfinished transaction aborts it: reads stop seeing its writes at once, and the
+/// layer holding them is discarded on the next write to the state.
+pub struct StateTransaction {
+    state: State,
+    /// The number of open layers, including this transaction's.
+    depth: usize,
+    /// Where the transaction's layer is recorded if it's dropped unfinished.
+    abandoned: AbandonedLayers,
+    finished: bool,
+}
+
+impl StateTransaction {
+    /// Begins a new transaction on `state`.
+    pub async fn begin(state: &State) -> Self {
+        let (depth, abandoned) = {
+            let mut overlay = state.write().await;
+            (overlay.push_layer(), overlay.abandoned_layers())
+        };
+        tracing::trace!(depth, "began state transaction");
+        Self {
+            state: state.clone(),
+            depth,
+            abandoned,
+            finished: false,
+        }
+    }
+
+    /// Applies the transaction's writes to the enclosing transaction, or to the state's pending
+    /// writes if there is none.
+    pub async fn commit(mut self) -> Result<()> {
+        self.finished = true;
+        self.state.write().await.merge_layer(self.depth)?;
+        tracing::trace!(depth = self.depth, "committed state transaction");
+        Ok(())
+    }
+
+    /// Discards the transaction's writes.
+    pub async fn abort(mut self) -> Result<()> {
+        self.finished = true;
+        self.state.write().await.discard_layer(self.depth)?;
+        tracing::trace!(depth = self.depth, "aborted state transaction");
+        Ok(())
+    }
+}
+
+impl Drop for StateTransaction {
+    fn drop(&mut self) {
+        // The state may be locked while the transaction is dropped, so the layer is only marked
+        // as abandoned, which can't fail, and the overlay discards it on its next write.
+        if !self.finished {
+            self.abandoned.abandon(self.depth);
+            tracing::debug!(depth = self.depth, "dropped unfinished state transaction");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{StateTransaction, Storage};
+
+    #[tokio::test]
+    async fn dropping_while_locked_aborts() {
+        let storage = Storage::in_memory();
+        let state = storage.state().await.unwrap();
+        let state_tx = StateTransaction::begin(&state).await;
+        state.write().await.put("test/a".to_string(), b"a".to_vec());
+        {
+            // The transaction is dropped while the state is locked.
+            let overlay = state.read().await;
+            drop(state_tx);
+            assert_eq!(overlay.get("test/a").await.unwrap(), None);
+        }
+
+        // The next write discards the abandoned layer, so it goes to the pending writes, and
+        // there are no open layers to prevent a commit.
+        let mut overlay = state.write().await;
+        overlay.put("test/b".to_string(), b"b".to_vec());
+        overlay.commit(storage.clone()).await.unwrap();
+        assert_eq!(storage.get_at_version("test/a", 0).await.unwrap(), None);
+        assert_eq!(
+            storage.get_at_version("test/b", 0).await.unwrap(),
+            Some(b"b".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn dropping_a_nested_transaction_keeps_the_outer_one() {
+        let storage = Storage::in_memory();
+        let state = storage.state().await.unwrap();
+        let outer = StateTransaction::begin(&state).await;
+        state.write().await.put("test/outer".to_string(), vec![1]);
+        let inner = StateTransaction::begin(&state).await;
+        state.write().await.put("test/inner".to_string(), vec![2]);
+        drop(inner);
+        outer.commit().await.unwrap();
+
+        let overlay = state.read().await;
+        assert_eq!(overlay.get("test/outer").await.unwrap(), Some(vec![1]));
+        assert_eq!(overlay.get("test/inner").await.unwrap(), None);
+    }
+}
@@ -0,0 +1,145 @@
+use std::{convert::TryInto, time::Instant};
+
+use anyhow::{anyhow, Result};
+use jmt::storage::{Node, NodeBatch, NodeKey};
+use metrics::{counter, histogram};
+
+use crate::{
+    metrics::{COMMIT_BYTES, NODES_WRITTEN, NODE_BATCH_WRITE_DURATION},
+    Storage,
+};
+
+/// A write-ahead log for node batches, so that a crash partway through writing a batch can't
+/// leave the tree in an inconsistent state.
+///
+/// Before any node of a batch is written, the whole batch is written to the log as a single
+/// entry, which is cleared once the batch has been written. If the log holds an entry on startup,
+/// the write was interrupted, and is replayed: writing a node is idempotent, so it doesn't matter
+/// how much of the batch was written before the crash. If the crash happened while writing the
+/// log entry itself, the backend never records it, and none of the batch was written.
+///
+/// The entry also holds the keys of the nodes which the batch replaces, so that they are added to
+/// the stale node index along with the batch.
+impl Storage {
+    /// Writes a batch of nodes to the backend through the write-ahead log, recording the nodes in
+    /// `stale` as replaced at the batch's version.
+    pub(crate) async fn write_nodes_logged(
+        &self,
+        node_batch: NodeBatch,
+        stale: Vec<NodeKey>,
+    ) -> Result<()> {
+        let start = Instant::now();
+        let nodes = node_batch.len();
+        let entry = encode_batch(&node_batch, &stale)?;
+        histogram!(COMMIT_BYTES, entry.len() as f64);
+
+        self.backend.write_wal(entry).await?;
+        self.put_batch(node_batch, stale).await?;
+        self.backend.clear_wal().await?;
+
+        counter!(NODES_WRITTEN, nodes as u64);
+        histogram!(NODE_BATCH_WRITE_DURATION, start.elapsed());
+        Ok(())
+    }
+
+    /// Replays the node batch in the write-ahead log, if a previous write was interrupted.
+    pub(crate) async fn recover(&self) -> Result<()> {
+        if let Some(entry) = self.backend.read_wal().await? {
+            let (node_batch, stale) = decode_batch(&entry)?;
+            tracing::warn!(
+                nodes = node_batch.len(),
+                stale = stale.len(),
+                "replaying interrupted commit from write-ahead log"
+            );
+            self.put_batch(node_batch, stale).await?;
+            self.backend.clear_wal().await?;
+            tracing::info!(
+                latest_version = ?self.latest_version().await?,
+                "recovered interrupted commit"
+            );
+        }
+        Ok(())
+    }
+
+    /// Writes a node batch to the backend, and records the nodes it replaces in the stale node
+    /// index.
+    async fn put_batch(&self, node_batch: NodeBatch, stale: Vec<NodeKey>) -> Result<()> {
+        let version = node_batch.keys().next().map(NodeKey::version);
+        self.backend.put_nodes(node_batch).await?;
+        if !stale.is_empty() {
+            let version =
+                version.ok_or_else(|| anyhow!("stale nodes recorded for an empty batch"))?;
+            self.backend.put_stale_nodes(version, stale).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Encodes a node batch as a count of nodes, followed by each encoded node key and node, then a
+/// count of stale nodes, followed by each encoded stale node key. Every field is prefixed by its
+/// length, and all integers are big-endian `u32`s.
+///
+/// Entries written before stale nodes were recorded end after the nodes, and are read as having no
+/// stale nodes.
+fn encode_batch(node_batch: &NodeBatch, stale: &[NodeKey]) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&u32::try_from(node_batch.len())?.to_be_bytes());
+    for (node_key, node) in node_batch.iter() {
+        for field in [node_key.encode()?, node.encode()?] {
+            write_field(&mut bytes, &field)?;
+        }
+    }
+    bytes.extend_from_slice(&u32::try_from(stale.len())?.to_be_bytes());
+    for node_key in stale {
+        write_field(&mut bytes, &node_key.encode()?)?;
+    }
+    Ok(bytes)
+}
+
+fn decode_batch(mut bytes: &[u8]) -> Result<(NodeBatch, Vec<NodeKey>)> {
+    let mut node_batch = NodeBatch::default();
+    let count = read_u32(&mut bytes)?;
+    for _ in 0..count {
+        let node_key = NodeKey::decode(read_field(&mut bytes)?)?;
+        let node = Node::decode(read_field(&mut bytes)?)?;
+        node_batch.insert(node_key, node);
+    }
+    let mut stale = Vec::new();
+    if !bytes.is_empty() {
+        let count = read_u32(&mut bytes)?;
+        for _ in 0..count {
+            stale.push(NodeKey::decode(read_field(&mut bytes)?)?);
+        }
+    }
+    if !bytes.is_empty() {
+        return Err(anyhow!("trailing bytes in write-ahead log entry"));
+    }
+    Ok((node_batch, stale))
+}
+
+fn write_field(bytes: &mut Vec<u8>, field: &[u8]) -> Result<()> {
+    bytes.extend_from_slice(&u32::try_from(field.len())?.to_be_bytes());
+    bytes.extend_from_slice(field);
+    Ok(())
+}
+
+fn read_u32(bytes: &mut &[u8]) -> Result<u32> {
+    if bytes.len() < 4 {
+        return Err(anyhow!("truncated write-ahead log entry"));
+    }
+    let (int, rest) = bytes.split_at(4);
+    *bytes = rest;
+    Ok(u32::from_be_bytes(
+        int.try_into().expect("length was checked"),
+    ))
+}
+
+fn read_field<'a>(bytes: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let len = read_u32(bytes)? as usize;
+    if bytes.len() < len {
+        return Err(anyhow!("truncated write-ahead log entry"));
+    }
+    let (field, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(field)
+}
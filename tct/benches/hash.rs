@@ -0,0 +1,47 @@
+//! Benchmarks for computing the root of a large tree whose hashes are not yet cached.
+//!
+//! Compare the results with and without the `parallel` feature:
+//!
+//! ```sh
+//! cargo bench -p penumbra-tct --bench hash
+//! cargo bench -p penumbra-tct --bench hash --features parallel
+//! ```
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+use penumbra_tct::{Commitment, Tree, Witness};
+
+/// Build a tree of witnessed commitments, spread over blocks of the given size.
+///
+/// Witnessed commitments are not hashed on insertion, so none of the tree's hashes are cached.
+fn tree(commitments: u64, block_size: u64) -> Tree {
+    let mut tree = Tree::new();
+    for i in 0..commitments {
+        tree.insert(Witness::Keep, Commitment(i.into())).unwrap();
+        if i % block_size == block_size - 1 {
+            tree.end_block().unwrap();
+        }
+    }
+    tree
+}
+
+fn bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tct-root");
+    // Every iteration hashes the whole tree, so we don't need as many runs
+    group.sample_size(10);
+    for commitments in [1_000, 10_000, 100_000] {
+        group.throughput(Throughput::Elements(commitments));
+
+        group.bench_function(format!("root_{}_commitments", commitments).as_str(), |b| {
+            b.iter_batched(
+                || tree(commitments, 1_000),
+                |tree| tree.root(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -0,0 +1,76 @@
+//! A bounded history of the past roots of a [`Tree`], for checking the anchors of transactions.
+
+use std::collections::VecDeque;
+
+use crate::prelude::*;
+
+/// The roots of a [`Tree`] at the end of each of its most recent blocks, up to some capacity.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Anchors {
+    /// The maximum number of roots to remember, or zero if the history is disabled.
+    capacity: usize,
+    /// The position of the tree at the end of each block, and its root at that position, oldest
+    /// first.
+    roots: VecDeque<(Position, Root)>,
+}
+
+impl Anchors {
+    /// Set the maximum number of roots to remember, forgetting the oldest ones if there are more.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.roots.len() > self.capacity {
+            self.roots.pop_front();
+        }
+    }
+
+    /// The maximum number of roots to remember.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The remembered positions and roots, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = (Position, Root)> + '_ {
+        self.roots.iter().copied()
+    }
+
+    /// Whether any roots are remembered.
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Remember the root of the tree at the given position, forgetting the oldest root if the
+    /// history is full.
+    pub fn record(&mut self, position: Position, root: Root) {
+        if !self.is_enabled() {
+            return;
+        }
+        // Ending a block can record the same position more than once
+        if matches!(self.roots.back(), Some((last, _)) if *last == position) {
+            return;
+        }
+        if self.roots.len() == self.capacity {
+            self.roots.pop_front();
+        }
+        self.roots.push_back((position, root));
+    }
+
+    /// Whether the given root is remembered.
+    pub fn contains(&self, root: Root) -> bool {
+        self.roots.iter().any(|&(_, remembered)| remembered == root)
+    }
+
+    /// The remembered root at the given position, if any.
+    pub fn root_at(&self, position: Position) -> Option<Root> {
+        self.roots
+            .iter()
+            .find(|&&(remembered, _)| remembered == position)
+            .map(|&(_, root)| root)
+    }
+
+    /// Forget every root after the given position.
+    pub fn truncate(&mut self, position: Position) {
+        let position = u64::from(position);
+        self.roots
+            .retain(|&(remembered, _)| u64::from(remembered) <= position);
+    }
+}
@@ -1,6 +1,14 @@
-//! Arbitrary implementation for [`Commitment`]s.
+//! Arbitrary implementations and [`proptest`] strategies for [`Commitment`]s and [`Tree`]s.
+//!
+//! The strategies for trees generate structurally valid trees by applying a random sequence of
+//! [`Action`]s to an empty tree, mixing witnessed and forgotten commitments across block and epoch
+//! boundaries, so that downstream crates can property-test their integration against realistic
+//! trees. Because the trees are generated from sequences of actions, failing cases shrink to
+//! short sequences.
 
-use super::Commitment;
+use proptest::{prelude::*, sample::Index};
+
+use super::{Commitment, Tree, Witness};
 
 impl proptest::arbitrary::Arbitrary for Commitment {
     type Parameters = Vec<Commitment>;
@@ -58,3 +66,102 @@ impl proptest::strategy::Strategy for CommitmentStrategy {
         }
     }
 }
+
+/// A single operation on a [`Tree`], as generated by [`actions`].
+#[derive(Clone, Debug)]
+pub enum Action {
+    /// Insert a commitment, either witnessing or forgetting it.
+    Insert(Witness, Commitment),
+    /// Forget one of the currently witnessed commitments, chosen by its index among them in order
+    /// of position, if any are witnessed.
+    Forget(Index),
+    /// End the current block.
+    EndBlock,
+    /// End the current epoch.
+    EndEpoch,
+}
+
+impl Action {
+    /// Apply this action to the tree.
+    ///
+    /// # Panics
+    ///
+    /// If the tree is full, which can't happen for trees generated by [`tree`].
+    pub fn apply(&self, tree: &mut Tree) {
+        match self {
+            Action::Insert(witness, commitment) => {
+                tree.insert(*witness, *commitment)
+                    .expect("generated tree is not full");
+            }
+            Action::Forget(index) => {
+                let mut witnessed = tree.commitments().collect::<Vec<_>>();
+                if !witnessed.is_empty() {
+                    witnessed.sort_by_key(|&(position, _)| u64::from(position));
+                    let (_, commitment) = witnessed[index.index(witnessed.len())];
+                    tree.forget(commitment);
+                }
+            }
+            Action::EndBlock => {
+                tree.end_block().expect("generated tree is not full");
+            }
+            Action::EndEpoch => {
+                tree.end_epoch().expect("generated tree is not full");
+            }
+        }
+    }
+}
+
+impl Arbitrary for Action {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Action>;
+
+    fn arbitrary_with((): Self::Parameters) -> Self::Strategy {
+        // Most actions are insertions, so that blocks and epochs usually contain something
+        prop_oneof![
+            12 => (any::<Witness>(), any::<Commitment>())
+                .prop_map(|(witness, commitment)| Action::Insert(witness, commitment)),
+            2 => any::<Index>().prop_map(Action::Forget),
+            2 => Just(Action::EndBlock),
+            1 => Just(Action::EndEpoch),
+        ]
+        .boxed()
+    }
+}
+
+/// A strategy generating sequences of up to `max` [`Action`]s.
+pub fn actions(max: usize) -> impl Strategy<Value = Vec<Action>> {
+    proptest::collection::vec(any::<Action>(), 0..=max)
+}
+
+/// A strategy generating [`Tree`]s by applying sequences of up to `max_actions` [`Action`]s to an
+/// empty tree.
+pub fn tree(max_actions: usize) -> impl Strategy<Value = Tree> {
+    actions(max_actions).prop_map(|actions| {
+        let mut tree = Tree::new();
+        for action in &actions {
+            action.apply(&mut tree);
+        }
+        tree
+    })
+}
+
+impl Arbitrary for Tree {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Tree>;
+
+    fn arbitrary_with((): Self::Parameters) -> Self::Strategy {
+        tree(100).boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn generated_trees_are_valid(tree in any::<Tree>()) {
+            crate::validate::check(&tree).unwrap();
+        }
+    }
+}
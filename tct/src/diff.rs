@@ -0,0 +1,222 @@
+//! Comparison of two [`Tree`]s, for debugging mismatched roots.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::prelude::*;
+use crate::storage::width;
+
+/// The differences between two [`Tree`]s, as returned by [`Tree::diff`](crate::Tree::diff).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeDiff {
+    /// The commitments witnessed by the first tree but not the second (or witnessed by the second
+    /// at a different position), in order of position.
+    pub only_in_self: Vec<(Position, Commitment)>,
+    /// The commitments witnessed by the second tree but not the first (or witnessed by the first
+    /// at a different position), in order of position.
+    pub only_in_other: Vec<(Position, Commitment)>,
+    /// The smallest subtrees known to have different hashes in the two trees, in order of height
+    /// and then position.
+    ///
+    /// A subtree is only listed if its hash is known in both trees and differs, and none of the
+    /// subtrees within it is also listed. If the trees have different roots, at least the whole
+    /// tree is listed.
+    pub diverging: Vec<Divergence>,
+}
+
+impl TreeDiff {
+    /// Whether the two trees witness the same commitments and have the same root.
+    pub fn is_empty(&self) -> bool {
+        self.only_in_self.is_empty() && self.only_in_other.is_empty() && self.diverging.is_empty()
+    }
+}
+
+/// A subtree whose hash differs between two [`Tree`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    /// The position of the first leaf of the subtree.
+    pub position: Position,
+    /// The height of the subtree: 0 for a single commitment, 8 for a whole block, 16 for a whole
+    /// epoch, and 24 for the whole tree.
+    pub height: u8,
+    /// The hash of the subtree in the first tree.
+    pub in_self: Hash,
+    /// The hash of the subtree in the second tree.
+    pub in_other: Hash,
+}
+
+/// Compare two trees.
+pub(crate) fn diff(tree: &Tree, other: &Tree) -> TreeDiff {
+    let witnessed = |tree: &Tree| tree.commitments().collect::<HashSet<_>>();
+    let (in_self, in_other) = (witnessed(tree), witnessed(other));
+    let difference = |a: &HashSet<(Position, Commitment)>, b| {
+        let mut difference = a.difference(b).copied().collect::<Vec<_>>();
+        difference.sort_by_key(|&(position, _)| position);
+        difference
+    };
+
+    let (hashes, other_hashes) = (Hashes::of(tree), Hashes::of(other));
+    let diverging: BTreeMap<(u8, u64), (Hash, Hash)> = hashes
+        .0
+        .iter()
+        .filter_map(|(&key, &hash)| match other_hashes.0.get(&key) {
+            Some(&other_hash) if other_hash != hash => Some((key, (hash, other_hash))),
+            _ => None,
+        })
+        .collect();
+
+    TreeDiff {
+        only_in_self: difference(&in_self, &in_other),
+        only_in_other: difference(&in_other, &in_self),
+        diverging: diverging
+            .iter()
+            .filter(|(&(height, position), _)| {
+                // Skip subtrees containing a smaller diverging subtree
+                !diverging
+                    .range(..(height, 0))
+                    .any(|(&(_, inner), _)| position <= inner && inner < position + width(height))
+            })
+            .map(|(&(height, position), &(in_self, in_other))| Divergence {
+                position: position.into(),
+                height,
+                in_self,
+                in_other,
+            })
+            .collect(),
+    }
+}
+
+/// The hashes of the subtrees of a tree which can be found without recomputing any, keyed by their
+/// height and then their position.
+struct Hashes(HashMap<(u8, u64), Hash>);
+
+impl Hashes {
+    fn of(tree: &Tree) -> Self {
+        let mut collector = Collector::default();
+        tree.encode(0, &mut collector);
+        let mut hashes = collector.hashes;
+
+        let top = <Tree as Height>::Height::HEIGHT;
+        hashes.insert((top, 0), tree.root().0);
+
+        // The authentication path of each witnessed commitment gives the hashes of the siblings
+        // of every node along the path to it
+        for (position, commitment) in tree.commitments() {
+            let proof = tree
+                .witness(commitment)
+                .expect("indexed commitment must be witnessed");
+            let position = u64::from(position);
+
+            for (siblings, height) in proof.auth_path().into_iter().zip((1..=top).rev()) {
+                let child_height = height - 1;
+                let parent = position & !(width(height) - 1);
+                let which = (position >> (2 * child_height)) & 0b11;
+                let others = (0..4).filter(|&child| child != which);
+                for (child, &hash) in others.zip(siblings.iter()) {
+                    hashes.insert((child_height, parent + child * width(child_height)), hash);
+                }
+            }
+        }
+
+        Hashes(hashes)
+    }
+}
+
+/// An [`Encoder`] which collects the hashes of the pruned subtrees and witnessed leaves of a tree.
+#[derive(Default)]
+struct Collector {
+    commitments: HashMap<u64, Commitment>,
+    hashes: HashMap<(u8, u64), Hash>,
+}
+
+impl Encoder for Collector {
+    fn index(&mut self, commitments: impl IntoIterator<Item = (Commitment, u64)>) {
+        self.commitments.extend(
+            commitments
+                .into_iter()
+                .map(|(commitment, position)| (position, commitment)),
+        );
+    }
+
+    fn hash(&mut self, position: u64, height: u8, hash: Hash) {
+        self.hashes.insert((height, position), hash);
+    }
+
+    fn subtree(&mut self, _position: u64, _height: u8) -> bool {
+        true
+    }
+
+    fn commitment(&mut self, position: u64) {
+        if let Some(&commitment) = self.commitments.get(&position) {
+            self.hashes.insert((0, position), Hash::of(commitment));
+        }
+    }
+
+    fn siblings(&mut self, _position: u64, _height: u8, _siblings: u8) {}
+
+    fn finalized(&mut self, _position: u64, _height: u8, _finalized: bool) {}
+
+    fn empty(&mut self, _empty: bool) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Witness;
+
+    fn commitment(i: u64) -> Commitment {
+        Commitment(poseidon377::Fq::from(i))
+    }
+
+    fn example(last: u64) -> Tree {
+        let mut tree = Tree::new();
+        for i in 0..10 {
+            tree.insert(Witness::Keep, commitment(i)).unwrap();
+        }
+        tree.end_block().unwrap();
+        tree.insert(Witness::Keep, commitment(last)).unwrap();
+        tree
+    }
+
+    #[test]
+    fn same_tree() {
+        let tree = example(10);
+        assert!(diff(&tree, &tree.clone()).is_empty());
+    }
+
+    #[test]
+    fn different_commitment() {
+        let (tree, other) = (example(10), example(11));
+        let diff = diff(&tree, &other);
+
+        let position = Position::new(0, 1, 0);
+        assert_eq!(diff.only_in_self, vec![(position, commitment(10))]);
+        assert_eq!(diff.only_in_other, vec![(position, commitment(11))]);
+
+        // The divergence is narrowed down to the differing leaf
+        assert_eq!(
+            diff.diverging,
+            vec![Divergence {
+                position,
+                height: 0,
+                in_self: Hash::of(commitment(10)),
+                in_other: Hash::of(commitment(11)),
+            }]
+        );
+    }
+
+    #[test]
+    fn forgotten_commitment() {
+        let tree = example(10);
+        let mut other = tree.clone();
+        other.forget(commitment(3));
+
+        // Forgetting a commitment doesn't change any hashes
+        let diff = diff(&tree, &other);
+        assert_eq!(
+            diff.only_in_self,
+            vec![(Position::new(0, 0, 3), commitment(3))]
+        );
+        assert!(diff.only_in_other.is_empty());
+        assert!(diff.diverging.is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+//! Rendering of a [`Tree`] as a [Graphviz](https://graphviz.org/) graph in the DOT language, for
+//! debugging and documentation.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+};
+
+use crate::prelude::*;
+use crate::storage::width;
+
+/// Write the DOT graph of the given tree to the writer.
+pub(crate) fn render(tree: &Tree, mut writer: impl io::Write) -> io::Result<()> {
+    let mut graph = Graph::default();
+    tree.encode(0, &mut graph);
+    graph.write(&mut writer)
+}
+
+/// The nodes of a tree, collected by visiting it as an [`Encoder`].
+#[derive(Default)]
+struct Graph {
+    commitments: HashMap<u64, Commitment>,
+    /// Every node, keyed by its height and then its position, so that they are written in order.
+    nodes: BTreeMap<(u8, u64), Node>,
+}
+
+struct Node {
+    kind: Kind,
+    /// Whether the node is part of the frontier.
+    frontier: bool,
+    /// Whether the node is the root of a finalized tier, if it is the root of a tier.
+    finalized: Option<bool>,
+}
+
+enum Kind {
+    /// An internal node with witnessed children.
+    Internal,
+    /// A witnessed leaf.
+    Leaf(Commitment),
+    /// A subtree pruned to its hash.
+    Pruned(Hash),
+}
+
+impl Graph {
+    fn node(&mut self, position: u64, height: u8) -> &mut Node {
+        self.nodes.entry((height, position)).or_insert(Node {
+            kind: Kind::Internal,
+            frontier: false,
+            finalized: None,
+        })
+    }
+
+    fn write(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        let top = <Tree as Height>::Height::HEIGHT;
+
+        writeln!(writer, "digraph tree {{")?;
+        writeln!(writer, "    node [fontname=\"monospace\"];")?;
+
+        for (&(height, position), node) in &self.nodes {
+            let name = id(height, position);
+
+            let mut label = match height {
+                8 => format!("block {}", label(position)),
+                16 => format!("epoch {}", label(position)),
+                _ if height == top => "root".to_string(),
+                _ => format!("{} @ height {}", label(position), height),
+            };
+            if node.finalized == Some(true) {
+                label.push_str(" (finalized)");
+            }
+
+            let (shape, label) = match node.kind {
+                Kind::Internal => ("ellipse", label),
+                Kind::Leaf(commitment) => ("box", format!("{}\\n{:?}", label, commitment)),
+                Kind::Pruned(hash) if hash == Hash::one() => ("box", "padding".to_string()),
+                Kind::Pruned(hash) => (
+                    "box",
+                    format!("{}\\n{}", label, &format!("{:?}", hash)[..8]),
+                ),
+            };
+            let style = match (&node.kind, node.frontier) {
+                (Kind::Pruned(_), _) => "dotted",
+                (_, true) => "bold",
+                (_, false) => "solid",
+            };
+
+            writeln!(
+                writer,
+                "    {} [label=\"{}\", shape={}, style={}];",
+                name, label, shape, style
+            )?;
+
+            if height < top {
+                let parent = position & !(width(height + 1) - 1);
+                writeln!(writer, "    {} -> {};", id(height + 1, parent), name)?;
+            }
+        }
+
+        writeln!(writer, "}}")
+    }
+}
+
+/// The DOT identifier of the node of the given height at the given position.
+fn id(height: u8, position: u64) -> String {
+    format!("n{}_{}", height, position)
+}
+
+/// A human-readable label for a position.
+fn label(position: u64) -> String {
+    let position = Position::from(position);
+    format!(
+        "{}.{}.{}",
+        position.epoch(),
+        position.block(),
+        position.commitment()
+    )
+}
+
+impl Encoder for Graph {
+    fn index(&mut self, commitments: impl IntoIterator<Item = (Commitment, u64)>) {
+        self.commitments.extend(
+            commitments
+                .into_iter()
+                .map(|(commitment, position)| (position, commitment)),
+        );
+    }
+
+    fn hash(&mut self, position: u64, height: u8, hash: Hash) {
+        self.node(position, height).kind = Kind::Pruned(hash);
+    }
+
+    fn subtree(&mut self, position: u64, height: u8) -> bool {
+        self.node(position, height);
+        true
+    }
+
+    fn commitment(&mut self, position: u64) {
+        let commitment = self.commitments[&position];
+        self.node(position, 0).kind = Kind::Leaf(commitment);
+    }
+
+    fn siblings(&mut self, position: u64, height: u8, _siblings: u8) {
+        self.node(position, height).frontier = true;
+    }
+
+    fn finalized(&mut self, position: u64, height: u8, finalized: bool) {
+        self.node(position, height).finalized = Some(finalized);
+    }
+
+    fn empty(&mut self, _empty: bool) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Witness;
+
+    #[test]
+    fn render_small_tree() {
+        let mut tree = Tree::new();
+        tree.insert(Witness::Keep, Commitment(0u64.into())).unwrap();
+        tree.insert(Witness::Forget, Commitment(1u64.into()))
+            .unwrap();
+        tree.end_block().unwrap();
+        tree.insert(Witness::Keep, Commitment(2u64.into())).unwrap();
+
+        let mut dot = Vec::new();
+        render(&tree, &mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+
+        assert!(dot.starts_with("digraph tree {"));
+        assert!(dot.trim_end().ends_with('}'));
+        assert!(dot.contains("label=\"root\""));
+        assert!(dot.contains("label=\"block 0.0.0\""));
+        assert!(dot.contains("label=\"block 0.1.0\""));
+        // Two witnessed leaves, and one forgotten leaf
+        assert_eq!(dot.matches("Commitment(").count(), 2);
+        assert!(dot.contains("n0_1 [label=\"0.0.1 @ height 0"));
+    }
+}
@@ -1,10 +1,11 @@
-//! Errors that can occur when inserting into a [`Tree`].
+//! Errors that can occur when inserting into or truncating a [`Tree`].
 
 use thiserror::Error;
 
 #[cfg(doc)]
 use super::Tree;
 use crate::builder::{block, epoch};
+use crate::Position;
 
 /// An error occurred when trying to insert an commitment into a [`Tree`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
@@ -60,6 +61,26 @@ impl From<InsertEpochError> for epoch::Finalized {
 #[non_exhaustive]
 pub struct InsertEpochRootError;
 
+/// An error occurred when trying to [`truncate`](Tree::truncate_to) a [`Tree`] to a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum TruncateError {
+    /// The position was after the current position of the [`Tree`].
+    #[error("cannot truncate tree to position {position:?}, which is after its current position")]
+    Ahead {
+        /// The position to which truncation was attempted.
+        position: Position,
+    },
+    /// A subtree straddling the position had been pruned to its hash, so the frontier of the
+    /// [`Tree`] at that position can't be rebuilt.
+    #[error("cannot truncate tree to position {position:?}, because the subtree containing position {pruned:?} was pruned")]
+    Pruned {
+        /// The position to which truncation was attempted.
+        position: Position,
+        /// A position before the truncation position whose commitment or hash was pruned.
+        pruned: Position,
+    },
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -69,5 +90,6 @@ mod test {
         static_assertions::assert_impl_all!(InsertError: Sync, Send);
         static_assertions::assert_impl_all!(InsertBlockError: Sync, Send);
         static_assertions::assert_impl_all!(InsertEpochError: Sync, Send);
+        static_assertions::assert_impl_all!(TruncateError: Sync, Send);
     }
 }
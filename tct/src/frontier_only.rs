@@ -0,0 +1,124 @@
+//! A minimal tree which only computes the evolving root, for consumers which never witness
+//! anything.
+
+use crate::error::*;
+use crate::prelude::*;
+
+/// A tree which tracks only its frontier and its root, for services (such as a full node) which
+/// need the evolving root of the tree, but never need to [`witness`](Tree::witness) a commitment.
+///
+/// Every commitment is inserted as if with [`Witness::Forget`](crate::Witness::Forget), so every
+/// complete subtree is pruned to its hash as soon as it is complete, and no index of commitments
+/// is kept. This is a [`Tree`] which is always [`compact`](Tree::set_compact), with only the
+/// methods which make sense for such a tree; its root is the same as that of a [`Tree`] into which
+/// the same commitments were inserted, and blocks and epochs ended.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "Tree", into = "Tree")]
+pub struct FrontierOnly(Tree);
+
+impl Default for FrontierOnly {
+    fn default() -> Self {
+        Self::from(Tree::new())
+    }
+}
+
+impl From<Tree> for FrontierOnly {
+    /// Convert a [`Tree`] into a [`FrontierOnly`] tree, forgetting every commitment it witnesses.
+    fn from(mut tree: Tree) -> Self {
+        tree.set_compact(true);
+        Self(tree)
+    }
+}
+
+impl From<FrontierOnly> for Tree {
+    fn from(frontier: FrontierOnly) -> Self {
+        frontier.0
+    }
+}
+
+impl FrontierOnly {
+    /// Create a new empty [`FrontierOnly`] tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the root hash of this tree.
+    ///
+    /// As for [`Tree::root`], hashes are computed lazily and cached.
+    pub fn root(&self) -> Root {
+        self.0.root()
+    }
+
+    /// The position in this tree at which the next [`Commitment`] would be inserted, or `None` if
+    /// the tree is full.
+    pub fn position(&self) -> Option<Position> {
+        self.0.position()
+    }
+
+    /// Add a new [`Commitment`] to the most recent block of the most recent epoch of this tree.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InsertError`] if the tree, the most recent epoch, or the most recent block is
+    /// full.
+    pub fn insert(&mut self, commitment: impl Into<Commitment>) -> Result<&mut Self, InsertError> {
+        self.0.insert(crate::Witness::Forget, commitment)?;
+        Ok(self)
+    }
+
+    /// Explicitly mark the end of the current block in this tree, advancing the position to the
+    /// next block.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InsertBlockError`] if the tree or the most recent epoch is full.
+    pub fn end_block(&mut self) -> Result<&mut Self, InsertBlockError> {
+        self.0.end_block()?;
+        Ok(self)
+    }
+
+    /// Explicitly mark the end of the current epoch in this tree, advancing the position to the
+    /// next epoch.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InsertEpochError`] if the tree is full.
+    pub fn end_epoch(&mut self) -> Result<&mut Self, InsertEpochError> {
+        self.0.end_epoch()?;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Witness;
+
+    #[test]
+    fn same_root_as_tree() {
+        let mut tree = Tree::new();
+        let mut frontier = FrontierOnly::new();
+        assert_eq!(tree.root(), frontier.root());
+
+        for i in 0u64..100 {
+            let commitment = Commitment(poseidon377::Fq::from(i));
+            tree.insert(Witness::Keep, commitment).unwrap();
+            frontier.insert(commitment).unwrap();
+            if i % 10 == 9 {
+                tree.end_block().unwrap();
+                frontier.end_block().unwrap();
+            }
+            if i % 40 == 39 {
+                tree.end_epoch().unwrap();
+                frontier.end_epoch().unwrap();
+            }
+            assert_eq!(tree.root(), frontier.root());
+            assert_eq!(tree.position(), frontier.position());
+        }
+
+        // Only the frontier is kept
+        let (tree, frontier) = (tree.size_stats(), frontier.0.size_stats());
+        assert_eq!(frontier.witnessed, 0);
+        assert!(frontier.nodes < tree.nodes);
+    }
+}
@@ -10,10 +10,15 @@ use serde::{Deserialize, Serialize};
 /// The index of an individual item in a block.
 ///
 /// Create this using `From<u16>`.
-#[derive(Copy, Clone, PartialEq, Eq, Default, Derivative, Serialize, Deserialize)]
-#[derivative(Debug = "transparent")]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
 pub struct Commitment(u16);
 
+impl core::fmt::Debug for Commitment {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 impl Commitment {
     /// Increment the commitment.
     pub fn increment(&mut self) {
@@ -41,10 +46,15 @@ impl From<Commitment> for u16 {
 /// The index of an individual block in an epoch.
 ///
 /// Create this using `From<u16>`.
-#[derive(Copy, Clone, PartialEq, Eq, Default, Derivative, Serialize, Deserialize)]
-#[derivative(Debug = "transparent")]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
 pub struct Block(u16);
 
+impl core::fmt::Debug for Block {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 impl From<u16> for Block {
     fn from(index: u16) -> Self {
         Self(index)
@@ -72,10 +82,15 @@ impl Block {
 /// The index of an individual epoch in a tree.
 ///
 /// Create this using `From<u16>`.
-#[derive(Copy, Clone, PartialEq, Eq, Default, Derivative, Serialize, Deserialize)]
-#[derivative(Debug = "transparent")]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
 pub struct Epoch(u16);
 
+impl core::fmt::Debug for Epoch {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 impl From<u16> for Epoch {
     fn from(index: u16) -> Self {
         Self(index)
@@ -188,7 +203,12 @@ pub mod within {
     }
 
     /// The index of an individual item within a tree.
-    #[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+    ///
+    /// Indices are ordered by their epoch, then their block, then their commitment, which is the
+    /// same as the order of their `u64` representation.
+    #[derive(
+        Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+    )]
     pub struct Tree {
         /// The index of the epoch within its tree.
         pub epoch: super::Epoch,
@@ -59,14 +59,19 @@
 //! [`frontier::Forget`] that is applicable to the by-value usage pattern of complete trees.
 
 pub mod hash;
+#[cfg(feature = "std")]
 pub mod height;
 pub mod path;
 pub mod proof;
+#[cfg(feature = "std")]
 pub mod three;
 
+#[cfg(feature = "std")]
 mod insert;
+#[cfg(feature = "std")]
 pub mod interface;
 
+#[cfg(feature = "std")]
 pub mod frontier {
     //! [`Frontier`] things can be inserted into and updated, always representing the rightmost
     //! (most recently inserted) element of a tree.
@@ -107,6 +112,7 @@ pub mod frontier {
     pub use top::Top;
 }
 
+#[cfg(feature = "std")]
 pub mod complete {
     //! [`Complete`] things are sparse representations of only the data that was inserted using
     //! [`Insert::Keep`](crate::Insert::Keep), with the data that was inserted using
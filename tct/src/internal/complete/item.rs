@@ -47,3 +47,21 @@ impl ForgetOwned for Item {
         (Insert::Hash(self.0), true)
     }
 }
+
+impl Encode for Item {
+    fn encode<E: Encoder>(&self, position: u64, encoder: &mut E) {
+        encoder.commitment(position);
+    }
+}
+
+impl Decode for Item {
+    fn decode<D: Decoder>(position: u64, decoder: &mut D) -> Result<Self, DeserializeError> {
+        Ok(Self(decoder.commitment(position)?))
+    }
+}
+
+impl Measure for Item {
+    fn measure(&self, stats: &mut SizeStats) {
+        stats.nodes += 1;
+    }
+}
@@ -46,3 +46,21 @@ impl<Item: ForgetOwned> ForgetOwned for Leaf<Item> {
         (item.map(Leaf), forgotten)
     }
 }
+
+impl<Item: Encode> Encode for Leaf<Item> {
+    fn encode<E: Encoder>(&self, position: u64, encoder: &mut E) {
+        self.0.encode(position, encoder)
+    }
+}
+
+impl<Item: Decode> Decode for Leaf<Item> {
+    fn decode<D: Decoder>(position: u64, decoder: &mut D) -> Result<Self, DeserializeError> {
+        Ok(Self(Item::decode(position, decoder)?))
+    }
+}
+
+impl<Item: Measure> Measure for Leaf<Item> {
+    fn measure(&self, stats: &mut SizeStats) {
+        self.0.measure(stats)
+    }
+}
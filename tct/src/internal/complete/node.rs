@@ -1,6 +1,7 @@
 use crate::prelude::*;
 
 use super::super::frontier;
+use crate::storage;
 
 pub mod children;
 pub use children::Children;
@@ -78,7 +79,13 @@ impl<Child: Height + GetHash> GetHash for Node<Child> {
     #[inline]
     fn hash(&self) -> Hash {
         self.hash.set_if_empty(|| {
-            let [a, b, c, d] = self.children.children().map(|x| x.hash());
+            let height = Child::Height::HEIGHT;
+            let [a, b, c, d] = self.children.children();
+            let ((a, b), (c, d)) = hash::join(
+                height,
+                || hash::join(height, || a.hash(), || b.hash()),
+                || hash::join(height, || c.hash(), || d.hash()),
+            );
             Hash::node(<Self as Height>::Height::HEIGHT, a, b, c, d)
         })
     }
@@ -171,6 +178,67 @@ impl<Child: GetHash + ForgetOwned> ForgetOwned for Node<Child> {
     }
 }
 
+impl<Child: Height + Encode> Encode for Node<Child> {
+    fn encode<E: Encoder>(&self, position: u64, encoder: &mut E) {
+        for (which, child) in (0..).zip(self.children()) {
+            let position = storage::child_position(position, Child::Height::HEIGHT, which);
+            match child {
+                Insert::Hash(hash) => encoder.hash(position, Child::Height::HEIGHT, hash),
+                Insert::Keep(child) => {
+                    if encoder.subtree(position, Child::Height::HEIGHT) {
+                        child.encode(position, encoder);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<Child: Height + Decode> Decode for Node<Child> {
+    fn decode<D: Decoder>(position: u64, decoder: &mut D) -> Result<Self, DeserializeError> {
+        let child_position =
+            |which| storage::child_position(position, Child::Height::HEIGHT, which);
+        let children = [
+            Insert::decode(child_position(0), decoder)?,
+            Insert::decode(child_position(1), decoder)?,
+            Insert::decode(child_position(2), decoder)?,
+            Insert::decode(child_position(3), decoder)?,
+        ];
+
+        // A node with no witnessed children would have been pruned to a hash
+        let children =
+            Children::try_from(children).map_err(|_| DeserializeError::EmptyNode(position))?;
+
+        Ok(Self {
+            hash: CachedHash::default(),
+            children,
+        })
+    }
+}
+
+impl<Child: Height + Measure> Measure for Node<Child> {
+    fn measure(&self, stats: &mut SizeStats) {
+        stats.nodes += 1;
+        if self.hash.get().is_some() {
+            stats.cached_hashes += 1;
+        }
+
+        // The children are boxed together, each taking up the space of either a hash or a child
+        for child in self.children() {
+            match child {
+                Insert::Hash(_) => {
+                    stats.pruned += 1;
+                    stats.heap_bytes += std::mem::size_of::<Hash>();
+                }
+                Insert::Keep(child) => {
+                    stats.heap_bytes += std::mem::size_of::<Child>();
+                    child.measure(stats);
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
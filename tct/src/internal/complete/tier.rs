@@ -53,3 +53,23 @@ impl<Item: Complete> From<frontier::Tier<Item::Focus>> for Insert<Tier<Item>> {
         frontier.finalize_owned()
     }
 }
+
+impl<Item: Height + Encode> Encode for Tier<Item> {
+    fn encode<E: Encoder>(&self, position: u64, encoder: &mut E) {
+        self.inner.encode(position, encoder)
+    }
+}
+
+impl<Item: Height + Decode> Decode for Tier<Item> {
+    fn decode<D: Decoder>(position: u64, decoder: &mut D) -> Result<Self, DeserializeError> {
+        Ok(Self {
+            inner: Nested::decode(position, decoder)?,
+        })
+    }
+}
+
+impl<Item: Height + Measure> Measure for Tier<Item> {
+    fn measure(&self, stats: &mut SizeStats) {
+        self.inner.measure(stats)
+    }
+}
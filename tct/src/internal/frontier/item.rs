@@ -82,3 +82,35 @@ impl Forget for Item {
         }
     }
 }
+
+impl Encode for Item {
+    fn encode<E: Encoder>(&self, position: u64, encoder: &mut E) {
+        match self.item {
+            Insert::Hash(hash) => encoder.hash(position, 0, hash),
+            Insert::Keep(_) => {
+                if encoder.subtree(position, 0) {
+                    encoder.commitment(position);
+                }
+            }
+        }
+    }
+}
+
+impl Decode for Item {
+    fn decode<D: Decoder>(position: u64, decoder: &mut D) -> Result<Self, DeserializeError> {
+        let item = match decoder.hash(position, 0)? {
+            Some(hash) => Insert::Hash(hash),
+            None => Insert::Keep(decoder.commitment(position)?),
+        };
+        Ok(Self { item })
+    }
+}
+
+impl Measure for Item {
+    fn measure(&self, stats: &mut SizeStats) {
+        match self.item {
+            Insert::Hash(_) => stats.pruned += 1,
+            Insert::Keep(_) => stats.nodes += 1,
+        }
+    }
+}
@@ -91,3 +91,23 @@ impl<Item: GetHash + Forget> Forget for Leaf<Item> {
         self.item.forget(index)
     }
 }
+
+impl<Item: Encode> Encode for Leaf<Item> {
+    fn encode<E: Encoder>(&self, position: u64, encoder: &mut E) {
+        self.item.encode(position, encoder)
+    }
+}
+
+impl<Item: Decode> Decode for Leaf<Item> {
+    fn decode<D: Decoder>(position: u64, decoder: &mut D) -> Result<Self, DeserializeError> {
+        Ok(Self {
+            item: Item::decode(position, decoder)?,
+        })
+    }
+}
+
+impl<Item: Measure> Measure for Leaf<Item> {
+    fn measure(&self, stats: &mut SizeStats) {
+        self.item.measure(stats)
+    }
+}
@@ -3,6 +3,7 @@ use std::fmt::Debug;
 use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
+use crate::storage;
 
 /// A frontier of a node in a tree, into which items can be inserted.
 #[derive(Clone, Derivative, Serialize, Deserialize)]
@@ -36,35 +37,24 @@ impl<Child: Focus> Height for Node<Child> {
 
 impl<Child: Focus> GetHash for Node<Child> {
     fn hash(&self) -> Hash {
-        // Extract the hashes of an array of `Insert<T>`s.
-        fn hashes_of_all<T: GetHash, const N: usize>(full: [&Insert<T>; N]) -> [Hash; N] {
-            full.map(|hash_or_t| match hash_or_t {
-                Insert::Hash(hash) => *hash,
-                Insert::Keep(t) => t.hash(),
-            })
-        }
-
         self.hash.set_if_empty(|| {
             // Get the four hashes of the node's siblings + focus, *in that order*, adding
             // zero-padding when there are less than four elements
-            let zero = Hash::zero();
-            let focus = self.focus.hash();
-
-            let (a, b, c, d) = match self.siblings.elems() {
-                Elems::_0([]) => (focus, zero, zero, zero),
-                Elems::_1(full) => {
-                    let [a] = hashes_of_all(full);
-                    (a, focus, zero, zero)
-                }
-                Elems::_2(full) => {
-                    let [a, b] = hashes_of_all(full);
-                    (a, b, focus, zero)
-                }
-                Elems::_3(full) => {
-                    let [a, b, c] = hashes_of_all(full);
-                    (a, b, c, focus)
-                }
-            };
+            let (focus, (mut hashes, len)) = hash::join(
+                Child::Height::HEIGHT,
+                || self.focus.hash(),
+                || {
+                    let mut hashes = [Hash::zero(); 4];
+                    let mut len = 0;
+                    for (hash, sibling) in hashes.iter_mut().zip(self.siblings.iter()) {
+                        *hash = sibling.hash();
+                        len += 1;
+                    }
+                    (hashes, len)
+                },
+            );
+            hashes[len] = focus;
+            let [a, b, c, d] = hashes;
 
             // Compute the hash of the node based on its height and the height of its children,
             // and cache it in the node
@@ -324,3 +314,65 @@ where
         }
     }
 }
+
+impl<Child: Focus + Encode> Encode for Node<Child>
+where
+    Child::Complete: Encode,
+{
+    fn encode<E: Encoder>(&self, position: u64, encoder: &mut E) {
+        encoder.siblings(position, Self::Height::HEIGHT, self.siblings.len());
+
+        let child_position =
+            |which| storage::child_position(position, Child::Height::HEIGHT, which);
+
+        for (which, sibling) in (0..).zip(self.siblings.iter()) {
+            sibling.encode(child_position(which), encoder);
+        }
+
+        self.focus
+            .encode(child_position(self.siblings.len()), encoder);
+    }
+}
+
+impl<Child: Focus + Frontier + Decode> Decode for Node<Child>
+where
+    Child::Complete: Decode,
+{
+    fn decode<D: Decoder>(position: u64, decoder: &mut D) -> Result<Self, DeserializeError> {
+        let len = decoder.siblings(position, Self::Height::HEIGHT)?;
+
+        let child_position =
+            |which| storage::child_position(position, Child::Height::HEIGHT, which);
+
+        let mut siblings = Three::new();
+        for which in 0..len {
+            let sibling = Insert::decode(child_position(which), decoder)?;
+            siblings = siblings
+                .push(sibling)
+                .unwrap_or_else(|_| panic!("at most 3 siblings are decoded"));
+        }
+
+        let focus = Child::decode(child_position(len), decoder)?;
+
+        Ok(Self::from_parts(siblings, focus))
+    }
+}
+
+impl<Child: Focus + Measure> Measure for Node<Child>
+where
+    Child::Complete: Measure,
+{
+    fn measure(&self, stats: &mut SizeStats) {
+        stats.nodes += 1;
+        if self.hash.get().is_some() {
+            stats.cached_hashes += 1;
+        }
+        // The siblings are always allocated with room for all three
+        stats.heap_bytes += 3 * std::mem::size_of::<Insert<Child::Complete>>();
+
+        for sibling in self.siblings.iter() {
+            sibling.measure(stats);
+        }
+        self.focus.measure(stats);
+    }
+}
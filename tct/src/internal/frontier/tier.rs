@@ -285,6 +285,63 @@ impl<Item: Focus> From<complete::Top<Item::Complete>> for Tier<Item> {
     }
 }
 
+impl<Item: Focus + Encode> Encode for Tier<Item>
+where
+    Item::Complete: Encode,
+{
+    fn encode<E: Encoder>(&self, position: u64, encoder: &mut E) {
+        let height = Self::Height::HEIGHT;
+        match &self.inner {
+            Inner::Hash(hash) => encoder.hash(position, height, *hash),
+            Inner::Frontier(frontier) => {
+                if encoder.subtree(position, height) {
+                    encoder.finalized(position, height, false);
+                    frontier.encode(position, encoder);
+                }
+            }
+            Inner::Complete(complete) => {
+                if encoder.subtree(position, height) {
+                    encoder.finalized(position, height, true);
+                    complete.encode(position, encoder);
+                }
+            }
+        }
+    }
+}
+
+impl<Item: Focus + Decode> Decode for Tier<Item>
+where
+    Item::Complete: Decode,
+{
+    fn decode<D: Decoder>(position: u64, decoder: &mut D) -> Result<Self, DeserializeError> {
+        let height = Self::Height::HEIGHT;
+        let inner = if let Some(hash) = decoder.hash(position, height)? {
+            Inner::Hash(hash)
+        } else if decoder.finalized(position, height)? {
+            Inner::Complete(complete::Nested::decode(position, decoder)?)
+        } else {
+            Inner::Frontier(Box::new(Nested::decode(position, decoder)?))
+        };
+        Ok(Self { inner })
+    }
+}
+
+impl<Item: Focus + Measure> Measure for Tier<Item>
+where
+    Item::Complete: Measure,
+{
+    fn measure(&self, stats: &mut SizeStats) {
+        match &self.inner {
+            Inner::Hash(_) => stats.pruned += 1,
+            Inner::Frontier(frontier) => {
+                stats.heap_bytes += std::mem::size_of::<Nested<Item>>();
+                frontier.measure(stats);
+            }
+            Inner::Complete(complete) => complete.measure(stats),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
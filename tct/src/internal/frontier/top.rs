@@ -173,3 +173,40 @@ where
         }
     }
 }
+
+impl<Item: Focus + Encode> Encode for Top<Item>
+where
+    Item::Complete: Encode,
+{
+    fn encode<E: Encoder>(&self, position: u64, encoder: &mut E) {
+        encoder.empty(self.inner.is_none());
+        if let Some(ref inner) = self.inner {
+            inner.encode(position, encoder);
+        }
+    }
+}
+
+impl<Item: Focus + Decode> Decode for Top<Item>
+where
+    Item::Complete: Decode,
+{
+    fn decode<D: Decoder>(position: u64, decoder: &mut D) -> Result<Self, DeserializeError> {
+        let inner = if decoder.empty()? {
+            None
+        } else {
+            Some(Nested::decode(position, decoder)?)
+        };
+        Ok(Self { inner })
+    }
+}
+
+impl<Item: Focus + Measure> Measure for Top<Item>
+where
+    Item::Complete: Measure,
+{
+    fn measure(&self, stats: &mut SizeStats) {
+        if let Some(ref inner) = self.inner {
+            inner.measure(stats);
+        }
+    }
+}
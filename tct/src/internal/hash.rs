@@ -2,14 +2,16 @@
 //! [`GetHash`] trait for computing and caching hashes of things, and the [`CachedHash`] type, which
 //! is used internally for lazy evaluation of hashes.
 
-use std::fmt::Debug;
+use core::fmt::Debug;
 
-use ark_ff::{fields::PrimeField, BigInteger256, Fp256, One, ToBytes, Zero};
+use ark_ff::{fields::PrimeField, BigInteger256, Fp256, One, Zero};
+#[cfg(feature = "std")]
 use once_cell::sync::Lazy;
 use poseidon377::Fq;
+#[cfg(feature = "std")]
 use serde::{Deserialize, Serialize};
 
-use crate::prelude::*;
+use crate::Commitment;
 
 #[cfg(not(feature = "fast_hash"))]
 use poseidon377::{hash_1, hash_4};
@@ -18,13 +20,20 @@ mod fast;
 #[cfg(feature = "fast_hash")]
 use fast::{hash_1, hash_4};
 
+#[cfg(feature = "std")]
 mod cache;
+#[cfg(feature = "std")]
 mod option;
+#[cfg(feature = "std")]
 pub use {cache::CachedHash, option::OptionHash};
 
 /// A type which can be transformed into a [`struct@Hash`], either by retrieving a cached hash, computing a
 /// hash for it, or some combination of both.
-pub trait GetHash {
+///
+/// Implementors must be [`Sync`], so that the hashes of siblings can be computed in parallel when
+/// the `parallel` feature is enabled.
+#[cfg(feature = "std")]
+pub trait GetHash: Sync {
     /// Get the hash of this item.
     ///
     /// # Correctness
@@ -45,6 +54,7 @@ pub trait GetHash {
     fn cached_hash(&self) -> Option<Hash>;
 }
 
+#[cfg(feature = "std")]
 impl<T: GetHash> GetHash for &T {
     #[inline]
     fn hash(&self) -> Hash {
@@ -57,6 +67,7 @@ impl<T: GetHash> GetHash for &T {
     }
 }
 
+#[cfg(feature = "std")]
 impl<T: GetHash> GetHash for &mut T {
     #[inline]
     fn hash(&self) -> Hash {
@@ -69,9 +80,37 @@ impl<T: GetHash> GetHash for &mut T {
     }
 }
 
+/// The minimum height of the children of a node for their hashes to be computed in parallel when
+/// the `parallel` feature is enabled: below this, the subtrees are too small for the work to
+/// outweigh the overhead of scheduling it.
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+const PARALLEL_MIN_HEIGHT: u8 = 4;
+
+/// Compute two things which are the hashes (or depend on the hashes) of children of the given
+/// height, in parallel if the `parallel` feature is enabled and the children are tall enough.
+///
+/// On `wasm32` targets, which have no threads to run on, this is always sequential.
+#[cfg(feature = "std")]
+#[inline]
+pub(crate) fn join<A: Send, B: Send>(
+    child_height: u8,
+    a: impl FnOnce() -> A + Send,
+    b: impl FnOnce() -> B + Send,
+) -> (A, B) {
+    #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+    if child_height >= PARALLEL_MIN_HEIGHT {
+        return rayon::join(a, b);
+    }
+    #[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+    let _ = child_height;
+
+    (a(), b())
+}
+
 /// The hash of an individual item, tree root, or intermediate node.
-#[derive(Clone, Copy, PartialEq, Eq, std::hash::Hash, Serialize, Deserialize)]
-pub struct Hash(#[serde(with = "crate::serialize::fq")] Fq);
+#[derive(Clone, Copy, PartialEq, Eq, core::hash::Hash)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Hash(#[cfg_attr(feature = "std", serde(with = "crate::serialize::fq"))] Fq);
 
 impl From<Hash> for Fq {
     #[inline]
@@ -81,18 +120,41 @@ impl From<Hash> for Fq {
 }
 
 impl Debug for Hash {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-        let mut bytes = Vec::with_capacity(4 * 8);
-        self.0.write(&mut bytes).unwrap();
-        write!(f, "{}", hex::encode(&bytes))
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> Result<(), core::fmt::Error> {
+        // The little-endian encoding of the field element, in hex.
+        for limb in self.0.into_repr().0 {
+            for byte in limb.to_le_bytes() {
+                write!(f, "{:02x}", byte)?;
+            }
+        }
+        Ok(())
     }
 }
 
 /// The domain separator used for leaves in the tree, and used as a base index for the domain
 /// separators of nodes in the tree (nodes get a domain separator of the form `DOMAIN_SEPARATOR +
 /// HEIGHT`).
-pub static DOMAIN_SEPARATOR: Lazy<Fq> =
-    Lazy::new(|| Fq::from_le_bytes_mod_order(blake2b_simd::blake2b(b"penumbra.tct").as_bytes()));
+#[cfg(feature = "std")]
+pub static DOMAIN_SEPARATOR: Lazy<Fq> = Lazy::new(compute_domain_separator);
+
+fn compute_domain_separator() -> Fq {
+    Fq::from_le_bytes_mod_order(blake2b_simd::blake2b(b"penumbra.tct").as_bytes())
+}
+
+/// The domain separator for leaves, computed only once.
+#[cfg(feature = "std")]
+#[inline]
+fn domain_separator() -> Fq {
+    *DOMAIN_SEPARATOR
+}
+
+/// The domain separator for leaves, recomputed on every use, since there is nowhere to cache it
+/// without `std`. This is cheap next to the hash it separates.
+#[cfg(not(feature = "std"))]
+#[inline]
+fn domain_separator() -> Fq {
+    compute_domain_separator()
+}
 
 #[allow(unused)]
 impl Hash {
@@ -126,16 +188,45 @@ impl Hash {
     /// Hash an individual item to be inserted into the tree.
     #[inline]
     pub fn of(item: Commitment) -> Hash {
-        Self(hash_1(&DOMAIN_SEPARATOR, item.into()))
+        Self(hash_1(&domain_separator(), item.into()))
     }
 
     /// Construct a hash for an internal node of the tree, given its height and the hashes of its
     /// four children.
     #[inline]
     pub fn node(height: u8, Hash(a): Hash, Hash(b): Hash, Hash(c): Hash, Hash(d): Hash) -> Hash {
-        let height = Fq::from_le_bytes_mod_order(&height.to_le_bytes());
-        Hash(hash_4(&(*DOMAIN_SEPARATOR + height), (a, b, c, d)))
+        Hash(hash_4(&node_domain_separator(height), (a, b, c, d)))
     }
+
+    /// Construct the hashes of many internal nodes of the tree at the same height, given the
+    /// hashes of each one's four children, in order.
+    ///
+    /// This is equivalent to calling [`Hash::node`] for each node, but computes the domain
+    /// separator for the height only once, and hashes the nodes in parallel if the `parallel`
+    /// feature is enabled.
+    #[cfg(feature = "std")]
+    pub fn nodes(height: u8, children: &[[Hash; 4]]) -> Vec<Hash> {
+        let domain_separator = node_domain_separator(height);
+        let hash = |&[Hash(a), Hash(b), Hash(c), Hash(d)]: &[Hash; 4]| {
+            Hash(hash_4(&domain_separator, (a, b, c, d)))
+        };
+
+        #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+        {
+            use rayon::prelude::*;
+            children.par_iter().map(hash).collect()
+        }
+        #[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+        {
+            children.iter().map(hash).collect()
+        }
+    }
+}
+
+/// The domain separator for the hashes of internal nodes at the given height.
+#[inline]
+fn node_domain_separator(height: u8) -> Fq {
+    domain_separator() + Fq::from_le_bytes_mod_order(&height.to_le_bytes())
 }
 
 #[cfg(any(test, feature = "arbitrary"))]
@@ -178,3 +269,20 @@ mod arbitrary {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn nodes_matches_node(height in 1u8..=24, children in prop::collection::vec(any::<[Hash; 4]>(), 0..10)) {
+            let expected = children
+                .iter()
+                .map(|&[a, b, c, d]| Hash::node(height, a, b, c, d))
+                .collect::<Vec<_>>();
+            prop_assert_eq!(Hash::nodes(height, &children), expected);
+        }
+    }
+}
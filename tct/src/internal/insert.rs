@@ -117,3 +117,34 @@ impl<T: Height + ForgetOwned> Forget for Insert<T> {
         forgotten
     }
 }
+
+impl<T: Encode> Encode for Insert<T> {
+    fn encode<E: Encoder>(&self, position: u64, encoder: &mut E) {
+        match self {
+            Insert::Hash(hash) => encoder.hash(position, T::Height::HEIGHT, *hash),
+            Insert::Keep(item) => {
+                if encoder.subtree(position, T::Height::HEIGHT) {
+                    item.encode(position, encoder);
+                }
+            }
+        }
+    }
+}
+
+impl<T: Decode> Decode for Insert<T> {
+    fn decode<D: Decoder>(position: u64, decoder: &mut D) -> Result<Self, DeserializeError> {
+        Ok(match decoder.hash(position, T::Height::HEIGHT)? {
+            Some(hash) => Insert::Hash(hash),
+            None => Insert::Keep(T::decode(position, decoder)?),
+        })
+    }
+}
+
+impl<T: Measure> Measure for Insert<T> {
+    fn measure(&self, stats: &mut SizeStats) {
+        match self {
+            Insert::Hash(_) => stats.pruned += 1,
+            Insert::Keep(item) => item.measure(stats),
+        }
+    }
+}
@@ -9,16 +9,20 @@
 //! These are wrapped in more specific domain types by the exposed crate API to make it more
 //! comprehensible.
 
+#[cfg(feature = "std")]
 use thiserror::Error;
 
+#[cfg(feature = "std")]
 use crate::prelude::*;
 
 /// An authentication path into a `Tree`.
 ///
 /// This is statically guaranteed to have the same length as the height of the tree.
+#[cfg(feature = "std")]
 pub type AuthPath<Tree> = <<Tree as Height>::Height as Path>::Path;
 
 /// Identifies the unique type representing an authentication path for the given height.
+#[cfg(feature = "std")]
 pub trait Path: IsHeight + Sized {
     /// The authentication path for this height.
     type Path;
@@ -28,9 +32,11 @@ pub trait Path: IsHeight + Sized {
 }
 
 /// The empty authentication path, for the zero-height tree.
+#[cfg(feature = "std")]
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
 pub struct Leaf;
 
+#[cfg(feature = "std")]
 impl Path for Zero {
     type Path = Leaf;
 
@@ -41,6 +47,7 @@ impl Path for Zero {
 }
 
 /// The authentication path for a node, whose height is always at least 1.
+#[cfg(feature = "std")]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct Node<Child> {
     /// The sibling hashes of the child.
@@ -53,6 +60,7 @@ pub struct Node<Child> {
     pub child: Child,
 }
 
+#[cfg(feature = "std")]
 impl<Child, N: Path<Path = Child>> Path for Succ<N> {
     type Path = Node<Child>;
 
@@ -208,6 +216,7 @@ mod test {
 // All the below is just for serialization to/from protobufs:
 
 /// When deserializing an authentication path, it was malformed.
+#[cfg(feature = "std")]
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Error)]
 #[error("could not decode authentication path")]
 pub struct PathDecodeError;
@@ -3,13 +3,19 @@
 //! These are wrapped in mode specific domain types by the exposed crate API to make it more
 //! comprehensible.
 
+use core::fmt::{self, Display};
+#[cfg(feature = "std")]
 use std::fmt::Debug;
 
+#[cfg(feature = "std")]
 use thiserror::Error;
 
+use crate::internal::hash::Hash;
+#[cfg(feature = "std")]
 use crate::prelude::*;
 
 /// A proof of inclusion for a single [`Commitment`](crate::Commitment) commitment in a tree.
+#[cfg(feature = "std")]
 #[derive(Derivative)]
 #[derivative(
     Debug(bound = "<Tree::Height as path::Path>::Path: Debug"),
@@ -23,6 +29,7 @@ pub struct Proof<Tree: Height> {
     pub(crate) leaf: Commitment,
 }
 
+#[cfg(feature = "std")]
 impl<Tree: Height> Proof<Tree> {
     /// Verify a [`Proof`] of inclusion against the root [`struct@Hash`] of a tree.
     ///
@@ -48,12 +55,21 @@ impl<Tree: Height> Proof<Tree> {
 }
 
 /// A proof of inclusion did not verify against the provided root hash.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
-#[error("invalid inclusion proof for root hash {root:?}")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct VerifyError {
-    root: Hash,
+    pub(crate) root: Hash,
 }
 
+// This is written out rather than derived, since `thiserror` requires `std`.
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid inclusion proof for root hash {:?}", self.root)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VerifyError {}
+
 impl VerifyError {
     /// Get the root hash against which the proof failed to verify.
     pub fn root(&self) -> Hash {
@@ -62,6 +78,7 @@ impl VerifyError {
 }
 
 /// When deserializing a proof, it was malformed.
+#[cfg(feature = "std")]
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Error)]
 #[error("could not decode proof")]
 pub struct ProofDecodeError;
@@ -68,6 +68,11 @@ impl<T> Three<T> {
         self.elems.len() as u8
     }
 
+    /// Iterate over the elements of this [`Three`] by reference.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.elems.iter()
+    }
+
     /// Get an enumeration of the elements of this [`Three`] by reference.
     pub fn elems(&self) -> Elems<T> {
         match self.elems.len() {
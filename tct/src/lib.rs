@@ -32,25 +32,71 @@
 //!         ┃ ╱││╲  ╱││╲  ╱││╲  ╱││╲ ◀─── Block Leaf
 //!                                       = Note Commitment
 //! ```
-
+//!
+//! ## Platform support
+//!
+//! This crate builds for `wasm32-unknown-unknown`, for use in browser wallets, where the `parallel`
+//! feature has no effect.
+//!
+//! The tree itself requires the `std` feature, which is enabled by default, because it relies on
+//! `std` for its lazily cached hashes (which are guarded by mutexes) and for its index of
+//! commitments. Consumers which only need to check inclusion proofs, such as circuit tooling, can
+//! disable default features to build the crate under `no_std`, leaving the [`verify`] module and
+//! the [`Position`], [`Root`] and [`Commitment`] types it works with. These neither allocate nor
+//! depend on any tree.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 // Cargo doc complains if the recursion limit isn't higher, even though cargo build succeeds:
 #![recursion_limit = "256"]
 #![warn(missing_docs)]
 
+#[cfg(feature = "std")]
 #[macro_use]
 extern crate derivative;
 
 #[macro_use]
 extern crate serde;
 
+#[cfg(feature = "std")]
+mod anchors;
+#[cfg(feature = "std")]
+mod diff;
+#[cfg(feature = "std")]
+mod dot;
+#[cfg(feature = "std")]
 mod error;
+#[cfg(feature = "std")]
+mod frontier_only;
 mod index;
+mod position;
+#[cfg(feature = "std")]
 mod proof;
+mod root;
+#[cfg(feature = "std")]
 mod serialize;
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "std")]
 mod tree;
 
+#[cfg(feature = "std")]
+pub mod storage;
+#[cfg(feature = "std")]
+pub mod validate;
+pub mod verify;
+
+#[cfg(feature = "std")]
+pub use diff::{Divergence, TreeDiff};
+#[cfg(feature = "std")]
+pub use frontier_only::FrontierOnly;
+pub use position::Position;
+#[cfg(feature = "std")]
 pub use proof::Proof;
-pub use tree::{Position, Root, Tree};
+pub use root::Root;
+#[cfg(feature = "std")]
+pub use stats::SizeStats;
+#[cfg(feature = "std")]
+pub use tree::Tree;
 
 #[cfg(any(doc, feature = "internal"))]
 pub mod internal;
@@ -60,6 +106,7 @@ mod internal;
 #[cfg(any(test, feature = "arbitrary"))]
 pub mod arbitrary;
 
+#[cfg(feature = "std")]
 pub mod builder {
     //! Builders for individual epochs and blocks within a tree.
     //!
@@ -78,11 +125,12 @@ pub mod builder {
 }
 
 #[doc(inline)]
-pub use crate::internal::{
-    path::PathDecodeError,
-    proof::{ProofDecodeError, VerifyError},
-};
+pub use crate::internal::proof::VerifyError;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use crate::internal::{path::PathDecodeError, proof::ProofDecodeError};
 
+#[cfg(feature = "std")]
 mod prelude {
     pub(crate) use super::{
         index,
@@ -90,12 +138,14 @@ mod prelude {
             complete::{self, Complete, ForgetOwned},
             frontier::{self, Focus, Forget, Frontier, Full, GetPosition, Insert, Item},
             hash::GetHash,
-            hash::{CachedHash, Hash, OptionHash},
+            hash::{self, CachedHash, Hash, OptionHash},
             height::{Height, IsHeight, Succ, Zero},
             interface::Witness,
             path::{self, AuthPath, Path, WhichWay},
             three::{Elems, ElemsMut, IntoElems, Three},
         },
+        stats::{Measure, SizeStats},
+        storage::{Decode, Decoder, DeserializeError, Encode, Encoder},
         Commitment, Position, Proof, Root, Tree, VerifyError,
     };
 }
@@ -119,15 +169,22 @@ pub enum Witness {
 ///
 /// This is an element of the base field of the curve used by the Poseidon hash function
 /// instantiated for BLS12-377.
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct Commitment(#[serde(with = "crate::serialize::fq")] pub poseidon377::Fq);
-
-impl std::fmt::Debug for Commitment {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-        use ark_ff::ToBytes;
-        let mut bytes = Vec::with_capacity(4 * 8);
-        self.0.write(&mut bytes).unwrap();
-        write!(f, "Commitment({})", hex::encode(&bytes[3 * 8 + 4..]))
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Commitment(
+    #[cfg_attr(feature = "std", serde(with = "crate::serialize::fq"))] pub poseidon377::Fq,
+);
+
+impl core::fmt::Debug for Commitment {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> Result<(), core::fmt::Error> {
+        use ark_ff::PrimeField;
+        // The last four bytes of the little-endian encoding, which are enough to tell commitments
+        // apart when debugging.
+        write!(f, "Commitment(")?;
+        for byte in &self.0.into_repr().0[3].to_le_bytes()[4..] {
+            write!(f, "{:02x}", byte)?;
+        }
+        write!(f, ")")
     }
 }
 
@@ -149,7 +206,7 @@ mod test {
 
     #[test]
     fn check_eternity_size() {
-        static_assertions::assert_eq_size!(Tree, [u8; 608]);
+        static_assertions::assert_eq_size!(Tree, [u8; 656]);
     }
 
     #[test]
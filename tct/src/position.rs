@@ -0,0 +1,84 @@
+//! The position of a commitment within a tree.
+
+use serde::{Deserialize, Serialize};
+
+use crate::index;
+#[cfg(all(doc, feature = "std"))]
+use crate::{
+    builder::{block::Block, epoch::Epoch},
+    Commitment, Tree,
+};
+
+/// The index of a [`Commitment`] within a [`Tree`].
+///
+/// A position is the index of an [`Epoch`] within the tree, of a [`Block`] within that epoch, and of
+/// a [`Commitment`] within that block. It converts to and from a [`u64`] holding those three 16-bit
+/// indices in its low 48 bits, from most to least significant. Positions are ordered in the order in which commitments
+/// are inserted into the tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Position(pub(crate) index::within::Tree);
+
+impl Position {
+    /// The last position in a [`Tree`].
+    pub const MAX: Position = Position(index::within::Tree::MAX);
+
+    /// Create the [`Position`] of the given [`Commitment`] within the given [`Block`] within the
+    /// given [`Epoch`].
+    pub fn new(epoch: u16, block: u16, commitment: u16) -> Self {
+        Position(index::within::Tree {
+            epoch: epoch.into(),
+            block: block.into(),
+            commitment: commitment.into(),
+        })
+    }
+
+    /// The position of the next commitment after this one, or `None` if this is the
+    /// [`MAX`](Position::MAX) position.
+    ///
+    /// The next position after the last commitment of a [`Block`] is the first commitment of the
+    /// next block, and similarly for [`Epoch`]s.
+    pub fn successor(&self) -> Option<Position> {
+        if *self == Position::MAX {
+            None
+        } else {
+            Some(Position::from(u64::from(*self) + 1))
+        }
+    }
+
+    /// The number of positions from this one to the other, in either direction.
+    ///
+    /// For example, the distance from the first position in a [`Block`] to the first position in
+    /// the next block is 65,536.
+    pub fn distance(&self, other: Position) -> u64 {
+        let (this, other) = (u64::from(*self), u64::from(other));
+        this.max(other) - this.min(other)
+    }
+
+    /// The index of the [`Commitment`] to which this [`Position`] refers within its [`Block`].
+    pub fn commitment(&self) -> u16 {
+        self.0.commitment.into()
+    }
+
+    /// The index of the [`Block`] to which this [`Position`] refers within its [`Epoch`].
+    pub fn block(&self) -> u16 {
+        self.0.block.into()
+    }
+
+    /// The index of the [`Epoch`] to which this [`Position`] refers within its [`Tree`].
+    pub fn epoch(&self) -> u16 {
+        self.0.epoch.into()
+    }
+}
+
+impl From<Position> for u64 {
+    fn from(position: Position) -> Self {
+        position.0.into()
+    }
+}
+
+impl From<u64> for Position {
+    /// Convert a `u64` into a [`Position`], ignoring all but its low 48 bits.
+    fn from(position: u64) -> Self {
+        Position(position.into())
+    }
+}
@@ -122,6 +122,9 @@ impl Proof {
     /// # Errors
     ///
     /// Returns [`VerifyError`] if the proof is invalid for that [`Root`].
+    ///
+    /// To verify a proof from its parts without constructing a [`Proof`], use
+    /// [`verify::verify`](crate::verify::verify).
     pub fn verify(&self, root: Root) -> Result<(), VerifyError> {
         self.0.verify(root.0)
     }
@@ -0,0 +1,66 @@
+//! The root hash of a tree.
+
+#[cfg(feature = "std")]
+use penumbra_proto::crypto as pb;
+use poseidon377::Fq;
+
+use crate::internal::hash::Hash;
+
+/// The root hash of a [`Tree`](crate::Tree).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "std",
+    derive(Serialize, Deserialize),
+    serde(try_from = "pb::MerkleRoot", into = "pb::MerkleRoot")
+)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
+pub struct Root(pub(crate) Hash);
+
+impl From<Root> for Fq {
+    fn from(root: Root) -> Self {
+        root.0.into()
+    }
+}
+
+#[cfg(feature = "std")]
+mod proto {
+    use std::fmt::Display;
+
+    use decaf377::{FieldExt, Fq};
+    use penumbra_proto::{crypto as pb, Protobuf};
+    use thiserror::Error;
+
+    use super::Root;
+    use crate::internal::hash::Hash;
+
+    /// An error occurred when decoding a tree root from bytes.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+    #[error("could not decode tree root")]
+    pub struct RootDecodeError;
+
+    impl TryFrom<pb::MerkleRoot> for Root {
+        type Error = RootDecodeError;
+
+        fn try_from(root: pb::MerkleRoot) -> Result<Root, Self::Error> {
+            let bytes: [u8; 32] = (&root.inner[..]).try_into().map_err(|_| RootDecodeError)?;
+            let inner = Fq::from_bytes(bytes).map_err(|_| RootDecodeError)?;
+            Ok(Root(Hash::new(inner)))
+        }
+    }
+
+    impl From<Root> for pb::MerkleRoot {
+        fn from(root: Root) -> Self {
+            Self {
+                inner: Fq::from(root.0).to_bytes().to_vec(),
+            }
+        }
+    }
+
+    impl Protobuf<pb::MerkleRoot> for Root {}
+
+    impl Display for Root {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "{}", hex::encode(&Fq::from(self.0).to_bytes()))
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! Accounting for the size of a [`Tree`] in memory.
+
+use crate::prelude::*;
+
+/// Statistics about the size of a [`Tree`] in memory, as returned by
+/// [`Tree::size_stats`](crate::Tree::size_stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SizeStats {
+    /// The number of commitments currently witnessed.
+    pub witnessed: usize,
+    /// The number of nodes stored in the tree, including leaves and the nodes of its frontier,
+    /// but not including subtrees which have been pruned to their hash.
+    pub nodes: usize,
+    /// The number of hashes stored in place of pruned subtrees (including the padding of
+    /// subtrees which were ended before they were full).
+    pub pruned: usize,
+    /// The number of nodes whose hash is currently cached.
+    pub cached_hashes: usize,
+    /// An estimate of the number of bytes the tree occupies on the heap, including its index.
+    ///
+    /// This counts the size of every allocation, but not the overhead of the allocator itself.
+    pub heap_bytes: usize,
+}
+
+/// Part of a tree whose size can be measured.
+pub(crate) trait Measure {
+    /// Add the size of this part of the tree to the statistics.
+    fn measure(&self, stats: &mut SizeStats);
+}
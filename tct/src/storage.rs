@@ -0,0 +1,222 @@
+//! Compact encodings of a [`Tree`], for persisting it to disk or to a database.
+//!
+//! The [`Serialize`](serde::Serialize) implementation of a [`Tree`] mirrors its in-memory
+//! representation, including the index of every witnessed commitment alongside the tree itself,
+//! which is both large and slow to process with self-describing formats. The encodings in this
+//! module instead store only what can't be recomputed: the hashes of the pruned parts of the tree,
+//! and the witnessed commitments themselves (whose hashes and positions are recomputed on load).
+//!
+//! There are two encodings:
+//!
+//! - [`serialize`] and [`deserialize`] convert a whole tree to and from a single compact, versioned
+//!   byte string, suitable for writing to a file.
+//! - [`points_since`] lists the individual hashes and commitments ([`Point`]s) of a tree which were
+//!   added since some previous position, and [`from_points`] rebuilds a tree from all the points
+//!   persisted so far. This allows a tree to be persisted into a database incrementally, writing
+//!   only what's new after each block, rather than rewriting the whole tree.
+
+use std::collections::HashMap;
+
+use hash_hasher::HashedMap;
+use thiserror::Error;
+
+use crate::prelude::*;
+
+mod bytes;
+mod points;
+
+pub use bytes::{deserialize, serialize, VERSION};
+pub use points::{from_points, points_since, Point};
+
+/// An error occurred when deserializing a [`Tree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum DeserializeError {
+    /// The bytes were encoded with an unsupported version of the encoding.
+    #[error("unsupported tree encoding version {0}")]
+    UnsupportedVersion(u8),
+    /// The bytes ended before the tree did.
+    #[error("unexpected end of tree encoding")]
+    UnexpectedEnd,
+    /// There were bytes remaining after the end of the tree.
+    #[error("{0} trailing bytes after end of tree encoding")]
+    TrailingBytes(usize),
+    /// A tag byte was out of range for the part of the tree it describes.
+    #[error("invalid tag {tag} for {what}")]
+    InvalidTag {
+        /// The part of the tree being decoded.
+        what: &'static str,
+        /// The invalid tag.
+        tag: u8,
+    },
+    /// A complete node had no witnessed children, so should have been pruned to a hash.
+    #[error("complete node at position {0} has no witnessed children")]
+    EmptyNode(u64),
+    /// A hash or commitment was not a valid field element.
+    #[error("invalid field element")]
+    InvalidFieldElement,
+    /// The same commitment was witnessed at more than one position.
+    #[error("commitment at position {0} is witnessed more than once")]
+    DuplicateCommitment(u64),
+    /// Neither a commitment nor the hash of a subtree containing it was persisted for a position.
+    #[error("no commitment or hash was persisted for position {0}")]
+    Missing(u64),
+}
+
+/// A destination for the parts of a tree, visited in order by [`Encode`].
+pub(crate) trait Encoder {
+    /// Record the commitments witnessed in the tree, so they can be written at their leaves.
+    fn index(&mut self, commitments: impl IntoIterator<Item = (Commitment, u64)>);
+
+    /// Write the hash of the pruned subtree of the given height, whose first leaf is at the given
+    /// position.
+    fn hash(&mut self, position: u64, height: u8, hash: Hash);
+
+    /// Note that the subtree of the given height at the given position is not pruned, returning
+    /// whether its contents should be written.
+    fn subtree(&mut self, position: u64, height: u8) -> bool;
+
+    /// Write the commitment witnessed at the given position.
+    fn commitment(&mut self, position: u64);
+
+    /// Write the number of complete siblings to the left of the focus of the frontier node of the
+    /// given height at the given position.
+    fn siblings(&mut self, position: u64, height: u8, siblings: u8);
+
+    /// Write whether the frontier tier of the given height at the given position is finalized.
+    fn finalized(&mut self, position: u64, height: u8, finalized: bool);
+
+    /// Write whether the whole tree is empty.
+    fn empty(&mut self, empty: bool);
+}
+
+/// A source for the parts of a tree, read in order by [`Decode`].
+pub(crate) trait Decoder {
+    /// Take the index of every commitment read so far.
+    fn take_index(&mut self) -> HashedMap<Commitment, index::within::Tree>;
+
+    /// Read the hash of the subtree of the given height at the given position, if it was pruned.
+    fn hash(&mut self, position: u64, height: u8) -> Result<Option<Hash>, DeserializeError>;
+
+    /// Read the commitment witnessed at the given position, returning its hash.
+    fn commitment(&mut self, position: u64) -> Result<Hash, DeserializeError>;
+
+    /// Read the number of complete siblings to the left of the focus of the frontier node of the
+    /// given height at the given position.
+    fn siblings(&mut self, position: u64, height: u8) -> Result<u8, DeserializeError>;
+
+    /// Read whether the frontier tier of the given height at the given position is finalized.
+    fn finalized(&mut self, position: u64, height: u8) -> Result<bool, DeserializeError>;
+
+    /// Read whether the whole tree is empty.
+    fn empty(&mut self) -> Result<bool, DeserializeError>;
+}
+
+/// Part of a tree which can be written to an [`Encoder`].
+pub(crate) trait Encode: Height {
+    /// Write this part of the tree, whose first leaf is at the given position.
+    fn encode<E: Encoder>(&self, position: u64, encoder: &mut E);
+}
+
+/// Part of a tree which can be read from a [`Decoder`].
+pub(crate) trait Decode: Height + Sized {
+    /// Read this part of the tree, whose first leaf is at the given position.
+    fn decode<D: Decoder>(position: u64, decoder: &mut D) -> Result<Self, DeserializeError>;
+}
+
+/// The position of the `which`th child of a node whose first leaf is at `position`, whose children
+/// are of the given height.
+pub(crate) fn child_position(position: u64, child_height: u8, which: u8) -> u64 {
+    position + ((which as u64) << (2 * child_height))
+}
+
+/// The number of leaf positions spanned by a subtree of the given height.
+pub(crate) fn width(height: u8) -> u64 {
+    1 << (2 * height)
+}
+
+/// The commitments witnessed in a tree, indexed by their position, for encoders to write at their
+/// leaves.
+#[derive(Default)]
+struct Commitments(HashMap<u64, Commitment>);
+
+impl Commitments {
+    fn extend(&mut self, commitments: impl IntoIterator<Item = (Commitment, u64)>) {
+        self.0.extend(
+            commitments
+                .into_iter()
+                .map(|(commitment, position)| (position, commitment)),
+        );
+    }
+
+    /// # Panics
+    ///
+    /// If no commitment is witnessed at the position, which would mean the tree's index was
+    /// inconsistent with the tree.
+    fn get(&self, position: u64) -> Commitment {
+        *self
+            .0
+            .get(&position)
+            .unwrap_or_else(|| panic!("witnessed leaf at position {} is not indexed", position))
+    }
+}
+
+/// The index of the commitments read by a decoder.
+#[derive(Default)]
+struct Index(HashedMap<Commitment, index::within::Tree>);
+
+impl Index {
+    /// Index a commitment read at the given position, returning its hash.
+    fn insert(&mut self, position: u64, commitment: Commitment) -> Result<Hash, DeserializeError> {
+        if self.0.insert(commitment, position.into()).is_some() {
+            return Err(DeserializeError::DuplicateCommitment(position));
+        }
+        Ok(Hash::of(commitment))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Witness;
+
+    pub(super) fn commitment(i: u64) -> Commitment {
+        Commitment(poseidon377::Fq::from(i))
+    }
+
+    /// Insert a mix of witnessed and forgotten commitments, ending blocks and epochs along the way.
+    pub(super) fn extend(tree: &mut Tree, commitments: std::ops::Range<u64>) {
+        for i in commitments {
+            let witness = if i % 3 == 0 {
+                Witness::Keep
+            } else {
+                Witness::Forget
+            };
+            tree.insert(witness, commitment(i)).unwrap();
+            if i % 10 == 9 {
+                tree.end_block().unwrap();
+            }
+            if i % 40 == 39 {
+                tree.end_epoch().unwrap();
+            }
+        }
+    }
+
+    /// A tree with a mix of witnessed and forgotten commitments across several blocks and epochs.
+    pub(super) fn example() -> Tree {
+        let mut tree = Tree::new();
+        extend(&mut tree, 0..100);
+        tree.forget(commitment(30));
+        tree
+    }
+
+    pub(super) fn assert_equivalent(tree: &Tree, other: &Tree) {
+        assert_eq!(tree.root(), other.root());
+        assert_eq!(tree.position(), other.position());
+        assert_eq!(tree.witnessed_count(), other.witnessed_count());
+        assert_eq!(tree.current_block_root(), other.current_block_root());
+        assert_eq!(tree.current_epoch_root(), other.current_epoch_root());
+        for i in 0..200 {
+            assert_eq!(tree.witness(commitment(i)), other.witness(commitment(i)));
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! A compact, versioned binary encoding of a whole [`Tree`].
+//!
+//! The encoding is a pre-order traversal of the tree, in which every hash and commitment is 32
+//! bytes, and every choice about the structure of the tree (whether a subtree is pruned, how many
+//! siblings a frontier node has, whether a tier is finalized) is a single tag byte. The first byte
+//! is the [`VERSION`] of the encoding.
+
+use decaf377::FieldExt;
+use poseidon377::Fq;
+
+use super::{Commitments, Index};
+use crate::prelude::*;
+
+/// The version of the encoding produced by [`serialize`].
+pub const VERSION: u8 = 1;
+
+/// Serialize a [`Tree`] into the compact binary encoding.
+pub fn serialize(tree: &Tree) -> Vec<u8> {
+    let mut writer = Writer {
+        bytes: vec![VERSION],
+        commitments: Commitments::default(),
+    };
+    tree.encode(0, &mut writer);
+    writer.bytes
+}
+
+/// Deserialize a [`Tree`] from the compact binary encoding produced by [`serialize`].
+///
+/// # Errors
+///
+/// Returns [`DeserializeError`] if the bytes are not a valid encoding of a tree, or were encoded
+/// with an unsupported [`VERSION`].
+pub fn deserialize(bytes: &[u8]) -> Result<Tree, DeserializeError> {
+    let mut reader = Reader {
+        bytes,
+        index: Index::default(),
+    };
+
+    let version = reader.byte()?;
+    if version != VERSION {
+        return Err(DeserializeError::UnsupportedVersion(version));
+    }
+
+    let tree = Tree::decode(0, &mut reader)?;
+    if !reader.bytes.is_empty() {
+        return Err(DeserializeError::TrailingBytes(reader.bytes.len()));
+    }
+
+    Ok(tree)
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+    commitments: Commitments,
+}
+
+impl Writer {
+    fn tag(&mut self, tag: u8) {
+        self.bytes.push(tag);
+    }
+}
+
+impl Encoder for Writer {
+    fn index(&mut self, commitments: impl IntoIterator<Item = (Commitment, u64)>) {
+        self.commitments.extend(commitments);
+    }
+
+    fn hash(&mut self, _position: u64, _height: u8, hash: Hash) {
+        self.tag(0);
+        self.bytes.extend_from_slice(&Fq::from(hash).to_bytes());
+    }
+
+    fn subtree(&mut self, _position: u64, _height: u8) -> bool {
+        self.tag(1);
+        true
+    }
+
+    fn commitment(&mut self, position: u64) {
+        let commitment = self.commitments.get(position);
+        self.bytes.extend_from_slice(&commitment.0.to_bytes());
+    }
+
+    fn siblings(&mut self, _position: u64, _height: u8, siblings: u8) {
+        self.tag(siblings);
+    }
+
+    fn finalized(&mut self, _position: u64, _height: u8, finalized: bool) {
+        self.tag(finalized as u8);
+    }
+
+    fn empty(&mut self, empty: bool) {
+        self.tag(empty as u8);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    index: Index,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, DeserializeError> {
+        let (&byte, rest) = self
+            .bytes
+            .split_first()
+            .ok_or(DeserializeError::UnexpectedEnd)?;
+        self.bytes = rest;
+        Ok(byte)
+    }
+
+    /// Read a single tag byte, which must be at most `max`.
+    fn tag(&mut self, what: &'static str, max: u8) -> Result<u8, DeserializeError> {
+        let tag = self.byte()?;
+        if tag > max {
+            return Err(DeserializeError::InvalidTag { what, tag });
+        }
+        Ok(tag)
+    }
+
+    fn field_element(&mut self) -> Result<Fq, DeserializeError> {
+        if self.bytes.len() < 32 {
+            return Err(DeserializeError::UnexpectedEnd);
+        }
+        let (bytes, rest) = self.bytes.split_at(32);
+        self.bytes = rest;
+        Fq::from_bytes(bytes.try_into().expect("slice is 32 bytes long"))
+            .map_err(|_| DeserializeError::InvalidFieldElement)
+    }
+}
+
+impl<'a> Decoder for Reader<'a> {
+    fn take_index(&mut self) -> hash_hasher::HashedMap<Commitment, index::within::Tree> {
+        std::mem::take(&mut self.index.0)
+    }
+
+    fn hash(&mut self, _position: u64, _height: u8) -> Result<Option<Hash>, DeserializeError> {
+        Ok(match self.tag("hash or subtree", 1)? {
+            0 => Some(Hash::new(self.field_element()?)),
+            _ => None,
+        })
+    }
+
+    fn commitment(&mut self, position: u64) -> Result<Hash, DeserializeError> {
+        let commitment = Commitment(self.field_element()?);
+        self.index.insert(position, commitment)
+    }
+
+    fn siblings(&mut self, _position: u64, _height: u8) -> Result<u8, DeserializeError> {
+        self.tag("frontier node siblings", 3)
+    }
+
+    fn finalized(&mut self, _position: u64, _height: u8) -> Result<bool, DeserializeError> {
+        Ok(self.tag("frontier tier finalization", 1)? == 1)
+    }
+
+    fn empty(&mut self) -> Result<bool, DeserializeError> {
+        Ok(self.tag("tree emptiness", 1)? == 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+    use crate::Witness;
+
+    #[test]
+    fn round_trip_empty() {
+        let tree = Tree::new();
+        let deserialized = deserialize(&serialize(&tree)).unwrap();
+        assert_equivalent(&tree, &deserialized);
+    }
+
+    #[test]
+    fn round_trip() {
+        let tree = example();
+        let deserialized = deserialize(&serialize(&tree)).unwrap();
+        assert_equivalent(&tree, &deserialized);
+    }
+
+    #[test]
+    fn round_trip_then_insert() {
+        let mut tree = example();
+        let mut deserialized = deserialize(&serialize(&tree)).unwrap();
+        for i in 100..120 {
+            tree.insert(Witness::Keep, commitment(i)).unwrap();
+            deserialized.insert(Witness::Keep, commitment(i)).unwrap();
+        }
+        tree.end_epoch().unwrap();
+        deserialized.end_epoch().unwrap();
+        assert_equivalent(&tree, &deserialized);
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let mut bytes = serialize(&example());
+        bytes[0] = VERSION + 1;
+        assert_eq!(
+            deserialize(&bytes).unwrap_err(),
+            DeserializeError::UnsupportedVersion(VERSION + 1)
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = serialize(&example());
+        assert_eq!(
+            deserialize(&bytes[..bytes.len() - 1]).unwrap_err(),
+            DeserializeError::UnexpectedEnd
+        );
+    }
+
+    #[test]
+    fn trailing_bytes() {
+        let mut bytes = serialize(&example());
+        bytes.push(0);
+        assert_eq!(
+            deserialize(&bytes).unwrap_err(),
+            DeserializeError::TrailingBytes(1)
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Incremental persistence of a [`Tree`] as its individual hashes and commitments.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{child_position, width, Commitments, Index};
+use crate::prelude::*;
+
+/// A single hash or commitment of a [`Tree`], as persisted incrementally by [`points_since`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Point {
+    /// The hash of a pruned subtree of the tree.
+    Hash {
+        /// The position of the first leaf of the subtree.
+        position: Position,
+        /// The height of the subtree: 0 for a single commitment, 8 for a whole block, and 16 for a
+        /// whole epoch.
+        height: u8,
+        /// The hash of the subtree.
+        hash: Hash,
+    },
+    /// A witnessed commitment.
+    Commitment {
+        /// The position of the commitment.
+        position: Position,
+        /// The commitment itself.
+        commitment: Commitment,
+    },
+}
+
+impl Point {
+    /// The point to persist in place of a witnessed commitment once it has been
+    /// [`forget`](Tree::forget)ten.
+    pub fn forgotten(position: Position, commitment: Commitment) -> Self {
+        Point::Hash {
+            position,
+            height: 0,
+            hash: Hash::of(commitment),
+        }
+    }
+}
+
+/// List the points of a [`Tree`] which were added since it was at the given position.
+///
+/// The position should be the [`position`](Tree::position) of the tree when its points were last
+/// persisted, or zero if they never have been. Persisting the returned points along with those
+/// persisted previously allows the tree to be rebuilt using [`from_points`]. Some of the returned
+/// points may be the hashes of subtrees containing previously persisted points, which are then
+/// superseded, and may be deleted.
+///
+/// Forgetting a commitment changes the tree at its position, rather than adding to it, so isn't
+/// reflected in the points added since a later position. To persist a [`forget`](Tree::forget),
+/// replace the point for the commitment with its [`Point::forgotten`].
+pub fn points_since(tree: &Tree, since: Position) -> Vec<Point> {
+    let mut collector = Collector {
+        since: since.into(),
+        commitments: Commitments::default(),
+        points: Vec::new(),
+    };
+    tree.encode(0, &mut collector);
+    collector.points
+}
+
+/// Rebuild a [`Tree`] from all the points persisted from it using [`points_since`].
+///
+/// The position should be the [`position`](Tree::position) of the tree when its points were last
+/// persisted. The points are applied in order, so that a later point for the same position and
+/// height replaces an earlier one (such as a [`Point::forgotten`] replacing a commitment).
+///
+/// A block or epoch which was filled without being explicitly ended is rebuilt as if it had been
+/// ended, since its points are the same either way.
+///
+/// # Errors
+///
+/// Returns [`DeserializeError`] if any of the points needed to rebuild the tree are missing, or if
+/// a commitment is witnessed at more than one position.
+pub fn from_points(
+    position: Option<Position>,
+    points: impl IntoIterator<Item = Point>,
+) -> Result<Tree, DeserializeError> {
+    let mut loader = Loader::new(position, points);
+    Tree::decode(0, &mut loader)
+}
+
+/// An [`Encoder`] which collects the points added to a tree since some position.
+struct Collector {
+    since: u64,
+    commitments: Commitments,
+    points: Vec<Point>,
+}
+
+impl Collector {
+    /// Whether the subtree of the given height at the given position spans any positions at or
+    /// after the one points are being collected since.
+    fn is_new(&self, position: u64, height: u8) -> bool {
+        position + width(height) > self.since
+    }
+}
+
+impl Encoder for Collector {
+    fn index(&mut self, commitments: impl IntoIterator<Item = (Commitment, u64)>) {
+        self.commitments.extend(commitments);
+    }
+
+    fn hash(&mut self, position: u64, height: u8, hash: Hash) {
+        if self.is_new(position, height) {
+            self.points.push(Point::Hash {
+                position: position.into(),
+                height,
+                hash,
+            });
+        }
+    }
+
+    fn subtree(&mut self, position: u64, height: u8) -> bool {
+        self.is_new(position, height)
+    }
+
+    fn commitment(&mut self, position: u64) {
+        if self.is_new(position, 0) {
+            self.points.push(Point::Commitment {
+                position: position.into(),
+                commitment: self.commitments.get(position),
+            });
+        }
+    }
+
+    fn siblings(&mut self, _position: u64, _height: u8, _siblings: u8) {}
+
+    fn finalized(&mut self, _position: u64, _height: u8, _finalized: bool) {}
+
+    fn empty(&mut self, _empty: bool) {}
+}
+
+/// A [`Decoder`] which rebuilds a tree from its persisted points.
+///
+/// The shape of the frontier isn't persisted, because it is determined by the position of the
+/// tree: the frontier leads to the most recently inserted leaf, and every tier ending before the
+/// position has been finalized.
+struct Loader {
+    /// The position of the tree, or the number of positions in a tree if it is full.
+    position: u64,
+    /// The hashes of pruned subtrees, keyed by their height and then their position.
+    hashes: BTreeMap<(u8, u64), Hash>,
+    commitments: BTreeMap<u64, Commitment>,
+    index: Index,
+}
+
+impl Loader {
+    fn new(position: Option<Position>, points: impl IntoIterator<Item = Point>) -> Self {
+        let mut hashes: BTreeMap<(u8, u64), Hash> = BTreeMap::new();
+        let mut commitments: BTreeMap<u64, Commitment> = BTreeMap::new();
+
+        for point in points {
+            match point {
+                Point::Hash {
+                    position,
+                    height,
+                    hash,
+                } => {
+                    let position: u64 = position.into();
+                    if height == 0 {
+                        commitments.remove(&position);
+                    }
+                    hashes.insert((height, position), hash);
+                }
+                Point::Commitment {
+                    position,
+                    commitment,
+                } => {
+                    let position: u64 = position.into();
+                    hashes.remove(&(0, position));
+                    commitments.insert(position, commitment);
+                }
+            }
+        }
+
+        // When every child of a complete node is forgotten, the node is pruned to its hash, but
+        // forgetting only replaces the persisted commitment, so compute the hashes of such nodes
+        let top = <Tree as Height>::Height::HEIGHT;
+        for height in 0..top {
+            let parents: BTreeSet<u64> = hashes
+                .range((height, 0)..(height + 1, 0))
+                .map(|(&(_, position), _)| position & !(width(height + 1) - 1))
+                .collect();
+
+            let (parents, children): (Vec<u64>, Vec<[Hash; 4]>) = parents
+                .into_iter()
+                .filter(|&parent| !hashes.contains_key(&(height + 1, parent)))
+                .filter_map(|parent| {
+                    let child = |which| {
+                        hashes
+                            .get(&(height, child_position(parent, height, which)))
+                            .copied()
+                    };
+                    Some((parent, [child(0)?, child(1)?, child(2)?, child(3)?]))
+                })
+                .unzip();
+
+            // Hash all the pruned nodes at this height at once
+            for (parent, hash) in parents.into_iter().zip(Hash::nodes(height + 1, &children)) {
+                hashes.insert((height + 1, parent), hash);
+            }
+        }
+
+        Self {
+            position: position.map(u64::from).unwrap_or_else(|| width(top)),
+            hashes,
+            commitments,
+            index: Index::default(),
+        }
+    }
+}
+
+impl Decoder for Loader {
+    fn take_index(&mut self) -> hash_hasher::HashedMap<Commitment, index::within::Tree> {
+        std::mem::take(&mut self.index.0)
+    }
+
+    fn hash(&mut self, position: u64, height: u8) -> Result<Option<Hash>, DeserializeError> {
+        Ok(self.hashes.get(&(height, position)).copied())
+    }
+
+    fn commitment(&mut self, position: u64) -> Result<Hash, DeserializeError> {
+        let commitment = *self
+            .commitments
+            .get(&position)
+            .ok_or(DeserializeError::Missing(position))?;
+        self.index.insert(position, commitment)
+    }
+
+    fn siblings(&mut self, position: u64, height: u8) -> Result<u8, DeserializeError> {
+        // The focus of the frontier contains the most recently inserted leaf
+        let last = self.position - 1;
+        debug_assert!(position <= last && last < position + width(height));
+        Ok(((last - position) >> (2 * (height - 1))) as u8)
+    }
+
+    fn finalized(&mut self, position: u64, height: u8) -> Result<bool, DeserializeError> {
+        Ok(self.position >= position + width(height))
+    }
+
+    fn empty(&mut self) -> Result<bool, DeserializeError> {
+        Ok(self.position == 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::*;
+
+    #[test]
+    fn round_trip_empty() {
+        let tree = Tree::new();
+        let points = points_since(&tree, Position::from(0));
+        assert!(points.is_empty());
+        let rebuilt = from_points(tree.position(), points).unwrap();
+        assert_equivalent(&tree, &rebuilt);
+    }
+
+    #[test]
+    fn round_trip() {
+        let tree = example();
+        let rebuilt = from_points(tree.position(), points_since(&tree, Position::from(0))).unwrap();
+        assert_equivalent(&tree, &rebuilt);
+    }
+
+    #[test]
+    fn incremental() {
+        let mut tree = Tree::new();
+        let mut persisted = Vec::new();
+        let mut since = Position::from(0);
+
+        for start in (0..200).step_by(17) {
+            extend(&mut tree, start..start + 17);
+            let points = points_since(&tree, since);
+            persisted.extend(points);
+            since = tree.position().unwrap();
+
+            let rebuilt = from_points(tree.position(), persisted.iter().copied()).unwrap();
+            assert_equivalent(&tree, &rebuilt);
+        }
+
+        // Only the points added since the last persisted position are listed
+        assert!(points_since(&tree, since).len() < points_since(&tree, Position::from(0)).len());
+    }
+
+    #[test]
+    fn forgotten() {
+        let mut tree = example();
+        let mut persisted = points_since(&tree, Position::from(0));
+
+        // Forget every witnessed commitment in the first block, so that it is pruned entirely
+        for i in (0..10).step_by(3) {
+            let position = tree.position_of(commitment(i)).unwrap();
+            tree.forget(commitment(i));
+            persisted.push(Point::forgotten(position, commitment(i)));
+        }
+
+        let rebuilt = from_points(tree.position(), persisted).unwrap();
+        assert_equivalent(&tree, &rebuilt);
+    }
+
+    #[test]
+    fn missing() {
+        let tree = example();
+        let points = points_since(&tree, Position::from(0))
+            .into_iter()
+            .filter(|point| !matches!(point, Point::Commitment { .. }))
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            from_points(tree.position(), points),
+            Err(DeserializeError::Missing(_))
+        ));
+    }
+}
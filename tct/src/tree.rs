@@ -1,13 +1,10 @@
-use std::fmt::Display;
-
-use decaf377::{FieldExt, Fq};
 use hash_hasher::HashedMap;
-use penumbra_proto::{crypto as pb, Protobuf};
 use serde::{Deserialize, Serialize};
-use thiserror::Error;
 
+use crate::anchors::Anchors;
 use crate::error::*;
 use crate::prelude::{Witness as _, *};
+use crate::storage;
 use crate::Witness;
 
 #[path = "epoch.rs"]
@@ -20,88 +17,39 @@ pub(crate) use epoch::block;
 pub struct Tree {
     index: HashedMap<Commitment, index::within::Tree>,
     inner: frontier::Top<frontier::Tier<frontier::Tier<frontier::Item>>>,
+    #[serde(default)]
+    anchors: Anchors,
+    #[serde(default)]
+    compact: bool,
 }
 
-/// The root hash of a [`Tree`].
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
-#[serde(try_from = "pb::MerkleRoot", into = "pb::MerkleRoot")]
-#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
-pub struct Root(pub(crate) Hash);
-
-impl From<Root> for Fq {
-    fn from(root: Root) -> Self {
-        root.0.into()
-    }
-}
-
-/// An error occurred when decoding a tree root from bytes.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
-#[error("could not decode tree root")]
-pub struct RootDecodeError;
-
-impl TryFrom<pb::MerkleRoot> for Root {
-    type Error = RootDecodeError;
-
-    fn try_from(root: pb::MerkleRoot) -> Result<Root, Self::Error> {
-        let bytes: [u8; 32] = (&root.inner[..]).try_into().map_err(|_| RootDecodeError)?;
-        let inner = Fq::from_bytes(bytes).map_err(|_| RootDecodeError)?;
-        Ok(Root(Hash::new(inner)))
-    }
-}
-
-impl From<Root> for pb::MerkleRoot {
-    fn from(root: Root) -> Self {
-        Self {
-            inner: Fq::from(root.0).to_bytes().to_vec(),
-        }
-    }
-}
-
-impl Protobuf<pb::MerkleRoot> for Root {}
-
-impl Display for Root {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", hex::encode(&Fq::from(self.0).to_bytes()))
-    }
-}
-
-/// The index of a [`Commitment`] within a [`Tree`].
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Position(index::within::Tree);
-
-impl Position {
-    /// The index of the [`Commitment`] to which this [`Position`] refers within its [`Block`].
-    pub fn commitment(&self) -> u16 {
-        self.0.commitment.into()
-    }
-
-    /// The index of the [`Block`] to which this [`Position`] refers within its [`Epoch`].
-    pub fn block(&self) -> u16 {
-        self.0.block.into()
-    }
-
-    /// The index of the [`Epoch`] to which this [`Position`] refers within its [`Tree`].
-    pub fn epoch(&self) -> u16 {
-        self.0.epoch.into()
-    }
+impl Height for Tree {
+    type Height = <frontier::Top<frontier::Tier<frontier::Tier<frontier::Item>>> as Height>::Height;
 }
 
-impl From<Position> for u64 {
-    fn from(position: Position) -> Self {
-        position.0.into()
+impl Encode for Tree {
+    fn encode<E: Encoder>(&self, position: u64, encoder: &mut E) {
+        encoder.index(
+            self.index
+                .iter()
+                .map(|(&commitment, &index)| (commitment, index.into())),
+        );
+        self.inner.encode(position, encoder);
     }
 }
 
-impl From<u64> for Position {
-    fn from(position: u64) -> Self {
-        Position(position.into())
+impl Decode for Tree {
+    fn decode<D: Decoder>(position: u64, decoder: &mut D) -> Result<Self, DeserializeError> {
+        let inner = frontier::Top::decode(position, decoder)?;
+        Ok(Tree {
+            index: decoder.take_index(),
+            inner,
+            anchors: Anchors::default(),
+            compact: false,
+        })
     }
 }
 
-impl Height for Tree {
-    type Height = <frontier::Top<frontier::Tier<frontier::Tier<frontier::Item>>> as Height>::Height;
-}
-
 impl Tree {
     /// Create a new empty [`Tree`] for storing all commitments to the end of time.
     pub fn new() -> Self {
@@ -120,6 +68,48 @@ impl Tree {
         Root(self.inner.hash())
     }
 
+    /// Compute and cache every hash in this [`Tree`] which is not already cached.
+    ///
+    /// Hashes are otherwise computed lazily, the first time they are needed by [`root`](Tree::root)
+    /// or [`witness`](Tree::witness). Calling this ahead of time (for instance, after inserting a
+    /// block's commitments, but before the block's processing needs the root) moves that latency
+    /// to a time of the caller's choosing. Afterwards, until the tree is next modified, calls to
+    /// [`root`](Tree::root) and [`witness`](Tree::witness) do no hashing.
+    ///
+    /// Since a [`Tree`] is [`Sync`], this can also be called from a background thread holding a
+    /// shared reference to the tree, such as one spawned with [`std::thread::scope`].
+    pub fn evaluate_hashes(&self) {
+        // Computing the root hash computes and caches the hash of every node beneath it
+        self.inner.hash();
+    }
+
+    /// Deserialize a [`Tree`] from an untrusted source, such as a file on disk or a snapshot
+    /// received from a peer, rejecting it unless it passes
+    /// [`validate::check`](crate::validate::check).
+    ///
+    /// The [`Deserialize`] implementation of a [`Tree`] trusts its input: it accepts whatever
+    /// cached hashes, index, and anchor history it is given, so a corrupted tree could report a
+    /// wrong root, produce invalid proofs, or accept a bogus anchor. This instead recomputes every
+    /// hash and checks that the structure of the tree is consistent, which takes time proportional
+    /// to the size of the tree.
+    ///
+    /// This can also be used to check a tree which is a field of a larger structure, by annotating
+    /// the field with `#[serde(deserialize_with = "Tree::deserialize_checked")]`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input can't be deserialized as a [`Tree`], or if the deserialized
+    /// tree is invalid, in which case the error describes the first
+    /// [`InvalidTree`](crate::validate::InvalidTree) found.
+    pub fn deserialize_checked<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let tree = Tree::deserialize(deserializer)?;
+        crate::validate::check(&tree).map_err(serde::de::Error::custom)?;
+        Ok(tree)
+    }
+
     /// Add a new [`Commitment`] to the most recent [`Block`] of the most recent [`Epoch`] of this
     /// [`Tree`].
     ///
@@ -140,6 +130,11 @@ impl Tree {
         commitment: impl Into<Commitment>,
     ) -> Result<&mut Self, InsertError> {
         let commitment = commitment.into();
+        let witness = if self.compact {
+            Witness::Forget
+        } else {
+            witness
+        };
         let item = match witness {
             Witness::Keep => commitment.into(),
             Witness::Forget => Hash::of(commitment).into(),
@@ -190,6 +185,33 @@ impl Tree {
         Ok(self)
     }
 
+    /// Add many new [`Commitment`]s, in order, to the most recent [`Block`] of the most recent
+    /// [`Epoch`] of this [`Tree`], such as all the commitments of a block during sync.
+    ///
+    /// This is equivalent to calling [`insert`](Tree::insert) for each commitment, but reserves
+    /// room in the index for the commitments up front. Internal hashes are not computed
+    /// until they are next needed (by [`root`](Tree::root) or [`witness`](Tree::witness)), except
+    /// for subtrees in which every commitment was forgotten, which are pruned to their hash as soon
+    /// as they are complete.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InsertError`] for the first commitment which could not be inserted, for any of the
+    /// reasons described for [`insert`](Tree::insert). The commitments before it remain inserted.
+    pub fn extend(
+        &mut self,
+        commitments: impl IntoIterator<Item = (Commitment, Witness)>,
+    ) -> Result<&mut Self, InsertError> {
+        let commitments = commitments.into_iter();
+        self.index.reserve(commitments.size_hint().0);
+
+        for (commitment, witness) in commitments {
+            self.insert(witness, commitment)?;
+        }
+
+        Ok(self)
+    }
+
     /// Get a [`Proof`] of inclusion for the commitment at this index in the tree.
     ///
     /// If the index is not witnessed in this tree, return `None`.
@@ -214,6 +236,52 @@ impl Tree {
         }))
     }
 
+    /// Get [`Proof`]s of inclusion for the commitments at many positions in the tree at once, such
+    /// as for all the notes spent by a sweep.
+    ///
+    /// Positions at which no commitment is witnessed are skipped.
+    ///
+    /// This is faster than calling [`witness`](Tree::witness) for each commitment: every hash in
+    /// the tree is computed once up front (in parallel, if the `parallel` feature is enabled), so
+    /// that the proofs, which share most of the upper levels of their authentication paths, are
+    /// then only read from the cached hashes.
+    pub fn witness_batch(
+        &self,
+        positions: impl IntoIterator<Item = Position>,
+    ) -> impl Iterator<Item = (Position, Proof)> + '_ {
+        // Look up commitments by position, rather than position by commitment
+        let commitments: std::collections::HashMap<u64, Commitment> = self
+            .index
+            .iter()
+            .map(|(&commitment, &index)| (index.into(), commitment))
+            .collect();
+
+        self.evaluate_hashes();
+
+        positions.into_iter().filter_map(move |position| {
+            let index = u64::from(position);
+            let commitment = *commitments.get(&index)?;
+
+            let (auth_path, leaf) = match self.inner.witness(index) {
+                Some(witness) => witness,
+                None => panic!(
+                    "commitment `{:?}` at position `{:?}` must be witnessed because it is indexed",
+                    commitment, position
+                ),
+            };
+            debug_assert_eq!(leaf, Hash::of(commitment));
+
+            Some((
+                position,
+                Proof(crate::internal::proof::Proof {
+                    position: index,
+                    auth_path,
+                    leaf: commitment,
+                }),
+            ))
+        })
+    }
+
     /// Forget about the witness for the given [`Commitment`].
     ///
     /// Returns `true` if the commitment was previously witnessed (and now is forgotten), and `false` if
@@ -236,12 +304,53 @@ impl Tree {
         forgotten
     }
 
+    /// Forget about the witness for every [`Commitment`] for which the predicate returns `false`,
+    /// keeping the rest.
+    ///
+    /// This is equivalent to calling [`forget`](Tree::forget) for each rejected commitment, such as
+    /// all the notes a wallet has spent, but visits the index only once.
+    pub fn retain(&mut self, mut keep: impl FnMut(Position, Commitment) -> bool) {
+        let inner = &mut self.inner;
+        self.index.retain(|&commitment, &mut within_epoch| {
+            if keep(Position(within_epoch), commitment) {
+                true
+            } else {
+                // Forget the index for this element in the tree
+                let forgotten = inner.forget(within_epoch);
+                debug_assert!(forgotten);
+                false
+            }
+        });
+    }
+
     /// Get the position in this [`Tree`] of the given [`Commitment`], if it is currently witnessed.
+    ///
+    /// This is a lookup in the index the tree already maintains for [`witness`](Tree::witness) and
+    /// [`forget`](Tree::forget), so there is no need to keep a separate map from commitments to
+    /// positions alongside the tree. Commitments inserted with [`Witness::Forget`], or since
+    /// forgotten, have no position.
     pub fn position_of(&self, commitment: impl Into<Commitment>) -> Option<Position> {
         let commitment = commitment.into();
         self.index.get(&commitment).map(|index| Position(*index))
     }
 
+    /// Iterate over every [`Commitment`] currently witnessed in this [`Tree`], along with its
+    /// [`Position`].
+    ///
+    /// The commitments are not returned in any particular order.
+    pub fn commitments(&self) -> impl Iterator<Item = (Position, Commitment)> + '_ {
+        self.index
+            .iter()
+            .map(|(&commitment, &index)| (Position(index), commitment))
+    }
+
+    /// Iterate over the [`Position`] of every [`Commitment`] currently witnessed in this [`Tree`].
+    ///
+    /// The positions are not returned in any particular order.
+    pub fn positions(&self) -> impl Iterator<Item = Position> + '_ {
+        self.index.values().map(|&index| Position(index))
+    }
+
     /// Add a new [`Block`] all at once to the most recently inserted [`Epoch`] of this [`Tree`].
     ///
     /// This function can be called on anything that implements `Into<block::Finalized>`; in
@@ -272,6 +381,13 @@ impl Tree {
             return Err(InsertBlockError::Full(block::Finalized { inner, index }));
         }
 
+        // A compact tree never witnesses anything, so keeps only the root of the block
+        let (inner, index) = if self.compact {
+            (Insert::Hash(inner.hash()), HashedMap::default())
+        } else {
+            (inner, index)
+        };
+
         // Convert the top level inside of the block to a tier that can be slotted into the epoch
         let inner = match inner {
             Insert::Keep(inner) => inner.into(),
@@ -326,6 +442,8 @@ impl Tree {
             }
         }
 
+        self.record_anchor();
+
         Ok(self)
     }
 
@@ -348,6 +466,8 @@ impl Tree {
             self.insert_block(block::Finalized::default())?;
         };
 
+        self.record_anchor();
+
         Ok(self)
     }
 
@@ -389,6 +509,13 @@ impl Tree {
             return Err(InsertEpochError(epoch::Finalized { inner, index }));
         }
 
+        // A compact tree never witnesses anything, so keeps only the root of the epoch
+        let (inner, index) = if self.compact {
+            (Insert::Hash(inner.hash()), HashedMap::default())
+        } else {
+            (inner, index)
+        };
+
         // Convert the top level inside of the epoch to a tier that can be slotted into the tree
         let inner = match inner {
             Insert::Keep(inner) => inner.into(),
@@ -430,6 +557,8 @@ impl Tree {
             }
         }
 
+        self.record_anchor();
+
         Ok(self)
     }
 
@@ -450,9 +579,142 @@ impl Tree {
             self.insert_epoch(epoch::Finalized::default())?;
         };
 
+        self.record_anchor();
+
+        Ok(self)
+    }
+
+    /// Discard everything inserted into this [`Tree`] at or after the given [`Position`], rolling
+    /// it back to the state it was in at that position, such as after following a short reorg or
+    /// ingesting a bad block.
+    ///
+    /// The position should be one which the tree previously had, such as its
+    /// [`position`](Tree::position) before the discarded blocks were inserted. If the position is
+    /// the start of a block or epoch, the truncated tree has ended the one before it.
+    ///
+    /// Commitments before the position which were forgotten after the tree was at that position
+    /// remain forgotten.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TruncateError`] without modifying the tree if the position is after the current
+    /// position of the tree, or if a subtree straddling the position has been pruned to its hash
+    /// (because every commitment in it was forgotten), so that the frontier of the tree at the
+    /// position can't be rebuilt.
+    pub fn truncate_to(&mut self, position: Position) -> Result<&mut Self, TruncateError> {
+        let truncate_to = u64::from(position);
+        if let Some(current) = self.position() {
+            if truncate_to > u64::from(current) {
+                return Err(TruncateError::Ahead { position });
+            } else if truncate_to == u64::from(current) {
+                return Ok(self);
+            }
+        }
+
+        // Keep only the points which lie entirely before the position, and rebuild the tree's
+        // frontier at that position from them
+        let points = storage::points_since(self, Position::from(0))
+            .into_iter()
+            .filter(|point| {
+                let (start, height) = match *point {
+                    storage::Point::Hash {
+                        position, height, ..
+                    } => (position, height),
+                    storage::Point::Commitment { position, .. } => (position, 0),
+                };
+                u64::from(start) + storage::width(height) <= truncate_to
+            });
+
+        let mut truncated =
+            storage::from_points(Some(position), points).map_err(|error| match error {
+                DeserializeError::Missing(pruned) => TruncateError::Pruned {
+                    position,
+                    pruned: pruned.into(),
+                },
+                error => unreachable!("points of a tree can't be invalid: {}", error),
+            })?;
+
+        // Keep the history of roots up to the position
+        truncated.anchors = std::mem::take(&mut self.anchors);
+        truncated.anchors.truncate(position);
+        truncated.compact = self.compact;
+        *self = truncated;
+
         Ok(self)
     }
 
+    /// Remember the roots of this [`Tree`] at the end of each of its most recent `capacity` blocks,
+    /// so that they can be checked by [`is_valid_anchor`](Tree::is_valid_anchor) and looked up by
+    /// [`root_at`](Tree::root_at).
+    ///
+    /// By default, no past roots are remembered. Setting the capacity to zero disables the history
+    /// again, and lowering it forgets the oldest roots beyond the new capacity. Roots are only
+    /// remembered from when the history is enabled, and are not persisted by the encodings in
+    /// [`storage`](crate::storage).
+    ///
+    /// While the history is enabled, the root is computed whenever a block ends, so that hashing
+    /// is done then rather than when the root is next requested.
+    pub fn set_anchor_history(&mut self, capacity: usize) {
+        self.anchors.set_capacity(capacity);
+    }
+
+    /// Make this [`Tree`] compact, so that it never witnesses any commitments, or make it witness
+    /// commitments again.
+    ///
+    /// A compact tree keeps only what's needed to compute its root as commitments are inserted:
+    /// every commitment is inserted as if with [`Witness::Forget`], and every block or epoch
+    /// inserted all at once is collapsed to its root, so each complete subtree of the tree is
+    /// pruned to its hash as soon as it is complete, and only the frontier remains. This is suited
+    /// to consumers like a full node, which track the evolving root but never need to
+    /// [`witness`](Tree::witness) a commitment, as opposed to a wallet.
+    ///
+    /// Making a tree compact forgets every commitment it currently witnesses. Making it no longer
+    /// compact doesn't restore them, but commitments inserted afterwards are witnessed as usual.
+    pub fn set_compact(&mut self, compact: bool) {
+        self.compact = compact;
+        if compact {
+            self.retain(|_, _| false);
+        }
+    }
+
+    /// Whether this [`Tree`] is [`compact`](Tree::set_compact), never witnessing any commitments.
+    pub fn is_compact(&self) -> bool {
+        self.compact
+    }
+
+    /// Check whether the given [`Root`] is a valid anchor for this [`Tree`]: either its current
+    /// root, or its root at the end of one of the blocks remembered by its
+    /// [`anchor history`](Tree::set_anchor_history).
+    pub fn is_valid_anchor(&self, root: Root) -> bool {
+        self.anchors.contains(root) || self.root() == root
+    }
+
+    /// Get the [`Root`] of this [`Tree`] when it was at the given [`Position`], if that is its
+    /// current position or the end of one of the blocks remembered by its
+    /// [`anchor history`](Tree::set_anchor_history).
+    pub fn root_at(&self, position: Position) -> Option<Root> {
+        if self.position() == Some(position) {
+            Some(self.root())
+        } else {
+            self.anchors.root_at(position)
+        }
+    }
+
+    /// The anchor history of this [`Tree`], for [`validate`](crate::validate) to check.
+    pub(crate) fn anchors(&self) -> &Anchors {
+        &self.anchors
+    }
+
+    /// Remember the current root in the anchor history, if it is enabled.
+    fn record_anchor(&mut self) {
+        if self.anchors.is_enabled() {
+            if let Some(position) = self.position() {
+                let root = self.root();
+                self.anchors.record(position, root);
+            }
+        }
+    }
+
     /// Get the root hash of the most recent [`Epoch`] in this [`Tree`].
     ///
     /// If the [`Tree`] is empty, returns `None`.
@@ -492,8 +754,323 @@ impl Tree {
         self.index.len()
     }
 
+    /// Write a [Graphviz](https://graphviz.org/) graph of the structure of this [`Tree`] to the
+    /// writer, in the DOT language.
+    ///
+    /// The graph shows the tiers of the tree, its frontier (in bold), its witnessed leaves, and the
+    /// subtrees which have been pruned to their hash (dotted). It can be rendered using, for
+    /// example, `dot -Tsvg`.
+    pub fn render_dot(&self, writer: impl std::io::Write) -> std::io::Result<()> {
+        crate::dot::render(self, writer)
+    }
+
+    /// Compare this [`Tree`] with another, such as a wallet's tree with a full node's, or a tree
+    /// with its deserialized copy: which commitments each witnesses that the other doesn't, and
+    /// which of their subtrees have different hashes.
+    ///
+    /// The subtrees whose hashes can be compared are those whose hashes are known without
+    /// recomputing them: the whole tree, pruned subtrees, witnessed commitments, and the siblings
+    /// along the authentication path of every witnessed commitment. The more commitments two trees
+    /// witness, the more precisely their differences can be located.
+    ///
+    /// This witnesses every commitment in both trees, so takes time proportional to their size.
+    pub fn diff(&self, other: &Tree) -> crate::TreeDiff {
+        crate::diff::diff(self, other)
+    }
+
+    /// Get statistics about the size of this [`Tree`] in memory: how many commitments it
+    /// witnesses, how many nodes and pruned hashes it stores, how many hashes are cached, and an
+    /// estimate of how much heap memory it occupies.
+    ///
+    /// This visits every node of the tree, so takes time proportional to its size.
+    pub fn size_stats(&self) -> SizeStats {
+        let mut stats = SizeStats {
+            witnessed: self.index.len(),
+            heap_bytes: self.index.capacity()
+                // Each entry in the index also has a byte of control metadata
+                * (std::mem::size_of::<(Commitment, index::within::Tree)>() + 1),
+            ..SizeStats::default()
+        };
+        self.inner.measure(&mut stats);
+        stats
+    }
+
     /// Check whether this [`Tree`] is empty.
     pub fn is_empty(&self) -> bool {
         self.inner.is_empty()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use decaf377::Fq;
+
+    use super::*;
+
+    fn commitment(i: u64) -> Commitment {
+        Commitment(Fq::from(i))
+    }
+
+    #[test]
+    fn truncate_to_previous_positions() {
+        let mut tree = Tree::new();
+        let mut history = Vec::new();
+
+        for i in 0..60 {
+            history.push((tree.position().unwrap(), tree.root(), tree.clone()));
+            let witness = if i % 2 == 0 {
+                Witness::Keep
+            } else {
+                Witness::Forget
+            };
+            tree.insert(witness, commitment(i)).unwrap();
+            if i % 10 == 9 {
+                tree.end_block().unwrap();
+            }
+            if i % 30 == 29 {
+                tree.end_epoch().unwrap();
+            }
+        }
+
+        for (position, root, before) in history {
+            let mut truncated = tree.clone();
+            truncated.truncate_to(position).unwrap();
+            assert_eq!(truncated.position(), Some(position));
+            assert_eq!(truncated.root(), root);
+            assert_eq!(truncated.witnessed_count(), before.witnessed_count());
+            for i in 0..60 {
+                assert_eq!(
+                    truncated.witness(commitment(i)),
+                    before.witness(commitment(i))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn truncate_to_ahead() {
+        let mut tree = Tree::new();
+        tree.insert(Witness::Keep, commitment(0)).unwrap();
+        assert_eq!(
+            tree.truncate_to(Position::from(2)).unwrap_err(),
+            TruncateError::Ahead {
+                position: Position::from(2)
+            }
+        );
+    }
+
+    #[test]
+    fn truncate_to_pruned() {
+        let mut tree = Tree::new();
+        for i in 0..5 {
+            tree.insert(Witness::Forget, commitment(i)).unwrap();
+        }
+        assert!(matches!(
+            tree.truncate_to(Position::from(2)),
+            Err(TruncateError::Pruned { .. })
+        ));
+    }
+
+    #[test]
+    fn size_stats() {
+        assert_eq!(Tree::new().size_stats(), SizeStats::default());
+
+        let mut tree = Tree::new();
+        for i in 0..20 {
+            let witness = if i % 4 == 0 {
+                Witness::Keep
+            } else {
+                Witness::Forget
+            };
+            tree.insert(witness, commitment(i)).unwrap();
+        }
+
+        let stats = tree.size_stats();
+        assert_eq!(stats.witnessed, 5);
+        assert!(stats.nodes > 0);
+        assert!(stats.pruned > 0);
+        assert!(stats.heap_bytes > 0);
+
+        // Hashes are computed lazily, so evaluating them caches more
+        tree.evaluate_hashes();
+        assert!(tree.size_stats().cached_hashes > stats.cached_hashes);
+    }
+
+    #[test]
+    fn anchor_history() {
+        let mut tree = Tree::new();
+        tree.set_anchor_history(2);
+
+        let mut roots = Vec::new();
+        for i in 0..3 {
+            tree.insert(Witness::Keep, commitment(i)).unwrap();
+            tree.end_block().unwrap();
+            roots.push((tree.position().unwrap(), tree.root()));
+        }
+        tree.insert(Witness::Keep, commitment(3)).unwrap();
+
+        // The oldest root has been forgotten, but the two most recent are remembered
+        let (oldest_position, oldest_root) = roots[0];
+        assert!(!tree.is_valid_anchor(oldest_root));
+        assert_eq!(tree.root_at(oldest_position), None);
+        for &(position, root) in &roots[1..] {
+            assert!(tree.is_valid_anchor(root));
+            assert_eq!(tree.root_at(position), Some(root));
+        }
+
+        // The current root is always a valid anchor
+        assert!(tree.is_valid_anchor(tree.root()));
+        assert_eq!(tree.root_at(tree.position().unwrap()), Some(tree.root()));
+
+        // Truncating the tree forgets the roots after the truncation position
+        let (position, root) = roots[1];
+        tree.truncate_to(position).unwrap();
+        assert!(tree.is_valid_anchor(root));
+        assert!(!tree.is_valid_anchor(roots[2].1));
+    }
+
+    #[test]
+    fn witness_batch() {
+        let mut tree = Tree::new();
+        for i in 0..50 {
+            let witness = if i % 5 == 0 {
+                Witness::Forget
+            } else {
+                Witness::Keep
+            };
+            tree.insert(witness, commitment(i)).unwrap();
+            if i % 8 == 7 {
+                tree.end_block().unwrap();
+            }
+        }
+
+        let positions = (0..50)
+            .map(|i| tree.position_of(commitment(i)))
+            .collect::<Vec<_>>();
+        let batch = tree
+            .witness_batch(positions.iter().flatten().copied())
+            .collect::<Vec<_>>();
+
+        // Forgotten commitments have no position, so every requested position is witnessed
+        assert_eq!(batch.len(), 40);
+        for (position, proof) in batch {
+            assert_eq!(proof.position(), position);
+            assert_eq!(Some(proof.clone()), tree.witness(proof.commitment()));
+            proof.verify(tree.root()).unwrap();
+        }
+
+        // Positions which aren't witnessed are skipped
+        let forgotten = tree.position().unwrap();
+        assert_eq!(tree.witness_batch([forgotten]).count(), 0);
+    }
+
+    #[test]
+    fn deserialize_checked() {
+        use bincode::Options;
+
+        fn round_trip(tree: &Tree) -> Result<Tree, bincode::Error> {
+            let options = bincode::DefaultOptions::new();
+            let bytes = options.serialize(tree).unwrap();
+            Tree::deserialize_checked(&mut bincode::Deserializer::from_slice(&bytes, options))
+        }
+
+        let mut tree = Tree::new();
+        tree.set_anchor_history(4);
+        for i in 0..20 {
+            tree.insert(Witness::Keep, commitment(i)).unwrap();
+            if i % 5 == 4 {
+                tree.end_block().unwrap();
+            }
+        }
+        assert_eq!(round_trip(&tree).unwrap().root(), tree.root());
+
+        // The index of a different tree leaves the leaves of this one unindexed
+        let mut other = Tree::new();
+        other.insert(Witness::Keep, commitment(100)).unwrap();
+        let corrupted = Tree {
+            index: other.index.clone(),
+            ..tree.clone()
+        };
+        assert!(round_trip(&corrupted).is_err());
+
+        // The anchor history of a longer tree records roots the tree hasn't reached
+        let mut longer = tree.clone();
+        longer.insert(Witness::Keep, commitment(100)).unwrap();
+        longer.end_block().unwrap();
+        let corrupted = Tree {
+            anchors: longer.anchors.clone(),
+            ..tree.clone()
+        };
+        assert!(round_trip(&corrupted).is_err());
+    }
+
+    #[test]
+    fn compact() {
+        let mut full = Tree::new();
+        let mut compact = Tree::new();
+        compact.set_compact(true);
+
+        for i in 0..100 {
+            full.insert(Witness::Keep, commitment(i)).unwrap();
+            compact.insert(Witness::Keep, commitment(i)).unwrap();
+            if i % 10 == 9 {
+                full.end_block().unwrap();
+                compact.end_block().unwrap();
+            }
+        }
+
+        let mut block = block::Builder::new();
+        block.insert(Witness::Keep, commitment(100)).unwrap();
+        full.insert_block(block.clone()).unwrap();
+        compact.insert_block(block).unwrap();
+
+        // The root is the same, but nothing is witnessed
+        assert_eq!(full.root(), compact.root());
+        assert_eq!(compact.witnessed_count(), 0);
+        assert_eq!(compact.witness(commitment(0)), None);
+        assert_eq!(compact.witness(commitment(100)), None);
+        assert!(compact.size_stats().nodes < full.size_stats().nodes);
+
+        // Making a tree compact forgets everything it witnessed
+        full.set_compact(true);
+        assert_eq!(full.witnessed_count(), 0);
+        assert_eq!(full.root(), compact.root());
+    }
+
+    #[test]
+    fn position_arithmetic() {
+        let position = Position::new(1, 2, 3);
+        assert_eq!(
+            (position.epoch(), position.block(), position.commitment()),
+            (1, 2, 3)
+        );
+        assert_eq!(u64::from(position), (1 << 32) | (2 << 16) | 3);
+        assert_eq!(Position::from(u64::from(position)), position);
+
+        // Successors roll over into the next block and epoch
+        assert_eq!(position.successor(), Some(Position::new(1, 2, 4)));
+        assert_eq!(
+            Position::new(1, 2, u16::MAX).successor(),
+            Some(Position::new(1, 3, 0))
+        );
+        assert_eq!(
+            Position::new(1, u16::MAX, u16::MAX).successor(),
+            Some(Position::new(2, 0, 0))
+        );
+        assert_eq!(Position::MAX.successor(), None);
+
+        // Positions are ordered by epoch, then block, then commitment
+        assert!(Position::new(0, 1, 0) > Position::new(0, 0, u16::MAX));
+        assert!(Position::new(1, 0, 0) > Position::new(0, u16::MAX, u16::MAX));
+
+        assert_eq!(
+            Position::new(0, 1, 0).distance(Position::new(0, 0, 0)),
+            1 << 16
+        );
+        assert_eq!(
+            Position::new(0, 0, 0).distance(Position::new(0, 1, 0)),
+            1 << 16
+        );
+        assert_eq!(position.distance(position), 0);
+    }
+}
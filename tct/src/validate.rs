@@ -0,0 +1,297 @@
+//! Checks of the internal invariants of a [`Tree`], for use in tests and after loading a tree
+//! which was persisted.
+//!
+//! A [`Tree`] built only through its own methods always satisfies these invariants, so a failure
+//! of [`check`] indicates either a bug in this crate, or that a tree was loaded from corrupted
+//! storage (the [`Deserialize`](serde::Deserialize) implementation of a [`Tree`] trusts its input;
+//! use [`Tree::deserialize_checked`] to check a tree as it is deserialized).
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::prelude::*;
+use crate::storage::{self, width};
+
+/// An invariant of a [`Tree`] did not hold, found by [`check`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum InvalidTree {
+    /// A witnessed leaf of the tree has no entry in the tree's index, so it can't be witnessed or
+    /// forgotten.
+    #[error("witnessed leaf at position {position:?} is not indexed")]
+    Unindexed {
+        /// The position of the leaf.
+        position: Position,
+    },
+    /// The tree's index refers to a position which is not a witnessed leaf of the tree.
+    #[error("indexed commitment at position {position:?} is not witnessed in the tree")]
+    Unreachable {
+        /// The position recorded in the index.
+        position: Position,
+    },
+    /// The frontier of the tree doesn't end where the position of the tree says it does.
+    #[error("frontier ends after {frontier} positions, but the tree is at position {tree}")]
+    FrontierPosition {
+        /// The number of positions spanned by the contents of the tree.
+        frontier: u64,
+        /// The position of the tree, or the number of positions in a tree if it is full.
+        tree: u64,
+    },
+    /// The cached root hash of the tree doesn't match the one recomputed from its contents.
+    #[error("cached root hash does not match its contents")]
+    RootHash,
+    /// The cached root hash of the current block or epoch doesn't match the one recomputed from its
+    /// contents.
+    #[error("cached hash of the frontier does not match its contents")]
+    FrontierHash,
+    /// An authentication path from the tree doesn't match the one recomputed from its contents,
+    /// because some cached hash along it is wrong.
+    #[error("authentication path for position {position:?} does not match the tree's contents")]
+    AuthPath {
+        /// The position of the witnessed commitment.
+        position: Position,
+    },
+    /// The anchor history of the tree records a root at a position the tree hasn't reached, out of
+    /// order, beyond its capacity, or which doesn't match the tree's current root at its current
+    /// position.
+    #[error("anchor history entry at position {position:?} is inconsistent with the tree")]
+    Anchor {
+        /// The position of the inconsistent entry.
+        position: Position,
+    },
+    /// Rebuilding the tree from its contents failed.
+    #[error("could not rebuild tree from its contents: {0}")]
+    Malformed(#[from] DeserializeError),
+}
+
+/// Check the internal invariants of a [`Tree`]:
+///
+/// - every witnessed leaf is indexed, and every indexed commitment is witnessed at the position
+///   the index records,
+/// - the frontier ends at the position of the tree, and
+/// - every cached hash (including the root, and the hashes along every authentication path)
+///   matches the hash recomputed from the contents of the tree, and
+/// - the anchor history is in order, within its capacity, and ends no later than the position of
+///   the tree (with the current root, if it ends at that position).
+///
+/// Roots in the anchor history from before the current position can't be recomputed, since the
+/// tree may have forgotten the commitments they depend on, so they are not checked.
+///
+/// This rebuilds a copy of the whole tree to recompute its hashes, so takes time and memory
+/// proportional to the size of the tree.
+///
+/// # Errors
+///
+/// Returns the first [`InvalidTree`] found, if any invariant doesn't hold.
+pub fn check(tree: &Tree) -> Result<(), InvalidTree> {
+    let mut checker = Checker::default();
+    tree.encode(0, &mut checker);
+
+    if let Some(error) = checker.error {
+        return Err(error);
+    }
+    if let Some(&position) = checker.indexed.keys().min() {
+        return Err(InvalidTree::Unreachable {
+            position: position.into(),
+        });
+    }
+
+    let position = tree.position().map(u64::from).unwrap_or_else(|| {
+        let top = <Tree as Height>::Height::HEIGHT;
+        width(top)
+    });
+    if checker.end != position {
+        return Err(InvalidTree::FrontierPosition {
+            frontier: checker.end,
+            tree: position,
+        });
+    }
+
+    // Rebuilding the tree from its encoding, which contains no cached hashes, recomputes them all
+    let rebuilt = storage::deserialize(&storage::serialize(tree))?;
+
+    if tree.root() != rebuilt.root() {
+        return Err(InvalidTree::RootHash);
+    }
+    if tree.current_block_root() != rebuilt.current_block_root()
+        || tree.current_epoch_root() != rebuilt.current_epoch_root()
+    {
+        return Err(InvalidTree::FrontierHash);
+    }
+    for (position, commitment) in tree.commitments() {
+        if tree.witness(commitment) != rebuilt.witness(commitment) {
+            return Err(InvalidTree::AuthPath { position });
+        }
+    }
+
+    check_anchors(tree, position)
+}
+
+/// Check the anchor history of a tree whose contents are already known to be valid, and which is
+/// at the given position.
+fn check_anchors(tree: &Tree, position: u64) -> Result<(), InvalidTree> {
+    let anchors = tree.anchors();
+    let mut previous = None;
+
+    for (index, (anchor, root)) in anchors.iter().enumerate() {
+        let anchor_position = u64::from(anchor);
+        if index >= anchors.capacity()
+            || previous.map_or(false, |previous| anchor_position <= previous)
+            || anchor_position > position
+            || (anchor_position == position && root != tree.root())
+        {
+            return Err(InvalidTree::Anchor { position: anchor });
+        }
+        previous = Some(anchor_position);
+    }
+
+    Ok(())
+}
+
+/// An [`Encoder`] which checks the structure of a tree as it is visited.
+#[derive(Default)]
+struct Checker {
+    /// The commitments in the index which haven't yet been visited, keyed by their position.
+    indexed: HashMap<u64, Commitment>,
+    /// The position after the last leaf or pruned subtree visited.
+    end: u64,
+    /// The first error found.
+    error: Option<InvalidTree>,
+}
+
+impl Checker {
+    fn visit(&mut self, position: u64, height: u8) {
+        self.end = position + width(height);
+    }
+}
+
+impl Encoder for Checker {
+    fn index(&mut self, commitments: impl IntoIterator<Item = (Commitment, u64)>) {
+        self.indexed.extend(
+            commitments
+                .into_iter()
+                .map(|(commitment, position)| (position, commitment)),
+        );
+    }
+
+    fn hash(&mut self, position: u64, height: u8, _hash: Hash) {
+        self.visit(position, height);
+    }
+
+    fn subtree(&mut self, _position: u64, _height: u8) -> bool {
+        // Stop visiting once an error is found, since the rest of the tree can't be trusted
+        self.error.is_none()
+    }
+
+    fn commitment(&mut self, position: u64) {
+        self.visit(position, 0);
+        if self.indexed.remove(&position).is_none() {
+            self.error = Some(InvalidTree::Unindexed {
+                position: position.into(),
+            });
+        }
+    }
+
+    fn siblings(&mut self, _position: u64, _height: u8, _siblings: u8) {}
+
+    fn finalized(&mut self, _position: u64, _height: u8, _finalized: bool) {}
+
+    fn empty(&mut self, _empty: bool) {}
+}
+
+#[cfg(test)]
+mod test {
+    use hash_hasher::HashedMap;
+
+    use super::*;
+    use crate::Witness;
+
+    fn commitment(i: u64) -> Commitment {
+        Commitment(poseidon377::Fq::from(i))
+    }
+
+    #[test]
+    fn empty_is_valid() {
+        check(&Tree::new()).unwrap();
+    }
+
+    #[test]
+    fn built_trees_are_valid() {
+        let mut tree = Tree::new();
+        for i in 0..100 {
+            let witness = if i % 3 == 0 {
+                Witness::Keep
+            } else {
+                Witness::Forget
+            };
+            tree.insert(witness, commitment(i)).unwrap();
+            if i % 10 == 9 {
+                tree.end_block().unwrap();
+            }
+            if i % 40 == 39 {
+                tree.end_epoch().unwrap();
+            }
+            check(&tree).unwrap();
+        }
+
+        for i in (0..100).step_by(6) {
+            tree.forget(commitment(i));
+            check(&tree).unwrap();
+        }
+    }
+
+    /// A [`Decoder`] for a tree with a single witnessed commitment at position zero, with the given
+    /// index.
+    struct OneLeaf(HashedMap<Commitment, index::within::Tree>);
+
+    impl Decoder for OneLeaf {
+        fn take_index(&mut self) -> HashedMap<Commitment, index::within::Tree> {
+            std::mem::take(&mut self.0)
+        }
+
+        fn hash(&mut self, _position: u64, _height: u8) -> Result<Option<Hash>, DeserializeError> {
+            Ok(None)
+        }
+
+        fn commitment(&mut self, _position: u64) -> Result<Hash, DeserializeError> {
+            Ok(Hash::of(commitment(0)))
+        }
+
+        fn siblings(&mut self, _position: u64, _height: u8) -> Result<u8, DeserializeError> {
+            Ok(0)
+        }
+
+        fn finalized(&mut self, _position: u64, _height: u8) -> Result<bool, DeserializeError> {
+            Ok(false)
+        }
+
+        fn empty(&mut self) -> Result<bool, DeserializeError> {
+            Ok(false)
+        }
+    }
+
+    #[test]
+    fn unindexed_leaf() {
+        let tree = Tree::decode(0, &mut OneLeaf(HashedMap::default())).unwrap();
+        assert_eq!(
+            check(&tree),
+            Err(InvalidTree::Unindexed {
+                position: Position::from(0)
+            })
+        );
+    }
+
+    #[test]
+    fn unreachable_index_entry() {
+        let mut index = HashedMap::default();
+        index.insert(commitment(0), 0u64.into());
+        index.insert(commitment(1), 1u64.into());
+        let tree = Tree::decode(0, &mut OneLeaf(index)).unwrap();
+        assert_eq!(
+            check(&tree),
+            Err(InvalidTree::Unreachable {
+                position: Position::from(1)
+            })
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! Verification of inclusion proofs from their raw parts, without constructing a [`Tree`] or a
+//! [`Proof`].
+//!
+//! A consumer holding only a [`Commitment`], its [`Position`], an authentication path of field
+//! elements, and an anchor [`Root`] (such as a circuit gadget's test harness, or a light client)
+//! can check inclusion using [`verify`]. The computation here uses no allocation or collections,
+//! only the hash function itself, so it mirrors exactly what a circuit must compute.
+//!
+//! This module is available without the `std` feature, for use under `no_std`.
+
+use poseidon377::Fq;
+
+use crate::{
+    internal::{hash::Hash, path::WhichWay},
+    Commitment, Position, Root, VerifyError,
+};
+#[cfg(all(doc, feature = "std"))]
+use crate::{Proof, Tree};
+
+/// The number of levels in the authentication path of a [`Commitment`] in a [`Tree`].
+pub const DEPTH: usize = 24;
+
+/// Compute the [`Root`] of a tree in which the given [`Commitment`] is at the given [`Position`],
+/// using its authentication path.
+///
+/// The authentication path is ordered from root to leaf, as returned by [`Proof::auth_path`]: each
+/// element is the three sibling hashes at that level, in left-to-right order, omitting the node on
+/// the path itself.
+pub fn root(commitment: Commitment, position: Position, auth_path: &[[Fq; 3]; DEPTH]) -> Root {
+    let position: u64 = position.into();
+    let mut hash = Hash::of(commitment);
+
+    // Walk up the path from the leaf, so the siblings are visited in reverse order
+    for (siblings, height) in auth_path.iter().rev().zip(1..=DEPTH as u8) {
+        let (which_way, _) = WhichWay::at(height, position);
+        let [a, b, c, d] = which_way.insert(hash, siblings.map(Hash::new));
+        hash = Hash::node(height, a, b, c, d);
+    }
+
+    Root(hash)
+}
+
+/// Verify that the given [`Commitment`] is at the given [`Position`] in the tree with the given
+/// [`Root`], using its authentication path (ordered as for [`root`]).
+///
+/// This is equivalent to [`Proof::verify`], but takes the parts of the proof directly.
+///
+/// # Errors
+///
+/// Returns [`VerifyError`] if the authentication path does not lead to that [`Root`].
+pub fn verify(
+    commitment: Commitment,
+    position: Position,
+    auth_path: &[[Fq; 3]; DEPTH],
+    root: Root,
+) -> Result<(), VerifyError> {
+    if self::root(commitment, position, auth_path) == root {
+        Ok(())
+    } else {
+        Err(VerifyError { root: root.0 })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Proof, Tree, Witness};
+
+    fn commitment(i: u64) -> Commitment {
+        Commitment(Fq::from(i))
+    }
+
+    fn raw_auth_path(proof: &Proof) -> [[Fq; 3]; DEPTH] {
+        proof.auth_path().map(|siblings| (*siblings).map(Fq::from))
+    }
+
+    fn example() -> Tree {
+        let mut tree = Tree::new();
+        for i in 0..50 {
+            tree.insert(Witness::Keep, commitment(i)).unwrap();
+            if i % 7 == 6 {
+                tree.end_block().unwrap();
+            }
+            if i % 20 == 19 {
+                tree.end_epoch().unwrap();
+            }
+        }
+        tree
+    }
+
+    #[test]
+    fn verifies_witnessed_proofs() {
+        let tree = example();
+        for i in 0..50 {
+            let proof = tree.witness(commitment(i)).unwrap();
+            let auth_path = raw_auth_path(&proof);
+            assert_eq!(
+                root(proof.commitment(), proof.position(), &auth_path),
+                tree.root()
+            );
+            verify(
+                proof.commitment(),
+                proof.position(),
+                &auth_path,
+                tree.root(),
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_proofs() {
+        let tree = example();
+        let proof = tree.witness(commitment(10)).unwrap();
+        let auth_path = raw_auth_path(&proof);
+
+        // Wrong commitment
+        assert!(verify(commitment(11), proof.position(), &auth_path, tree.root()).is_err());
+
+        // Wrong position
+        let position = tree.position_of(commitment(11)).unwrap();
+        assert!(verify(proof.commitment(), position, &auth_path, tree.root()).is_err());
+
+        // Tampered authentication path
+        let mut tampered = auth_path;
+        tampered[DEPTH - 1][0] += Fq::from(1u64);
+        assert!(verify(proof.commitment(), proof.position(), &tampered, tree.root()).is_err());
+    }
+}
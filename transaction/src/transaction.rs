@@ -6,13 +6,13 @@ use bytes::Bytes;
 use decaf377::FieldExt;
 use penumbra_crypto::{
     merkle,
-    rdsa::{Binding, Signature, VerificationKey, VerificationKeyBytes},
+    rdsa::{Binding, Signature, SpendAuth, VerificationKey, VerificationKeyBytes},
     Fr, Nullifier, Value, STAKING_TOKEN_ASSET_ID,
 };
 use penumbra_proto::{ibc as pb_ibc, stake as pbs, transaction as pbt, Message, Protobuf};
 
 use crate::{
-    action::{output, Delegate, Undelegate},
+    action::{output, Delegate, Spend, Undelegate},
     Action,
 };
 
@@ -148,6 +148,52 @@ impl Transaction {
         self.transaction_body.clone()
     }
 
+    pub fn spends(&self) -> impl Iterator<Item = &Spend> {
+        self.actions().filter_map(|action| {
+            if let Action::Spend(s) = action {
+                Some(s)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns the spend authorization randomizer of each spend, in order, for authorizing
+    /// spends added with [`Builder::add_unauthorized_spend`].
+    pub fn spend_auth_randomizers(&self) -> Vec<Fr> {
+        self.spends()
+            .map(|spend| spend.body.proof.spend_auth_randomizer)
+            .collect()
+    }
+
+    /// Fills in the spend authorization signature of the `index`th spend, which must verify
+    /// under the spend's randomized verification key.
+    pub fn authorize_spend(
+        &mut self,
+        index: usize,
+        auth_sig: Signature<SpendAuth>,
+    ) -> anyhow::Result<()> {
+        let sighash = self.transaction_body.sighash();
+        let spend = self
+            .transaction_body
+            .actions
+            .iter_mut()
+            .filter_map(|action| match action {
+                Action::Spend(spend) => Some(spend),
+                _ => None,
+            })
+            .nth(index)
+            .ok_or_else(|| anyhow::anyhow!("transaction has no spend {}", index))?;
+
+        spend
+            .body
+            .rk
+            .verify(&sighash, &auth_sig)
+            .map_err(|_| anyhow::anyhow!("signature does not authorize spend {}", index))?;
+        spend.auth_sig = auth_sig;
+        Ok(())
+    }
+
     pub fn binding_sig(&self) -> &Signature<Binding> {
         &self.binding_sig
     }
@@ -3,7 +3,7 @@ use std::ops::Deref;
 use ark_ff::{UniformRand, Zero};
 use incrementalmerkletree::Tree;
 use penumbra_crypto::{
-    keys::{OutgoingViewingKey, SpendKey},
+    keys::{FullViewingKey, OutgoingViewingKey, SpendKey},
     memo::MemoPlaintext,
     merkle::{self, NoteCommitmentTree},
     rdsa::{Binding, Signature, SigningKey, SpendAuth},
@@ -22,7 +22,9 @@ use crate::{
 pub struct Builder {
     /// List of spends. We store the spend key and body rather than a Spend
     /// so we can defer signing until the complete transaction is ready.
-    pub spends: Vec<(SigningKey<SpendAuth>, spend::Body)>,
+    ///
+    /// Spends without a key are left unsigned, to be authorized later.
+    pub spends: Vec<(Option<SigningKey<SpendAuth>>, spend::Body)>,
     /// List of outputs in the transaction.
     pub outputs: Vec<Output>,
     /// List of delegations in the transaction.
@@ -55,6 +57,54 @@ impl Builder {
         note_commitment_tree: &NoteCommitmentTree,
         spend_key: &SpendKey,
         note: Note,
+    ) -> Result<&mut Self, anyhow::Error> {
+        let spend_auth_randomizer = Fr::rand(rng);
+        let rsk = spend_key.spend_auth_key().randomize(&spend_auth_randomizer);
+
+        self.push_spend(
+            rng,
+            note_commitment_tree,
+            spend_key.full_viewing_key(),
+            spend_auth_randomizer,
+            Some(rsk),
+            note,
+        )
+    }
+
+    /// Create a new `Spend` of a note controlled by `fvk`, whose spend authorization signature is
+    /// left blank, for spends authorized by a key the builder doesn't hold (such as a threshold
+    /// key).
+    ///
+    /// Once the transaction is finalized, the signature for the spend is made over its
+    /// [`sighash`](crate::TransactionBody::sighash) with the spend authorization key randomized by
+    /// its [`spend_auth_randomizers`](Transaction::spend_auth_randomizers) entry, and filled in
+    /// with [`Transaction::authorize_spend`].
+    pub fn add_unauthorized_spend<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        note_commitment_tree: &NoteCommitmentTree,
+        fvk: &FullViewingKey,
+        note: Note,
+    ) -> Result<&mut Self, anyhow::Error> {
+        let spend_auth_randomizer = Fr::rand(rng);
+        self.push_spend(
+            rng,
+            note_commitment_tree,
+            fvk,
+            spend_auth_randomizer,
+            None,
+            note,
+        )
+    }
+
+    fn push_spend<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        note_commitment_tree: &NoteCommitmentTree,
+        fvk: &FullViewingKey,
+        spend_auth_randomizer: Fr,
+        rsk: Option<SigningKey<SpendAuth>>,
+        note: Note,
     ) -> Result<&mut Self, anyhow::Error> {
         let merkle_path = note_commitment_tree
             .authentication_path(&note.commit())
@@ -74,17 +124,14 @@ impl Builder {
             Fr::from(note.value().amount) * note.value().asset_id.value_generator();
         self.value_commitments += value_commitment.0;
 
-        let spend_auth_randomizer = Fr::rand(rng);
-        let rsk = spend_key.spend_auth_key().randomize(&spend_auth_randomizer);
-
         let body = spend::Body::new(
             value_commitment,
-            *spend_key.spend_auth_key(),
+            *fvk.spend_verification_key(),
             spend_auth_randomizer,
             merkle_path,
             note,
             v_blinding,
-            *spend_key.nullifier_key(),
+            *fvk.nullifier_key(),
         );
 
         self.spends.push((rsk, body));
@@ -301,9 +348,12 @@ impl Builder {
         // so we can compute the sighash value....
         let sighash = transaction_body.sighash();
 
-        // and use it to fill in the spendauth sigs...
+        // and use it to fill in the spendauth sigs we have keys for...
         for i in 0..self.spends.len() {
-            let (rsk, _) = self.spends[i];
+            let rsk = match self.spends[i].0 {
+                Some(rsk) => rsk,
+                None => continue,
+            };
             if let Action::Spend(Spend {
                 ref mut auth_sig, ..
             }) = transaction_body.actions[i]
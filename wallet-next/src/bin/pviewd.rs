@@ -0,0 +1,108 @@
+#![allow(clippy::clone_on_copy)]
+use anyhow::{Context, Result};
+use penumbra_crypto::FullViewingKey;
+use penumbra_proto::{
+    client::oblivious::oblivious_query_client::ObliviousQueryClient,
+    wallet::wallet_protocol_server::WalletProtocolServer,
+};
+use penumbra_wallet_next::{Storage, WalletService};
+use std::env;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use tonic::transport::Server;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "pviewd",
+    about = "The Penumbra view service daemon, which scans the chain with a full viewing key and serves the results to wallets.",
+    version = env!("VERGEN_GIT_SEMVER"),
+)]
+struct Opt {
+    /// Command to run.
+    #[structopt(subcommand)]
+    cmd: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Create the state database for a full viewing key.
+    Init {
+        /// The path used to store the SQLite state database.
+        #[structopt(short, long)]
+        sqlite_path: PathBuf,
+        /// The full viewing key to scan with.
+        #[structopt(short, long)]
+        fvk: String,
+    },
+    /// Start running the view service daemon.
+    Start {
+        /// The path of the SQLite state database created by `pviewd init`.
+        #[structopt(short, long)]
+        sqlite_path: PathBuf,
+        /// Bind the services to this host.
+        #[structopt(long, default_value = "127.0.0.1")]
+        host: String,
+        /// Bind the view service gRPC server to this port.
+        #[structopt(short, long, default_value = "8081")]
+        wallet_port: u16,
+        /// The address of the pd+tendermint node.
+        #[structopt(short, long, default_value = "testnet.penumbra.zone")]
+        node: String,
+        /// The port to use to speak to pd.
+        #[structopt(short, long, default_value = "8080")]
+        pd_port: u16,
+    },
+}
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+    let opt = Opt::from_args();
+
+    match opt.cmd {
+        Command::Init { sqlite_path, fvk } => {
+            let fvk = fvk
+                .parse::<FullViewingKey>()
+                .context("invalid full viewing key")?;
+
+            let storage = Storage::load(&sqlite_path).await?;
+            storage.initialize(&fvk).await?;
+
+            println!(
+                "Initialized view service database at {}",
+                sqlite_path.display()
+            );
+        }
+        Command::Start {
+            sqlite_path,
+            host,
+            wallet_port,
+            node,
+            pd_port,
+        } => {
+            tracing::info!(?host, ?wallet_port, ?node, ?pd_port, "starting pviewd");
+
+            if !sqlite_path.exists() {
+                return Err(anyhow::anyhow!(
+                    "no database at {}, create one with `pviewd init`",
+                    sqlite_path.display()
+                ));
+            }
+            let storage = Storage::load(&sqlite_path).await?;
+
+            let client =
+                ObliviousQueryClient::connect(format!("http://{}:{}", node, pd_port)).await?;
+            let service = WalletService::new(storage, client).await?;
+
+            Server::builder()
+                .add_service(WalletProtocolServer::new(service))
+                .serve(
+                    format!("{}:{}", host, wallet_port)
+                        .parse()
+                        .context("invalid bind address")?,
+                )
+                .await?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,10 +1,20 @@
-use std::pin::Pin;
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
 
+use penumbra_crypto::{
+    asset,
+    keys::{DiversifierIndex, FullViewingKeyHash},
+    merkle::{AuthPath, Tree, TreeExt},
+    note, Value,
+};
 use penumbra_proto::{
     client::oblivious::oblivious_query_client::ObliviousQueryClient,
     crypto as pbc,
     wallet::{self as pb, wallet_protocol_server::WalletProtocol},
 };
+use tokio::sync::watch;
 use tonic::{async_trait, transport::Channel};
 
 use crate::{Storage, Worker};
@@ -20,9 +30,12 @@ use crate::{Storage, Worker};
 #[derive(Clone)]
 pub struct WalletService {
     storage: Storage,
-    // TODO: add a way for the WalletService to poll the state of its worker task, to determine if it failed
-    // this probably looks like a handle for the task, wrapped in an arc'd mutex or something
-    // error_slot: Arc<Mutex<Option<anyhow::Error>>>,
+    /// The hash of the full viewing key the storage was initialized with.
+    fvk_hash: FullViewingKeyHash,
+    /// The error the worker task failed with, if it has failed.
+    error_slot: Arc<Mutex<Option<anyhow::Error>>>,
+    /// The latest block height reported by the node to the worker.
+    chain_height: watch::Receiver<u64>,
     // TODO: add a way for the WalletService to signal the worker that it should shut down
     // this probably looks like an Arc<oneshot::Sender<()>> or something,
     // where the receiver is held by the worker and the worker checks if it's closed (=> all sender handles dropped)
@@ -36,22 +49,50 @@ impl WalletService {
     /// To create multiple [`WalletService`]s, clone the [`WalletService`] returned
     /// by this method, rather than calling it multiple times.  That way, each clone
     /// will be backed by the same scanning task, rather than each spawning its own.
-    pub fn new(storage: Storage, client: ObliviousQueryClient<Channel>) -> Self {
-        let worker = Worker::new(storage.clone(), client);
-        tokio::spawn(worker.run());
+    pub async fn new(
+        storage: Storage,
+        client: ObliviousQueryClient<Channel>,
+    ) -> Result<Self, anyhow::Error> {
+        let fvk_hash = storage.full_viewing_key().await?.hash();
+
+        let (worker, chain_height) = Worker::new(storage.clone(), client);
+        let error_slot = Arc::new(Mutex::new(None));
+        let worker_error_slot = error_slot.clone();
+        tokio::spawn(async move {
+            if let Err(e) = worker.run().await {
+                tracing::error!(?e, "wallet worker failed");
+                *worker_error_slot.lock().unwrap() = Some(e);
+            }
+        });
 
-        Self { storage }
+        Ok(Self {
+            storage,
+            fvk_hash,
+            error_slot,
+            chain_height,
+        })
     }
 
     async fn check_fvk(&self, fvk: Option<&pbc::FullViewingKeyHash>) -> Result<(), tonic::Status> {
-        // TODO: check the fvk against the Storage
         // Takes an Option to avoid making the caller handle missing fields,
         // should error on None or wrong FVK hash
+        let fvk = fvk.ok_or_else(|| tonic::Status::invalid_argument("missing fvk hash"))?;
+        let fvk = FullViewingKeyHash::try_from(fvk.clone())
+            .map_err(|_| tonic::Status::invalid_argument("invalid fvk hash"))?;
+
+        if fvk != self.fvk_hash {
+            return Err(tonic::Status::not_found("unknown full viewing key"));
+        }
         Ok(())
     }
 
     async fn check_worker(&self) -> Result<(), tonic::Status> {
-        // TODO: check whether the worker is still alive, else fail, when we have a way to do that
+        if let Some(e) = self.error_slot.lock().unwrap().as_ref() {
+            return Err(tonic::Status::internal(format!(
+                "wallet worker failed: {:#}",
+                e
+            )));
+        }
         Ok(())
     }
 }
@@ -68,7 +109,19 @@ impl WalletProtocol for WalletService {
         self.check_worker().await?;
         self.check_fvk(request.get_ref().fvk_hash.as_ref()).await?;
 
-        todo!()
+        let sync_height = self
+            .storage
+            .last_sync_height()
+            .await
+            .map_err(|_| tonic::Status::unavailable("database error"))?;
+        let chain_height = *self.chain_height.borrow();
+
+        Ok(tonic::Response::new(pb::StatusResponse {
+            // The chain height is only known once a sync has finished.
+            synchronized: chain_height > 0 && sync_height.unwrap_or(0) >= chain_height,
+            chain_height,
+            sync_height: sync_height.unwrap_or(0),
+        }))
     }
 
     async fn notes(
@@ -78,7 +131,63 @@ impl WalletProtocol for WalletService {
         self.check_worker().await?;
         self.check_fvk(request.get_ref().fvk_hash.as_ref()).await?;
 
-        todo!()
+        let request = request.into_inner();
+        let asset_id = request
+            .asset_id
+            .map(asset::Id::try_from)
+            .transpose()
+            .map_err(|_| tonic::Status::invalid_argument("invalid asset id"))?;
+        let diversifier_index = request
+            .diversifier_index
+            .map(DiversifierIndex::try_from)
+            .transpose()
+            .map_err(|_| tonic::Status::invalid_argument("invalid diversifier index"))?;
+
+        let records = self
+            .storage
+            .notes(
+                request.include_spent,
+                asset_id,
+                diversifier_index,
+                request.amount_to_spend,
+            )
+            .await
+            .map_err(|_| tonic::Status::unavailable("database error"))?;
+
+        let stream = futures::stream::iter(
+            records
+                .into_iter()
+                .map(|record| Ok(pb::NoteRecord::from(record))),
+        );
+        Ok(tonic::Response::new(Box::pin(stream)))
+    }
+
+    async fn balance(
+        &self,
+        request: tonic::Request<pb::BalanceRequest>,
+    ) -> Result<tonic::Response<pb::BalanceResponse>, tonic::Status> {
+        self.check_worker().await?;
+        self.check_fvk(request.get_ref().fvk_hash.as_ref()).await?;
+
+        let diversifier_index = request
+            .into_inner()
+            .diversifier_index
+            .map(DiversifierIndex::try_from)
+            .transpose()
+            .map_err(|_| tonic::Status::invalid_argument("invalid diversifier index"))?;
+
+        let balance = self
+            .storage
+            .balance(diversifier_index)
+            .await
+            .map_err(|_| tonic::Status::unavailable("database error"))?;
+
+        Ok(tonic::Response::new(pb::BalanceResponse {
+            balances: balance
+                .into_iter()
+                .map(|(asset_id, amount)| Value { amount, asset_id }.into())
+                .collect(),
+        }))
     }
 
     async fn auth_paths(
@@ -88,6 +197,39 @@ impl WalletProtocol for WalletService {
         self.check_worker().await?;
         self.check_fvk(request.get_ref().fvk_hash.as_ref()).await?;
 
-        todo!()
+        // All of the paths are computed from a single copy of the tree, so they share a root.
+        let note_commitment_tree = self
+            .storage
+            .note_commitment_tree()
+            .await
+            .map_err(|_| tonic::Status::unavailable("database error"))?;
+
+        let mut paths = Vec::new();
+        for note_commitment in request.into_inner().notes {
+            let note_commitment = note::Commitment::try_from(note_commitment)
+                .map_err(|_| tonic::Status::invalid_argument("invalid note commitment"))?;
+            let (position, path) = note_commitment_tree
+                .authentication_path(&note_commitment)
+                .ok_or_else(|| {
+                    tonic::Status::not_found(format!(
+                        "note commitment {:?} is not witnessed",
+                        note_commitment
+                    ))
+                })?;
+
+            paths.push(
+                AuthPath {
+                    note_commitment,
+                    position,
+                    path,
+                }
+                .into(),
+            );
+        }
+
+        Ok(tonic::Response::new(pb::AuthPathsResponse {
+            root: Some(note_commitment_tree.root2().into()),
+            paths,
+        }))
     }
 }
@@ -1,7 +1,24 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::anyhow;
 use penumbra_chain::params::ChainParams;
-use penumbra_crypto::merkle::NoteCommitmentTree;
-use penumbra_proto::{crypto::FullViewingKey, Message, Protobuf};
-use sqlx::{query, Pool, Sqlite};
+use penumbra_crypto::{
+    asset,
+    keys::DiversifierIndex,
+    merkle::{NoteCommitmentTree, Tree},
+    note, FieldExt, Fq, FullViewingKey, Note, Nullifier, Value,
+};
+use penumbra_proto::{crypto as pbc, Protobuf};
+use sqlx::{
+    query,
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions, SqliteRow},
+    Pool, Row, Sqlite,
+};
+
+use crate::{sync::ScanResult, NoteRecord};
+
+/// The number of checkpoints kept by the note commitment tree.
+const MAX_MERKLE_CHECKPOINTS: usize = 10;
 
 #[derive(Clone)]
 pub struct Storage {
@@ -13,10 +30,50 @@ impl Storage {
         Self { pool }
     }
 
+    /// Opens the database at `path`, creating it if it doesn't exist, and runs any pending
+    /// migrations.
+    pub async fn load(path: &Path) -> anyhow::Result<Self> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let pool = SqlitePoolOptions::new().connect_with(options).await?;
+
+        let storage = Self::new(pool);
+        storage.migrate().await?;
+        Ok(storage)
+    }
+
     pub async fn migrate(self: &Storage) -> anyhow::Result<()> {
         sqlx::migrate!().run(&self.pool).await.map_err(Into::into)
     }
 
+    /// Records the full viewing key to scan with and an empty note commitment tree, failing if
+    /// the database has already been initialized.
+    pub async fn initialize(&self, fvk: &FullViewingKey) -> anyhow::Result<()> {
+        let mut dbtx = self.pool.begin().await?;
+
+        let existing = sqlx::query("SELECT bytes FROM full_viewing_key LIMIT 1")
+            .fetch_optional(&mut dbtx)
+            .await?;
+        if existing.is_some() {
+            return Err(anyhow!("database is already initialized"));
+        }
+
+        sqlx::query("INSERT INTO full_viewing_key (bytes) VALUES (?)")
+            .bind(fvk.encode_to_vec())
+            .execute(&mut dbtx)
+            .await?;
+        sqlx::query("INSERT INTO note_commitment_tree (bytes) VALUES (?)")
+            .bind(bincode::serialize(&NoteCommitmentTree::new(
+                MAX_MERKLE_CHECKPOINTS,
+            ))?)
+            .execute(&mut dbtx)
+            .await?;
+
+        dbtx.commit().await?;
+        Ok(())
+    }
+
     /// The last block height we've scanned to, if any.
     pub async fn last_sync_height(&self) -> anyhow::Result<Option<u64>> {
         let result = sqlx::query!(
@@ -30,7 +87,7 @@ impl Storage {
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(result[0].height.map(|h| h as u64))
+        Ok(result.first().and_then(|row| row.height).map(|h| h as u64))
     }
 
     pub async fn chain_params(&self) -> anyhow::Result<ChainParams> {
@@ -44,7 +101,29 @@ impl Storage {
         .fetch_all(&self.pool)
         .await?;
 
-        ChainParams::decode(result[0].bytes.as_ref().unwrap().as_slice())
+        ChainParams::decode(
+            result
+                .first()
+                .and_then(|row| row.bytes.as_ref())
+                .ok_or_else(|| anyhow!("chain parameters have not been fetched"))?
+                .as_slice(),
+        )
+    }
+
+    /// Replaces the stored chain parameters.
+    pub async fn set_chain_params(&self, params: ChainParams) -> anyhow::Result<()> {
+        let mut dbtx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM chain_params")
+            .execute(&mut dbtx)
+            .await?;
+        sqlx::query("INSERT INTO chain_params (bytes) VALUES (?)")
+            .bind(params.encode_to_vec())
+            .execute(&mut dbtx)
+            .await?;
+
+        dbtx.commit().await?;
+        Ok(())
     }
 
     pub async fn full_viewing_key(&self) -> anyhow::Result<FullViewingKey> {
@@ -58,9 +137,13 @@ impl Storage {
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(FullViewingKey::decode(
-            result[0].bytes.as_ref().unwrap().as_slice(),
-        )?)
+        FullViewingKey::decode(
+            result
+                .first()
+                .and_then(|row| row.bytes.as_ref())
+                .ok_or_else(|| anyhow!("database has not been initialized"))?
+                .as_slice(),
+        )
     }
 
     pub async fn note_commitment_tree(&self) -> anyhow::Result<NoteCommitmentTree> {
@@ -74,10 +157,210 @@ impl Storage {
         .fetch_all(&self.pool)
         .await?;
 
-        //let nct_data = bincode::serialize(&result)?;
-
         Ok(bincode::deserialize(
-            result[0].bytes.as_ref().unwrap().as_slice(),
+            result
+                .first()
+                .and_then(|row| row.bytes.as_ref())
+                .ok_or_else(|| anyhow!("database has not been initialized"))?
+                .as_slice(),
         )?)
     }
+
+    /// Records the results of scanning a run of consecutive blocks, along with the note
+    /// commitment tree after the last of them.
+    ///
+    /// Notes spent in these blocks are marked as spent, and their commitments are no longer
+    /// witnessed in `note_commitment_tree`.  Either all of the blocks are recorded or none are.
+    pub async fn record_blocks(
+        &self,
+        blocks: Vec<ScanResult>,
+        note_commitment_tree: &mut NoteCommitmentTree,
+    ) -> anyhow::Result<()> {
+        let height = match blocks.last() {
+            Some(block) => block.height,
+            None => return Ok(()),
+        };
+
+        let mut dbtx = self.pool.begin().await?;
+
+        for block in blocks {
+            for record in block.new_notes {
+                let note = &record.note;
+                sqlx::query(
+                    "INSERT INTO notes (
+                        note_commitment,
+                        height_spent,
+                        height_created,
+                        diversifier,
+                        amount,
+                        asset_id,
+                        transmission_key,
+                        blinding_factor,
+                        diversifier_index,
+                        nullifier
+                    ) VALUES (?, NULL, ?, ?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(pbc::NoteCommitment::from(record.note_commitment).inner)
+                .bind(record.height_created as i64)
+                .bind(note.diversifier().0.to_vec())
+                // Amounts are stored as their bit pattern, since SQLite integers are signed.
+                .bind(note.amount() as i64)
+                .bind(pbc::AssetId::from(note.asset_id()).inner)
+                .bind(note.transmission_key().0.to_vec())
+                .bind(note.note_blinding().to_bytes().to_vec())
+                .bind(pbc::DiversifierIndex::from(record.diversifier_index).inner)
+                .bind(pbc::Nullifier::from(record.nullifier).inner)
+                .execute(&mut dbtx)
+                .await?;
+            }
+
+            for nullifier in block.spent_nullifiers {
+                let spent = sqlx::query(
+                    "SELECT note_commitment FROM notes WHERE nullifier = ? AND height_spent IS NULL",
+                )
+                .bind(pbc::Nullifier::from(nullifier).inner)
+                .fetch_optional(&mut dbtx)
+                .await?;
+
+                if let Some(row) = spent {
+                    let commitment_bytes: Vec<u8> = row.get("note_commitment");
+                    sqlx::query("UPDATE notes SET height_spent = ? WHERE note_commitment = ?")
+                        .bind(block.height as i64)
+                        .bind(&commitment_bytes)
+                        .execute(&mut dbtx)
+                        .await?;
+
+                    let note_commitment = note::Commitment::try_from(pbc::NoteCommitment {
+                        inner: commitment_bytes,
+                    })?;
+                    tracing::debug!(?note_commitment, "marking note as spent");
+                    note_commitment_tree.remove_witness(&note_commitment);
+                }
+            }
+        }
+
+        sqlx::query("DELETE FROM sync_height")
+            .execute(&mut dbtx)
+            .await?;
+        sqlx::query("INSERT INTO sync_height (height) VALUES (?)")
+            .bind(height as i64)
+            .execute(&mut dbtx)
+            .await?;
+
+        sqlx::query("DELETE FROM note_commitment_tree")
+            .execute(&mut dbtx)
+            .await?;
+        sqlx::query("INSERT INTO note_commitment_tree (bytes) VALUES (?)")
+            .bind(bincode::serialize(&*note_commitment_tree)?)
+            .execute(&mut dbtx)
+            .await?;
+
+        dbtx.commit().await?;
+        Ok(())
+    }
+
+    /// Queries for notes, optionally including spent notes and filtering by asset and diversifier
+    /// index.
+    ///
+    /// If `amount_to_spend` is nonzero, only as many notes (smallest first) as are needed to
+    /// cover that amount are returned.
+    pub async fn notes(
+        &self,
+        include_spent: bool,
+        asset_id: Option<asset::Id>,
+        diversifier_index: Option<DiversifierIndex>,
+        amount_to_spend: u64,
+    ) -> anyhow::Result<Vec<NoteRecord>> {
+        let mut sql = String::from("SELECT * FROM notes WHERE 1");
+        if !include_spent {
+            sql.push_str(" AND height_spent IS NULL");
+        }
+        if asset_id.is_some() {
+            sql.push_str(" AND asset_id = ?");
+        }
+        if diversifier_index.is_some() {
+            sql.push_str(" AND diversifier_index = ?");
+        }
+        sql.push_str(" ORDER BY height_created, note_commitment");
+
+        let mut query = sqlx::query(&sql);
+        if let Some(asset_id) = asset_id {
+            query = query.bind(pbc::AssetId::from(asset_id).inner);
+        }
+        if let Some(diversifier_index) = diversifier_index {
+            query = query.bind(pbc::DiversifierIndex::from(diversifier_index).inner);
+        }
+
+        let mut records = query
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(note_record)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        if amount_to_spend > 0 && asset_id.is_some() && !include_spent {
+            records.sort_by_key(|record| record.note.amount());
+
+            let mut total = 0u64;
+            records.retain(|record| {
+                let needed = total < amount_to_spend;
+                total = total.saturating_add(record.note.amount());
+                needed
+            });
+        }
+
+        Ok(records)
+    }
+
+    /// The total amount of each asset in unspent notes, optionally only those sent to a
+    /// particular diversifier index.
+    pub async fn balance(
+        &self,
+        diversifier_index: Option<DiversifierIndex>,
+    ) -> anyhow::Result<BTreeMap<asset::Id, u64>> {
+        let mut balance = BTreeMap::new();
+        for record in self.notes(false, None, diversifier_index, 0).await? {
+            *balance.entry(record.note.asset_id()).or_insert(0u64) += record.note.amount();
+        }
+        Ok(balance)
+    }
+}
+
+/// Reconstructs a [`NoteRecord`] from a row of the `notes` table.
+fn note_record(row: &SqliteRow) -> anyhow::Result<NoteRecord> {
+    let note = Note::from_parts(
+        row.get::<Vec<u8>, _>("diversifier").as_slice().try_into()?,
+        row.get::<Vec<u8>, _>("transmission_key")
+            .as_slice()
+            .try_into()?,
+        Value {
+            amount: row.get::<i64, _>("amount") as u64,
+            asset_id: pbc::AssetId {
+                inner: row.get("asset_id"),
+            }
+            .try_into()?,
+        },
+        Fq::from_bytes(
+            row.get::<Vec<u8>, _>("blinding_factor")
+                .as_slice()
+                .try_into()?,
+        )?,
+    )?;
+
+    Ok(NoteRecord {
+        note_commitment: pbc::NoteCommitment {
+            inner: row.get("note_commitment"),
+        }
+        .try_into()?,
+        note,
+        diversifier_index: pbc::DiversifierIndex {
+            inner: row.get("diversifier_index"),
+        }
+        .try_into()?,
+        nullifier: Nullifier::try_from(pbc::Nullifier {
+            inner: row.get("nullifier"),
+        })?,
+        height_created: row.get::<i64, _>("height_created") as u64,
+        height_spent: row.get::<Option<i64>, _>("height_spent").map(|h| h as u64),
+    })
 }
@@ -1,15 +1,14 @@
 use penumbra_chain::CompactBlock;
-use penumbra_crypto::Nullifier;
 use penumbra_crypto::{
     merkle::{Frontier, Tree},
-    FullViewingKey, Note,
+    FullViewingKey, Note, Nullifier,
 };
 use penumbra_transaction::action::output;
 
 use crate::NoteRecord;
 
 /// Contains the results of scanning a single block.
-struct ScanResult {
+pub(crate) struct ScanResult {
     // write as new rows
     pub new_notes: Vec<NoteRecord>,
     // use to update existing rows
@@ -17,8 +16,8 @@ struct ScanResult {
     pub height: u64,
 }
 
-#[tracing::instrument(skip(fvk, outputs, nullifiers))]
-fn scan_block(
+#[tracing::instrument(skip(fvk, note_commitment_tree, outputs, nullifiers))]
+pub(crate) fn scan_block(
     fvk: &FullViewingKey,
     note_commitment_tree: &mut penumbra_crypto::merkle::NoteCommitmentTree,
     CompactBlock {
@@ -69,8 +68,8 @@ fn scan_block(
         }
     }
 
-    // TODO: write a query to mark all matching rows as spent
-
+    // Spent nullifiers are matched against our notes when the results are recorded, since they
+    // may spend notes found in earlier blocks.
     ScanResult {
         new_notes,
         spent_nullifiers: nullifiers,
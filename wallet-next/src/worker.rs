@@ -1,24 +1,121 @@
-use crate::Storage;
-use penumbra_proto::client::oblivious::oblivious_query_client::ObliviousQueryClient;
+use std::mem;
+
+use anyhow::anyhow;
+use penumbra_chain::{params::ChainParams, CompactBlock};
+use penumbra_proto::client::oblivious::{
+    oblivious_query_client::ObliviousQueryClient, ChainParamsRequest, CompactBlockRangeRequest,
+};
+use tokio::sync::watch;
 use tonic::transport::Channel;
 
+use crate::{sync::scan_block, Storage};
+
+/// The number of blocks scanned between writes to the database.
+const BLOCKS_PER_COMMIT: usize = 1000;
+
 pub struct Worker {
     storage: Storage,
     client: ObliviousQueryClient<Channel>,
-    // TODO: notifications (see TODOs on WalletService)
+    /// The latest block height reported by the node.
+    chain_height: watch::Sender<u64>,
 }
 
 impl Worker {
-    pub fn new(storage: Storage, client: ObliviousQueryClient<Channel>) -> Self {
-        Self { storage, client }
+    /// Creates a new worker, along with a receiver for the latest block height reported by the
+    /// node, which is 0 until the first sync has finished.
+    pub fn new(
+        storage: Storage,
+        client: ObliviousQueryClient<Channel>,
+    ) -> (Self, watch::Receiver<u64>) {
+        let (chain_height, chain_height_rx) = watch::channel(0);
+        (
+            Self {
+                storage,
+                client,
+                chain_height,
+            },
+            chain_height_rx,
+        )
     }
 
-    pub async fn sync_to_latest(&self) -> Result<u64, anyhow::Error> {
-        // Do a single sync run, up to whatever the latest block height is
-        todo!()
+    /// Does a single sync run, up to whatever the latest block height is, returning the height
+    /// synced to.
+    #[tracing::instrument(skip(self))]
+    pub async fn sync_to_latest(&mut self) -> Result<u64, anyhow::Error> {
+        let fvk = self.storage.full_viewing_key().await?;
+
+        // Chain parameters can change, so refresh them each time, checking that the node is
+        // still on the chain we started syncing.
+        let expected_chain_id = self
+            .storage
+            .chain_params()
+            .await
+            .map(|params| params.chain_id)
+            .unwrap_or_default();
+        let params: ChainParams = self
+            .client
+            .chain_params(tonic::Request::new(ChainParamsRequest {
+                chain_id: expected_chain_id,
+                height: 0,
+            }))
+            .await?
+            .into_inner()
+            .into();
+        let chain_id = params.chain_id.clone();
+        self.storage.set_chain_params(params).await?;
+
+        let mut note_commitment_tree = self.storage.note_commitment_tree().await?;
+        let start_height = self
+            .storage
+            .last_sync_height()
+            .await?
+            .map(|h| h + 1)
+            .unwrap_or(0);
+
+        let mut blocks = self
+            .client
+            .compact_block_range(tonic::Request::new(CompactBlockRangeRequest {
+                chain_id,
+                start_height,
+                end_height: 0,
+            }))
+            .await?
+            .into_inner();
+
+        let mut next_height = start_height;
+        let mut scanned = Vec::new();
+        while let Some(block) = blocks.message().await? {
+            let block = CompactBlock::try_from(block)?;
+            if block.height != next_height {
+                return Err(anyhow!(
+                    "unexpected block height {}, expecting {}",
+                    block.height,
+                    next_height
+                ));
+            }
+            next_height += 1;
+
+            scanned.push(scan_block(&fvk, &mut note_commitment_tree, block));
+            if scanned.len() >= BLOCKS_PER_COMMIT {
+                self.storage
+                    .record_blocks(mem::take(&mut scanned), &mut note_commitment_tree)
+                    .await?;
+                tracing::info!(height = next_height - 1, "syncing...");
+            }
+        }
+        self.storage
+            .record_blocks(scanned, &mut note_commitment_tree)
+            .await?;
+
+        // The range was streamed up to the latest block, so that's the chain height.
+        let height = next_height.saturating_sub(1);
+        let _ = self.chain_height.send(height);
+        tracing::debug!(height, "finished sync");
+
+        Ok(height)
     }
 
-    pub async fn run(self) -> Result<(), anyhow::Error> {
+    pub async fn run(mut self) -> Result<(), anyhow::Error> {
         loop {
             self.sync_to_latest().await?;
 
@@ -0,0 +1,67 @@
+use penumbra_chain::params::ChainParams;
+use penumbra_crypto::merkle::{self, NoteCommitmentTree, TreeExt};
+use serde::{Deserialize, Serialize};
+
+/// A snapshot of the public chain state at a particular height, from which a fresh wallet can
+/// begin scanning without replaying the chain history before that height.
+///
+/// A checkpoint contains no private data, so it can be published and shared.  However, a wallet
+/// initialized from a checkpoint will never discover notes created at or before the checkpoint
+/// height, so checkpoints are only appropriate for wallets whose keys were generated after that
+/// height.  Since the checkpoint is trusted, its [`root`](Checkpoint::root) should be compared
+/// against a trusted source before use.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "serde_helpers::CheckpointHelper")]
+#[serde(into = "serde_helpers::CheckpointHelper")]
+pub struct Checkpoint {
+    /// The height of the last block included in the checkpoint.
+    pub height: u64,
+    /// The global chain parameters as of the checkpoint height.
+    pub chain_params: ChainParams,
+    /// The note commitment tree as of the checkpoint height, with no witnessed commitments.
+    pub note_commitment_tree: NoteCommitmentTree,
+}
+
+impl Checkpoint {
+    /// Returns the root of the note commitment tree at the checkpoint height.
+    pub fn root(&self) -> merkle::Root {
+        self.note_commitment_tree.root2()
+    }
+}
+
+mod serde_helpers {
+    use serde_with::serde_as;
+
+    use super::*;
+
+    #[serde_as]
+    #[derive(Serialize, Deserialize)]
+    pub struct CheckpointHelper {
+        height: u64,
+        chain_params: ChainParams,
+        #[serde_as(as = "serde_with::hex::Hex")]
+        note_commitment_tree: Vec<u8>,
+    }
+
+    impl From<Checkpoint> for CheckpointHelper {
+        fn from(checkpoint: Checkpoint) -> Self {
+            Self {
+                height: checkpoint.height,
+                chain_params: checkpoint.chain_params,
+                note_commitment_tree: bincode::serialize(&checkpoint.note_commitment_tree).unwrap(),
+            }
+        }
+    }
+
+    impl TryFrom<CheckpointHelper> for Checkpoint {
+        type Error = anyhow::Error;
+
+        fn try_from(checkpoint: CheckpointHelper) -> Result<Self, Self::Error> {
+            Ok(Self {
+                height: checkpoint.height,
+                chain_params: checkpoint.chain_params,
+                note_commitment_tree: bincode::deserialize(&checkpoint.note_commitment_tree)?,
+            })
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use penumbra_crypto::{note, Note};
+
+/// Callbacks invoked while a [`ClientState`](crate::ClientState) syncs, so that embedders can
+/// react to new notes, spends and sync progress without polling the client state.
+///
+/// Every method has a default implementation which does nothing, so implementors only need to
+/// provide the ones they care about.  The hooks are called synchronously from
+/// [`ClientState::apply_scanned_block_with_hooks`](crate::ClientState::apply_scanned_block_with_hooks),
+/// so they should return quickly, handing off any slow work to another task.
+pub trait SyncHooks {
+    /// Called when a note sent to us is confirmed on-chain, including change from our own
+    /// transactions.
+    ///
+    /// Notes which were already known, such as those encountered again while rescanning, are not
+    /// reported.
+    fn on_note_received(&mut self, note_commitment: note::Commitment, note: &Note) {
+        let _ = (note_commitment, note);
+    }
+
+    /// Called when the spend of one of our notes is confirmed on-chain.
+    fn on_note_spent(&mut self, note_commitment: note::Commitment, note: &Note) {
+        let _ = (note_commitment, note);
+    }
+
+    /// Called once a block has been applied, with the height the client state has synced to.
+    fn on_sync_height(&mut self, height: u64) {
+        let _ = height;
+    }
+}
+
+/// No hooks at all.
+impl SyncHooks for () {}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+/// The version of the interoperable export format produced by this crate.
+pub const INTEROP_FORMAT_VERSION: u32 = 1;
+
+/// Wallet state in a documented, versioned format, for exporting to other client implementations.
+///
+/// The export is a JSON object with the following fields (version 1):
+///
+/// - `version`: the format version, currently `1`.
+/// - `chain_id`: the chain the state was synced from, if known.
+/// - `full_viewing_key`: the wallet's full viewing key, in its Bech32 encoding.
+/// - `sync_height`: the height of the last block scanned, or `null` if nothing has been scanned.
+/// - `frontier`: the frontier of the note commitment tree, or `null` if it is empty:
+///   - `root`: the hex-encoded root of the tree.
+///   - `position`: the position of the most recently appended note commitment.
+///   - `leaf`: that note commitment, hex-encoded.
+///   - `auth_path`: its authentication path, as hex-encoded sibling hashes from the leaf
+///     upwards, which together with the leaf is enough to continue appending to the tree.
+/// - `notes`: every note known to the wallet, each with:
+///   - `note_commitment`: the hex-encoded note commitment.
+///   - `note`: the hex-encoded 116-byte note encoding: a type byte (`0`), the 11-byte
+///     diversifier, the amount as 8 little-endian bytes, the 32-byte asset ID, the 32-byte note
+///     blinding factor, and the 32-byte transmission key.
+///   - `diversifier_index`: the index of the address the note was sent to, as its hex-encoded
+///     11-byte little-endian encoding (indices are 88 bits, so they may not fit in a JSON number).
+///   - `status`: one of `unspent`, `pending_spend` (spent by a transaction which has not yet been
+///     confirmed), `pending_change` (change from a transaction which has not yet been confirmed)
+///     or `spent`.
+///   - `position` and `auth_path`: the note's position in the note commitment tree and its
+///     authentication path (hex-encoded, leaf upwards), if it is witnessed; spent notes and
+///     pending change are not.
+///   - `label`: the local label attached to the note, if any.
+///
+/// All hex-encoded field elements use their canonical 32-byte little-endian encoding.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InteropState {
+    pub version: u32,
+    pub chain_id: Option<String>,
+    pub full_viewing_key: String,
+    pub sync_height: Option<u64>,
+    pub frontier: Option<InteropFrontier>,
+    pub notes: Vec<InteropNote>,
+}
+
+/// The frontier of the note commitment tree, in the interoperable export format.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InteropFrontier {
+    pub root: String,
+    pub position: u64,
+    pub leaf: String,
+    pub auth_path: Vec<String>,
+}
+
+/// A note known to the wallet, in the interoperable export format.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InteropNote {
+    pub note_commitment: String,
+    pub note: String,
+    pub diversifier_index: String,
+    pub status: InteropNoteStatus,
+    pub position: Option<u64>,
+    pub auth_path: Option<Vec<String>>,
+    pub label: Option<String>,
+}
+
+/// Whether a note has been spent, in the interoperable export format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InteropNoteStatus {
+    Unspent,
+    PendingSpend,
+    PendingChange,
+    Spent,
+}
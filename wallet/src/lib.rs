@@ -1,5 +1,19 @@
+mod checkpoint;
+mod hooks;
+mod interop;
+mod plan;
+mod scan;
+mod select;
 mod state;
 mod wallet;
 
+pub use checkpoint::Checkpoint;
+pub use hooks::SyncHooks;
+pub use interop::{
+    InteropFrontier, InteropNote, InteropNoteStatus, InteropState, INTEROP_FORMAT_VERSION,
+};
+pub use plan::{OutputPlan, TransactionPlan, TransactionPlanner};
+pub use scan::{DecryptionPool, ScannedBlock};
+pub use select::{NoteSelector, SelectionStrategy};
 pub use state::{ClientState, UnspentNote};
 pub use wallet::Wallet;
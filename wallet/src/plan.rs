@@ -0,0 +1,520 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use penumbra_crypto::{
+    asset::Denom,
+    memo::{MemoPlaintext, MEMO_LEN_BYTES},
+    merkle::{self, TreeExt},
+    Address, DelegationToken, Note, Value, STAKING_TOKEN_DENOM,
+};
+use penumbra_stake::{rate::RateData, validator};
+use penumbra_transaction::action::{Delegate, Undelegate};
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::{ClientState, NoteSelector};
+
+/// Everything that goes into a transaction, decided ahead of proving and signing it.
+///
+/// A plan is produced by a [`TransactionPlanner`] and turned into a transaction by
+/// [`ClientState::build_transaction`].  It can be serialized, so that a plan made in one place
+/// can be proved and signed in another.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TransactionPlan {
+    /// The chain the transaction is for.
+    pub chain_id: String,
+    /// The fee paid by the transaction, in the staking token.
+    pub fee: u64,
+    /// The expiry height of the transaction, if any.
+    pub expiry_height: Option<u32>,
+    /// The note commitment tree root the spends are proved against.
+    pub anchor: merkle::Root,
+    /// The notes spent by the transaction.
+    pub spends: Vec<Note>,
+    /// The outputs created by the transaction, including change.
+    pub outputs: Vec<OutputPlan>,
+    /// The delegations made by the transaction.
+    pub delegations: Vec<Delegate>,
+    /// The undelegations made by the transaction.
+    pub undelegations: Vec<Undelegate>,
+    /// The validator definitions uploaded by the transaction.
+    pub validator_definitions: Vec<validator::Definition>,
+}
+
+/// An output to be created by a transaction.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OutputPlan {
+    /// The value sent.
+    pub value: Value,
+    /// The address the value is sent to.
+    pub dest_address: Address,
+    /// The memo attached to the output.
+    pub memo: MemoPlaintext,
+    /// Whether the output returns value to our own wallet, so that it should be tracked as
+    /// pending change once the transaction is built.
+    pub is_change: bool,
+}
+
+/// Something a transaction should do.
+#[derive(Clone, Debug)]
+enum Intent {
+    Send {
+        values: Vec<Value>,
+        dest_address: Address,
+        memo: MemoPlaintext,
+    },
+    Delegate {
+        rate_data: RateData,
+        unbonded_amount: u64,
+    },
+    Undelegate {
+        rate_data: RateData,
+        delegation_amount: u64,
+    },
+    ValidatorDefinition(validator::Definition),
+}
+
+/// Turns a list of intents (send this value there, delegate that much stake) into a
+/// [`TransactionPlan`].
+///
+/// Planning chooses the notes to spend and works out the change, but doesn't touch any keys or
+/// modify the client state, so every frontend can share the same planning logic however it
+/// proves and signs transactions.  Given the same client state, intents and randomness, the same
+/// plan is produced.
+#[derive(Clone, Debug, Default)]
+pub struct TransactionPlanner {
+    intents: Vec<Intent>,
+    fee: u64,
+    expiry_height: Option<u32>,
+    source_address: Option<u64>,
+}
+
+impl TransactionPlanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the fee paid by the transaction.
+    pub fn set_fee(&mut self, fee: u64) -> &mut Self {
+        self.fee = fee;
+        self
+    }
+
+    /// Set the expiry height of the transaction.
+    pub fn set_expiry_height(&mut self, expiry_height: u32) -> &mut Self {
+        self.expiry_height = Some(expiry_height);
+        self
+    }
+
+    /// Only spend notes sent to the address with this index, and return any value released by
+    /// the transaction to it.
+    pub fn set_source_address(&mut self, source_address: Option<u64>) -> &mut Self {
+        self.source_address = source_address;
+        self
+    }
+
+    /// Send `values` to `dest_address`, with the given memo on each output.
+    pub fn send(
+        &mut self,
+        values: &[Value],
+        dest_address: Address,
+        memo: MemoPlaintext,
+    ) -> &mut Self {
+        self.intents.push(Intent::Send {
+            values: values.to_vec(),
+            dest_address,
+            memo,
+        });
+        self
+    }
+
+    /// Delegate `unbonded_amount` of the staking token to the validator described by
+    /// `rate_data`.
+    pub fn delegate(&mut self, rate_data: RateData, unbonded_amount: u64) -> &mut Self {
+        self.intents.push(Intent::Delegate {
+            rate_data,
+            unbonded_amount,
+        });
+        self
+    }
+
+    /// Undelegate `delegation_amount` of delegation tokens from the validator described by
+    /// `rate_data`.
+    pub fn undelegate(&mut self, rate_data: RateData, delegation_amount: u64) -> &mut Self {
+        self.intents.push(Intent::Undelegate {
+            rate_data,
+            delegation_amount,
+        });
+        self
+    }
+
+    /// Upload a validator definition.
+    pub fn validator_definition(&mut self, definition: validator::Definition) -> &mut Self {
+        self.intents.push(Intent::ValidatorDefinition(definition));
+        self
+    }
+
+    /// Plans a transaction carrying out the intents, choosing the notes to spend with
+    /// `selector`.
+    ///
+    /// Any asset the transaction consumes more of than it produces is paid for by spending
+    /// notes, with change returned to the address of the last note spent.  Any asset it produces
+    /// more of than it consumes, such as the delegation tokens from a delegation, is sent to the
+    /// source address (or the default address).  Transactions which delegate or undelegate
+    /// return their change to that address too, alongside the delegation tokens or unbonded
+    /// stake.
+    ///
+    /// The state is only read: the spent notes are marked as spent when the plan is built with
+    /// [`ClientState::build_transaction`], so a plan which is never built changes nothing.
+    pub fn plan<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        state: &ClientState,
+        selector: &impl NoteSelector,
+    ) -> Result<TransactionPlan, anyhow::Error> {
+        let mut plan = TransactionPlan {
+            chain_id: state
+                .chain_id()
+                .ok_or_else(|| anyhow!("missing chain_id"))?,
+            fee: self.fee,
+            expiry_height: self.expiry_height,
+            anchor: state.note_commitment_tree().root2(),
+            spends: Vec::new(),
+            outputs: Vec::new(),
+            delegations: Vec::new(),
+            undelegations: Vec::new(),
+            validator_definitions: Vec::new(),
+        };
+
+        // The amount of each asset the transaction consumes, less the amount it produces.
+        let mut balance = Balance::default();
+        balance.consume(&STAKING_TOKEN_DENOM, self.fee);
+
+        for intent in &self.intents {
+            match intent {
+                Intent::Send {
+                    values,
+                    dest_address,
+                    memo,
+                } => {
+                    for value in values {
+                        let denom = state.asset_cache().get(&value.asset_id).ok_or_else(|| {
+                            anyhow!("unknown denomination for asset id {}", value.asset_id)
+                        })?;
+                        balance.consume(denom, value.amount);
+                        plan.outputs.push(OutputPlan {
+                            value: *value,
+                            dest_address: *dest_address,
+                            memo: memo.clone(),
+                            is_change: false,
+                        });
+                    }
+                }
+                Intent::Delegate {
+                    rate_data,
+                    unbonded_amount,
+                } => {
+                    let delegation_denom =
+                        DelegationToken::new(rate_data.identity_key.clone()).denom();
+                    balance.consume(&STAKING_TOKEN_DENOM, *unbonded_amount);
+                    balance.produce(
+                        &delegation_denom,
+                        rate_data.delegation_amount(*unbonded_amount),
+                    );
+                    plan.delegations
+                        .push(rate_data.build_delegate(*unbonded_amount));
+                }
+                Intent::Undelegate {
+                    rate_data,
+                    delegation_amount,
+                } => {
+                    // Because the outputs of an undelegation are quarantined, we want to avoid
+                    // any unnecessary change outputs, so fees are paid out of the unbonded
+                    // amount rather than by spending staking tokens.
+                    let unbonded_amount = rate_data.unbonded_amount(*delegation_amount);
+                    if unbonded_amount < self.fee {
+                        return Err(anyhow!(
+                            "unbonded amount {} from delegation amount {} is insufficient to pay fees {}",
+                            unbonded_amount,
+                            delegation_amount,
+                            self.fee
+                        ));
+                    }
+                    let delegation_denom =
+                        DelegationToken::new(rate_data.identity_key.clone()).denom();
+                    balance.consume(&delegation_denom, *delegation_amount);
+                    balance.produce(&STAKING_TOKEN_DENOM, unbonded_amount);
+                    plan.undelegations
+                        .push(rate_data.build_undelegate(*delegation_amount));
+                }
+                Intent::ValidatorDefinition(definition) => {
+                    plan.validator_definitions.push(definition.clone());
+                }
+            }
+        }
+
+        let (_label, self_address) = state
+            .wallet()
+            .address_by_index(self.source_address.unwrap_or(0) as usize)?;
+        let change_to_source = !plan.delegations.is_empty() || !plan.undelegations.is_empty();
+
+        for (denom, amount) in balance.0 {
+            if amount > 0 {
+                let amount = u64::try_from(amount)
+                    .map_err(|_| anyhow!("cannot spend {} of {}", amount, denom))?;
+
+                // Select a list of notes that provides at least the required amount.
+                let notes =
+                    state.select_notes(rng, amount, &denom, self.source_address, selector)?;
+                let change_address = if change_to_source {
+                    self_address
+                } else {
+                    state
+                        .wallet()
+                        .change_address(notes.last().expect("spent at least one note"))?
+                };
+                let spent: u64 = notes.iter().map(|note| note.amount()).sum();
+                plan.spends.extend(notes);
+
+                // TODO: support dummy notes, and produce a change output unconditionally.
+                let change = spent - amount;
+                if change > 0 {
+                    plan.outputs.push(OutputPlan {
+                        value: Value {
+                            amount: change,
+                            asset_id: denom.id(),
+                        },
+                        dest_address: change_address,
+                        memo: MemoPlaintext([0u8; MEMO_LEN_BYTES]),
+                        is_change: true,
+                    });
+                }
+            } else if amount < 0 {
+                let amount = u64::try_from(-amount)
+                    .map_err(|_| anyhow!("cannot receive {} of {}", -amount, denom))?;
+                plan.outputs.push(OutputPlan {
+                    value: Value {
+                        amount,
+                        asset_id: denom.id(),
+                    },
+                    dest_address: self_address,
+                    memo: MemoPlaintext([0u8; MEMO_LEN_BYTES]),
+                    is_change: true,
+                });
+            }
+        }
+
+        Ok(plan)
+    }
+}
+
+/// The amount of each asset consumed by a transaction, less the amount it produces.
+#[derive(Default)]
+struct Balance(BTreeMap<Denom, i128>);
+
+impl Balance {
+    fn consume(&mut self, denom: &Denom, amount: u64) {
+        *self.0.entry(denom.clone()).or_default() += i128::from(amount);
+    }
+
+    fn produce(&mut self, denom: &Denom, amount: u64) {
+        *self.0.entry(denom.clone()).or_default() -= i128::from(amount);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use penumbra_chain::params::ChainParams;
+    use penumbra_crypto::{
+        keys::SeedPhrase,
+        rdsa::{SigningKey, SpendAuth},
+        IdentityKey, STAKING_TOKEN_ASSET_ID,
+    };
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::{ScannedBlock, SelectionStrategy, UnspentNote, Wallet};
+
+    fn rate_data() -> RateData {
+        RateData {
+            identity_key: IdentityKey(SigningKey::<SpendAuth>::new(OsRng).into()),
+            epoch_index: 0,
+            validator_reward_rate: 0,
+            validator_exchange_rate: 1_0000_0000,
+        }
+    }
+
+    /// A client state holding a note for each of `values`, sent to the addresses with the given
+    /// indices.
+    fn client(rate_data: &RateData, values: &[(usize, Value)]) -> ClientState {
+        let mut state =
+            ClientState::new(Wallet::from_seed_phrase(SeedPhrase::generate(&mut OsRng)));
+        state.wallet_mut().new_address("Other".to_string());
+        *state.chain_params_mut() = Some(ChainParams::default());
+        state.asset_cache_mut().extend([
+            STAKING_TOKEN_DENOM.clone(),
+            DelegationToken::new(rate_data.identity_key.clone()).denom(),
+        ]);
+
+        let outputs = values
+            .iter()
+            .map(|(index, value)| {
+                let (_, address) = state.wallet().address_by_index(*index).unwrap();
+                let note = Note::generate(&mut OsRng, &address, *value);
+                (note.commit(), Some(note))
+            })
+            .collect();
+        state
+            .apply_scanned_block(ScannedBlock {
+                height: 0,
+                outputs,
+                nullifiers: vec![],
+            })
+            .unwrap();
+        state
+    }
+
+    fn staking(amount: u64) -> Value {
+        Value {
+            amount,
+            asset_id: *STAKING_TOKEN_ASSET_ID,
+        }
+    }
+
+    fn delegation(rate_data: &RateData, amount: u64) -> Value {
+        Value {
+            amount,
+            asset_id: DelegationToken::new(rate_data.identity_key.clone()).id(),
+        }
+    }
+
+    fn address(state: &ClientState, index: usize) -> Address {
+        state.wallet().address_by_index(index).unwrap().1
+    }
+
+    fn ready_notes(state: &ClientState) -> usize {
+        state
+            .unspent_notes()
+            .filter(|(_, _, note)| matches!(note, UnspentNote::Ready(_)))
+            .count()
+    }
+
+    #[test]
+    fn send_returns_change_to_the_spent_address() {
+        let rate_data = rate_data();
+        let state = client(&rate_data, &[(1, staking(10))]);
+        let dest_address = address(&client(&rate_data, &[]), 0);
+
+        let plan = TransactionPlanner::new()
+            .set_fee(1)
+            .send(&[staking(3)], dest_address, MemoPlaintext::default())
+            .plan(&mut OsRng, &state, &SelectionStrategy::default())
+            .unwrap();
+
+        assert_eq!(plan.spends.len(), 1);
+        assert_eq!(plan.outputs.len(), 2);
+        assert_eq!(plan.outputs[0].value, staking(3));
+        assert_eq!(plan.outputs[0].dest_address, dest_address);
+        assert!(!plan.outputs[0].is_change);
+        assert_eq!(plan.outputs[1].value, staking(6));
+        assert_eq!(plan.outputs[1].dest_address, address(&state, 1));
+        assert!(plan.outputs[1].is_change);
+
+        // Planning leaves the note available until the plan is built.
+        assert_eq!(ready_notes(&state), 1);
+
+        // Plans are handed between processes as JSON.
+        let json = serde_json::to_string(&plan).unwrap();
+        let decoded: TransactionPlan = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+    }
+
+    #[test]
+    fn delegate_returns_tokens_and_change_to_the_source_address() {
+        let rate_data = rate_data();
+        let state = client(&rate_data, &[(1, staking(10))]);
+
+        let plan = TransactionPlanner::new()
+            .set_fee(1)
+            .delegate(rate_data.clone(), 4)
+            .plan(&mut OsRng, &state, &SelectionStrategy::default())
+            .unwrap();
+
+        assert_eq!(plan.spends.len(), 1);
+        assert_eq!(plan.delegations.len(), 1);
+        let mut outputs = plan
+            .outputs
+            .iter()
+            .map(|output| {
+                assert!(output.is_change);
+                assert_eq!(output.dest_address, address(&state, 0));
+                output.value
+            })
+            .collect::<Vec<_>>();
+        outputs.sort_by_key(|value| value.amount);
+        assert_eq!(outputs, [delegation(&rate_data, 4), staking(5)]);
+    }
+
+    #[test]
+    fn undelegate_pays_fees_from_the_unbonded_amount() {
+        let rate_data = rate_data();
+        let state = client(
+            &rate_data,
+            &[(0, staking(10)), (1, delegation(&rate_data, 5))],
+        );
+
+        let plan = TransactionPlanner::new()
+            .set_fee(1)
+            .undelegate(rate_data.clone(), 2)
+            .plan(&mut OsRng, &state, &SelectionStrategy::default())
+            .unwrap();
+
+        assert_eq!(plan.spends.len(), 1);
+        assert_eq!(plan.spends[0].value(), delegation(&rate_data, 5));
+        assert_eq!(plan.undelegations.len(), 1);
+        let mut outputs = plan
+            .outputs
+            .iter()
+            .map(|output| {
+                assert!(output.is_change);
+                assert_eq!(output.dest_address, address(&state, 0));
+                output.value
+            })
+            .collect::<Vec<_>>();
+        outputs.sort_by_key(|value| value.amount);
+        assert_eq!(outputs, [staking(1), delegation(&rate_data, 3)]);
+
+        // Staking tokens are never spent to make up the fee.
+        assert!(TransactionPlanner::new()
+            .set_fee(3)
+            .undelegate(rate_data, 2)
+            .plan(&mut OsRng, &state, &SelectionStrategy::default())
+            .is_err());
+    }
+
+    #[test]
+    fn building_a_plan_spends_its_notes() {
+        let rate_data = rate_data();
+        let mut state = client(&rate_data, &[(0, staking(10))]);
+        let dest_address = address(&client(&rate_data, &[]), 0);
+
+        let plan = TransactionPlanner::new()
+            .set_fee(1)
+            .send(&[staking(3)], dest_address, MemoPlaintext::default())
+            .plan(&mut OsRng, &state, &SelectionStrategy::default())
+            .unwrap();
+        assert_eq!(ready_notes(&state), 1);
+
+        state.build_transaction(&mut OsRng, plan.clone()).unwrap();
+        assert_eq!(ready_notes(&state), 0);
+        assert!(state
+            .unspent_notes()
+            .any(|(_, _, note)| matches!(note, UnspentNote::SubmittedSpend(_))));
+        assert!(state
+            .unspent_notes()
+            .any(|(_, _, note)| matches!(note, UnspentNote::SubmittedChange(_))));
+
+        // The note has already been spent, so the same plan can't be built again.
+        assert!(state.build_transaction(&mut OsRng, plan).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use penumbra_chain::sync::CompactBlock;
+use penumbra_crypto::{keys::IncomingViewingKey, note, Note, Nullifier};
+use penumbra_transaction::action::output;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+/// A [`CompactBlock`] whose outputs have been trial-decrypted, ready to be applied to a
+/// [`ClientState`](crate::ClientState).
+///
+/// Trial decryption is the expensive part of scanning, but it only depends on the incoming viewing
+/// key and not on any other client state, so it can be performed ahead of time and in parallel,
+/// leaving only the (cheap, but order-dependent) state updates to be done sequentially.
+#[derive(Clone, Debug)]
+pub struct ScannedBlock {
+    /// The height of the scanned block.
+    pub height: u64,
+    /// The note commitments of every output in the block, in order, along with the decrypted
+    /// note for each output that was addressed to us.
+    pub outputs: Vec<(note::Commitment, Option<Note>)>,
+    /// Nullifiers identifying notes spent in the block.
+    pub nullifiers: Vec<Nullifier>,
+}
+
+impl ScannedBlock {
+    /// Prepare the block to be applied without trial-decrypting any of its outputs.
+    ///
+    /// This is used when rescanning blocks whose notes have already been discovered: the note
+    /// commitments are still needed to rebuild the note commitment tree, but the notes themselves
+    /// are already known.
+    pub fn without_decryption(
+        CompactBlock {
+            height,
+            outputs,
+            nullifiers,
+        }: CompactBlock,
+    ) -> Self {
+        Self {
+            height,
+            outputs: outputs
+                .into_iter()
+                .map(|output| (output.note_commitment, None))
+                .collect(),
+            nullifiers,
+        }
+    }
+
+    /// Trial-decrypt every output in the block using the provided incoming viewing key.
+    ///
+    /// Outputs are decrypted in parallel with [`note::decrypt_batch`] on the current [`rayon`]
+    /// thread pool, so this should be called from a blocking context (e.g.
+    /// [`tokio::task::spawn_blocking`]) rather than directly from an async task.
+    pub fn trial_decrypt(
+        ivk: &IncomingViewingKey,
+        CompactBlock {
+            height,
+            outputs,
+            nullifiers,
+        }: CompactBlock,
+    ) -> Self {
+        let (note_commitments, encrypted_notes): (Vec<_>, Vec<_>) = outputs
+            .into_iter()
+            .map(
+                |output::Body {
+                     note_commitment,
+                     ephemeral_key,
+                     encrypted_note,
+                 }| (note_commitment, (ephemeral_key, encrypted_note)),
+            )
+            .unzip();
+
+        // Try to decrypt each encrypted note using its ephemeral key and our persistent incoming
+        // viewing key -- if it doesn't decrypt, it wasn't meant for us.
+        let outputs = note_commitments
+            .into_iter()
+            .zip(note::decrypt_batch(ivk, &encrypted_notes))
+            .collect();
+
+        Self {
+            height,
+            outputs,
+            nullifiers,
+        }
+    }
+}
+
+/// A thread pool dedicated to trial decryption, so that the number of cores used for scanning
+/// can be limited.
+///
+/// The pool can be cloned cheaply; clones share the same threads.
+#[derive(Clone, Debug)]
+pub struct DecryptionPool {
+    pool: Arc<ThreadPool>,
+}
+
+impl DecryptionPool {
+    /// Creates a pool with `threads` threads, or one per core if `threads` is `None`.
+    pub fn new(threads: Option<usize>) -> Result<Self, anyhow::Error> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads.unwrap_or(0))
+            .thread_name(|i| format!("trial-decryption-{}", i))
+            .build()?;
+        Ok(Self {
+            pool: Arc::new(pool),
+        })
+    }
+
+    /// The number of threads in the pool.
+    pub fn threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Trial-decrypt every output in the block on this pool, as in
+    /// [`ScannedBlock::trial_decrypt`].
+    ///
+    /// This blocks until decryption is finished, so it should also be called from a blocking
+    /// context.
+    pub fn trial_decrypt(&self, ivk: &IncomingViewingKey, block: CompactBlock) -> ScannedBlock {
+        self.pool
+            .install(|| ScannedBlock::trial_decrypt(ivk, block))
+    }
+}
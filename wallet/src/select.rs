@@ -0,0 +1,323 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use penumbra_crypto::Note;
+use rand::seq::SliceRandom;
+use rand_core::{CryptoRng, RngCore};
+
+/// A strategy for choosing which notes to spend to release some amount of an asset.
+pub trait NoteSelector {
+    /// Chooses notes from `candidates`, which are all ready to spend and all of the same asset,
+    /// whose total amount is at least `amount`.
+    ///
+    /// Returns `None` if the candidates don't have enough value between them, and an error if
+    /// the total amount of the notes considered overflows.
+    fn select<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        candidates: Vec<Note>,
+        amount: u64,
+    ) -> Result<Option<Vec<Note>>>;
+}
+
+/// The note selection strategies provided by the wallet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelectionStrategy {
+    /// Spend as few notes as possible, largest first.
+    MinimizeInputs,
+    /// Spend the notes whose total is closest to the amount, to produce as little change as
+    /// possible.
+    MinimizeChange,
+    /// Spend as few notes as possible, since every note spent in the same transaction is
+    /// revealed to have the same owner, but choose among suitable notes at random, so that which
+    /// notes are spent doesn't reveal anything about the rest of the wallet.
+    Private,
+}
+
+impl Default for SelectionStrategy {
+    fn default() -> Self {
+        SelectionStrategy::Private
+    }
+}
+
+impl SelectionStrategy {
+    /// The names of the strategies, as parsed by [`FromStr`].
+    pub const VARIANTS: &'static [&'static str] =
+        &["minimize-inputs", "minimize-change", "private"];
+}
+
+impl FromStr for SelectionStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minimize-inputs" => Ok(SelectionStrategy::MinimizeInputs),
+            "minimize-change" => Ok(SelectionStrategy::MinimizeChange),
+            "private" => Ok(SelectionStrategy::Private),
+            other => Err(anyhow!(
+                "unknown note selection strategy {:?}, expected one of {:?}",
+                other,
+                Self::VARIANTS
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for SelectionStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SelectionStrategy::MinimizeInputs => "minimize-inputs",
+            SelectionStrategy::MinimizeChange => "minimize-change",
+            SelectionStrategy::Private => "private",
+        })
+    }
+}
+
+impl NoteSelector for SelectionStrategy {
+    fn select<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        candidates: Vec<Note>,
+        amount: u64,
+    ) -> Result<Option<Vec<Note>>> {
+        match self {
+            SelectionStrategy::MinimizeInputs => minimize_inputs(candidates, amount),
+            SelectionStrategy::MinimizeChange => minimize_change(candidates, amount),
+            SelectionStrategy::Private => private(rng, candidates, amount),
+        }
+    }
+}
+
+/// Adds the amount of `note` to a running total.
+fn add(total: u64, note: &Note) -> Result<u64> {
+    total
+        .checked_add(note.amount())
+        .ok_or_else(|| anyhow!("total amount of notes overflows"))
+}
+
+/// The total amount of a set of notes.
+fn total(notes: &[Note]) -> Result<u64> {
+    notes.iter().try_fold(0u64, add)
+}
+
+/// Takes the largest notes until the amount is covered, which uses the fewest notes possible.
+fn minimize_inputs(mut candidates: Vec<Note>, amount: u64) -> Result<Option<Vec<Note>>> {
+    candidates.sort_by_key(|note| std::cmp::Reverse(note.amount()));
+
+    let mut selected = Vec::new();
+    let mut total = 0u64;
+    for note in candidates {
+        if total >= amount {
+            break;
+        }
+        total = add(total, &note)?;
+        selected.push(note);
+    }
+
+    if total >= amount {
+        Ok(Some(selected))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Chooses whichever of two candidate selections produces less change: the smallest single note
+/// covering the amount, or the smallest notes which together cover it, with any notes that turn
+/// out to be unnecessary removed.
+fn minimize_change(mut candidates: Vec<Note>, amount: u64) -> Result<Option<Vec<Note>>> {
+    candidates.sort_by_key(|note| note.amount());
+
+    let single = candidates
+        .iter()
+        .find(|note| note.amount() >= amount)
+        .cloned()
+        .map(|note| vec![note]);
+
+    let mut accumulated = Vec::new();
+    let mut accumulated_total = 0u64;
+    for note in candidates {
+        if accumulated_total >= amount {
+            break;
+        }
+        accumulated_total = add(accumulated_total, &note)?;
+        accumulated.push(note);
+    }
+    let accumulated = if accumulated_total >= amount {
+        // The last note added may make some of the smaller ones unnecessary.
+        let mut i = 0;
+        while i < accumulated.len() {
+            let without = accumulated_total - accumulated[i].amount();
+            if without >= amount {
+                accumulated_total = without;
+                accumulated.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        Some(accumulated)
+    } else {
+        None
+    };
+
+    Ok(match (single, accumulated) {
+        (Some(single), Some(accumulated)) => {
+            let single_change = total(&single)? - amount;
+            let accumulated_change = accumulated_total - amount;
+            // Prefer fewer notes when the change is the same.
+            if single_change <= accumulated_change {
+                Some(single)
+            } else {
+                Some(accumulated)
+            }
+        }
+        (single, accumulated) => single.or(accumulated),
+    })
+}
+
+/// Spends as few notes as possible: a random note covering the amount if there is one, and
+/// otherwise the largest notes needed, with the last of them chosen at random among the notes
+/// which would complete the amount.
+fn private<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    candidates: Vec<Note>,
+    amount: u64,
+) -> Result<Option<Vec<Note>>> {
+    let covering = candidates
+        .iter()
+        .filter(|note| note.amount() >= amount)
+        .collect::<Vec<_>>();
+    if let Some(note) = covering.choose(rng) {
+        return Ok(Some(vec![(*note).clone()]));
+    }
+
+    let mut selected = match minimize_inputs(candidates.clone(), amount)? {
+        Some(selected) => selected,
+        None => return Ok(None),
+    };
+
+    // Replace the smallest selected note with a random note which still completes the amount.
+    let last = match selected.pop() {
+        Some(last) => last,
+        None => return Ok(Some(selected)),
+    };
+    let rest = total(&selected)?;
+    let mut completing = Vec::new();
+    for note in candidates {
+        if !selected.contains(&note) && add(rest, &note)? >= amount {
+            completing.push(note);
+        }
+    }
+    selected.push(completing.choose(rng).cloned().unwrap_or(last));
+
+    Ok(Some(selected))
+}
+
+#[cfg(test)]
+mod tests {
+    use penumbra_crypto::{
+        keys::{SeedPhrase, SpendKey, SpendSeed},
+        Note, Value, STAKING_TOKEN_ASSET_ID,
+    };
+    use rand_core::OsRng;
+
+    use super::{total, NoteSelector, SelectionStrategy};
+
+    const STRATEGIES: [SelectionStrategy; 3] = [
+        SelectionStrategy::MinimizeInputs,
+        SelectionStrategy::MinimizeChange,
+        SelectionStrategy::Private,
+    ];
+
+    fn notes(amounts: &[u64]) -> Vec<Note> {
+        let seed_phrase = SeedPhrase::generate(&mut OsRng);
+        let sk = SpendKey::new(SpendSeed::from_seed_phrase(seed_phrase, 0));
+        let (address, _dtk) = sk.incoming_viewing_key().payment_address(0u64.into());
+        amounts
+            .iter()
+            .map(|&amount| {
+                Note::generate(
+                    &mut OsRng,
+                    &address,
+                    Value {
+                        amount,
+                        asset_id: *STAKING_TOKEN_ASSET_ID,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn select(strategy: SelectionStrategy, candidates: &[Note], amount: u64) -> Option<Vec<u64>> {
+        let selected = strategy
+            .select(&mut OsRng, candidates.to_vec(), amount)
+            .unwrap()?;
+        let mut amounts = selected.iter().map(Note::amount).collect::<Vec<_>>();
+        amounts.sort_unstable();
+        Some(amounts)
+    }
+
+    #[test]
+    fn exact_match() {
+        let candidates = notes(&[2, 5, 20]);
+        assert_eq!(
+            select(SelectionStrategy::MinimizeInputs, &candidates, 25),
+            Some(vec![5, 20])
+        );
+        assert_eq!(
+            select(SelectionStrategy::MinimizeChange, &candidates, 7),
+            Some(vec![2, 5])
+        );
+        assert_eq!(
+            select(SelectionStrategy::Private, &candidates, 27),
+            Some(vec![2, 5, 20])
+        );
+    }
+
+    #[test]
+    fn single_covering_note() {
+        let candidates = notes(&[1, 2, 10]);
+        for strategy in STRATEGIES {
+            assert_eq!(
+                select(strategy, &candidates, 8),
+                Some(vec![10]),
+                "{}",
+                strategy
+            );
+        }
+
+        // The smallest covering note produces the least change.
+        let candidates = notes(&[3, 4, 10, 50]);
+        assert_eq!(
+            select(SelectionStrategy::MinimizeChange, &candidates, 9),
+            Some(vec![10])
+        );
+        assert_eq!(
+            select(SelectionStrategy::MinimizeInputs, &candidates, 9),
+            Some(vec![50])
+        );
+    }
+
+    #[test]
+    fn insufficient_funds() {
+        let candidates = notes(&[1, 2, 3]);
+        for strategy in STRATEGIES {
+            assert_eq!(select(strategy, &candidates, 7), None, "{}", strategy);
+            assert_eq!(select(strategy, &[], 1), None, "{}", strategy);
+        }
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        let candidates = notes(&[u64::MAX - 1, u64::MAX - 1]);
+        assert!(total(&candidates).is_err());
+        for strategy in STRATEGIES {
+            assert!(
+                strategy
+                    .select(&mut OsRng, candidates.clone(), u64::MAX)
+                    .is_err(),
+                "{}",
+                strategy
+            );
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet},
     mem,
     time::{Duration, SystemTime},
 };
@@ -10,23 +10,28 @@ use penumbra_crypto::{
     asset::{self, Denom},
     memo,
     merkle::{Frontier, NoteCommitmentTree, Tree, TreeExt},
-    note, Address, DelegationToken, FieldExt, Note, Nullifier, Value, STAKING_TOKEN_ASSET_ID,
-    STAKING_TOKEN_DENOM,
+    note, Address, FieldExt, Note, Nullifier, Value,
 };
 use penumbra_stake::{rate::RateData, validator};
-use penumbra_transaction::{action::output, Transaction};
-use rand::seq::SliceRandom;
+use penumbra_transaction::Transaction;
 use rand_core::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use tracing::instrument;
 
-use crate::Wallet;
+use crate::{
+    Checkpoint, InteropFrontier, InteropNote, InteropNoteStatus, InteropState, NoteSelector,
+    ScannedBlock, SelectionStrategy, SyncHooks, TransactionPlan, TransactionPlanner, Wallet,
+    INTEROP_FORMAT_VERSION,
+};
 
 const MAX_MERKLE_CHECKPOINTS_CLIENT: usize = 10;
 
 /// The time after which a locally cached submitted transaction is considered to have failed.
 const SUBMITTED_TRANSACTION_TIMEOUT: Duration = Duration::from_secs(60);
 
+/// The number of blocks after a note's spend is observed on-chain before the note is forgotten.
+const SPENT_NOTE_PRUNING_DEPTH: u64 = 100;
+
 /// State about the chain and our transactions.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(
@@ -39,6 +44,9 @@ pub struct ClientState {
     /// Note commitment tree.
     note_commitment_tree: NoteCommitmentTree,
     /// Our nullifiers and the notes they correspond to.
+    ///
+    /// Every note we receive has its nullifier recorded here, so that its spend is recognized
+    /// when the nullifier appears on-chain, whether or not this client authored the spend.
     nullifier_map: BTreeMap<Nullifier, note::Commitment>,
     /// Notes that we have received.
     unspent_set: BTreeMap<note::Commitment, Note>,
@@ -48,8 +56,12 @@ pub struct ClientState {
     submitted_change_set: BTreeMap<note::Commitment, (SystemTime, Note)>,
     /// Notes that we have spent.
     spent_set: BTreeMap<note::Commitment, Note>,
+    /// The block heights at which the notes in the spent set were spent.
+    spent_heights: BTreeMap<note::Commitment, u64>,
     /// Map of note commitment to full transaction data for transactions we have visibility into.
     transactions: BTreeMap<note::Commitment, Option<Vec<u8>>>,
+    /// Local labels attached to notes by the user.
+    note_labels: BTreeMap<note::Commitment, String>,
     /// Map of asset IDs to (raw) asset denominations.
     asset_cache: asset::Cache,
     /// Key material.
@@ -108,13 +120,53 @@ impl ClientState {
             submitted_spend_set: BTreeMap::new(),
             submitted_change_set: BTreeMap::new(),
             spent_set: BTreeMap::new(),
+            spent_heights: BTreeMap::new(),
             transactions: BTreeMap::new(),
+            note_labels: BTreeMap::new(),
             asset_cache: Default::default(),
             wallet,
             chain_params: None,
         }
     }
 
+    /// Create a new client state which begins scanning immediately after the provided
+    /// [`Checkpoint`], rather than at genesis.
+    ///
+    /// The resulting client state will never discover notes created at or before the checkpoint
+    /// height, so this should only be used for wallets whose keys were created after it.
+    pub fn from_checkpoint(wallet: Wallet, checkpoint: Checkpoint) -> Self {
+        let mut state = Self::new(wallet);
+        state.last_block_height = Some(checkpoint.height);
+        state.note_commitment_tree = checkpoint.note_commitment_tree;
+        state.chain_params = Some(checkpoint.chain_params);
+        state
+    }
+
+    /// Export a [`Checkpoint`] of the public chain state this client has synced to.
+    ///
+    /// All of the wallet's own note commitments are forgotten in the exported tree, so the
+    /// checkpoint does not reveal which notes belong to this wallet.
+    pub fn checkpoint(&self) -> Result<Checkpoint, anyhow::Error> {
+        let height = self
+            .last_block_height
+            .ok_or_else(|| anyhow!("cannot checkpoint a client state which has not synced"))?;
+        let chain_params = self
+            .chain_params
+            .clone()
+            .ok_or_else(|| anyhow!("cannot checkpoint a client state without chain parameters"))?;
+
+        let mut note_commitment_tree = self.note_commitment_tree.clone();
+        for note_commitment in self.nullifier_map.values() {
+            note_commitment_tree.remove_witness(note_commitment);
+        }
+
+        Ok(Checkpoint {
+            height,
+            chain_params,
+            note_commitment_tree,
+        })
+    }
+
     /// Returns a reference to the note commitment tree.
     pub fn note_commitment_tree(&self) -> &NoteCommitmentTree {
         &self.note_commitment_tree
@@ -187,7 +239,7 @@ impl ClientState {
     }
 
     /// Returns a list of notes to spend to release (at least) the provided
-    /// value.
+    /// value, chosen by the given [`NoteSelector`].
     ///
     /// The returned notes are removed from the unspent set and marked as having
     /// been spent (pending confirmation) by the chain.
@@ -200,13 +252,36 @@ impl ClientState {
         amount: u64,
         denom: &Denom,
         source_address: Option<u64>,
+        selector: &impl NoteSelector,
+    ) -> Result<Vec<Note>, anyhow::Error> {
+        let notes_to_spend = self.select_notes(rng, amount, denom, source_address, selector)?;
+
+        // Before returning the notes to the caller, mark them as having been
+        // spent.  (If the caller does not spend them, or the tx fails, etc.,
+        // this state will be erased after the timeout).
+        for note in &notes_to_spend {
+            self.register_spend(note);
+        }
+
+        Ok(notes_to_spend)
+    }
+
+    /// Chooses notes to spend to release (at least) the provided value, as
+    /// [`ClientState::notes_to_spend`] does, but without marking them as spent.
+    pub fn select_notes<R: CryptoRng + RngCore>(
+        &self,
+        rng: &mut R,
+        amount: u64,
+        denom: &Denom,
+        source_address: Option<u64>,
+        selector: &impl NoteSelector,
     ) -> Result<Vec<Note>, anyhow::Error> {
         let mut notes_by_address = self
             .unspent_notes_by_denom_and_address()
             .remove(denom)
             .ok_or_else(|| anyhow::anyhow!("no notes of denomination {} found", denom))?;
 
-        let mut notes = if let Some(source) = source_address {
+        let notes = if let Some(source) = source_address {
             notes_by_address.remove(&source).ok_or_else(|| {
                 anyhow::anyhow!(
                     "no notes of denomination {} found in address {}",
@@ -218,38 +293,16 @@ impl ClientState {
             notes_by_address.values().flatten().cloned().collect()
         };
 
-        // Draw notes in a random order, to avoid leaking information via arity.
-        notes.shuffle(rng);
-
-        let mut notes_to_spend = Vec::new();
-        let mut total_spend_value = 0u64;
-        for note in notes.into_iter() {
-            // A note is only spendable if it has been confirmed on chain to us (change outputs
-            // cannot be spent yet because they do not have a position):
-            if let UnspentNote::Ready(note) = note {
-                notes_to_spend.push(note.clone());
-                total_spend_value += note.amount();
+        // A note is only spendable if it has been confirmed on chain to us (change outputs
+        // cannot be spent yet because they do not have a position):
+        let candidates = notes
+            .into_iter()
+            .filter_map(|note| note.as_ready().cloned())
+            .collect();
 
-                if total_spend_value >= amount {
-                    break;
-                }
-            }
-        }
-
-        if total_spend_value >= amount {
-            // Before returning the notes to the caller, mark them as having been
-            // spent.  (If the caller does not spend them, or the tx fails, etc.,
-            // this state will be erased after the timeout).
-            for note in &notes_to_spend {
-                self.register_spend(note);
-            }
-
-            Ok(notes_to_spend)
-        } else {
-            Err(anyhow::anyhow!(
-                "not enough available notes for requested spend"
-            ))
-        }
+        selector
+            .select(rng, candidates, amount)?
+            .ok_or_else(|| anyhow::anyhow!("not enough available notes for requested spend"))
     }
 
     /// Returns the chain id, if the chain parameters are set.
@@ -267,66 +320,16 @@ impl ClientState {
         fee: u64,
         source_address: Option<u64>,
     ) -> Result<Transaction, anyhow::Error> {
-        // If the source address is set, send the delegation tokens to the same
-        // address; otherwise, send them to the default address.
-        let (_label, self_address) = self
-            .wallet()
-            .address_by_index(source_address.unwrap_or(0) as usize)?;
-
-        let mut tx_builder = Transaction::build_with_root(self.note_commitment_tree.root2());
-
-        tx_builder
+        let plan = TransactionPlanner::new()
             .set_fee(fee)
-            .set_chain_id(self.chain_id().ok_or_else(|| anyhow!("missing chain_id"))?)
-            .add_delegation(rate_data.build_delegate(unbonded_amount));
-
-        let spend_amount = unbonded_amount + fee;
-        let mut spent_amount = 0;
-
-        for note in self.notes_to_spend(rng, spend_amount, &*STAKING_TOKEN_DENOM, source_address)? {
-            spent_amount += note.amount();
-            tx_builder.add_spend(
-                rng,
-                &self.note_commitment_tree,
-                self.wallet.spend_key(),
-                note,
-            )?;
-        }
-
-        let delegation_note = tx_builder.add_output_producing_note(
-            rng,
-            &self_address,
-            Value {
-                amount: rate_data.delegation_amount(unbonded_amount),
-                asset_id: DelegationToken::new(rate_data.identity_key).id(),
-            },
-            memo::MemoPlaintext([0u8; memo::MEMO_LEN_BYTES]),
-            self.wallet.outgoing_viewing_key(),
-        );
-
-        let change_amount = spent_amount - spend_amount;
-        // TODO: support dummy notes, and produce a change output unconditionally.
-        // let change_note = if change_amount > 0 { ... } else { /* dummy note */}
-        if change_amount > 0 {
-            let change_note = tx_builder.add_output_producing_note(
-                rng,
-                &self_address,
-                Value {
-                    amount: change_amount,
-                    asset_id: *STAKING_TOKEN_ASSET_ID,
-                },
-                memo::MemoPlaintext([0u8; memo::MEMO_LEN_BYTES]),
-                self.wallet.outgoing_viewing_key(),
-            );
-            self.register_change(change_note);
-        }
+            .set_source_address(source_address)
+            .delegate(rate_data, unbonded_amount)
+            .plan(rng, self, &SelectionStrategy::default())?;
 
-        self.register_change(delegation_note);
-
-        tx_builder.finalize(rng).map_err(Into::into)
+        self.build_transaction(rng, plan)
     }
 
-    /// Generate a new transaction delegating stake
+    /// Generate a new transaction undelegating stake
     #[instrument(skip(self, rng))]
     pub fn build_undelegate<R: RngCore + CryptoRng>(
         &mut self,
@@ -336,82 +339,16 @@ impl ClientState {
         fee: u64,
         source_address: Option<u64>,
     ) -> Result<Transaction, anyhow::Error> {
-        // If the source address is set, send the delegation tokens to the same
-        // address; otherwise, send them to the default address.
-        let (_label, self_address) = self
-            .wallet()
-            .address_by_index(source_address.unwrap_or(0) as usize)?;
-
-        let mut tx_builder = Transaction::build_with_root(self.note_commitment_tree.root2());
-
-        tx_builder
-            .set_fee(fee)
-            .set_chain_id(self.chain_id().ok_or_else(|| anyhow!("missing chain_id"))?)
-            .add_undelegation(rate_data.build_undelegate(delegation_amount));
-
-        // Because the outputs of an undelegation are quarantined, we want to
-        // avoid any unnecessary change outputs, so we pay fees out of the
-        // unbonded amount.
-        let unbonded_amount = rate_data.unbonded_amount(delegation_amount);
-        let output_amount = unbonded_amount.checked_sub(fee).ok_or_else(|| {
-            anyhow::anyhow!(
-                "unbonded amount {} from delegation amount {} is insufficient to pay fees {}",
-                unbonded_amount,
-                delegation_amount,
-                fee
-            )
-        })?;
-
-        let delegation_denom = DelegationToken::new(rate_data.identity_key).denom();
-
         // XXX if the undelegation is for less than their total amount of delegation tokens,
         // all of their remaining delegation tokens will also be quarantined.
         // this sucks lmao
-        let mut spent_amount = 0;
-
-        for note in
-            self.notes_to_spend(rng, delegation_amount, &delegation_denom, source_address)?
-        {
-            spent_amount += note.amount();
-            tx_builder.add_spend(
-                rng,
-                &self.note_commitment_tree,
-                self.wallet.spend_key(),
-                note,
-            )?;
-        }
-
-        let output_note = tx_builder.add_output_producing_note(
-            rng,
-            &self_address,
-            Value {
-                amount: output_amount,
-                asset_id: *STAKING_TOKEN_ASSET_ID,
-            },
-            memo::MemoPlaintext([0u8; memo::MEMO_LEN_BYTES]),
-            self.wallet.outgoing_viewing_key(),
-        );
-
-        let change_amount = spent_amount - delegation_amount;
-        // TODO: support dummy notes, and produce a change output unconditionally.
-        // let change_note = if change_amount > 0 { ... } else { /* dummy note */}
-        if change_amount > 0 {
-            let change_note = tx_builder.add_output_producing_note(
-                rng,
-                &self_address,
-                Value {
-                    amount: change_amount,
-                    asset_id: delegation_denom.id(),
-                },
-                memo::MemoPlaintext([0u8; memo::MEMO_LEN_BYTES]),
-                self.wallet.outgoing_viewing_key(),
-            );
-            self.register_change(change_note);
-        }
-
-        self.register_change(output_note);
+        let plan = TransactionPlanner::new()
+            .set_fee(fee)
+            .set_source_address(source_address)
+            .undelegate(rate_data, delegation_amount)
+            .plan(rng, self, &SelectionStrategy::default())?;
 
-        tx_builder.finalize(rng).map_err(Into::into)
+        self.build_transaction(rng, plan)
     }
 
     /// Generate a new transaction uploading a validator definition.
@@ -423,72 +360,13 @@ impl ClientState {
         fee: u64,
         source_address: Option<u64>,
     ) -> Result<Transaction, anyhow::Error> {
-        let mut tx_builder = Transaction::build_with_root(self.note_commitment_tree.root2());
-
-        tx_builder
+        let plan = TransactionPlanner::new()
             .set_fee(fee)
-            .set_chain_id(self.chain_id().ok_or_else(|| anyhow!("missing chain_id"))?);
-
-        // Add the Validator to the tx_builder.
-        tx_builder.add_validator_definition(new_validator.into());
-
-        // If there are any fees, they need to be spent.
-        let mut value_to_spend = HashMap::<Denom, u64>::new();
-        if fee > 0 {
-            *value_to_spend
-                .entry(STAKING_TOKEN_DENOM.clone())
-                .or_default() += fee;
-        }
-
-        for (denom, amount) in value_to_spend {
-            // Only produce an output if the amount is greater than zero
-            if amount == 0 {
-                continue;
-            }
-
-            // Select a list of notes that provides at least the required amount.
-            let notes: Vec<Note> = self.notes_to_spend(rng, amount, &denom, source_address)?;
-            let change_address = self
-                .wallet
-                .change_address(notes.last().expect("spent at least one note"))?;
-            let spent: u64 = notes.iter().map(|note| note.amount()).sum();
-
-            // Spend each of the notes we selected.
-            for note in notes {
-                tx_builder.add_spend(
-                    rng,
-                    &self.note_commitment_tree,
-                    // The active wallet pays the fees for all the validators it is defining.
-                    self.wallet.spend_key(),
-                    note,
-                )?;
-            }
+            .set_source_address(source_address)
+            .validator_definition(new_validator)
+            .plan(rng, self, &SelectionStrategy::default())?;
 
-            // Find out how much change we have and whether to add a change output.
-            let change = spent - amount;
-            if change > 0 {
-                // xx: add memo handling
-                let memo = memo::MemoPlaintext([0u8; 512]);
-                let note = tx_builder.add_output_producing_note(
-                    rng,
-                    &change_address,
-                    Value {
-                        amount: change,
-                        asset_id: denom.id(),
-                    },
-                    memo,
-                    self.wallet.outgoing_viewing_key(),
-                );
-
-                self.register_change(note);
-            }
-        }
-
-        let transaction = tx_builder
-            .finalize(rng)
-            .map_err(|err| anyhow::anyhow!("error during transaction finalization: {}", err))?;
-
-        Ok(transaction)
+        self.build_transaction(rng, plan)
     }
 
     /// Generate a new transaction sending value to `dest_address`.
@@ -501,94 +379,101 @@ impl ClientState {
         dest_address: Address,
         source_address: Option<u64>,
         tx_memo: Option<String>,
+        selector: &impl NoteSelector,
     ) -> Result<Transaction, anyhow::Error> {
-        let mut tx_builder = Transaction::build_with_root(self.note_commitment_tree.root2());
+        let memo = match tx_memo {
+            Some(input_memo) => input_memo.try_into()?,
+            None => memo::MemoPlaintext([0u8; memo::MEMO_LEN_BYTES]),
+        };
 
-        tx_builder
+        let plan = TransactionPlanner::new()
             .set_fee(fee)
-            .set_chain_id(self.chain_id().ok_or_else(|| anyhow!("missing chain_id"))?);
-
-        let mut output_value = HashMap::<Denom, u64>::new();
-        for Value { amount, asset_id } in values {
-            let denom = self
-                .asset_cache()
-                .get(asset_id)
-                .ok_or_else(|| anyhow::anyhow!("unknown denomination for asset id {}", asset_id))?;
-            output_value.insert(denom.clone(), *amount);
-        }
+            .set_source_address(source_address)
+            .send(values, dest_address, memo)
+            .plan(rng, self, selector)?;
 
-        for (denom, amount) in &output_value {
-            let memo: memo::MemoPlaintext = match tx_memo {
-                Some(ref input_memo) => input_memo.clone().try_into()?,
-                None => memo::MemoPlaintext([0u8; memo::MEMO_LEN_BYTES]),
-            };
-            tx_builder.add_output(
-                rng,
-                &dest_address,
-                Value {
-                    amount: *amount,
-                    asset_id: denom.id(),
-                },
-                memo,
-                self.wallet.outgoing_viewing_key(),
-            );
-        }
+        self.build_transaction(rng, plan)
+    }
 
-        // The value we need to spend is the output value, plus fees.
-        let mut value_to_spend = output_value;
-        if fee > 0 {
-            *value_to_spend
-                .entry(STAKING_TOKEN_DENOM.clone())
-                .or_default() += fee;
+    /// Proves and signs the transaction described by `plan`, which must have been planned
+    /// against the current note commitment tree.
+    ///
+    /// Once the transaction is built, the notes it spends are marked as spent (pending
+    /// confirmation), as with [`ClientState::notes_to_spend`], and outputs returning value to our
+    /// own wallet are registered as pending change.
+    pub fn build_transaction<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        plan: TransactionPlan,
+    ) -> Result<Transaction, anyhow::Error> {
+        if plan.anchor != self.note_commitment_tree.root2() {
+            return Err(anyhow!(
+                "note commitment tree has changed since the transaction was planned"
+            ));
         }
-
-        for (denom, amount) in value_to_spend {
-            // Only produce an output if the amount is greater than zero
-            if amount == 0 {
-                continue;
+        // Each note can only be spent once, so a plan can't be built twice, or alongside another
+        // plan spending the same notes.
+        let mut spent = BTreeSet::new();
+        for note in &plan.spends {
+            let commitment = note.commit();
+            if !self.unspent_set.contains_key(&commitment) || !spent.insert(commitment) {
+                return Err(anyhow!("note {} is not available to spend", commitment));
             }
+        }
 
-            // Select a list of notes that provides at least the required amount.
-            let notes: Vec<Note> = self.notes_to_spend(rng, amount, &denom, source_address)?;
-            let change_address = self
-                .wallet
-                .change_address(notes.last().expect("spent at least one note"))?;
-            let spent: u64 = notes.iter().map(|note| note.amount()).sum();
-
-            // Spend each of the notes we selected.
-            for note in notes {
-                tx_builder.add_spend(
-                    rng,
-                    &self.note_commitment_tree,
-                    self.wallet.spend_key(),
-                    note,
-                )?;
-            }
+        let mut tx_builder = Transaction::build_with_root(plan.anchor);
 
-            // Find out how much change we have and whether to add a change output.
-            let change = spent - amount;
-            if change > 0 {
-                // xx: add memo handling
-                let memo = memo::MemoPlaintext([0u8; 512]);
-                let note = tx_builder.add_output_producing_note(
-                    rng,
-                    &change_address,
-                    Value {
-                        amount: change,
-                        asset_id: denom.id(),
-                    },
-                    memo,
-                    self.wallet.outgoing_viewing_key(),
-                );
+        tx_builder.set_fee(plan.fee).set_chain_id(plan.chain_id);
+        if let Some(expiry_height) = plan.expiry_height {
+            tx_builder.set_expiry_height(expiry_height);
+        }
 
-                self.register_change(note);
+        for note in &plan.spends {
+            tx_builder.add_spend(
+                rng,
+                &self.note_commitment_tree,
+                self.wallet.spend_key(),
+                note.clone(),
+            )?;
+        }
+
+        let mut change = Vec::new();
+        for output in plan.outputs {
+            let note = tx_builder.add_output_producing_note(
+                rng,
+                &output.dest_address,
+                output.value,
+                output.memo,
+                self.wallet.outgoing_viewing_key(),
+            );
+            if output.is_change {
+                change.push(note);
             }
         }
 
+        for delegation in plan.delegations {
+            tx_builder.add_delegation(delegation);
+        }
+        for undelegation in plan.undelegations {
+            tx_builder.add_undelegation(undelegation);
+        }
+        for definition in plan.validator_definitions {
+            tx_builder.add_validator_definition(definition.into());
+        }
+
         let transaction = tx_builder
             .finalize(rng)
             .map_err(|err| anyhow::anyhow!("error during transaction finalization: {}", err))?;
 
+        // Only update the state once nothing can fail, so that a failed build leaves the notes
+        // available to spend.
+        for note in &plan.spends {
+            self.register_spend(note);
+        }
+        for note in change {
+            self.register_change(note);
+        }
+
         Ok(transaction)
     }
 
@@ -666,6 +551,154 @@ impl ClientState {
         notemap
     }
 
+    /// Returns the note with the given commitment, if it is one of ours, whether or not it has
+    /// been spent.
+    pub fn note_by_commitment(&self, commitment: &note::Commitment) -> Option<&Note> {
+        self.unspent_set
+            .get(commitment)
+            .or_else(|| {
+                self.submitted_spend_set
+                    .get(commitment)
+                    .map(|(_, note)| note)
+            })
+            .or_else(|| {
+                self.submitted_change_set
+                    .get(commitment)
+                    .map(|(_, note)| note)
+            })
+            .or_else(|| self.spent_set.get(commitment))
+    }
+
+    /// Attach a local label to one of our notes, replacing any existing label.
+    ///
+    /// Labels are never shared with anyone; they exist only in the client state.
+    pub fn set_note_label(
+        &mut self,
+        commitment: note::Commitment,
+        label: String,
+    ) -> Result<(), anyhow::Error> {
+        if self.note_by_commitment(&commitment).is_none() {
+            return Err(anyhow!(
+                "note {} does not belong to this wallet",
+                commitment
+            ));
+        }
+        self.note_labels.insert(commitment, label);
+        Ok(())
+    }
+
+    /// Remove the label from a note, returning the label if there was one.
+    pub fn remove_note_label(&mut self, commitment: &note::Commitment) -> Option<String> {
+        self.note_labels.remove(commitment)
+    }
+
+    /// Returns the label attached to a note, if any.
+    pub fn note_label(&self, commitment: &note::Commitment) -> Option<&str> {
+        self.note_labels.get(commitment).map(String::as_str)
+    }
+
+    /// Returns the asset IDs of notes we have received whose denominations are not in the asset
+    /// cache.
+    ///
+    /// The asset registry is fetched as part of the sync, so this is normally empty, but a note
+    /// may arrive before its asset appears in the registry we last fetched.
+    pub fn unknown_asset_ids(&self) -> BTreeSet<asset::Id> {
+        self.unspent_set
+            .values()
+            .chain(self.submitted_spend_set.values().map(|(_, note)| note))
+            .chain(self.submitted_change_set.values().map(|(_, note)| note))
+            .chain(self.spent_set.values())
+            .map(|note| note.asset_id())
+            .filter(|id| !self.asset_cache.contains_key(id))
+            .collect()
+    }
+
+    /// Export the client state in the documented, versioned format described by
+    /// [`InteropState`], so that it can be imported by other client implementations.
+    ///
+    /// The export contains the full viewing key, so it must be kept private.
+    pub fn export_interop(&self) -> Result<InteropState, anyhow::Error> {
+        let tree = &self.note_commitment_tree;
+
+        // The frontier is exported as the authentication path of the most recent leaf, which is
+        // only available once that leaf is witnessed.
+        let frontier = match (tree.current_position(), tree.current_leaf()) {
+            (Some(position), Some(leaf)) => {
+                let mut tree = tree.clone();
+                tree.witness();
+                let (_, auth_path) = tree.authentication_path(leaf).ok_or_else(|| {
+                    anyhow!("could not compute the note commitment tree frontier")
+                })?;
+                Some(InteropFrontier {
+                    root: hex::encode(tree.root2().0.to_bytes()),
+                    position: u64::from(position),
+                    leaf: hex::encode(leaf.0.to_bytes()),
+                    auth_path: auth_path
+                        .iter()
+                        .map(|hash| hex::encode(hash.0.to_bytes()))
+                        .collect(),
+                })
+            }
+            _ => None,
+        };
+
+        let notes = self
+            .unspent_set
+            .iter()
+            .map(|(commitment, note)| (commitment, note, InteropNoteStatus::Unspent))
+            .chain(
+                self.submitted_spend_set
+                    .iter()
+                    .map(|(commitment, (_, note))| {
+                        (commitment, note, InteropNoteStatus::PendingSpend)
+                    }),
+            )
+            .chain(
+                self.submitted_change_set
+                    .iter()
+                    .map(|(commitment, (_, note))| {
+                        (commitment, note, InteropNoteStatus::PendingChange)
+                    }),
+            )
+            .chain(
+                self.spent_set
+                    .iter()
+                    .map(|(commitment, note)| (commitment, note, InteropNoteStatus::Spent)),
+            )
+            .map(|(commitment, note, status)| {
+                let diversifier_index = self
+                    .wallet
+                    .incoming_viewing_key()
+                    .index_for_diversifier(&note.diversifier());
+                let witness = tree.authentication_path(commitment);
+
+                InteropNote {
+                    note_commitment: hex::encode(commitment.0.to_bytes()),
+                    note: hex::encode(note.to_bytes()),
+                    diversifier_index: hex::encode(diversifier_index.0),
+                    status,
+                    position: witness.as_ref().map(|(position, _)| u64::from(*position)),
+                    auth_path: witness.map(|(_, auth_path)| {
+                        auth_path
+                            .iter()
+                            .map(|hash| hex::encode(hash.0.to_bytes()))
+                            .collect()
+                    }),
+                    label: self.note_labels.get(commitment).cloned(),
+                }
+            })
+            .collect();
+
+        Ok(InteropState {
+            version: INTEROP_FORMAT_VERSION,
+            chain_id: self.chain_id(),
+            full_viewing_key: self.wallet.full_viewing_key().to_string(),
+            sync_height: self.last_block_height,
+            frontier,
+            notes,
+        })
+    }
+
     /// Returns the last block height the client state has synced up to, if any.
     pub fn last_block_height(&self) -> Option<u64> {
         self.last_block_height
@@ -728,17 +761,115 @@ impl ClientState {
         }
     }
 
+    /// Reset the note commitment tree and sync height, so that the chain can be rescanned.
+    ///
+    /// Notes which have already been discovered are retained: when their commitments are
+    /// encountered again during the rescan they are re-witnessed without being trial-decrypted
+    /// (see [`ScannedBlock::without_decryption`]), so a rescan only needs to trial-decrypt the
+    /// blocks from which notes should be rediscovered.
+    pub fn reset_for_rescan(&mut self) {
+        tracing::info!(
+            last_block_height = ?self.last_block_height,
+            "resetting note commitment tree for rescan"
+        );
+        self.last_block_height = None;
+        self.note_commitment_tree = NoteCommitmentTree::new(MAX_MERKLE_CHECKPOINTS_CLIENT);
+        self.nullifier_map.clear();
+    }
+
+    /// Returns the note with the given commitment, if it is one we have received and not yet
+    /// seen spent on-chain.
+    fn known_unspent_note(&self, note_commitment: &note::Commitment) -> Option<Note> {
+        self.unspent_set
+            .get(note_commitment)
+            .or_else(|| {
+                self.submitted_spend_set
+                    .get(note_commitment)
+                    .map(|(_, note)| note)
+            })
+            .cloned()
+    }
+
+    /// Move a note whose nullifier was observed on-chain at `height` into the spent set.
+    ///
+    /// We will never need to spend it again, so its commitment stops being witnessed.
+    fn mark_spent(
+        &mut self,
+        note_commitment: note::Commitment,
+        note: Note,
+        height: u64,
+        hooks: &mut impl SyncHooks,
+    ) {
+        hooks.on_note_spent(note_commitment, &note);
+        self.spent_set.insert(note_commitment, note);
+        self.spent_heights.insert(note_commitment, height);
+        self.note_commitment_tree.remove_witness(&note_commitment);
+    }
+
+    /// Forget notes whose spends were observed at least [`SPENT_NOTE_PRUNING_DEPTH`] blocks
+    /// before `height`, along with their nullifiers, labels and transactions, so that the client
+    /// state doesn't grow forever.
+    ///
+    /// If the chain is rescanned, pruned notes are rediscovered and pruned again.
+    fn prune_spent_notes(&mut self, height: u64) {
+        let cutoff = match height.checked_sub(SPENT_NOTE_PRUNING_DEPTH) {
+            Some(cutoff) => cutoff,
+            None => return,
+        };
+
+        let pruned = self
+            .spent_heights
+            .iter()
+            .filter(|(_, &spent_height)| spent_height <= cutoff)
+            .map(|(note_commitment, _)| *note_commitment)
+            .collect::<BTreeSet<_>>();
+        if pruned.is_empty() {
+            return;
+        }
+
+        for note_commitment in &pruned {
+            tracing::debug!(?note_commitment, "pruning spent note");
+            self.spent_set.remove(note_commitment);
+            self.spent_heights.remove(note_commitment);
+            self.note_labels.remove(note_commitment);
+            self.transactions.remove(note_commitment);
+            self.note_commitment_tree.remove_witness(note_commitment);
+        }
+        self.nullifier_map
+            .retain(|_, note_commitment| !pruned.contains(note_commitment));
+    }
+
     /// Scan the provided block and update the client state.
     ///
     /// The provided block must be the one immediately following [`Self::last_block_height`].
-    #[instrument(skip(self, outputs, nullifiers))]
-    pub fn scan_block(
+    ///
+    /// This is equivalent to trial-decrypting the block with [`ScannedBlock::trial_decrypt`] and
+    /// then calling [`Self::apply_scanned_block`].
+    pub fn scan_block(&mut self, block: CompactBlock) -> Result<(), anyhow::Error> {
+        let scanned = ScannedBlock::trial_decrypt(self.wallet.incoming_viewing_key(), block);
+        self.apply_scanned_block(scanned)
+    }
+
+    /// Apply an already trial-decrypted block to the client state.
+    ///
+    /// The provided block must be the one immediately following [`Self::last_block_height`], and
+    /// must have been decrypted using this wallet's incoming viewing key.
+    pub fn apply_scanned_block(&mut self, block: ScannedBlock) -> Result<(), anyhow::Error> {
+        self.apply_scanned_block_with_hooks(block, &mut ())
+    }
+
+    /// Apply an already trial-decrypted block to the client state, as with
+    /// [`Self::apply_scanned_block`], calling `hooks` as notes are received and spent and once
+    /// the block has been applied.
+    #[instrument(skip(self, outputs, nullifiers, hooks))]
+    pub fn apply_scanned_block_with_hooks(
         &mut self,
-        CompactBlock {
+        ScannedBlock {
             height,
             outputs,
             nullifiers,
-        }: CompactBlock,
+        }: ScannedBlock,
+        hooks: &mut impl SyncHooks,
     ) -> Result<(), anyhow::Error> {
         // We have to do a bit of a dance to use None as "-1" and handle genesis notes.
         match (height, self.last_block_height()) {
@@ -754,52 +885,58 @@ impl ClientState {
         }
         tracing::debug!(outputs_len = outputs.len(), "starting block scan");
 
-        for output::Body {
-            note_commitment,
-            ephemeral_key,
-            encrypted_note,
-        } in outputs.into_iter()
-        {
+        // Append the whole block to the note commitment tree, witnessing our own notes as they go
+        // by.  Rather than computing an authentication path for each of our notes to find its
+        // position, we record the position it was appended at, and derive the nullifiers for the
+        // whole block afterwards.
+        let mut found = Vec::new();
+        for (note_commitment, note) in outputs.into_iter() {
             // Unconditionally insert the note commitment into the merkle tree
-            tracing::debug!(?note_commitment, "appending to note commitment tree");
             self.note_commitment_tree.append(&note_commitment);
 
-            // Try to decrypt the encrypted note using the ephemeral key and persistent incoming
-            // viewing key -- if it doesn't decrypt, it wasn't meant for us.
-            if let Ok(note) = Note::decrypt(
-                encrypted_note.as_ref(),
-                self.wallet.incoming_viewing_key(),
-                &ephemeral_key,
-            ) {
+            // If trial decryption succeeded, the note was meant for us.  If this block wasn't
+            // trial-decrypted because we are rescanning, we still need to re-witness any notes we
+            // already know about.
+            if let Some(note) = note.or_else(|| self.known_unspent_note(&note_commitment)) {
                 tracing::debug!(?note_commitment, ?note, "found note while scanning");
                 // Mark the most-recently-inserted note commitment (the one corresponding to this
                 // note) as worth keeping track of, because it's ours
                 self.note_commitment_tree.witness();
-
-                // Insert the note associated with its computed nullifier into the nullifier map
-                let (pos, _auth_path) = self
+                let position = self
                     .note_commitment_tree
-                    .authentication_path(&note_commitment)
-                    .expect("we just witnessed this commitment");
-                self.nullifier_map.insert(
-                    self.wallet
-                        .full_viewing_key()
-                        .derive_nullifier(pos, &note_commitment),
-                    note_commitment,
-                );
+                    .current_position()
+                    .expect("we just appended this commitment");
+                found.push((note_commitment, position, note));
+            }
+        }
 
-                // If the note was a submitted change note, remove it from the submitted change set
-                if self.submitted_change_set.remove(&note_commitment).is_some() {
-                    tracing::debug!(value = ?note.value(), "found submitted change note while scanning, removing it from the submitted change set");
-                }
+        let fvk = self.wallet.full_viewing_key();
+        for (note_commitment, position, note) in found {
+            // Insert the note associated with its computed nullifier into the nullifier map
+            self.nullifier_map.insert(
+                fvk.derive_nullifier(position, &note_commitment),
+                note_commitment,
+            );
+
+            // If the note was a submitted change note, remove it from the submitted change set
+            if self.submitted_change_set.remove(&note_commitment).is_some() {
+                tracing::debug!(value = ?note.value(), "found submitted change note while scanning, removing it from the submitted change set");
+            }
 
-                // Insert the note into the received set
-                self.unspent_set.insert(note_commitment, note.clone());
+            // Insert the note into the received set, unless we're rescanning and have
+            // already submitted a spend of it
+            if !self.submitted_spend_set.contains_key(&note_commitment) {
+                if !self.unspent_set.contains_key(&note_commitment) {
+                    hooks.on_note_received(note_commitment, &note);
+                }
+                self.unspent_set.insert(note_commitment, note);
             }
         }
 
         // Scan through the list of nullifiers to find those which refer to notes in our unspent
         // set, submitted change set, or submitted spend set and move them into the spent set.
+        // This doesn't depend on having submitted the spend ourselves, so notes spent by another
+        // client holding the same keys are marked as spent too.
         for nullifier in nullifiers {
             // Try to find the corresponding note commitment in the nullifier map
             if let Some(&note_commitment) = self.nullifier_map.get(&nullifier) {
@@ -811,8 +948,7 @@ impl ClientState {
                         ?nullifier,
                         "found nullifier for unspent note, marking it as spent"
                     );
-                    self.spent_set.insert(note_commitment, note);
-                    self.note_commitment_tree.remove_witness(&note_commitment);
+                    self.mark_spent(note_commitment, note, height, hooks);
                 } else if let Some((_, note)) = self.submitted_spend_set.remove(&note_commitment) {
                     // Insert the note into the spent set
                     tracing::debug!(
@@ -820,8 +956,7 @@ impl ClientState {
                         ?nullifier,
                         "found nullifier for submitted spend note, marking it as spent"
                     );
-                    self.spent_set.insert(note_commitment, note);
-                    self.note_commitment_tree.remove_witness(&note_commitment);
+                    self.mark_spent(note_commitment, note, height, hooks);
                 } else if let Some((_, note)) = self.submitted_change_set.remove(&note_commitment) {
                     // Insert the note into the spent set
                     tracing::debug!(
@@ -829,8 +964,7 @@ impl ClientState {
                         ?nullifier,
                         "found nullifier for submitted change note, marking it as spent"
                     );
-                    self.spent_set.insert(note_commitment, note);
-                    self.note_commitment_tree.remove_witness(&note_commitment);
+                    self.mark_spent(note_commitment, note, height, hooks);
                 } else if self.spent_set.contains_key(&note_commitment) {
                     // If the nullifier is already in the spent set, it means we've already
                     // processed this note and it's spent. This should never happen
@@ -846,9 +980,12 @@ impl ClientState {
             }
         }
 
+        self.prune_spent_notes(height);
+
         // Remember that we've scanned this block & we're ready for the next one.
         self.last_block_height = Some(height);
         tracing::debug!(self.last_block_height, "finished scanning block");
+        hooks.on_sync_height(height);
 
         Ok(())
     }
@@ -873,7 +1010,11 @@ mod serde_helpers {
         #[serde(default, alias = "pending_change_set")]
         submitted_change_set: Vec<(String, SystemTime, String)>,
         spent_set: Vec<(String, String)>,
+        #[serde(default)]
+        spent_heights: Vec<(String, u64)>,
         transactions: Vec<(String, String)>,
+        #[serde(default)]
+        note_labels: Vec<(String, String)>,
         asset_registry: Vec<(asset::Id, String)>,
         chain_params: Option<ChainParams>,
     }
@@ -977,6 +1118,18 @@ mod serde_helpers {
                         )
                     })
                     .collect(),
+                spent_heights: state
+                    .spent_heights
+                    .iter()
+                    .map(|(commitment, height)| (hex::encode(commitment.0.to_bytes()), *height))
+                    .collect(),
+                note_labels: state
+                    .note_labels
+                    .iter()
+                    .map(|(commitment, label)| {
+                        (hex::encode(commitment.0.to_bytes()), label.clone())
+                    })
+                    .collect(),
                 asset_registry: state
                     .asset_cache
                     .iter()
@@ -1026,7 +1179,7 @@ mod serde_helpers {
                 );
             }
 
-            let mut spent_set = BTreeMap::new();
+            let mut spent_set: BTreeMap<note::Commitment, Note> = BTreeMap::new();
             for (commitment, note) in state.spent_set.into_iter() {
                 spent_set.insert(
                     hex::decode(commitment)?.as_slice().try_into()?,
@@ -1034,6 +1187,23 @@ mod serde_helpers {
                 );
             }
 
+            let mut spent_heights = BTreeMap::new();
+            for (commitment, height) in state.spent_heights.into_iter() {
+                spent_heights.insert(hex::decode(commitment)?.as_slice().try_into()?, height);
+            }
+            // Notes spent before spend heights were recorded are treated as having been spent at
+            // the current height, so they're pruned in due course.
+            for commitment in spent_set.keys() {
+                spent_heights
+                    .entry(*commitment)
+                    .or_insert_with(|| state.last_block_height.unwrap_or(0));
+            }
+
+            let mut note_labels = BTreeMap::new();
+            for (commitment, label) in state.note_labels.into_iter() {
+                note_labels.insert(hex::decode(commitment)?.as_slice().try_into()?, label);
+            }
+
             let mut asset_registry = BTreeMap::new();
             for (id, denom) in state.asset_registry.into_iter() {
                 asset_registry.insert(id, denom);
@@ -1048,11 +1218,187 @@ mod serde_helpers {
                 submitted_spend_set,
                 submitted_change_set,
                 spent_set,
+                spent_heights,
                 asset_cache: asset_registry.try_into()?,
                 // TODO: serialize full transactions
                 transactions: Default::default(),
+                note_labels,
                 chain_params: state.chain_params,
             })
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use penumbra_chain::{params::ChainParams, sync::CompactBlock};
+    use penumbra_crypto::{
+        ka,
+        keys::{DiversifierIndex, SeedPhrase},
+        merkle::TreeExt,
+        note, Note, Value, STAKING_TOKEN_ASSET_ID,
+    };
+    use penumbra_transaction::action::output;
+    use rand_core::OsRng;
+
+    use super::ClientState;
+    use crate::{Checkpoint, InteropState, ScannedBlock, Wallet};
+
+    fn client() -> ClientState {
+        ClientState::new(Wallet::from_seed_phrase(SeedPhrase::generate(&mut OsRng)))
+    }
+
+    fn note_for(state: &ClientState, amount: u64) -> Note {
+        let (_, address) = state.wallet().address_by_index(0).unwrap();
+        Note::generate(
+            &mut OsRng,
+            &address,
+            Value {
+                amount,
+                asset_id: *STAKING_TOKEN_ASSET_ID,
+            },
+        )
+    }
+
+    fn witnessed(state: &ClientState, commitment: &note::Commitment) -> bool {
+        state
+            .note_commitment_tree()
+            .authentication_path(commitment)
+            .is_some()
+    }
+
+    fn output(note: &Note) -> output::Body {
+        let esk = ka::Secret::new(&mut OsRng);
+        output::Body {
+            note_commitment: note.commit(),
+            ephemeral_key: esk.diversified_public(&note.diversified_generator()),
+            encrypted_note: note.encrypt(&esk),
+        }
+    }
+
+    #[test]
+    fn checkpoint_round_trip() {
+        let mut state = client();
+        let ours = note_for(&state, 1);
+        let theirs = note_for(&client(), 2);
+        state
+            .apply_scanned_block(ScannedBlock {
+                height: 0,
+                outputs: vec![(ours.commit(), Some(ours.clone())), (theirs.commit(), None)],
+                nullifiers: vec![],
+            })
+            .unwrap();
+        *state.chain_params_mut() = Some(ChainParams::default());
+        assert!(witnessed(&state, &ours.commit()));
+
+        // Checkpoints are shared as JSON, so round-trip through the serialized form too.
+        let checkpoint = state.checkpoint().unwrap();
+        let checkpoint: Checkpoint =
+            serde_json::from_str(&serde_json::to_string(&checkpoint).unwrap()).unwrap();
+        assert_eq!(checkpoint.height, 0);
+        assert_eq!(checkpoint.root(), state.note_commitment_tree().root2());
+
+        let restored = ClientState::from_checkpoint(
+            Wallet::from_seed_phrase(SeedPhrase::generate(&mut OsRng)),
+            checkpoint,
+        );
+        assert_eq!(restored.last_block_height(), Some(0));
+        assert_eq!(
+            restored.note_commitment_tree().root2(),
+            state.note_commitment_tree().root2()
+        );
+        assert!(!witnessed(&restored, &ours.commit()));
+        assert!(!witnessed(&restored, &theirs.commit()));
+    }
+
+    #[test]
+    fn rescan_rewitnesses_and_rediscovers_notes() {
+        let mut state = client();
+        let below = note_for(&state, 1);
+        let above = note_for(&state, 2);
+        let blocks = [
+            CompactBlock {
+                height: 0,
+                outputs: vec![output(&below)],
+                nullifiers: vec![],
+            },
+            CompactBlock {
+                height: 1,
+                outputs: vec![output(&above)],
+                nullifiers: vec![],
+            },
+        ];
+
+        // The first sync misses the note in block 1, as it would if the note was sent to the
+        // wallet's keys before they were imported.
+        state.scan_block(blocks[0].clone()).unwrap();
+        state
+            .apply_scanned_block(ScannedBlock::without_decryption(blocks[1].clone()))
+            .unwrap();
+        assert!(state.unspent_set.contains_key(&below.commit()));
+        assert!(!state.unspent_set.contains_key(&above.commit()));
+        let root = state.note_commitment_tree().root2();
+
+        // Rescan from height 1 as `pcli sync --rescan-from 1` does: every block is replayed from
+        // genesis, but only the blocks from height 1 onwards are trial-decrypted.
+        state.reset_for_rescan();
+        state
+            .apply_scanned_block(ScannedBlock::without_decryption(blocks[0].clone()))
+            .unwrap();
+        state.scan_block(blocks[1].clone()).unwrap();
+
+        assert_eq!(state.last_block_height(), Some(1));
+        assert_eq!(state.note_commitment_tree().root2(), root);
+        for note in [&below, &above] {
+            let commitment = note.commit();
+            assert!(state.unspent_set.contains_key(&commitment));
+            assert!(witnessed(&state, &commitment));
+            assert!(state.nullifier_map.values().any(|c| *c == commitment));
+        }
+    }
+
+    #[test]
+    fn interop_export_round_trips_wide_diversifier_indices() {
+        let mut state = client();
+
+        // An index above `u64::MAX`, which only fits in the full 88 bits.
+        let mut index = [0; 11];
+        index[0] = 7;
+        index[10] = 1;
+        let index = DiversifierIndex(index);
+        let (address, _) = state.wallet().incoming_viewing_key().payment_address(index);
+        let note = Note::generate(
+            &mut OsRng,
+            &address,
+            Value {
+                amount: 1,
+                asset_id: *STAKING_TOKEN_ASSET_ID,
+            },
+        );
+        state
+            .apply_scanned_block(ScannedBlock {
+                height: 0,
+                outputs: vec![(note.commit(), Some(note.clone()))],
+                nullifiers: vec![],
+            })
+            .unwrap();
+
+        let export = state.export_interop().unwrap();
+        let export: InteropState =
+            serde_json::from_str(&serde_json::to_string(&export).unwrap()).unwrap();
+        assert_eq!(export.notes.len(), 1);
+        let exported = &export.notes[0];
+        let decoded = DiversifierIndex(
+            hex::decode(&exported.diversifier_index)
+                .unwrap()
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(decoded, index);
+        let (decoded_address, _) = state
+            .wallet()
+            .incoming_viewing_key()
+            .payment_address(decoded);
+        assert_eq!(decoded_address.diversifier(), &note.diversifier());
+    }
+}
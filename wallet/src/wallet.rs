@@ -79,22 +79,37 @@ impl Wallet {
             .address_labels
             .get(index)
             .ok_or_else(|| anyhow::anyhow!("no address with index {}", index))?;
-        let (address, _dtk) = self.incoming_viewing_key().payment_address(index.into());
+        let address = self.full_viewing_key().address_at(index.into());
         Ok((label.clone(), address))
     }
 
     /// Iterate through the addresses in this wallet.
     pub fn addresses(&self) -> impl Iterator<Item = (usize, String, Address)> {
-        let incoming = self.incoming_viewing_key().clone();
+        let fvk = self.full_viewing_key().clone();
         self.address_labels
             .clone()
             .into_iter()
             .enumerate()
-            .map(move |(index, label)| {
-                let (address, _dtk) = incoming.payment_address(index.into());
+            .map(move |(index, label)| (index, label, fvk.address_at(index.into())))
+    }
 
-                (index, label, address)
-            })
+    /// Returns the index and label of the given address, or `None` if it isn't one of this
+    /// wallet's addresses.
+    ///
+    /// Addresses at indices which were never given a label are still recognized, with an empty
+    /// label.
+    pub fn index_for_address(&self, address: &Address) -> Option<(u64, String)> {
+        let index: u64 = self
+            .full_viewing_key()
+            .index_for_address(address)?
+            .try_into()
+            .ok()?;
+        let label = self
+            .address_labels
+            .get(index as usize)
+            .cloned()
+            .unwrap_or_default();
+        Some((index, label))
     }
 
     /// Computes the change address for the given note.
@@ -113,6 +128,7 @@ impl Wallet {
 mod serde_helpers {
     use penumbra_crypto::keys::SpendSeed;
     use serde_with::serde_as;
+    use zeroize::Zeroize;
 
     use super::*;
 
@@ -124,10 +140,16 @@ mod serde_helpers {
         spend_seed: [u8; 32],
     }
 
+    impl Drop for WalletHelper {
+        fn drop(&mut self) {
+            self.spend_seed.zeroize();
+        }
+    }
+
     impl From<WalletHelper> for Wallet {
-        fn from(w: WalletHelper) -> Self {
+        fn from(mut w: WalletHelper) -> Self {
             Self {
-                address_labels: w.address_labels,
+                address_labels: std::mem::take(&mut w.address_labels),
                 spend_key: SpendKey::from(SpendSeed(w.spend_seed)),
             }
         }
@@ -136,8 +158,8 @@ mod serde_helpers {
     impl From<Wallet> for WalletHelper {
         fn from(w: Wallet) -> Self {
             Self {
-                address_labels: w.address_labels,
-                spend_seed: w.spend_key.seed().clone().0,
+                address_labels: w.address_labels.clone(),
+                spend_seed: w.spend_key.seed().0,
             }
         }
     }